@@ -0,0 +1,127 @@
+use crate::{
+    color_primaries::Chromaticity,
+    hdr_metadata::{from_chromaticity, read_u16, read_u32, to_chromaticity, CHROMATICITY_UNITS},
+    image_buffer::{CVImageBuffer, CVImageBufferKeys},
+    r#return::CVError,
+};
+
+pub const AMBIENT_VIEWING_ENVIRONMENT_SIZE: usize = 8;
+
+const ILLUMINANCE_UNITS: f64 = 10000.0; /* ambient illuminance comes in increments of 0.0001 lux */
+
+// H.265 ambient viewing environment SEI: the ambient illuminance as a big-endian 32-bit value, then the x, y
+// chromaticity of the ambient light as big-endian 16-bit values
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct AmbientViewingEnvironment {
+    pub ambient_illuminance: u32,
+    pub ambient_light: (u16, u16),
+}
+
+impl AmbientViewingEnvironment {
+    // Illuminance in lux
+    pub fn new(illuminance: f64, chromaticity: Chromaticity) -> Self {
+        AmbientViewingEnvironment {
+            ambient_illuminance: (illuminance * ILLUMINANCE_UNITS).round().clamp(0.0, u32::MAX as f64) as u32,
+            ambient_light: from_chromaticity(chromaticity),
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CVError> {
+        if bytes.len() != AMBIENT_VIEWING_ENVIRONMENT_SIZE {
+            return Err(CVError::InvalidSize);
+        }
+        let ambient_viewing_environment =
+            AmbientViewingEnvironment { ambient_illuminance: read_u32(bytes, 0), ambient_light: (read_u16(bytes, 4), read_u16(bytes, 6)) };
+        ambient_viewing_environment.validate()?;
+        Ok(ambient_viewing_environment)
+    }
+
+    pub fn to_bytes(&self) -> Result<[u8; AMBIENT_VIEWING_ENVIRONMENT_SIZE], CVError> {
+        self.validate()?;
+        let mut bytes = [0u8; AMBIENT_VIEWING_ENVIRONMENT_SIZE];
+        bytes[..4].copy_from_slice(&self.ambient_illuminance.to_be_bytes());
+        bytes[4..6].copy_from_slice(&self.ambient_light.0.to_be_bytes());
+        bytes[6..].copy_from_slice(&self.ambient_light.1.to_be_bytes());
+        Ok(bytes)
+    }
+
+    // The illuminance must not be zero and the coordinates must lie within [0, 1]
+    pub fn validate(&self) -> Result<(), CVError> {
+        let limit = CHROMATICITY_UNITS as u16;
+        if self.ambient_illuminance == 0 || self.ambient_light.0 > limit || self.ambient_light.1 > limit {
+            return Err(CVError::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    // In lux
+    #[inline]
+    pub fn illuminance(&self) -> f64 {
+        self.ambient_illuminance as f64 / ILLUMINANCE_UNITS
+    }
+
+    #[inline]
+    pub fn chromaticity(&self) -> Chromaticity {
+        to_chromaticity(self.ambient_light)
+    }
+}
+
+impl CVImageBuffer {
+    // None when the attachment is missing or malformed
+    pub fn get_ambient_viewing_environment(&self) -> Option<AmbientViewingEnvironment> {
+        AmbientViewingEnvironment::from_bytes(self.get_data_attachment(CVImageBufferKeys::AmbientViewingEnvironment)?.bytes()).ok()
+    }
+
+    pub fn set_ambient_viewing_environment(&self, ambient_viewing_environment: &AmbientViewingEnvironment) -> Result<(), CVError> {
+        self.set_data_attachment(CVImageBufferKeys::AmbientViewingEnvironment, &ambient_viewing_environment.to_bytes()?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color_primaries::D65;
+
+    // 5 lux of D65 light, the dim surround of ITU-R BT.2100 reference viewing
+    const DIM_D65: [u8; AMBIENT_VIEWING_ENVIRONMENT_SIZE] = [0x00, 0x00, 0xC3, 0x50, 0x3D, 0x13, 0x40, 0x42];
+
+    #[test]
+    fn bytes() {
+        let ambient_viewing_environment = AmbientViewingEnvironment::new(5.0, D65);
+        assert_eq!(ambient_viewing_environment, AmbientViewingEnvironment { ambient_illuminance: 50000, ambient_light: (15635, 16450) });
+        assert_eq!(ambient_viewing_environment.to_bytes(), Ok(DIM_D65));
+        assert_eq!(AmbientViewingEnvironment::from_bytes(&DIM_D65), Ok(ambient_viewing_environment));
+        assert_eq!(ambient_viewing_environment.illuminance(), 5.0);
+        assert_eq!(ambient_viewing_environment.chromaticity(), D65);
+
+        assert_eq!(AmbientViewingEnvironment::from_bytes(&DIM_D65[..7]), Err(CVError::InvalidSize));
+        assert_eq!(AmbientViewingEnvironment::from_bytes(&[0; 9]), Err(CVError::InvalidSize));
+        assert_eq!(AmbientViewingEnvironment::from_bytes(&[]), Err(CVError::InvalidSize));
+
+        // A zero illuminance and coordinates above 1 are rejected both ways
+        assert_eq!(AmbientViewingEnvironment::from_bytes(&[0, 0, 0, 0, 0x3D, 0x13, 0x40, 0x42]), Err(CVError::InvalidArgument));
+        assert_eq!(AmbientViewingEnvironment::from_bytes(&[0, 0, 0xC3, 0x50, 0xC3, 0x51, 0x40, 0x42]), Err(CVError::InvalidArgument));
+        assert_eq!(AmbientViewingEnvironment::default().to_bytes(), Err(CVError::InvalidArgument));
+        assert_eq!(AmbientViewingEnvironment::new(0.00004, D65).to_bytes(), Err(CVError::InvalidArgument));
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    #[test]
+    fn attachments() {
+        use crate::{
+            image_buffer::TCVImageBuffer,
+            pixel_buffer::{kCVPixelFormatType_32BGRA, CVPixelBuffer},
+        };
+
+        let pixel_buffer = CVPixelBuffer::new(kCVPixelFormatType_32BGRA, 16, 16, None).unwrap();
+        let image_buffer = pixel_buffer.as_image_buffer();
+        assert_eq!(image_buffer.get_ambient_viewing_environment(), None);
+        image_buffer.set_ambient_viewing_environment(&AmbientViewingEnvironment::new(5.0, D65)).unwrap();
+        assert_eq!(image_buffer.get_data_attachment(CVImageBufferKeys::AmbientViewingEnvironment).unwrap().bytes(), DIM_D65);
+        assert_eq!(image_buffer.get_ambient_viewing_environment(), Some(AmbientViewingEnvironment::new(5.0, D65)));
+        assert_eq!(image_buffer.set_ambient_viewing_environment(&AmbientViewingEnvironment::default()), Err(CVError::InvalidArgument));
+        image_buffer.set_data_attachment(CVImageBufferKeys::AmbientViewingEnvironment, &DIM_D65[..4]);
+        assert_eq!(image_buffer.get_ambient_viewing_environment(), None);
+    }
+}
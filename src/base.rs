@@ -43,7 +43,7 @@ pub enum CVTimeFlags {
 }
 
 #[repr(C)]
-#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Default)]
 pub struct CVTime {
     pub timeValue: i64,
     pub timeScale: i32,
@@ -64,20 +64,20 @@ pub struct CVTimeStamp {
     pub reserved: u64,
 }
 
-#[repr(u64)]
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
-pub enum CVTimeStampFlags {
-    kCVTimeStampVideoTimeValid     = (1 << 0),
-    kCVTimeStampHostTimeValid      = (1 << 1),
-    kCVTimeStampSMPTETimeValid     = (1 << 2),
-    kCVTimeStampVideoRefreshPeriodValid = (1 << 3),
-    kCVTimeStampRateScalarValid    = (1 << 4),
-    kCVTimeStampTopField           = (1 << 16),
-    kCVTimeStampBottomField        = (1 << 17),
-    kCVTimeStampVideoHostTimeValid = (1 << 0) | (1 << 1), /* kCVTimeStampVideoTimeValid |
-                                                           * kCVTimeStampHostTimeValid */
-    kCVTimeStampIsInterlaced       = (1 << 16) | (1 << 17), /* kCVTimeStampTopField |
-                                                             * kCVTimeStampBottomField */
+bitflags! {
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+    pub struct CVTimeStampFlags: u64 {
+        const kCVTimeStampVideoTimeValid = 1 << 0;
+        const kCVTimeStampHostTimeValid = 1 << 1;
+        const kCVTimeStampSMPTETimeValid = 1 << 2;
+        const kCVTimeStampVideoRefreshPeriodValid = 1 << 3;
+        const kCVTimeStampRateScalarValid = 1 << 4;
+        const kCVTimeStampTopField = 1 << 16;
+        const kCVTimeStampBottomField = 1 << 17;
+        const kCVTimeStampVideoHostTimeValid = Self::kCVTimeStampVideoTimeValid.bits() | Self::kCVTimeStampHostTimeValid.bits();
+        const kCVTimeStampIsInterlaced = Self::kCVTimeStampTopField.bits() | Self::kCVTimeStampBottomField.bits();
+    }
 }
 
 extern "C" {
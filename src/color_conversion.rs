@@ -0,0 +1,496 @@
+use crate::{
+    color_primaries::ColorMatrix,
+    image_buffer::{
+        CVImageBufferAlphaChannelMode, CVImageBufferChromaLocation, CVImageBufferColorPrimaries, CVImageBufferKeys, CVImageBufferTransferFunction,
+        CVImageBufferYCbCrMatrix,
+    },
+    pixel_buffer::CVPixelBuffer,
+    pixel_buffer_backend::PixelBufferBackend,
+    pixel_format::PixelFormat,
+    r#return::CVError,
+    transfer_function::{set_gamma_level, TransferCurve, TransferFunction},
+    ycbcr::{
+        check_size, get_chroma_location, read_ycbcr, rgb_format, set_attachments, write_ycbcr, ycbcr_format, RGBFormat, YCbCrCoefficients,
+        YCbCrFormat, YCbCrImage,
+    },
+    OSType,
+};
+
+pub(crate) const ALPHA_CHANNEL_MODES: [CVImageBufferAlphaChannelMode; 2] =
+    [CVImageBufferAlphaChannelMode::StraightAlpha, CVImageBufferAlphaChannelMode::PremultipliedAlpha];
+
+#[derive(Clone, Copy, Debug)]
+enum PixelLayout {
+    BiPlanarYCbCr(YCbCrFormat),
+    PackedRGB(RGBFormat),
+}
+
+fn pixel_layout(pixel_buffer: &CVPixelBuffer) -> Result<PixelLayout, CVError> {
+    let pixel_format = PixelFormat::from(pixel_buffer.get_pixel_format());
+    ycbcr_format(pixel_format)
+        .map(PixelLayout::BiPlanarYCbCr)
+        .or_else(|| rgb_format(pixel_format).map(PixelLayout::PackedRGB))
+        .ok_or(CVError::InvalidPixelFormat)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ColorSpace {
+    matrix: CVImageBufferYCbCrMatrix,
+    chroma_location: CVImageBufferChromaLocation,
+    primaries: CVImageBufferColorPrimaries,
+    transfer_function: TransferFunction,
+    premultiplied: bool,
+}
+
+impl ColorSpace {
+    // Untagged buffers are taken as ITU-R BT.601 up to 576 lines and ITU-R BT.709 above, chroma sited left, ITU-R BT.709
+    // primaries, the ITU-R BT.709 curve for YCbCr, sRGB for 32BGRA, linear light for floating point RGB and straight alpha
+    fn defaults(pixel_buffer: &CVPixelBuffer, layout: PixelLayout) -> Self {
+        let transfer_function = match layout {
+            PixelLayout::BiPlanarYCbCr(_) => CVImageBufferTransferFunction::ITU_R_709_2,
+            PixelLayout::PackedRGB(RGBFormat::BGRA8) => CVImageBufferTransferFunction::sRGB,
+            PixelLayout::PackedRGB(_) => CVImageBufferTransferFunction::Linear,
+        };
+        ColorSpace {
+            matrix: if pixel_buffer.get_height() <= 576 { CVImageBufferYCbCrMatrix::ITU_R_601_4 } else { CVImageBufferYCbCrMatrix::ITU_R_709_2 },
+            chroma_location: CVImageBufferChromaLocation::Left,
+            primaries: CVImageBufferColorPrimaries::ITU_R_709_2,
+            transfer_function: TransferFunction::from(transfer_function),
+            premultiplied: false,
+        }
+    }
+
+    fn from_source(pixel_buffer: &CVPixelBuffer, layout: PixelLayout) -> Self {
+        let mut color_space = Self::from_attachments(pixel_buffer, layout, &Self::defaults(pixel_buffer, layout));
+        if let PixelLayout::BiPlanarYCbCr(_) = layout {
+            color_space.premultiplied = false;
+        }
+        color_space
+    }
+
+    // An RGB destination without colorimetry attachments gets the defaults above, sRGB or linear sRGB, rather than the
+    // source's; everything else a destination is not tagged with is carried over from the source, and a YCbCr
+    // destination keeps the source's alpha mode since it has no alpha of its own
+    fn from_destination(pixel_buffer: &CVPixelBuffer, layout: PixelLayout, source: &ColorSpace) -> Self {
+        let defaults = match layout {
+            PixelLayout::BiPlanarYCbCr(_) => *source,
+            PixelLayout::PackedRGB(_) => ColorSpace { premultiplied: source.premultiplied, ..Self::defaults(pixel_buffer, layout) },
+        };
+        let mut color_space = Self::from_attachments(pixel_buffer, layout, &defaults);
+        if let PixelLayout::BiPlanarYCbCr(_) = layout {
+            color_space.premultiplied = source.premultiplied;
+        }
+        color_space
+    }
+
+    fn from_attachments(pixel_buffer: &CVPixelBuffer, layout: PixelLayout, defaults: &ColorSpace) -> Self {
+        let (matrix, chroma_location) = match layout {
+            PixelLayout::BiPlanarYCbCr(_) => (
+                pixel_buffer.get_image_attachment(CVImageBufferKeys::YCbCrMatrix).unwrap_or(defaults.matrix),
+                get_chroma_location(pixel_buffer).unwrap_or(defaults.chroma_location),
+            ),
+            PixelLayout::PackedRGB(_) => (defaults.matrix, defaults.chroma_location),
+        };
+        let alpha_channel_mode = pixel_buffer.get_image_attachment::<CVImageBufferAlphaChannelMode>(CVImageBufferKeys::AlphaChannelMode);
+        ColorSpace {
+            matrix,
+            chroma_location,
+            primaries: pixel_buffer.get_image_attachment(CVImageBufferKeys::ColorPrimaries).unwrap_or(defaults.primaries),
+            transfer_function: TransferFunction::from_attachments(pixel_buffer).unwrap_or(defaults.transfer_function),
+            premultiplied: alpha_channel_mode.map_or(defaults.premultiplied, |mode| mode == CVImageBufferAlphaChannelMode::PremultipliedAlpha),
+        }
+    }
+
+    fn set_attachments(&self, pixel_buffer: &CVPixelBuffer, layout: PixelLayout) {
+        match layout {
+            PixelLayout::BiPlanarYCbCr(_) => set_attachments(pixel_buffer, self.matrix, self.chroma_location),
+            PixelLayout::PackedRGB(_) => pixel_buffer.set_image_attachment(
+                CVImageBufferKeys::AlphaChannelMode,
+                if self.premultiplied { CVImageBufferAlphaChannelMode::PremultipliedAlpha } else { CVImageBufferAlphaChannelMode::StraightAlpha },
+            ),
+        }
+        pixel_buffer.set_image_attachment(CVImageBufferKeys::ColorPrimaries, self.primaries);
+        pixel_buffer.set_image_attachment(CVImageBufferKeys::TransferFunction, self.transfer_function.function);
+        if self.transfer_function.function == CVImageBufferTransferFunction::UseGamma {
+            set_gamma_level(pixel_buffer, self.transfer_function.gamma);
+        }
+    }
+}
+
+// One pass over the whole frame of nonlinear (or, between Decode and Encode, linear) RGBA, with its curve or matrix
+// worked out when the pipeline is planned
+#[derive(Clone, Debug)]
+enum Stage {
+    Unpremultiply,
+    Decode(TransferCurve),
+    Convert(ColorMatrix),
+    Encode(TransferCurve),
+    Premultiply,
+}
+
+impl Stage {
+    fn apply(&self, pixels: &mut [[f32; 4]]) {
+        match self {
+            Stage::Unpremultiply => pixels.iter_mut().filter(|pixel| pixel[3] > 0.0).for_each(|pixel| {
+                let alpha = pixel[3];
+                pixel[..3].iter_mut().for_each(|component| *component /= alpha);
+            }),
+            Stage::Decode(curve) | Stage::Encode(curve) => pixels.iter_mut().for_each(|pixel| curve.apply_slice(&mut pixel[..3])),
+            Stage::Convert(matrix) => pixels.iter_mut().for_each(|pixel| {
+                let rgb = matrix.transform_f32([pixel[0], pixel[1], pixel[2]]);
+                pixel[..3].copy_from_slice(&rgb);
+            }),
+            Stage::Premultiply => pixels.iter_mut().for_each(|pixel| {
+                let alpha = pixel[3];
+                pixel[..3].iter_mut().for_each(|component| *component *= alpha);
+            }),
+        }
+    }
+}
+
+// Premultiplied color is only divided out when the curves or primaries change, or the destination wants straight alpha
+fn plan_stages(source: &ColorSpace, destination: &ColorSpace) -> Vec<Stage> {
+    let linearize = source.primaries != destination.primaries || source.transfer_function != destination.transfer_function;
+    let unpremultiply = source.premultiplied && (linearize || !destination.premultiplied);
+    let premultiply = destination.premultiplied && (unpremultiply || !source.premultiplied);
+
+    let mut stages = Vec::new();
+    if unpremultiply {
+        stages.push(Stage::Unpremultiply);
+    }
+    if linearize {
+        if source.transfer_function.function != CVImageBufferTransferFunction::Linear {
+            stages.push(Stage::Decode(source.transfer_function.decoder()));
+        }
+        if source.primaries != destination.primaries {
+            stages.push(Stage::Convert(ColorMatrix::primaries_conversion(source.primaries, destination.primaries)));
+        }
+        if destination.transfer_function.function != CVImageBufferTransferFunction::Linear {
+            stages.push(Stage::Encode(destination.transfer_function.encoder()));
+        }
+    }
+    if premultiply {
+        stages.push(Stage::Premultiply);
+    }
+    stages
+}
+
+// Everything about converting between a pair of buffers that depends only on their formats, sizes and attachments,
+// worked out once so that frames go through a fixed sequence of whole-frame stages. The source is described by its
+// YCbCr matrix, chroma location, color primaries, transfer function and alpha mode attachments, the destination by its
+// own, falling back to the source's except for the colorimetry of RGB destinations, which defaults to sRGB
+#[derive(Clone, Debug)]
+pub struct ColorConversionPipeline {
+    source_pixel_format: OSType,
+    destination_pixel_format: OSType,
+    width: usize,
+    height: usize,
+    source_layout: PixelLayout,
+    destination_layout: PixelLayout,
+    source_color_space: ColorSpace,
+    destination_color_space: ColorSpace,
+    stages: Vec<Stage>,
+}
+
+impl ColorConversionPipeline {
+    pub fn new(source: &CVPixelBuffer, destination: &CVPixelBuffer) -> Result<Self, CVError> {
+        check_size(source, destination)?;
+        let (source_layout, destination_layout) = (pixel_layout(source)?, pixel_layout(destination)?);
+        let source_color_space = ColorSpace::from_source(source, source_layout);
+        let destination_color_space = ColorSpace::from_destination(destination, destination_layout, &source_color_space);
+
+        let stages = plan_stages(&source_color_space, &destination_color_space);
+
+        Ok(ColorConversionPipeline {
+            source_pixel_format: source.get_pixel_format(),
+            destination_pixel_format: destination.get_pixel_format(),
+            width: source.get_width(),
+            height: source.get_height(),
+            source_layout,
+            destination_layout,
+            source_color_space,
+            destination_color_space,
+            stages,
+        })
+    }
+
+    // Converts a frame of the buffers the pipeline was planned for, attachments included; the buffers' own
+    // attachments are not looked at again
+    pub fn run(&self, source: &CVPixelBuffer, destination: &mut CVPixelBuffer) -> Result<(), CVError> {
+        if source.get_pixel_format() != self.source_pixel_format || destination.get_pixel_format() != self.destination_pixel_format {
+            return Err(CVError::InvalidPixelFormat);
+        }
+        if (source.get_width(), source.get_height()) != (self.width, self.height) {
+            return Err(CVError::InvalidSize);
+        }
+        check_size(source, destination)?;
+
+        let (source_color_space, destination_color_space) = (&self.source_color_space, &self.destination_color_space);
+        match (self.source_layout, self.destination_layout) {
+            // Nothing to do in RGB, only range, bit depth and chroma siting change
+            (PixelLayout::BiPlanarYCbCr(source_format), PixelLayout::BiPlanarYCbCr(destination_format))
+                if self.stages.is_empty() && source_color_space.matrix == destination_color_space.matrix =>
+            {
+                let mut image = read_ycbcr(source, &source_format)?;
+                if source_color_space.chroma_location != destination_color_space.chroma_location {
+                    image = image.resampled((1, 1), source_color_space.chroma_location);
+                }
+                let image = image.resampled(destination_format.subsampling, destination_color_space.chroma_location);
+                write_ycbcr(destination, &destination_format, &image, false)?;
+            }
+            (source_layout, destination_layout) => {
+                let mut pixels = self.read_rgba(source, source_layout)?;
+                self.stages.iter().for_each(|stage| stage.apply(&mut pixels));
+                self.write_rgba(destination, destination_layout, &pixels)?;
+            }
+        }
+        destination_color_space.set_attachments(destination, self.destination_layout);
+        Ok(())
+    }
+
+    fn read_rgba(&self, source: &CVPixelBuffer, layout: PixelLayout) -> Result<Vec<[f32; 4]>, CVError> {
+        match layout {
+            PixelLayout::BiPlanarYCbCr(format) => {
+                let coefficients = YCbCrCoefficients::from(self.source_color_space.matrix);
+                let image = read_ycbcr(source, &format)?.resampled((1, 1), self.source_color_space.chroma_location);
+                Ok(image
+                    .y
+                    .iter()
+                    .zip(image.cb.iter().zip(&image.cr))
+                    .map(|(&y, (&cb, &cr))| {
+                        let [r, g, b] = coefficients.to_rgb(y, cb, cr);
+                        [r, g, b, 1.0]
+                    })
+                    .collect())
+            }
+            PixelLayout::PackedRGB(format) => {
+                let read = format.reader();
+                let guard = source.lock_read()?;
+                let plane = guard.plane(0).ok_or(CVError::InvalidArgument)?;
+                let mut pixels = Vec::with_capacity(self.width * self.height);
+                for row in plane.rows() {
+                    pixels.extend(row.chunks_exact(format.bytes_per_pixel()).take(self.width).map(read));
+                }
+                Ok(pixels)
+            }
+        }
+    }
+
+    // A YCbCr destination drops alpha, premultiplied color stays as it is, that is composited over black
+    fn write_rgba(&self, destination: &mut CVPixelBuffer, layout: PixelLayout, pixels: &[[f32; 4]]) -> Result<(), CVError> {
+        match layout {
+            PixelLayout::BiPlanarYCbCr(format) => {
+                let coefficients = YCbCrCoefficients::from(self.destination_color_space.matrix);
+                let mut image = YCbCrImage {
+                    width: self.width,
+                    height: self.height,
+                    subsampling: (1, 1),
+                    y: Vec::with_capacity(pixels.len()),
+                    cb: Vec::with_capacity(pixels.len()),
+                    cr: Vec::with_capacity(pixels.len()),
+                };
+                for pixel in pixels {
+                    let (y, cb, cr) = coefficients.to_ycbcr([pixel[0], pixel[1], pixel[2]]);
+                    image.y.push(y);
+                    image.cb.push(cb);
+                    image.cr.push(cr);
+                }
+                let image = image.resampled(format.subsampling, self.destination_color_space.chroma_location);
+                write_ycbcr(destination, &format, &image, false)
+            }
+            PixelLayout::PackedRGB(format) => {
+                let write = format.writer();
+                let mut guard = destination.lock_write()?;
+                let mut plane = guard.plane_mut(0).ok_or(CVError::InvalidArgument)?;
+                for (y, (row, line)) in plane.rows_mut().zip(pixels.chunks_exact(self.width)).enumerate() {
+                    for (x, (bytes, &pixel)) in row.chunks_exact_mut(format.bytes_per_pixel()).zip(line).enumerate() {
+                        write(bytes, pixel, false, x, y);
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// Plans and runs a ColorConversionPipeline in one go; keep the pipeline around instead when converting a stream of
+// frames between the same pair of buffer configurations
+pub fn convert(source: &CVPixelBuffer, destination: &mut CVPixelBuffer) -> Result<(), CVError> {
+    ColorConversionPipeline::new(source, destination)?.run(source, destination)
+}
+
+#[cfg(all(test, any(feature = "portable", target_os = "macos", target_os = "ios")))]
+mod tests {
+    use super::*;
+
+    fn new_buffer(pixel_format: PixelFormat, width: usize, height: usize) -> CVPixelBuffer {
+        CVPixelBuffer::new(pixel_format.into(), width, height, None).unwrap()
+    }
+
+    fn color_space(primaries: CVImageBufferColorPrimaries, transfer_function: CVImageBufferTransferFunction, premultiplied: bool) -> ColorSpace {
+        ColorSpace {
+            matrix: CVImageBufferYCbCrMatrix::ITU_R_709_2,
+            chroma_location: CVImageBufferChromaLocation::Left,
+            primaries,
+            transfer_function: TransferFunction::from(transfer_function),
+            premultiplied,
+        }
+    }
+
+    #[test]
+    fn stages() {
+        use CVImageBufferColorPrimaries::{ITU_R_2020, ITU_R_709_2};
+        use CVImageBufferTransferFunction::{sRGB, Linear, ITU_R_2100_HLG};
+
+        let plan = |source: ColorSpace, destination: ColorSpace| {
+            plan_stages(&source, &destination)
+                .iter()
+                .map(|stage| match stage {
+                    Stage::Unpremultiply => "unpremultiply",
+                    Stage::Decode(_) => "decode",
+                    Stage::Convert(_) => "convert",
+                    Stage::Encode(_) => "encode",
+                    Stage::Premultiply => "premultiply",
+                })
+                .collect::<Vec<_>>()
+        };
+        assert!(plan(color_space(ITU_R_709_2, sRGB, false), color_space(ITU_R_709_2, sRGB, false)).is_empty());
+        assert!(plan(color_space(ITU_R_709_2, sRGB, true), color_space(ITU_R_709_2, sRGB, true)).is_empty());
+        assert_eq!(plan(color_space(ITU_R_709_2, Linear, false), color_space(ITU_R_709_2, sRGB, false)), ["encode"]);
+        assert_eq!(plan(color_space(ITU_R_709_2, sRGB, true), color_space(ITU_R_709_2, sRGB, false)), ["unpremultiply"]);
+        assert_eq!(plan(color_space(ITU_R_709_2, sRGB, false), color_space(ITU_R_709_2, sRGB, true)), ["premultiply"]);
+        assert_eq!(
+            plan(color_space(ITU_R_2020, ITU_R_2100_HLG, true), color_space(ITU_R_709_2, sRGB, true)),
+            ["unpremultiply", "decode", "convert", "encode", "premultiply"]
+        );
+    }
+
+    // Without attachments, 32BGRA destinations are sRGB whatever the source is
+    #[test]
+    fn untagged_rgb_destination() {
+        let mut source = new_buffer(PixelFormat::_128RGBAFloat, 2, 1);
+        source
+            .lock_write()
+            .unwrap()
+            .plane_mut(0)
+            .unwrap()
+            .row_as_mut::<f32>(0)
+            .unwrap()
+            .copy_from_slice(&[0.5, 0.18, 0.0, 1.0, 1.0, 1.0, 1.0, 0.5]);
+        let mut destination = new_buffer(PixelFormat::_32BGRA, 2, 1);
+        convert(&source, &mut destination).unwrap();
+        assert_eq!(destination.lock_read().unwrap().plane(0).unwrap().row(0).unwrap(), [0, 118, 188, 255, 255, 255, 255, 128]);
+
+        // Mid gray on the ITU-R BT.709 curve is re-encoded with the sRGB one
+        let mut source = new_buffer(PixelFormat::_444YpCbCr8BiPlanarVideoRange, 2, 1);
+        {
+            let mut guard = source.lock_write().unwrap();
+            guard.plane_mut(0).unwrap().as_bytes_mut().fill(126);
+            guard.plane_mut(1).unwrap().as_bytes_mut().fill(128);
+        }
+        convert(&source, &mut destination).unwrap();
+        assert_eq!(destination.lock_read().unwrap().plane(0).unwrap().row(0).unwrap(), [140, 140, 140, 255, 140, 140, 140, 255]);
+
+        // And floating point RGB destinations are linear
+        let mut linear = new_buffer(PixelFormat::_128RGBAFloat, 2, 1);
+        convert(&destination, &mut linear).unwrap();
+        let guard = linear.lock_read().unwrap();
+        let pixels = guard.plane(0).unwrap().row_as::<f32>(0).unwrap().to_vec();
+        assert!((pixels[0] - TransferFunction::new(CVImageBufferTransferFunction::sRGB).decode(140.0 / 255.0)).abs() < 1e-5);
+        assert_eq!(pixels[3], 1.0);
+    }
+
+    #[test]
+    fn ycbcr_to_ycbcr() {
+        // Video range 4:2:0 to full range 4:2:2, chroma sited at the center rather than the left
+        let mut source = new_buffer(PixelFormat::_420YpCbCr8BiPlanarVideoRange, 4, 2);
+        {
+            let mut guard = source.lock_write().unwrap();
+            guard.plane_mut(0).unwrap().rows_mut().for_each(|row| row.copy_from_slice(&[16, 126, 235, 235]));
+            guard.plane_mut(1).unwrap().as_bytes_mut().fill(128);
+        }
+        source.set_image_attachment(CVImageBufferKeys::ChromaLocationTopField, CVImageBufferChromaLocation::Center);
+        let mut destination = new_buffer(PixelFormat::_422YpCbCr8BiPlanarFullRange, 4, 2);
+        let pipeline = ColorConversionPipeline::new(&source, &destination).unwrap();
+        assert!(pipeline.stages.is_empty());
+        pipeline.run(&source, &mut destination).unwrap();
+
+        let guard = destination.lock_read().unwrap();
+        assert!(guard.plane(0).unwrap().rows().all(|row| row == [0, 128, 255, 255]));
+        assert!(guard.plane(1).unwrap().rows().all(|row| row == [128; 4]));
+        assert_eq!(destination.get_image_attachment(CVImageBufferKeys::YCbCrMatrix), Some(CVImageBufferYCbCrMatrix::ITU_R_601_4));
+        for key in [CVImageBufferKeys::ChromaLocationTopField, CVImageBufferKeys::ChromaLocationBottomField] {
+            assert_eq!(destination.get_image_attachment(key), Some(CVImageBufferChromaLocation::Center));
+        }
+    }
+
+    #[test]
+    fn primaries() {
+        // Linear ITU-R BT.2020 to linear ITU-R BT.709, both D65, so white stays white
+        let mut source = new_buffer(PixelFormat::_128RGBAFloat, 2, 1);
+        source
+            .lock_write()
+            .unwrap()
+            .plane_mut(0)
+            .unwrap()
+            .row_as_mut::<f32>(0)
+            .unwrap()
+            .copy_from_slice(&[1.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0]);
+        source.set_image_attachment(CVImageBufferKeys::ColorPrimaries, CVImageBufferColorPrimaries::ITU_R_2020);
+        let mut destination = new_buffer(PixelFormat::_128RGBAFloat, 2, 1);
+        convert(&source, &mut destination).unwrap();
+
+        let guard = destination.lock_read().unwrap();
+        let pixels = guard.plane(0).unwrap().row_as::<f32>(0).unwrap().to_vec();
+        for (pixel, expected) in pixels.iter().zip([1.6605, -0.1246, -0.0182, 1.0, 1.0, 1.0, 1.0, 1.0]) {
+            assert!((pixel - expected).abs() < 1e-3, "{:?}", pixels);
+        }
+        assert_eq!(destination.get_image_attachment(CVImageBufferKeys::ColorPrimaries), Some(CVImageBufferColorPrimaries::ITU_R_709_2));
+        assert_eq!(destination.get_image_attachment(CVImageBufferKeys::TransferFunction), Some(CVImageBufferTransferFunction::Linear));
+    }
+
+    #[test]
+    fn plan_from_attachments() {
+        let source = new_buffer(PixelFormat::_420YpCbCr8BiPlanarVideoRange, 4, 2);
+        source.set_image_attachment(CVImageBufferKeys::YCbCrMatrix, CVImageBufferYCbCrMatrix::ITU_R_2020);
+        source.set_image_attachment(CVImageBufferKeys::ColorPrimaries, CVImageBufferColorPrimaries::ITU_R_2020);
+        source.set_image_attachment(CVImageBufferKeys::TransferFunction, CVImageBufferTransferFunction::ITU_R_2100_HLG);
+        source.set_image_attachment(CVImageBufferKeys::ChromaLocationBottomField, CVImageBufferChromaLocation::TopLeft);
+        let destination = new_buffer(PixelFormat::_32BGRA, 4, 2);
+        destination.set_image_attachment(CVImageBufferKeys::AlphaChannelMode, CVImageBufferAlphaChannelMode::PremultipliedAlpha);
+
+        let pipeline = ColorConversionPipeline::new(&source, &destination).unwrap();
+        assert_eq!(
+            pipeline.source_color_space,
+            ColorSpace {
+                matrix: CVImageBufferYCbCrMatrix::ITU_R_2020,
+                chroma_location: CVImageBufferChromaLocation::TopLeft,
+                primaries: CVImageBufferColorPrimaries::ITU_R_2020,
+                transfer_function: TransferFunction::new(CVImageBufferTransferFunction::ITU_R_2100_HLG),
+                premultiplied: false,
+            }
+        );
+        assert_eq!(
+            pipeline.destination_color_space,
+            ColorSpace {
+                matrix: CVImageBufferYCbCrMatrix::ITU_R_601_4,
+                chroma_location: CVImageBufferChromaLocation::Left,
+                primaries: CVImageBufferColorPrimaries::ITU_R_709_2,
+                transfer_function: TransferFunction::new(CVImageBufferTransferFunction::sRGB),
+                premultiplied: true,
+            }
+        );
+        let stages: Vec<_> = pipeline
+            .stages
+            .iter()
+            .map(|stage| matches!(stage, Stage::Decode(_) | Stage::Convert(_) | Stage::Encode(_)))
+            .collect();
+        assert_eq!(stages, [true, true, true, false]);
+        assert!(matches!(pipeline.stages[3], Stage::Premultiply));
+
+        // The top field's siting wins over the bottom field's
+        source.set_image_attachment(CVImageBufferKeys::ChromaLocationTopField, CVImageBufferChromaLocation::Center);
+        let pipeline = ColorConversionPipeline::new(&source, &destination).unwrap();
+        assert_eq!(pipeline.source_color_space.chroma_location, CVImageBufferChromaLocation::Center);
+    }
+}
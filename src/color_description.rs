@@ -0,0 +1,276 @@
+use core_foundation::string::CFString;
+
+use crate::{
+    color_conversion::ALPHA_CHANNEL_MODES,
+    color_primaries::COLOR_PRIMARIES,
+    image_buffer::{
+        get_number_attachment, get_string_attachment, set_number_attachment, set_string_attachment, CVImageBuffer, CVImageBufferAlphaChannelMode,
+        CVImageBufferChromaLocation, CVImageBufferChromaSubsampling, CVImageBufferColorPrimaries, CVImageBufferKeys, CVImageBufferTransferFunction,
+        CVImageBufferYCbCrMatrix,
+    },
+    transfer_function::TRANSFER_FUNCTIONS,
+    ycbcr::{CHROMA_LOCATIONS, YCBCR_MATRICES},
+};
+
+pub(crate) const CHROMA_SUBSAMPLINGS: [CVImageBufferChromaSubsampling; 3] =
+    [CVImageBufferChromaSubsampling::_420, CVImageBufferChromaSubsampling::_422, CVImageBufferChromaSubsampling::_411];
+
+pub const UNSPECIFIED_CODE_POINT: i32 = 2;
+
+const TRANSFER_CHARACTERISTICS_GAMMA_22: i32 = 4;
+const TRANSFER_CHARACTERISTICS_GAMMA_28: i32 = 5;
+
+// ITU-T H.273 colour_primaries, transfer_characteristics and matrix_coefficients
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ColorCodePoints {
+    pub color_primaries: i32,
+    pub transfer_characteristics: i32,
+    pub matrix_coefficients: i32,
+}
+
+impl Default for ColorCodePoints {
+    fn default() -> Self {
+        ColorCodePoints {
+            color_primaries: UNSPECIFIED_CODE_POINT,
+            transfer_characteristics: UNSPECIFIED_CODE_POINT,
+            matrix_coefficients: UNSPECIFIED_CODE_POINT,
+        }
+    }
+}
+
+// The colorimetry attachments of an image buffer, None for those that are not attached
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ColorDescription {
+    pub matrix: Option<CVImageBufferYCbCrMatrix>,
+    pub primaries: Option<CVImageBufferColorPrimaries>,
+    pub transfer: Option<CVImageBufferTransferFunction>,
+    pub chroma_location_top: Option<CVImageBufferChromaLocation>,
+    pub chroma_location_bottom: Option<CVImageBufferChromaLocation>,
+    pub chroma_subsampling: Option<CVImageBufferChromaSubsampling>,
+    pub gamma: Option<f32>, /* kCVImageBufferGammaLevelKey, the exponent used with UseGamma */
+    pub alpha_mode: Option<CVImageBufferAlphaChannelMode>,
+}
+
+impl ColorDescription {
+    // Code points CoreVideo has no value for are left out; gamma 2.2 and 2.8 become UseGamma with that gamma, and the
+    // chromaticity-derived matrix resolves to a P3 matrix when the primaries are P3
+    pub fn from_code_points(code_points: &ColorCodePoints) -> Self {
+        let primaries = CVImageBufferColorPrimaries::from_integer_code_point(code_points.color_primaries);
+        let matrix = CVImageBufferYCbCrMatrix::from_integer_code_point_with_primaries(code_points.matrix_coefficients, primaries);
+        let (transfer, gamma) = match code_points.transfer_characteristics {
+            TRANSFER_CHARACTERISTICS_GAMMA_22 => (Some(CVImageBufferTransferFunction::UseGamma), Some(2.2)),
+            TRANSFER_CHARACTERISTICS_GAMMA_28 => (Some(CVImageBufferTransferFunction::UseGamma), Some(2.8)),
+            code_point => (CVImageBufferTransferFunction::from_integer_code_point(code_point), None),
+        };
+        ColorDescription { matrix, primaries, transfer, gamma, ..Default::default() }
+    }
+
+    // Anything missing or without a code point of its own is unspecified
+    pub fn code_points(&self) -> ColorCodePoints {
+        let transfer_characteristics = match (self.transfer, self.gamma) {
+            (Some(CVImageBufferTransferFunction::UseGamma), Some(gamma)) if (gamma - 2.2).abs() < 0.01 => TRANSFER_CHARACTERISTICS_GAMMA_22,
+            (Some(CVImageBufferTransferFunction::UseGamma), Some(gamma)) if (gamma - 2.8).abs() < 0.01 => TRANSFER_CHARACTERISTICS_GAMMA_28,
+            (transfer, _) => transfer.map_or(UNSPECIFIED_CODE_POINT, CVImageBufferTransferFunction::integer_code_point),
+        };
+        ColorCodePoints {
+            color_primaries: self.primaries.map_or(UNSPECIFIED_CODE_POINT, CVImageBufferColorPrimaries::integer_code_point),
+            transfer_characteristics,
+            matrix_coefficients: self.matrix.map_or(UNSPECIFIED_CODE_POINT, CVImageBufferYCbCrMatrix::integer_code_point),
+        }
+    }
+}
+
+impl CVImageBuffer {
+    pub fn get_color_description(&self) -> ColorDescription {
+        let buffer = self.as_buffer();
+        ColorDescription {
+            matrix: get_string_attachment(&buffer, CVImageBufferKeys::YCbCrMatrix, &YCBCR_MATRICES),
+            primaries: get_string_attachment(&buffer, CVImageBufferKeys::ColorPrimaries, &COLOR_PRIMARIES),
+            transfer: get_string_attachment(&buffer, CVImageBufferKeys::TransferFunction, &TRANSFER_FUNCTIONS),
+            chroma_location_top: get_string_attachment(&buffer, CVImageBufferKeys::ChromaLocationTopField, &CHROMA_LOCATIONS),
+            chroma_location_bottom: get_string_attachment(&buffer, CVImageBufferKeys::ChromaLocationBottomField, &CHROMA_LOCATIONS),
+            chroma_subsampling: get_string_attachment(&buffer, CVImageBufferKeys::ChromaSubsampling, &CHROMA_SUBSAMPLINGS),
+            gamma: get_number_attachment(&buffer, CVImageBufferKeys::GammaLevel).map(|gamma| gamma as f32),
+            alpha_mode: get_string_attachment(&buffer, CVImageBufferKeys::AlphaChannelMode, &ALPHA_CHANNEL_MODES),
+        }
+    }
+
+    // Attaches every field that is set and removes the attachments of those that are not, so the description read back
+    // is the one given
+    pub fn set_color_description(&self, color_description: &ColorDescription) {
+        let buffer = self.as_buffer();
+        let set = |key: CVImageBufferKeys, value: Option<CFString>| match value {
+            Some(value) => set_string_attachment(&buffer, key, value),
+            None => buffer.remove_attachment(&CFString::from(key)),
+        };
+        set(CVImageBufferKeys::YCbCrMatrix, color_description.matrix.map(CFString::from));
+        set(CVImageBufferKeys::ColorPrimaries, color_description.primaries.map(CFString::from));
+        set(CVImageBufferKeys::TransferFunction, color_description.transfer.map(CFString::from));
+        set(CVImageBufferKeys::ChromaLocationTopField, color_description.chroma_location_top.map(CFString::from));
+        set(CVImageBufferKeys::ChromaLocationBottomField, color_description.chroma_location_bottom.map(CFString::from));
+        set(CVImageBufferKeys::ChromaSubsampling, color_description.chroma_subsampling.map(CFString::from));
+        set(CVImageBufferKeys::AlphaChannelMode, color_description.alpha_mode.map(CFString::from));
+        match color_description.gamma {
+            Some(gamma) => set_number_attachment(&buffer, CVImageBufferKeys::GammaLevel, gamma as f64),
+            None => buffer.remove_attachment(&CFString::from(CVImageBufferKeys::GammaLevel)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code_points(color_primaries: i32, transfer_characteristics: i32, matrix_coefficients: i32) -> ColorCodePoints {
+        ColorCodePoints { color_primaries, transfer_characteristics, matrix_coefficients }
+    }
+
+    #[test]
+    fn code_point_round_trip() {
+        let descriptions = [
+            (
+                code_points(1, 1, 1),
+                CVImageBufferColorPrimaries::ITU_R_709_2,
+                CVImageBufferTransferFunction::ITU_R_709_2,
+                CVImageBufferYCbCrMatrix::ITU_R_709_2,
+            ),
+            (
+                code_points(9, 16, 9),
+                CVImageBufferColorPrimaries::ITU_R_2020,
+                CVImageBufferTransferFunction::SMPTE_ST_2084_PQ,
+                CVImageBufferYCbCrMatrix::ITU_R_2020,
+            ),
+            (
+                code_points(9, 18, 9),
+                CVImageBufferColorPrimaries::ITU_R_2020,
+                CVImageBufferTransferFunction::ITU_R_2100_HLG,
+                CVImageBufferYCbCrMatrix::ITU_R_2020,
+            ),
+            (code_points(12, 13, 12), CVImageBufferColorPrimaries::P3_D65, CVImageBufferTransferFunction::sRGB, CVImageBufferYCbCrMatrix::P3_D65),
+            (
+                code_points(11, 17, 12),
+                CVImageBufferColorPrimaries::DCI_P3,
+                CVImageBufferTransferFunction::SMPTE_ST_428_1,
+                CVImageBufferYCbCrMatrix::DCI_P3,
+            ),
+            (
+                code_points(6, 7, 7),
+                CVImageBufferColorPrimaries::SMPTE_C,
+                CVImageBufferTransferFunction::SMPTE_240M_1995,
+                CVImageBufferYCbCrMatrix::SMPTE_240M_1995,
+            ),
+            (
+                code_points(5, 8, 6),
+                CVImageBufferColorPrimaries::EBU_3213,
+                CVImageBufferTransferFunction::Linear,
+                CVImageBufferYCbCrMatrix::ITU_R_601_4,
+            ),
+            (
+                code_points(22, 14, 6),
+                CVImageBufferColorPrimaries::P22,
+                CVImageBufferTransferFunction::ITU_R_2020,
+                CVImageBufferYCbCrMatrix::ITU_R_601_4,
+            ),
+        ];
+        for &(code_points, primaries, transfer, matrix) in &descriptions {
+            let color_description = ColorDescription::from_code_points(&code_points);
+            assert_eq!(
+                color_description,
+                ColorDescription { matrix: Some(matrix), primaries: Some(primaries), transfer: Some(transfer), ..Default::default() }
+            );
+            assert_eq!(color_description.code_points(), code_points);
+        }
+
+        // Aliases read as the value CoreVideo has for them and are written back as its own code point
+        let color_description = ColorDescription::from_code_points(&code_points(7, 6, 5));
+        assert_eq!(color_description.primaries, Some(CVImageBufferColorPrimaries::SMPTE_C));
+        assert_eq!(color_description.transfer, Some(CVImageBufferTransferFunction::ITU_R_709_2));
+        assert_eq!(color_description.matrix, Some(CVImageBufferYCbCrMatrix::ITU_R_601_4));
+        assert_eq!(color_description.code_points(), code_points(6, 1, 6));
+        assert_eq!(ColorDescription::from_code_points(&code_points(9, 15, 9)).code_points(), code_points(9, 14, 9));
+    }
+
+    #[test]
+    fn gamma() {
+        for &(transfer_characteristics, gamma) in &[(TRANSFER_CHARACTERISTICS_GAMMA_22, 2.2), (TRANSFER_CHARACTERISTICS_GAMMA_28, 2.8)] {
+            let color_description = ColorDescription::from_code_points(&code_points(1, transfer_characteristics, 1));
+            assert_eq!(color_description.transfer, Some(CVImageBufferTransferFunction::UseGamma));
+            assert_eq!(color_description.gamma, Some(gamma));
+            assert_eq!(color_description.code_points().transfer_characteristics, transfer_characteristics);
+        }
+
+        let with_gamma = |gamma: Option<f32>| {
+            ColorDescription { transfer: Some(CVImageBufferTransferFunction::UseGamma), gamma, ..Default::default() }
+                .code_points()
+                .transfer_characteristics
+        };
+        assert_eq!(with_gamma(Some(2.195)), TRANSFER_CHARACTERISTICS_GAMMA_22);
+        assert_eq!(with_gamma(Some(2.205)), TRANSFER_CHARACTERISTICS_GAMMA_22);
+        assert_eq!(with_gamma(Some(2.795)), TRANSFER_CHARACTERISTICS_GAMMA_28);
+        assert_eq!(with_gamma(Some(2.185)), UNSPECIFIED_CODE_POINT);
+        assert_eq!(with_gamma(Some(2.4)), UNSPECIFIED_CODE_POINT);
+        assert_eq!(with_gamma(None), UNSPECIFIED_CODE_POINT);
+
+        // The gamma only counts with UseGamma
+        let color_description = ColorDescription { transfer: Some(CVImageBufferTransferFunction::sRGB), gamma: Some(2.2), ..Default::default() };
+        assert_eq!(color_description.code_points().transfer_characteristics, 13);
+    }
+
+    #[test]
+    fn left_out_code_points() {
+        // Reserved, unspecified and code points CoreVideo has no value for, such as the FCC, log and
+        // chromaticity-derived ones without P3 primaries
+        for &code_points in &[
+            code_points(0, 0, 0),
+            code_points(2, 2, 2),
+            code_points(4, 9, 0),
+            code_points(10, 10, 8),
+            code_points(13, 11, 12),
+            code_points(3, 12, 14),
+        ] {
+            let color_description = ColorDescription::from_code_points(&code_points);
+            assert_eq!(color_description, ColorDescription::default());
+            assert_eq!(color_description.code_points(), ColorCodePoints::default());
+        }
+        assert_eq!(ColorDescription::from_code_points(&code_points(1, 1, 12)).matrix, None);
+        assert_eq!(ColorDescription::from_code_points(&code_points(12, 1, 12)).matrix, Some(CVImageBufferYCbCrMatrix::P3_D65));
+
+        // Attachments without a code point do not show up in the code points
+        let color_description = ColorDescription {
+            chroma_location_top: Some(CVImageBufferChromaLocation::Left),
+            chroma_subsampling: Some(CVImageBufferChromaSubsampling::_420),
+            alpha_mode: Some(CVImageBufferAlphaChannelMode::StraightAlpha),
+            ..Default::default()
+        };
+        assert_eq!(color_description.code_points(), ColorCodePoints::default());
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    #[test]
+    fn attachments() {
+        use crate::{
+            image_buffer::TCVImageBuffer,
+            pixel_buffer::{kCVPixelFormatType_420YpCbCr8BiPlanarVideoRange, CVPixelBuffer},
+        };
+
+        let pixel_buffer = CVPixelBuffer::new(kCVPixelFormatType_420YpCbCr8BiPlanarVideoRange, 16, 16, None).unwrap();
+        let image_buffer = pixel_buffer.as_image_buffer();
+        image_buffer.as_buffer().remove_all_attachments();
+        assert_eq!(image_buffer.get_color_description(), ColorDescription::default());
+
+        let color_description = ColorDescription {
+            chroma_location_top: Some(CVImageBufferChromaLocation::TopLeft),
+            chroma_location_bottom: Some(CVImageBufferChromaLocation::Left),
+            chroma_subsampling: Some(CVImageBufferChromaSubsampling::_420),
+            alpha_mode: Some(CVImageBufferAlphaChannelMode::PremultipliedAlpha),
+            ..ColorDescription::from_code_points(&code_points(9, 18, 9))
+        };
+        image_buffer.set_color_description(&color_description);
+        assert_eq!(image_buffer.get_color_description(), color_description);
+
+        let color_description = ColorDescription::from_code_points(&code_points(1, TRANSFER_CHARACTERISTICS_GAMMA_22, 6));
+        image_buffer.set_color_description(&color_description);
+        assert_eq!(image_buffer.get_color_description(), color_description);
+        assert_eq!(image_buffer.get_color_description().code_points(), code_points(1, TRANSFER_CHARACTERISTICS_GAMMA_22, 6));
+    }
+}
@@ -0,0 +1,278 @@
+use std::ops::Mul;
+
+use crate::{image_buffer::CVImageBufferColorPrimaries, pixel_buffer::CVPixelBuffer, pixel_format::PixelFormat, r#return::CVError};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Chromaticity {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Chromaticity {
+    #[inline]
+    pub const fn new(x: f64, y: f64) -> Self {
+        Chromaticity { x, y }
+    }
+
+    // XYZ of the chromaticity at unit luminance
+    #[inline]
+    pub fn to_xyz(&self) -> [f64; 3] {
+        [self.x / self.y, 1.0, (1.0 - self.x - self.y) / self.y]
+    }
+}
+
+pub(crate) const COLOR_PRIMARIES: [CVImageBufferColorPrimaries; 7] = [
+    CVImageBufferColorPrimaries::ITU_R_709_2,
+    CVImageBufferColorPrimaries::EBU_3213,
+    CVImageBufferColorPrimaries::SMPTE_C,
+    CVImageBufferColorPrimaries::P22,
+    CVImageBufferColorPrimaries::DCI_P3,
+    CVImageBufferColorPrimaries::P3_D65,
+    CVImageBufferColorPrimaries::ITU_R_2020,
+];
+
+pub const D65: Chromaticity = Chromaticity::new(0.3127, 0.3290);
+pub const DCI_WHITE: Chromaticity = Chromaticity::new(0.314, 0.351);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorPrimaries {
+    pub red: Chromaticity,
+    pub green: Chromaticity,
+    pub blue: Chromaticity,
+    pub white: Chromaticity,
+}
+
+impl From<CVImageBufferColorPrimaries> for ColorPrimaries {
+    fn from(color_primaries: CVImageBufferColorPrimaries) -> Self {
+        let (red, green, blue, white) = match color_primaries {
+            CVImageBufferColorPrimaries::ITU_R_709_2 => ((0.640, 0.330), (0.300, 0.600), (0.150, 0.060), D65),
+            CVImageBufferColorPrimaries::EBU_3213 => ((0.640, 0.330), (0.290, 0.600), (0.150, 0.060), D65),
+            CVImageBufferColorPrimaries::SMPTE_C => ((0.630, 0.340), (0.310, 0.595), (0.155, 0.070), D65),
+            CVImageBufferColorPrimaries::P22 => ((0.625, 0.340), (0.280, 0.595), (0.155, 0.070), D65),
+            CVImageBufferColorPrimaries::DCI_P3 => ((0.680, 0.320), (0.265, 0.690), (0.150, 0.060), DCI_WHITE),
+            CVImageBufferColorPrimaries::P3_D65 => ((0.680, 0.320), (0.265, 0.690), (0.150, 0.060), D65),
+            CVImageBufferColorPrimaries::ITU_R_2020 => ((0.708, 0.292), (0.170, 0.797), (0.131, 0.046), D65),
+        };
+        ColorPrimaries {
+            red: Chromaticity::new(red.0, red.1),
+            green: Chromaticity::new(green.0, green.1),
+            blue: Chromaticity::new(blue.0, blue.1),
+            white,
+        }
+    }
+}
+
+impl ColorPrimaries {
+    // Linear RGB to XYZ, scaled so that RGB white has the luminance 1
+    pub fn rgb_to_xyz(&self) -> ColorMatrix {
+        let [red, green, blue] = [self.red.to_xyz(), self.green.to_xyz(), self.blue.to_xyz()];
+        let primaries = ColorMatrix([[red[0], green[0], blue[0]], [red[1], green[1], blue[1]], [red[2], green[2], blue[2]]]);
+        let scale = primaries.inverse().unwrap_or_default().transform(self.white.to_xyz());
+        ColorMatrix(primaries.0.map(|row| [row[0] * scale[0], row[1] * scale[1], row[2] * scale[2]]))
+    }
+
+    #[inline]
+    pub fn xyz_to_rgb(&self) -> ColorMatrix {
+        self.rgb_to_xyz().inverse().unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorMatrix(pub [[f64; 3]; 3]);
+
+impl Default for ColorMatrix {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Mul for ColorMatrix {
+    type Output = ColorMatrix;
+
+    fn mul(self, other: ColorMatrix) -> ColorMatrix {
+        let mut product = [[0.0; 3]; 3];
+        for (row, product_row) in product.iter_mut().enumerate() {
+            for (column, product) in product_row.iter_mut().enumerate() {
+                *product = (0..3).map(|index| self.0[row][index] * other.0[index][column]).sum();
+            }
+        }
+        ColorMatrix(product)
+    }
+}
+
+const BRADFORD: ColorMatrix = ColorMatrix([[0.8951, 0.2664, -0.1614], [-0.7502, 1.7135, 0.0367], [0.0389, -0.0685, 1.0296]]);
+
+impl ColorMatrix {
+    #[inline]
+    pub const fn identity() -> Self {
+        ColorMatrix([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]])
+    }
+
+    pub fn inverse(&self) -> Option<ColorMatrix> {
+        let m = &self.0;
+        let cofactor = |row: usize, column: usize| {
+            let (r0, r1) = ((row + 1) % 3, (row + 2) % 3);
+            let (c0, c1) = ((column + 1) % 3, (column + 2) % 3);
+            m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+        };
+        let determinant = (0..3).map(|column| m[0][column] * cofactor(0, column)).sum::<f64>();
+        if determinant.abs() < f64::EPSILON {
+            return None;
+        }
+        let mut inverse = [[0.0; 3]; 3];
+        for (row, inverse_row) in inverse.iter_mut().enumerate() {
+            for (column, inverse) in inverse_row.iter_mut().enumerate() {
+                *inverse = cofactor(column, row) / determinant;
+            }
+        }
+        Some(ColorMatrix(inverse))
+    }
+
+    #[inline]
+    pub fn transform(&self, vector: [f64; 3]) -> [f64; 3] {
+        self.0.map(|row| row[0] * vector[0] + row[1] * vector[1] + row[2] * vector[2])
+    }
+
+    #[inline]
+    pub fn transform_f32(&self, vector: [f32; 3]) -> [f32; 3] {
+        self.transform(vector.map(f64::from)).map(|component| component as f32)
+    }
+
+    // Bradford chromatic adaptation of XYZ values from one white point to another
+    pub fn bradford(from: Chromaticity, to: Chromaticity) -> ColorMatrix {
+        let inverse = BRADFORD.inverse().unwrap_or_default();
+        let (from, to) = (BRADFORD.transform(from.to_xyz()), BRADFORD.transform(to.to_xyz()));
+        let scale = ColorMatrix([[to[0] / from[0], 0.0, 0.0], [0.0, to[1] / from[1], 0.0], [0.0, 0.0, to[2] / from[2]]]);
+        inverse * scale * BRADFORD
+    }
+
+    // Linear RGB in `from` primaries to linear RGB in `to` primaries, adapting white with Bradford when the white points
+    // differ, so that white stays white
+    pub fn primaries_conversion(from: CVImageBufferColorPrimaries, to: CVImageBufferColorPrimaries) -> ColorMatrix {
+        let (from, to) = (ColorPrimaries::from(from), ColorPrimaries::from(to));
+        let adaptation = if from.white == to.white { ColorMatrix::identity() } else { ColorMatrix::bradford(from.white, to.white) };
+        to.xyz_to_rgb() * adaptation * from.rgb_to_xyz()
+    }
+}
+
+// Applies `matrix` in place to the RGB components of a 64RGBAHalf (with the `half` feature) or 128RGBAFloat buffer, which
+// are expected to hold linear light; alpha is left untouched
+pub fn apply_color_matrix(pixel_buffer: &mut CVPixelBuffer, matrix: &ColorMatrix) -> Result<(), CVError> {
+    let pixel_format = PixelFormat::from(pixel_buffer.get_pixel_format());
+    let mut guard = pixel_buffer.lock_write()?;
+    let mut plane = guard.plane_mut(0).ok_or(CVError::InvalidArgument)?;
+    match pixel_format {
+        PixelFormat::_128RGBAFloat => {
+            for row in plane.rows_as_mut::<f32>() {
+                for pixel in row.ok_or(CVError::InvalidArgument)?.chunks_exact_mut(4) {
+                    let rgb = matrix.transform_f32([pixel[0], pixel[1], pixel[2]]);
+                    pixel[..3].copy_from_slice(&rgb);
+                }
+            }
+        }
+        #[cfg(feature = "half")]
+        PixelFormat::_64RGBAHalf => {
+            use half::f16;
+
+            for row in plane.rows_as_mut::<f16>() {
+                for pixel in row.ok_or(CVError::InvalidArgument)?.chunks_exact_mut(4) {
+                    let rgb = matrix.transform_f32([pixel[0].to_f32(), pixel[1].to_f32(), pixel[2].to_f32()]);
+                    pixel[..3].copy_from_slice(&rgb.map(f16::from_f32));
+                }
+            }
+        }
+        _ => return Err(CVError::InvalidPixelFormat),
+    }
+    Ok(())
+}
+
+pub fn convert_color_primaries(
+    pixel_buffer: &mut CVPixelBuffer,
+    from: CVImageBufferColorPrimaries,
+    to: CVImageBufferColorPrimaries,
+) -> Result<(), CVError> {
+    apply_color_matrix(pixel_buffer, &ColorMatrix::primaries_conversion(from, to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_matrix_close(actual: &ColorMatrix, expected: &[[f64; 3]; 3], tolerance: f64, context: &str) {
+        let close = actual
+            .0
+            .iter()
+            .flatten()
+            .zip(expected.iter().flatten())
+            .all(|(actual, expected)| (actual - expected).abs() <= tolerance);
+        assert!(close, "{}: {:?} != {:?}", context, actual.0, expected);
+    }
+
+    // IEC 61966-2-1 and ITU-R BT.2087, published to four decimals
+    #[test]
+    fn rgb_to_xyz() {
+        let bt709 = ColorPrimaries::from(CVImageBufferColorPrimaries::ITU_R_709_2);
+        let expected = [[0.4124, 0.3576, 0.1805], [0.2126, 0.7152, 0.0722], [0.0193, 0.1192, 0.9505]];
+        assert_matrix_close(&bt709.rgb_to_xyz(), &expected, 1e-4, "BT.709");
+
+        let bt2020 = ColorPrimaries::from(CVImageBufferColorPrimaries::ITU_R_2020);
+        let expected = [[0.6370, 0.1446, 0.1689], [0.2627, 0.6780, 0.0593], [0.0000, 0.0281, 1.0610]];
+        assert_matrix_close(&bt2020.rgb_to_xyz(), &expected, 1e-4, "BT.2020");
+
+        for &color_primaries in &COLOR_PRIMARIES {
+            let primaries = ColorPrimaries::from(color_primaries);
+            let white = primaries.rgb_to_xyz().transform([1.0, 1.0, 1.0]);
+            let expected = primaries.white.to_xyz();
+            assert!(white.iter().zip(&expected).all(|(a, b)| (a - b).abs() < 1e-12), "{:?}", color_primaries);
+            assert_matrix_close(
+                &(primaries.xyz_to_rgb() * primaries.rgb_to_xyz()),
+                &ColorMatrix::identity().0,
+                1e-12,
+                &format!("{:?}", color_primaries),
+            );
+        }
+    }
+
+    #[test]
+    fn bradford() {
+        assert_matrix_close(
+            &(ColorMatrix::bradford(D65, DCI_WHITE) * ColorMatrix::bradford(DCI_WHITE, D65)),
+            &ColorMatrix::identity().0,
+            1e-12,
+            "D65",
+        );
+        assert_matrix_close(&ColorMatrix::bradford(D65, D65), &ColorMatrix::identity().0, 1e-12, "D65 to D65");
+
+        let adapted = ColorMatrix::bradford(D65, DCI_WHITE).transform(D65.to_xyz());
+        assert!(adapted.iter().zip(&DCI_WHITE.to_xyz()).all(|(a, b)| (a - b).abs() < 1e-12));
+
+        // D65 to D50, as published by Lindbloom from nearly the same white points
+        let d50 = Chromaticity::new(0.3457, 0.3585);
+        let expected = [[1.0478, 0.0229, -0.0501], [0.0295, 0.9905, -0.0170], [-0.0092, 0.0150, 0.7521]];
+        assert_matrix_close(&ColorMatrix::bradford(D65, d50), &expected, 5e-4, "D65 to D50");
+    }
+
+    #[test]
+    fn primaries_conversion() {
+        for &color_primaries in &COLOR_PRIMARIES {
+            let conversion = ColorMatrix::primaries_conversion(color_primaries, color_primaries);
+            assert_matrix_close(&conversion, &ColorMatrix::identity().0, 1e-12, &format!("{:?}", color_primaries));
+            for &other in &COLOR_PRIMARIES {
+                let white = ColorMatrix::primaries_conversion(color_primaries, other).transform([1.0, 1.0, 1.0]);
+                assert!(white.iter().all(|component| (component - 1.0).abs() < 1e-12), "{:?} to {:?}", color_primaries, other);
+            }
+        }
+
+        // ITU-R BT.2087
+        let expected = [[0.6274, 0.3293, 0.0433], [0.0691, 0.9195, 0.0114], [0.0164, 0.0880, 0.8956]];
+        let conversion = ColorMatrix::primaries_conversion(CVImageBufferColorPrimaries::ITU_R_709_2, CVImageBufferColorPrimaries::ITU_R_2020);
+        assert_matrix_close(&conversion, &expected, 1e-4, "BT.709 to BT.2020");
+    }
+
+    #[test]
+    fn inverse() {
+        assert_eq!(ColorMatrix([[1.0, 2.0, 3.0], [2.0, 4.0, 6.0], [0.0, 0.0, 1.0]]).inverse(), None);
+        let matrix = ColorMatrix([[2.0, 0.0, 1.0], [1.0, 3.0, 0.0], [0.0, 1.0, 4.0]]);
+        assert_matrix_close(&(matrix * matrix.inverse().unwrap()), &ColorMatrix::identity().0, 1e-12, "inverse");
+    }
+}
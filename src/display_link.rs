@@ -12,7 +12,7 @@ pub type CVDisplayLinkRef = *mut __CVDisplayLink;
 
 use crate::{
     base::{CVOptionFlags, CVTime, CVTimeStamp},
-    r#return::{kCVReturnSuccess, CVReturn},
+    r#return::{CVError, CVReturn},
     CGLContextObj, CGLPixelFormatObj,
 };
 
@@ -74,65 +74,45 @@ impl_CFTypeDescription!(CVDisplayLink);
 
 impl CVDisplayLink {
     #[inline]
-    pub fn from_cg_displays(display_array: &[CGDirectDisplayID]) -> Result<CVDisplayLink, CVReturn> {
+    pub fn from_cg_displays(display_array: &[CGDirectDisplayID]) -> Result<CVDisplayLink, CVError> {
         let mut display_link: CVDisplayLinkRef = null_mut();
         unsafe {
             let result = CVDisplayLinkCreateWithCGDisplays(display_array.as_ptr(), display_array.len() as CFIndex, &mut display_link);
-            if result == kCVReturnSuccess {
-                Ok(TCFType::wrap_under_create_rule(display_link))
-            } else {
-                Err(result)
-            }
+            CVError::result(result).map(|_| TCFType::wrap_under_create_rule(display_link))
         }
     }
 
     #[inline]
-    pub fn from_opengl_display_mask(mask: CGOpenGLDisplayMask) -> Result<CVDisplayLink, CVReturn> {
+    pub fn from_opengl_display_mask(mask: CGOpenGLDisplayMask) -> Result<CVDisplayLink, CVError> {
         let mut display_link: CVDisplayLinkRef = null_mut();
         unsafe {
             let result = CVDisplayLinkCreateWithOpenGLDisplayMask(mask, &mut display_link);
-            if result == kCVReturnSuccess {
-                Ok(TCFType::wrap_under_create_rule(display_link))
-            } else {
-                Err(result)
-            }
+            CVError::result(result).map(|_| TCFType::wrap_under_create_rule(display_link))
         }
     }
 
     #[inline]
-    pub fn from_cg_display(display_id: CGDirectDisplayID) -> Result<CVDisplayLink, CVReturn> {
+    pub fn from_cg_display(display_id: CGDirectDisplayID) -> Result<CVDisplayLink, CVError> {
         let mut display_link: CVDisplayLinkRef = null_mut();
         unsafe {
             let result = CVDisplayLinkCreateWithCGDisplay(display_id, &mut display_link);
-            if result == kCVReturnSuccess {
-                Ok(TCFType::wrap_under_create_rule(display_link))
-            } else {
-                Err(result)
-            }
+            CVError::result(result).map(|_| TCFType::wrap_under_create_rule(display_link))
         }
     }
 
     #[inline]
-    pub fn from_active_cg_displays() -> Result<CVDisplayLink, CVReturn> {
+    pub fn from_active_cg_displays() -> Result<CVDisplayLink, CVError> {
         let mut display_link: CVDisplayLinkRef = null_mut();
         unsafe {
             let result = CVDisplayLinkCreateWithActiveCGDisplays(&mut display_link);
-            if result == kCVReturnSuccess {
-                Ok(TCFType::wrap_under_create_rule(display_link))
-            } else {
-                Err(result)
-            }
+            CVError::result(result).map(|_| TCFType::wrap_under_create_rule(display_link))
         }
     }
 
     #[inline]
-    pub fn set_current_cg_display(&self, display_id: CGDirectDisplayID) -> Result<(), CVReturn> {
+    pub fn set_current_cg_display(&self, display_id: CGDirectDisplayID) -> Result<(), CVError> {
         let result = unsafe { CVDisplayLinkSetCurrentCGDisplay(self.as_concrete_TypeRef(), display_id) };
-        if result == kCVReturnSuccess {
-            Ok(())
-        } else {
-            Err(result)
-        }
+        CVError::result(result)
     }
 
     #[inline]
@@ -140,13 +120,9 @@ impl CVDisplayLink {
         &self,
         cgl_context: CGLContextObj,
         cgl_pixel_format: CGLPixelFormatObj,
-    ) -> Result<(), CVReturn> {
+    ) -> Result<(), CVError> {
         let result = unsafe { CVDisplayLinkSetCurrentCGDisplayFromOpenGLContext(self.as_concrete_TypeRef(), cgl_context, cgl_pixel_format) };
-        if result == kCVReturnSuccess {
-            Ok(())
-        } else {
-            Err(result)
-        }
+        CVError::result(result)
     }
 
     #[inline]
@@ -154,16 +130,12 @@ impl CVDisplayLink {
         unsafe { CVDisplayLinkGetCurrentCGDisplay(self.as_concrete_TypeRef()) }
     }
 
-    pub unsafe fn set_output_callback(&self, callback: CVDisplayLinkOutputCallback, user_info: *mut c_void) -> Result<(), CVReturn> {
+    pub unsafe fn set_output_callback(&self, callback: CVDisplayLinkOutputCallback, user_info: *mut c_void) -> Result<(), CVError> {
         let result = unsafe { CVDisplayLinkSetOutputCallback(self.as_concrete_TypeRef(), callback, user_info) };
-        if result == kCVReturnSuccess {
-            Ok(())
-        } else {
-            Err(result)
-        }
+        CVError::result(result)
     }
 
-    pub fn set_output_closure<F>(&self, closure: Option<F>) -> Result<(), CVReturn>
+    pub fn set_output_closure<F>(&self, closure: Option<F>) -> Result<(), CVError>
     where
         F: Fn(&CVDisplayLink, &CVTimeStamp, &CVTimeStamp, CVOptionFlags, &mut CVOptionFlags) -> CVReturn + 'static,
     {
@@ -185,31 +157,19 @@ impl CVDisplayLink {
             .copy()
         });
         let result = unsafe { CVDisplayLinkSetOutputHandler(self.as_concrete_TypeRef(), handler.as_ref().map_or(null(), |h| &**h)) };
-        if result == kCVReturnSuccess {
-            Ok(())
-        } else {
-            Err(result)
-        }
+        CVError::result(result)
     }
 
     #[inline]
-    pub fn start(&self) -> Result<(), CVReturn> {
+    pub fn start(&self) -> Result<(), CVError> {
         let result = unsafe { CVDisplayLinkStart(self.as_concrete_TypeRef()) };
-        if result == kCVReturnSuccess {
-            Ok(())
-        } else {
-            Err(result)
-        }
+        CVError::result(result)
     }
 
     #[inline]
-    pub fn stop(&self) -> Result<(), CVReturn> {
+    pub fn stop(&self) -> Result<(), CVError> {
         let result = unsafe { CVDisplayLinkStop(self.as_concrete_TypeRef()) };
-        if result == kCVReturnSuccess {
-            Ok(())
-        } else {
-            Err(result)
-        }
+        CVError::result(result)
     }
 
     #[inline]
@@ -233,24 +193,16 @@ impl CVDisplayLink {
     }
 
     #[inline]
-    pub fn get_current_time(&self) -> Result<CVTime, CVReturn> {
+    pub fn get_current_time(&self) -> Result<CVTime, CVError> {
         let mut outTime = CVTime::default();
         let result = unsafe { CVDisplayLinkGetCurrentTime(self.as_concrete_TypeRef(), &mut outTime) };
-        if result == kCVReturnSuccess {
-            Ok(outTime)
-        } else {
-            Err(result)
-        }
+        CVError::result(result).map(|_| outTime)
     }
 
     #[inline]
-    pub fn translate_time(&self, in_time: &CVTime) -> Result<CVTime, CVReturn> {
+    pub fn translate_time(&self, in_time: &CVTime) -> Result<CVTime, CVError> {
         let mut out_time = CVTime::default();
         let result = unsafe { CVDisplayLinkTranslateTime(self.as_concrete_TypeRef(), in_time, &mut out_time) };
-        if result == kCVReturnSuccess {
-            Ok(out_time)
-        } else {
-            Err(result)
-        }
+        CVError::result(result).map(|_| out_time)
     }
 }
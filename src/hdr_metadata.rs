@@ -0,0 +1,275 @@
+use core_foundation::{base::TCFType, data::CFData, string::CFString};
+
+use crate::{
+    buffer::kCVAttachmentMode_ShouldPropagate,
+    color_primaries::{Chromaticity, ColorPrimaries},
+    image_buffer::{CVImageBuffer, CVImageBufferKeys},
+    r#return::CVError,
+};
+
+pub const MASTERING_DISPLAY_COLOR_VOLUME_SIZE: usize = 24;
+pub const CONTENT_LIGHT_LEVEL_INFO_SIZE: usize = 4;
+
+pub(crate) const CHROMATICITY_UNITS: f64 = 50000.0; /* chromaticity coordinates come in increments of 0.00002 */
+const LUMINANCE_UNITS: f64 = 10000.0; /* mastering luminances come in increments of 0.0001 cd/m² */
+
+#[inline]
+pub(crate) fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+#[inline]
+pub(crate) fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+#[inline]
+pub(crate) fn to_chromaticity(coordinates: (u16, u16)) -> Chromaticity {
+    Chromaticity::new(coordinates.0 as f64 / CHROMATICITY_UNITS, coordinates.1 as f64 / CHROMATICITY_UNITS)
+}
+
+#[inline]
+pub(crate) fn from_chromaticity(chromaticity: Chromaticity) -> (u16, u16) {
+    let coordinate = |value: f64| (value * CHROMATICITY_UNITS).round().clamp(0.0, CHROMATICITY_UNITS) as u16;
+    (coordinate(chromaticity.x), coordinate(chromaticity.y))
+}
+
+// SMPTE ST 2086 mastering display color volume, laid out as in the HEVC SEI message: the green, blue and red primaries
+// and the white point as x, y pairs of 16-bit values, then the maximum and minimum luminance as 32-bit values, all
+// big-endian
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct MasteringDisplayColorVolume {
+    pub display_primaries: [(u16, u16); 3], /* green, blue, red */
+    pub white_point: (u16, u16),
+    pub max_display_mastering_luminance: u32,
+    pub min_display_mastering_luminance: u32,
+}
+
+impl MasteringDisplayColorVolume {
+    // Luminances in cd/m²
+    pub fn new(primaries: &ColorPrimaries, max_luminance: f64, min_luminance: f64) -> Self {
+        let luminance = |value: f64| (value * LUMINANCE_UNITS).round().clamp(0.0, u32::MAX as f64) as u32;
+        MasteringDisplayColorVolume {
+            display_primaries: [from_chromaticity(primaries.green), from_chromaticity(primaries.blue), from_chromaticity(primaries.red)],
+            white_point: from_chromaticity(primaries.white),
+            max_display_mastering_luminance: luminance(max_luminance),
+            min_display_mastering_luminance: luminance(min_luminance),
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CVError> {
+        if bytes.len() != MASTERING_DISPLAY_COLOR_VOLUME_SIZE {
+            return Err(CVError::InvalidSize);
+        }
+        let coordinates = |offset: usize| (read_u16(bytes, offset), read_u16(bytes, offset + 2));
+        let mastering_display_color_volume = MasteringDisplayColorVolume {
+            display_primaries: [coordinates(0), coordinates(4), coordinates(8)],
+            white_point: coordinates(12),
+            max_display_mastering_luminance: read_u32(bytes, 16),
+            min_display_mastering_luminance: read_u32(bytes, 20),
+        };
+        mastering_display_color_volume.validate()?;
+        Ok(mastering_display_color_volume)
+    }
+
+    pub fn to_bytes(&self) -> Result<[u8; MASTERING_DISPLAY_COLOR_VOLUME_SIZE], CVError> {
+        self.validate()?;
+        let mut bytes = [0u8; MASTERING_DISPLAY_COLOR_VOLUME_SIZE];
+        for (chunk, &(x, y)) in bytes.chunks_exact_mut(4).zip(self.display_primaries.iter().chain([self.white_point].iter())) {
+            chunk[..2].copy_from_slice(&x.to_be_bytes());
+            chunk[2..].copy_from_slice(&y.to_be_bytes());
+        }
+        bytes[16..20].copy_from_slice(&self.max_display_mastering_luminance.to_be_bytes());
+        bytes[20..24].copy_from_slice(&self.min_display_mastering_luminance.to_be_bytes());
+        Ok(bytes)
+    }
+
+    // Coordinates must lie within [0, 1] and the minimum luminance below a nonzero maximum
+    pub fn validate(&self) -> Result<(), CVError> {
+        let limit = CHROMATICITY_UNITS as u16;
+        let coordinates_valid = self
+            .display_primaries
+            .iter()
+            .chain([self.white_point].iter())
+            .all(|&(x, y)| x <= limit && y <= limit);
+        if !coordinates_valid ||
+            self.max_display_mastering_luminance == 0 ||
+            self.min_display_mastering_luminance >= self.max_display_mastering_luminance
+        {
+            return Err(CVError::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    pub fn color_primaries(&self) -> ColorPrimaries {
+        let [green, blue, red] = self.display_primaries;
+        ColorPrimaries {
+            red: to_chromaticity(red),
+            green: to_chromaticity(green),
+            blue: to_chromaticity(blue),
+            white: to_chromaticity(self.white_point),
+        }
+    }
+
+    // In cd/m²
+    #[inline]
+    pub fn max_luminance(&self) -> f64 {
+        self.max_display_mastering_luminance as f64 / LUMINANCE_UNITS
+    }
+
+    // In cd/m²
+    #[inline]
+    pub fn min_luminance(&self) -> f64 {
+        self.min_display_mastering_luminance as f64 / LUMINANCE_UNITS
+    }
+}
+
+// CTA-861.3 content light level information, MaxCLL then MaxFALL in cd/m² as big-endian 16-bit values; zero stands for
+// unknown
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct ContentLightLevelInfo {
+    pub max_content_light_level: u16,
+    pub max_pic_average_light_level: u16,
+}
+
+impl ContentLightLevelInfo {
+    #[inline]
+    pub fn new(max_content_light_level: u16, max_pic_average_light_level: u16) -> Self {
+        ContentLightLevelInfo { max_content_light_level, max_pic_average_light_level }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CVError> {
+        if bytes.len() != CONTENT_LIGHT_LEVEL_INFO_SIZE {
+            return Err(CVError::InvalidSize);
+        }
+        // Not validated: encoders do emit a MaxFALL above MaxCLL, and such streams should still report their levels
+        Ok(Self::new(read_u16(bytes, 0), read_u16(bytes, 2)))
+    }
+
+    pub fn to_bytes(&self) -> Result<[u8; CONTENT_LIGHT_LEVEL_INFO_SIZE], CVError> {
+        self.validate()?;
+        let mut bytes = [0u8; CONTENT_LIGHT_LEVEL_INFO_SIZE];
+        bytes[..2].copy_from_slice(&self.max_content_light_level.to_be_bytes());
+        bytes[2..].copy_from_slice(&self.max_pic_average_light_level.to_be_bytes());
+        Ok(bytes)
+    }
+
+    // No frame average can be brighter than the brightest pixel, unless either is unknown; only checked when writing
+    pub fn validate(&self) -> Result<(), CVError> {
+        if self.max_content_light_level != 0 && self.max_pic_average_light_level > self.max_content_light_level {
+            return Err(CVError::InvalidArgument);
+        }
+        Ok(())
+    }
+}
+
+impl CVImageBuffer {
+    pub(crate) fn get_data_attachment(&self, key: CVImageBufferKeys) -> Option<CFData> {
+        let mut attachment_mode = kCVAttachmentMode_ShouldPropagate;
+        self.as_buffer().get_attachment(&CFString::from(key), &mut attachment_mode)?.downcast::<CFData>()
+    }
+
+    pub(crate) fn set_data_attachment(&self, key: CVImageBufferKeys, bytes: &[u8]) {
+        self.as_buffer()
+            .set_attachment(&CFString::from(key), &CFData::from_buffer(bytes).as_CFType(), kCVAttachmentMode_ShouldPropagate);
+    }
+
+    // None when the attachment is missing or malformed
+    pub fn get_mastering_display_color_volume(&self) -> Option<MasteringDisplayColorVolume> {
+        MasteringDisplayColorVolume::from_bytes(self.get_data_attachment(CVImageBufferKeys::MasteringDisplayColorVolume)?.bytes()).ok()
+    }
+
+    pub fn set_mastering_display_color_volume(&self, mastering_display_color_volume: &MasteringDisplayColorVolume) -> Result<(), CVError> {
+        self.set_data_attachment(CVImageBufferKeys::MasteringDisplayColorVolume, &mastering_display_color_volume.to_bytes()?);
+        Ok(())
+    }
+
+    // None when the attachment is missing or malformed
+    pub fn get_content_light_level_info(&self) -> Option<ContentLightLevelInfo> {
+        ContentLightLevelInfo::from_bytes(self.get_data_attachment(CVImageBufferKeys::ContentLightLevelInfo)?.bytes()).ok()
+    }
+
+    pub fn set_content_light_level_info(&self, content_light_level_info: &ContentLightLevelInfo) -> Result<(), CVError> {
+        self.set_data_attachment(CVImageBufferKeys::ContentLightLevelInfo, &content_light_level_info.to_bytes()?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_buffer::CVImageBufferColorPrimaries;
+
+    // A P3 D65 display mastered from 0.0001 to 1000 cd/m², as commonly signalled in HDR10 streams
+    const P3_D65_1000: [u8; MASTERING_DISPLAY_COLOR_VOLUME_SIZE] = [
+        0x33, 0xC2, 0x86, 0xC4, 0x1D, 0x4C, 0x0B, 0xB8, 0x84, 0xD0, 0x3E, 0x80, 0x3D, 0x13, 0x40, 0x42, 0x00, 0x98, 0x96, 0x80, 0x00, 0x00, 0x00,
+        0x01,
+    ];
+
+    #[test]
+    fn mastering_display_color_volume() {
+        let mastering_display_color_volume = MasteringDisplayColorVolume::new(&CVImageBufferColorPrimaries::P3_D65.into(), 1000.0, 0.0001);
+        assert_eq!(mastering_display_color_volume.display_primaries, [(13250, 34500), (7500, 3000), (34000, 16000)]);
+        assert_eq!(mastering_display_color_volume.white_point, (15635, 16450));
+        assert_eq!(mastering_display_color_volume.to_bytes(), Ok(P3_D65_1000));
+        assert_eq!(MasteringDisplayColorVolume::from_bytes(&P3_D65_1000), Ok(mastering_display_color_volume));
+        assert_eq!(mastering_display_color_volume.max_luminance(), 1000.0);
+        assert_eq!(mastering_display_color_volume.min_luminance(), 0.0001);
+        let primaries = mastering_display_color_volume.color_primaries();
+        assert_eq!((primaries.red.x, primaries.red.y), (0.68, 0.32));
+        assert_eq!((primaries.white.x, primaries.white.y), (0.3127, 0.329));
+
+        assert_eq!(MasteringDisplayColorVolume::from_bytes(&P3_D65_1000[..23]), Err(CVError::InvalidSize));
+        assert_eq!(MasteringDisplayColorVolume::from_bytes(&[0; 25]), Err(CVError::InvalidSize));
+        assert_eq!(MasteringDisplayColorVolume::from_bytes(&[]), Err(CVError::InvalidSize));
+
+        // Coordinates above 1 and a minimum luminance at or above the maximum are rejected both ways
+        let mut bytes = P3_D65_1000;
+        bytes[0..2].copy_from_slice(&50001u16.to_be_bytes());
+        assert_eq!(MasteringDisplayColorVolume::from_bytes(&bytes), Err(CVError::InvalidArgument));
+        let invalid = MasteringDisplayColorVolume { min_display_mastering_luminance: 10000000, ..mastering_display_color_volume };
+        assert_eq!(invalid.to_bytes(), Err(CVError::InvalidArgument));
+        assert_eq!(MasteringDisplayColorVolume::default().to_bytes(), Err(CVError::InvalidArgument));
+    }
+
+    #[test]
+    fn content_light_level_info() {
+        let content_light_level_info = ContentLightLevelInfo::new(1000, 400);
+        assert_eq!(content_light_level_info.to_bytes(), Ok([0x03, 0xE8, 0x01, 0x90]));
+        assert_eq!(ContentLightLevelInfo::from_bytes(&[0x03, 0xE8, 0x01, 0x90]), Ok(content_light_level_info));
+        assert_eq!(ContentLightLevelInfo::default().to_bytes(), Ok([0; CONTENT_LIGHT_LEVEL_INFO_SIZE]));
+        assert_eq!(ContentLightLevelInfo::new(0, 400).to_bytes(), Ok([0x00, 0x00, 0x01, 0x90]));
+
+        assert_eq!(ContentLightLevelInfo::from_bytes(&[0x03, 0xE8, 0x01]), Err(CVError::InvalidSize));
+        assert_eq!(ContentLightLevelInfo::from_bytes(&[0x03, 0xE8, 0x01, 0x90, 0x00]), Err(CVError::InvalidSize));
+
+        // A MaxFALL above MaxCLL is read as is but never written
+        let inconsistent = ContentLightLevelInfo::from_bytes(&[0x01, 0x90, 0x03, 0xE8]).unwrap();
+        assert_eq!(inconsistent, ContentLightLevelInfo::new(400, 1000));
+        assert_eq!(inconsistent.validate(), Err(CVError::InvalidArgument));
+        assert_eq!(inconsistent.to_bytes(), Err(CVError::InvalidArgument));
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    #[test]
+    fn attachments() {
+        use crate::{
+            image_buffer::TCVImageBuffer,
+            pixel_buffer::{kCVPixelFormatType_32BGRA, CVPixelBuffer},
+        };
+
+        let pixel_buffer = CVPixelBuffer::new(kCVPixelFormatType_32BGRA, 16, 16, None).unwrap();
+        let image_buffer = pixel_buffer.as_image_buffer();
+        assert_eq!(image_buffer.get_content_light_level_info(), None);
+        image_buffer.set_data_attachment(CVImageBufferKeys::ContentLightLevelInfo, &[0x01, 0x90, 0x03, 0xE8]);
+        assert_eq!(image_buffer.get_content_light_level_info(), Some(ContentLightLevelInfo::new(400, 1000)));
+        assert_eq!(image_buffer.set_content_light_level_info(&ContentLightLevelInfo::new(400, 1000)), Err(CVError::InvalidArgument));
+        image_buffer.set_content_light_level_info(&ContentLightLevelInfo::new(1000, 400)).unwrap();
+        assert_eq!(image_buffer.get_content_light_level_info(), Some(ContentLightLevelInfo::new(1000, 400)));
+
+        image_buffer.set_data_attachment(CVImageBufferKeys::MasteringDisplayColorVolume, &P3_D65_1000[..20]);
+        assert_eq!(image_buffer.get_mastering_display_color_volume(), None);
+        image_buffer.set_data_attachment(CVImageBufferKeys::MasteringDisplayColorVolume, &P3_D65_1000);
+        assert_eq!(image_buffer.get_mastering_display_color_volume().unwrap().to_bytes(), Ok(P3_D65_1000));
+    }
+}
@@ -1,3 +1,11 @@
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+use std::sync::OnceLock;
+use std::{
+    convert::TryFrom,
+    ops::{Add, Sub},
+    time::{Duration, Instant},
+};
+
 use crate::libc::c_double;
 
 extern "C" {
@@ -6,14 +14,304 @@ extern "C" {
     pub fn CVGetHostClockMinimumTimeDelta() -> u32;
 }
 
+const NANOSECONDS_PER_SECOND: u128 = 1_000_000_000;
+
+// Off Apple platforms the host clock is a monotonic clock counting nanoseconds from its first use
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+fn monotonic_epoch() -> Instant {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    *EPOCH.get_or_init(Instant::now)
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
 pub fn get_current_host_time() -> u64 {
     unsafe { CVGetCurrentHostTime() }
 }
 
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+pub fn get_current_host_time() -> u64 {
+    u64::try_from(monotonic_epoch().elapsed().as_nanos()).unwrap_or(u64::MAX)
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
 pub fn get_host_clock_frequency() -> f64 {
     unsafe { CVGetHostClockFrequency() }
 }
 
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+pub fn get_host_clock_frequency() -> f64 {
+    NANOSECONDS_PER_SECOND as f64
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
 pub fn get_host_clock_minimum_time_delta() -> u32 {
     unsafe { CVGetHostClockMinimumTimeDelta() }
 }
+
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+pub fn get_host_clock_minimum_time_delta() -> u32 {
+    1
+}
+
+// Conversions for a clock of `frequency` ticks per second
+fn ticks_to_nanos(ticks: u64, frequency: u64) -> u128 {
+    ticks as u128 * NANOSECONDS_PER_SECOND / frequency as u128
+}
+
+fn nanos_to_ticks(nanoseconds: u128, frequency: u64) -> Option<u64> {
+    let ticks = nanoseconds.checked_mul(frequency as u128)?.checked_add(NANOSECONDS_PER_SECOND - 1)? / NANOSECONDS_PER_SECOND;
+    u64::try_from(ticks).ok()
+}
+
+fn ticks_to_duration(ticks: u64, frequency: u64) -> Duration {
+    let nanoseconds = (ticks % frequency) as u128 * NANOSECONDS_PER_SECOND / frequency as u128;
+    Duration::new(ticks / frequency, nanoseconds as u32)
+}
+
+// Ticks of the host clock, as in CVTimeStamp.hostTime
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct HostTime(pub u64);
+
+impl HostTime {
+    #[inline]
+    pub fn now() -> Self {
+        HostTime(get_current_host_time())
+    }
+
+    #[inline]
+    pub const fn from_ticks(ticks: u64) -> Self {
+        HostTime(ticks)
+    }
+
+    #[inline]
+    pub const fn ticks(self) -> u64 {
+        self.0
+    }
+
+    // Whole ticks per second; the host clock frequencies are integers, so rounding only removes floating point noise
+    #[inline]
+    pub fn frequency() -> u64 {
+        (get_host_clock_frequency().round() as u64).max(1)
+    }
+
+    // Rounds down to whole nanoseconds
+    #[inline]
+    pub fn as_nanos(self) -> u128 {
+        ticks_to_nanos(self.0, HostTime::frequency())
+    }
+
+    // Rounds up, so converting the nanoseconds of a host time back gives the same host time
+    #[inline]
+    pub fn from_nanos(nanoseconds: u128) -> Option<Self> {
+        nanos_to_ticks(nanoseconds, HostTime::frequency()).map(HostTime)
+    }
+
+    // Rounds down to whole nanoseconds
+    #[inline]
+    pub fn to_duration(self) -> Duration {
+        ticks_to_duration(self.0, HostTime::frequency())
+    }
+
+    #[inline]
+    pub fn from_duration(duration: Duration) -> Option<Self> {
+        HostTime::from_nanos(duration.as_nanos())
+    }
+
+    #[inline]
+    pub fn checked_add(self, duration: Duration) -> Option<Self> {
+        self.0.checked_add(HostTime::from_duration(duration)?.0).map(HostTime)
+    }
+
+    #[inline]
+    pub fn checked_sub(self, duration: Duration) -> Option<Self> {
+        self.0.checked_sub(HostTime::from_duration(duration)?.0).map(HostTime)
+    }
+
+    // None when `earlier` is later than `self`
+    #[inline]
+    pub fn checked_duration_since(self, earlier: HostTime) -> Option<Duration> {
+        self.0.checked_sub(earlier.0).map(|ticks| HostTime(ticks).to_duration())
+    }
+
+    #[inline]
+    pub fn saturating_duration_since(self, earlier: HostTime) -> Duration {
+        self.checked_duration_since(earlier).unwrap_or_default()
+    }
+
+    #[inline]
+    pub fn elapsed(self) -> Duration {
+        HostTime::now().saturating_duration_since(self)
+    }
+
+    // The instant of the same moment, measured against the current time of both clocks; None when it is out of the
+    // range of Instant
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    pub fn to_instant(self) -> Option<Instant> {
+        let (now, instant) = (HostTime::now(), Instant::now());
+        if self >= now {
+            instant.checked_add(self.checked_duration_since(now)?)
+        } else {
+            instant.checked_sub(now.checked_duration_since(self)?)
+        }
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    pub fn to_instant(self) -> Option<Instant> {
+        monotonic_epoch().checked_add(self.to_duration())
+    }
+
+    // None when the instant is before the start of the host clock or too far ahead
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    pub fn from_instant(instant: Instant) -> Option<Self> {
+        let (now, now_instant) = (HostTime::now(), Instant::now());
+        match instant.checked_duration_since(now_instant) {
+            Some(duration) => now.checked_add(duration),
+            None => now.checked_sub(now_instant.duration_since(instant)),
+        }
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    pub fn from_instant(instant: Instant) -> Option<Self> {
+        HostTime::from_duration(instant.checked_duration_since(monotonic_epoch())?)
+    }
+}
+
+impl From<u64> for HostTime {
+    #[inline]
+    fn from(ticks: u64) -> Self {
+        HostTime(ticks)
+    }
+}
+
+impl From<HostTime> for u64 {
+    #[inline]
+    fn from(host_time: HostTime) -> Self {
+        host_time.0
+    }
+}
+
+impl Add<Duration> for HostTime {
+    type Output = HostTime;
+
+    fn add(self, duration: Duration) -> HostTime {
+        self.checked_add(duration).expect("overflow when adding duration to host time")
+    }
+}
+
+impl Sub<Duration> for HostTime {
+    type Output = HostTime;
+
+    fn sub(self, duration: Duration) -> HostTime {
+        self.checked_sub(duration).expect("overflow when subtracting duration from host time")
+    }
+}
+
+impl Sub for HostTime {
+    type Output = Duration;
+
+    // Saturates to zero like Instant when `earlier` is later
+    fn sub(self, earlier: HostTime) -> Duration {
+        self.saturating_duration_since(earlier)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{prelude::*, sample::select};
+
+    use super::*;
+
+    // The host clock frequencies of Apple silicon Macs and iOS devices, Intel Macs and the monotonic fallback, and one
+    // that does not divide a second evenly
+    const FREQUENCIES: [u64; 4] = [24_000_000, 1_000_000_000, 19_200_000, 7];
+
+    #[test]
+    fn rounding() {
+        let frequency = 24_000_000;
+        assert_eq!(ticks_to_nanos(1, frequency), 41);
+        assert_eq!(nanos_to_ticks(41, frequency), Some(1));
+        assert_eq!(nanos_to_ticks(42, frequency), Some(2));
+        assert_eq!(nanos_to_ticks(0, frequency), Some(0));
+        assert_eq!(ticks_to_duration(25, frequency), Duration::from_nanos(1041));
+        assert_eq!(ticks_to_duration(24_000_001, frequency), Duration::new(1, 41));
+        assert_eq!(ticks_to_duration(u64::MAX, frequency), Duration::new(u64::MAX / frequency, 564_650_625));
+        assert_eq!(ticks_to_duration(6, 7), Duration::from_nanos(857_142_857));
+        assert_eq!(nanos_to_ticks(u128::from(u64::MAX), 1_000_000_000), Some(u64::MAX));
+        assert_eq!(nanos_to_ticks(u128::from(u64::MAX) + 1, 1_000_000_000), None);
+        assert_eq!(nanos_to_ticks(u128::MAX, 24_000_000), None);
+
+        assert_eq!(HostTime::frequency(), 1_000_000_000);
+        assert_eq!(HostTime(1_500_000_000).to_duration(), Duration::from_millis(1500));
+        assert_eq!(HostTime(1_500_000_000).as_nanos(), 1_500_000_000);
+        assert_eq!(HostTime::from_duration(Duration::new(2, 5)), Some(HostTime(2_000_000_005)));
+    }
+
+    #[test]
+    fn overflow() {
+        let nanosecond = Duration::from_nanos(1);
+        assert_eq!(HostTime(u64::MAX - 1).checked_add(nanosecond), Some(HostTime(u64::MAX)));
+        assert_eq!(HostTime(u64::MAX).checked_add(nanosecond), None);
+        assert_eq!(HostTime(0).checked_add(Duration::MAX), None);
+        assert_eq!(HostTime(1).checked_sub(nanosecond), Some(HostTime(0)));
+        assert_eq!(HostTime(0).checked_sub(nanosecond), None);
+        assert_eq!(HostTime(u64::MAX).checked_sub(Duration::MAX), None);
+        assert_eq!(HostTime(10) + nanosecond - nanosecond, HostTime(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "overflow when adding duration to host time")]
+    fn add_overflow() {
+        let _ = HostTime(u64::MAX) + Duration::from_nanos(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "overflow when subtracting duration from host time")]
+    fn sub_overflow() {
+        let _ = HostTime(0) - Duration::from_nanos(1);
+    }
+
+    #[test]
+    fn sub_saturates() {
+        assert_eq!(HostTime(10) - HostTime(5), Duration::from_nanos(5));
+        assert_eq!(HostTime(5) - HostTime(10), Duration::ZERO);
+        assert_eq!(HostTime(5).checked_duration_since(HostTime(10)), None);
+        assert_eq!(HostTime(5).saturating_duration_since(HostTime(5)), Duration::ZERO);
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    #[test]
+    fn instants() {
+        let epoch = monotonic_epoch();
+        assert_eq!(HostTime::from_instant(epoch), Some(HostTime(0)));
+        assert_eq!(HostTime(0).to_instant(), Some(epoch));
+        if let Some(before) = epoch.checked_sub(Duration::from_secs(1)) {
+            assert_eq!(HostTime::from_instant(before), None);
+        }
+
+        let now = HostTime::now();
+        let instant = now.to_instant().unwrap();
+        assert_eq!(HostTime::from_instant(instant), Some(now));
+        assert_eq!(instant.duration_since(epoch), now.to_duration());
+        assert!(HostTime::now() >= now);
+        assert!(now.elapsed() <= instant.elapsed());
+        assert_eq!(HostTime::from_instant(instant + Duration::from_millis(1)), Some(now + Duration::from_millis(1)));
+    }
+
+    proptest! {
+        #[test]
+        fn ticks_round_trip(ticks in any::<u64>(), frequency in select(&FREQUENCIES[..])) {
+            prop_assert_eq!(nanos_to_ticks(ticks_to_nanos(ticks, frequency), frequency), Some(ticks));
+            prop_assert_eq!(ticks_to_duration(ticks, frequency).as_nanos(), ticks_to_nanos(ticks, frequency));
+        }
+
+        #[test]
+        fn nanos_round_up(nanoseconds in 0u128..1 << 80, frequency in select(&FREQUENCIES[..])) {
+            if let Some(ticks) = nanos_to_ticks(nanoseconds, frequency) {
+                // The fewest ticks that reach `nanoseconds`
+                prop_assert!(ticks as u128 * NANOSECONDS_PER_SECOND >= nanoseconds * frequency as u128);
+                prop_assert!(ticks == 0 || (ticks as u128 - 1) * NANOSECONDS_PER_SECOND < nanoseconds * frequency as u128);
+                prop_assert!(ticks_to_nanos(ticks, frequency) >= nanoseconds);
+            }
+        }
+    }
+}
@@ -1,17 +1,28 @@
 use std::mem;
 
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
 use core_foundation::{
     base::{Boolean, CFGetTypeID, CFType, CFTypeID, CFTypeRef, TCFType, TCFTypeRef},
-    dictionary::{CFDictionary, CFDictionaryRef},
+    number::CFNumber,
     string::{CFString, CFStringRef},
 };
+#[cfg(any(target_os = "macos", target_os = "ios"))]
 use core_graphics::{
     color_space::{CGColorSpace, CGColorSpaceRef},
     geometry::{CGRect, CGSize},
 };
 use libc::c_void;
 
-use crate::buffer::{CVBuffer, CVBufferRef, CVBufferRelease, CVBufferRetain, TCVBuffer};
+use crate::{
+    ambient_viewing_environment::AmbientViewingEnvironment,
+    buffer::{kCVAttachmentMode_ShouldPropagate, CVBuffer, CVBufferRef, CVBufferRelease, CVBufferRetain, TCVBuffer},
+    color_description::ColorDescription,
+    hdr_metadata::{ContentLightLevelInfo, MasteringDisplayColorVolume},
+    image_geometry::{CleanAperture, PixelAspectRatio},
+    r#return::CVError,
+    region_of_interest::RegionOfInterest,
+};
 
 pub type CVImageBufferRef = CVBufferRef;
 
@@ -87,11 +98,16 @@ extern "C" {
     pub fn CVColorPrimariesGetStringForIntegerCodePoint(colorPrimariesCodePoint: i32) -> CFStringRef;
     pub fn CVTransferFunctionGetStringForIntegerCodePoint(transferFunctionCodePoint: i32) -> CFStringRef;
 
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
     pub fn CVImageBufferGetEncodedSize(imageBuffer: CVImageBufferRef) -> CGSize;
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
     pub fn CVImageBufferGetDisplaySize(imageBuffer: CVImageBufferRef) -> CGSize;
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
     pub fn CVImageBufferGetCleanRect(imageBuffer: CVImageBufferRef) -> CGRect;
     pub fn CVImageBufferIsFlipped(imageBuffer: CVImageBufferRef) -> Boolean;
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
     pub fn CVImageBufferGetColorSpace(imageBuffer: CVImageBufferRef) -> CGColorSpaceRef;
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
     pub fn CVImageBufferCreateColorSpaceFromAttachments(attachments: CFDictionaryRef) -> CGColorSpaceRef;
 
     pub static kCVImageBufferMasteringDisplayColorVolumeKey: CFStringRef;
@@ -100,6 +116,7 @@ extern "C" {
     pub static kCVImageBufferRegionOfInterestKey: CFStringRef;
 }
 
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum CVImageBufferKeys {
     CGColorSpace,
     CleanAperture,
@@ -186,6 +203,32 @@ impl From<CVImageBufferFieldDetail> for CFString {
     }
 }
 
+pub enum CVImageBufferCleanAperture {
+    Width,
+    Height,
+    HorizontalOffset,
+    VerticalOffset,
+}
+
+impl From<CVImageBufferCleanAperture> for CFStringRef {
+    fn from(clean_aperture: CVImageBufferCleanAperture) -> CFStringRef {
+        unsafe {
+            match clean_aperture {
+                CVImageBufferCleanAperture::Width => kCVImageBufferCleanApertureWidthKey,
+                CVImageBufferCleanAperture::Height => kCVImageBufferCleanApertureHeightKey,
+                CVImageBufferCleanAperture::HorizontalOffset => kCVImageBufferCleanApertureHorizontalOffsetKey,
+                CVImageBufferCleanAperture::VerticalOffset => kCVImageBufferCleanApertureVerticalOffsetKey,
+            }
+        }
+    }
+}
+
+impl From<CVImageBufferCleanAperture> for CFString {
+    fn from(clean_aperture: CVImageBufferCleanAperture) -> CFString {
+        unsafe { CFString::wrap_under_get_rule(CFStringRef::from(clean_aperture)) }
+    }
+}
+
 pub enum CVImageBufferPixelAspectRatio {
     HorizontalSpacing,
     VerticalSpacing,
@@ -208,6 +251,7 @@ impl From<CVImageBufferPixelAspectRatio> for CFString {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum CVImageBufferYCbCrMatrix {
     ITU_R_709_2,
     ITU_R_601_4,
@@ -238,6 +282,41 @@ impl From<CVImageBufferYCbCrMatrix> for CFString {
     }
 }
 
+impl CVImageBufferYCbCrMatrix {
+    // ITU-T H.273 matrix_coefficients; the P3 matrices are the chromaticity-derived one, so from_integer_code_point
+    // cannot map them back and from_integer_code_point_with_primaries is needed for a round trip
+    pub fn integer_code_point(self) -> i32 {
+        match self {
+            CVImageBufferYCbCrMatrix::ITU_R_709_2 => 1,
+            CVImageBufferYCbCrMatrix::ITU_R_601_4 => 6,
+            CVImageBufferYCbCrMatrix::SMPTE_240M_1995 => 7,
+            CVImageBufferYCbCrMatrix::ITU_R_2020 => 9,
+            CVImageBufferYCbCrMatrix::DCI_P3 | CVImageBufferYCbCrMatrix::P3_D65 => 12,
+        }
+    }
+
+    // None for code points with no matrix of their own, among them the chromaticity-derived ones
+    pub fn from_integer_code_point(code_point: i32) -> Option<Self> {
+        match code_point {
+            1 => Some(CVImageBufferYCbCrMatrix::ITU_R_709_2),
+            5 | 6 => Some(CVImageBufferYCbCrMatrix::ITU_R_601_4),
+            7 => Some(CVImageBufferYCbCrMatrix::SMPTE_240M_1995),
+            9 => Some(CVImageBufferYCbCrMatrix::ITU_R_2020),
+            _ => None,
+        }
+    }
+
+    // Like from_integer_code_point, but resolves the chromaticity-derived code point when the primaries are P3
+    pub fn from_integer_code_point_with_primaries(code_point: i32, primaries: Option<CVImageBufferColorPrimaries>) -> Option<Self> {
+        match (code_point, primaries) {
+            (12, Some(CVImageBufferColorPrimaries::DCI_P3)) => Some(CVImageBufferYCbCrMatrix::DCI_P3),
+            (12, Some(CVImageBufferColorPrimaries::P3_D65)) => Some(CVImageBufferYCbCrMatrix::P3_D65),
+            _ => Self::from_integer_code_point(code_point),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum CVImageBufferColorPrimaries {
     ITU_R_709_2,
     EBU_3213,
@@ -270,6 +349,36 @@ impl From<CVImageBufferColorPrimaries> for CFString {
     }
 }
 
+impl CVImageBufferColorPrimaries {
+    // ITU-T H.273 colour_primaries
+    pub fn integer_code_point(self) -> i32 {
+        match self {
+            CVImageBufferColorPrimaries::ITU_R_709_2 => 1,
+            CVImageBufferColorPrimaries::EBU_3213 => 5,
+            CVImageBufferColorPrimaries::SMPTE_C => 6,
+            CVImageBufferColorPrimaries::ITU_R_2020 => 9,
+            CVImageBufferColorPrimaries::DCI_P3 => 11,
+            CVImageBufferColorPrimaries::P3_D65 => 12,
+            CVImageBufferColorPrimaries::P22 => 22,
+        }
+    }
+
+    // None for code points with no primaries of their own
+    pub fn from_integer_code_point(code_point: i32) -> Option<Self> {
+        match code_point {
+            1 => Some(CVImageBufferColorPrimaries::ITU_R_709_2),
+            5 => Some(CVImageBufferColorPrimaries::EBU_3213),
+            6 | 7 => Some(CVImageBufferColorPrimaries::SMPTE_C),
+            9 => Some(CVImageBufferColorPrimaries::ITU_R_2020),
+            11 => Some(CVImageBufferColorPrimaries::DCI_P3),
+            12 => Some(CVImageBufferColorPrimaries::P3_D65),
+            22 => Some(CVImageBufferColorPrimaries::P22),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum CVImageBufferTransferFunction {
     ITU_R_709_2,
     SMPTE_240M_1995,
@@ -306,6 +415,39 @@ impl From<CVImageBufferTransferFunction> for CFString {
     }
 }
 
+impl CVImageBufferTransferFunction {
+    // ITU-T H.273 transfer_characteristics; UseGamma is unspecified since the code point depends on the gamma level
+    pub fn integer_code_point(self) -> i32 {
+        match self {
+            CVImageBufferTransferFunction::ITU_R_709_2 => 1,
+            CVImageBufferTransferFunction::SMPTE_240M_1995 => 7,
+            CVImageBufferTransferFunction::Linear => 8,
+            CVImageBufferTransferFunction::sRGB => 13,
+            CVImageBufferTransferFunction::ITU_R_2020 => 14,
+            CVImageBufferTransferFunction::SMPTE_ST_2084_PQ => 16,
+            CVImageBufferTransferFunction::SMPTE_ST_428_1 => 17,
+            CVImageBufferTransferFunction::ITU_R_2100_HLG => 18,
+            CVImageBufferTransferFunction::UseGamma => 2,
+        }
+    }
+
+    // None for code points with no transfer function of their own, among them the pure gamma curves
+    pub fn from_integer_code_point(code_point: i32) -> Option<Self> {
+        match code_point {
+            1 | 6 => Some(CVImageBufferTransferFunction::ITU_R_709_2),
+            7 => Some(CVImageBufferTransferFunction::SMPTE_240M_1995),
+            8 => Some(CVImageBufferTransferFunction::Linear),
+            13 => Some(CVImageBufferTransferFunction::sRGB),
+            14 | 15 => Some(CVImageBufferTransferFunction::ITU_R_2020),
+            16 => Some(CVImageBufferTransferFunction::SMPTE_ST_2084_PQ),
+            17 => Some(CVImageBufferTransferFunction::SMPTE_ST_428_1),
+            18 => Some(CVImageBufferTransferFunction::ITU_R_2100_HLG),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum CVImageBufferChromaLocation {
     Left,
     Center,
@@ -338,6 +480,7 @@ impl From<CVImageBufferChromaLocation> for CFString {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum CVImageBufferChromaSubsampling {
     _420,
     _422,
@@ -362,6 +505,7 @@ impl From<CVImageBufferChromaSubsampling> for CFString {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum CVImageBufferAlphaChannelMode {
     StraightAlpha,
     PremultipliedAlpha,
@@ -384,6 +528,26 @@ impl From<CVImageBufferAlphaChannelMode> for CFString {
     }
 }
 
+// The enum among `candidates` whose string the attachment under `key` holds
+pub(crate) fn get_string_attachment<T: Copy + Into<CFString>>(buffer: &CVBuffer, key: CVImageBufferKeys, candidates: &[T]) -> Option<T> {
+    let mut attachment_mode = kCVAttachmentMode_ShouldPropagate;
+    let value = buffer.get_attachment(&CFString::from(key), &mut attachment_mode)?.downcast::<CFString>()?;
+    candidates.iter().copied().find(|&candidate| candidate.into() == value)
+}
+
+pub(crate) fn set_string_attachment<T: Into<CFString>>(buffer: &CVBuffer, key: CVImageBufferKeys, value: T) {
+    buffer.set_attachment(&CFString::from(key), &value.into().as_CFType(), kCVAttachmentMode_ShouldPropagate);
+}
+
+pub(crate) fn get_number_attachment(buffer: &CVBuffer, key: CVImageBufferKeys) -> Option<f64> {
+    let mut attachment_mode = kCVAttachmentMode_ShouldPropagate;
+    buffer.get_attachment(&CFString::from(key), &mut attachment_mode)?.downcast::<CFNumber>()?.to_f64()
+}
+
+pub(crate) fn set_number_attachment(buffer: &CVBuffer, key: CVImageBufferKeys, value: f64) {
+    buffer.set_attachment(&CFString::from(key), &CFNumber::from(value).as_CFType(), kCVAttachmentMode_ShouldPropagate);
+}
+
 pub fn ycbcr_matrix_get_integer_code_point_for_string(ycbcr_matrix_string: CFString) -> i32 {
     unsafe { CVYCbCrMatrixGetIntegerCodePointForString(ycbcr_matrix_string.as_concrete_TypeRef()) }
 }
@@ -491,6 +655,76 @@ pub trait TCVImageBuffer: TCVBuffer {
         mem::forget(self);
         unsafe { CVImageBuffer::wrap_under_create_rule(reference) }
     }
+
+    #[inline]
+    fn get_color_description(&self) -> ColorDescription {
+        self.as_image_buffer().get_color_description()
+    }
+
+    #[inline]
+    fn set_color_description(&self, color_description: &ColorDescription) {
+        self.as_image_buffer().set_color_description(color_description)
+    }
+
+    #[inline]
+    fn get_mastering_display_color_volume(&self) -> Option<MasteringDisplayColorVolume> {
+        self.as_image_buffer().get_mastering_display_color_volume()
+    }
+
+    #[inline]
+    fn set_mastering_display_color_volume(&self, mastering_display_color_volume: &MasteringDisplayColorVolume) -> Result<(), CVError> {
+        self.as_image_buffer().set_mastering_display_color_volume(mastering_display_color_volume)
+    }
+
+    #[inline]
+    fn get_content_light_level_info(&self) -> Option<ContentLightLevelInfo> {
+        self.as_image_buffer().get_content_light_level_info()
+    }
+
+    #[inline]
+    fn set_content_light_level_info(&self, content_light_level_info: &ContentLightLevelInfo) -> Result<(), CVError> {
+        self.as_image_buffer().set_content_light_level_info(content_light_level_info)
+    }
+
+    #[inline]
+    fn get_ambient_viewing_environment(&self) -> Option<AmbientViewingEnvironment> {
+        self.as_image_buffer().get_ambient_viewing_environment()
+    }
+
+    #[inline]
+    fn set_ambient_viewing_environment(&self, ambient_viewing_environment: &AmbientViewingEnvironment) -> Result<(), CVError> {
+        self.as_image_buffer().set_ambient_viewing_environment(ambient_viewing_environment)
+    }
+
+    #[inline]
+    fn get_region_of_interest(&self) -> Option<RegionOfInterest> {
+        self.as_image_buffer().get_region_of_interest()
+    }
+
+    #[inline]
+    fn set_region_of_interest(&self, region_of_interest: &RegionOfInterest) -> Result<(), CVError> {
+        self.as_image_buffer().set_region_of_interest(region_of_interest)
+    }
+
+    #[inline]
+    fn get_clean_aperture(&self) -> Option<CleanAperture> {
+        self.as_image_buffer().get_clean_aperture()
+    }
+
+    #[inline]
+    fn set_clean_aperture(&self, clean_aperture: &CleanAperture) -> Result<(), CVError> {
+        self.as_image_buffer().set_clean_aperture(clean_aperture)
+    }
+
+    #[inline]
+    fn get_pixel_aspect_ratio(&self) -> Option<PixelAspectRatio> {
+        self.as_image_buffer().get_pixel_aspect_ratio()
+    }
+
+    #[inline]
+    fn set_pixel_aspect_ratio(&self, pixel_aspect_ratio: &PixelAspectRatio) -> Result<(), CVError> {
+        self.as_image_buffer().set_pixel_aspect_ratio(pixel_aspect_ratio)
+    }
 }
 
 impl CVImageBuffer {
@@ -533,16 +767,19 @@ impl CVImageBuffer {
         unsafe { CVBuffer::wrap_under_create_rule(reference) }
     }
 
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
     #[inline]
     pub fn get_encoded_size(&self) -> CGSize {
         unsafe { CVImageBufferGetEncodedSize(self.as_concrete_TypeRef()) }
     }
 
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
     #[inline]
     pub fn get_display_size(&self) -> CGSize {
         unsafe { CVImageBufferGetDisplaySize(self.as_concrete_TypeRef()) }
     }
 
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
     #[inline]
     pub fn get_clean_rect(&self) -> CGRect {
         unsafe { CVImageBufferGetCleanRect(self.as_concrete_TypeRef()) }
@@ -553,6 +790,7 @@ impl CVImageBuffer {
         unsafe { CVImageBufferIsFlipped(self.as_concrete_TypeRef()) != 0 }
     }
 
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
     #[inline]
     pub fn get_color_space(&self) -> Option<CGColorSpace> {
         unsafe {
@@ -566,6 +804,7 @@ impl CVImageBuffer {
     }
 }
 
+#[cfg(any(target_os = "macos", target_os = "ios"))]
 pub fn create_color_space_from_attachments(attachments: &CFDictionary<CFString, CFType>) -> Option<CGColorSpace> {
     unsafe {
         let color_space = CVImageBufferCreateColorSpaceFromAttachments(attachments.as_concrete_TypeRef());
@@ -576,3 +815,71 @@ pub fn create_color_space_from_attachments(attachments: &CFDictionary<CFString,
         }
     }
 }
+
+#[cfg(all(test, any(target_os = "macos", target_os = "ios")))]
+mod tests {
+    use super::*;
+    use crate::{color_primaries::COLOR_PRIMARIES, transfer_function::TRANSFER_FUNCTIONS, ycbcr::YCBCR_MATRICES};
+
+    // The enum CoreVideo maps `code_point` to, None when it returns no string or one we have no value for
+    fn core_video_value<T: Copy + Into<CFString>>(
+        get_string: unsafe extern "C" fn(i32) -> CFStringRef,
+        code_point: i32,
+        candidates: &[T],
+    ) -> Option<T> {
+        let string = unsafe { get_string(code_point) };
+        if string.is_null() {
+            return None;
+        }
+        let string = unsafe { CFString::wrap_under_get_rule(string) };
+        candidates.iter().copied().find(|&candidate| candidate.into() == string)
+    }
+
+    #[test]
+    fn ycbcr_matrix_code_points() {
+        for &matrix in &YCBCR_MATRICES {
+            assert_eq!(ycbcr_matrix_get_integer_code_point_for_string(matrix.into()), matrix.integer_code_point(), "{:?}", matrix);
+        }
+        for code_point in 0..=255 {
+            let expected = core_video_value(CVYCbCrMatrixGetStringForIntegerCodePoint, code_point, &YCBCR_MATRICES);
+            if code_point == 12 {
+                // Chromaticity-derived, only resolved together with the primaries
+                assert_eq!(CVImageBufferYCbCrMatrix::from_integer_code_point(code_point), None);
+                assert!(matches!(expected, None | Some(CVImageBufferYCbCrMatrix::DCI_P3) | Some(CVImageBufferYCbCrMatrix::P3_D65)));
+                continue;
+            }
+            assert_eq!(CVImageBufferYCbCrMatrix::from_integer_code_point(code_point), expected, "{}", code_point);
+        }
+        for &primaries in &[CVImageBufferColorPrimaries::DCI_P3, CVImageBufferColorPrimaries::P3_D65] {
+            let matrix = CVImageBufferYCbCrMatrix::from_integer_code_point_with_primaries(12, Some(primaries)).unwrap();
+            assert_eq!(matrix.integer_code_point(), 12);
+        }
+    }
+
+    #[test]
+    fn color_primaries_code_points() {
+        for &primaries in &COLOR_PRIMARIES {
+            assert_eq!(color_primaries_get_integer_code_point_for_string(primaries.into()), primaries.integer_code_point(), "{:?}", primaries);
+        }
+        for code_point in 0..=255 {
+            let expected = core_video_value(CVColorPrimariesGetStringForIntegerCodePoint, code_point, &COLOR_PRIMARIES);
+            assert_eq!(CVImageBufferColorPrimaries::from_integer_code_point(code_point), expected, "{}", code_point);
+        }
+    }
+
+    #[test]
+    fn transfer_function_code_points() {
+        for &transfer_function in &TRANSFER_FUNCTIONS {
+            assert_eq!(
+                transfer_function_get_integer_code_point_for_string(transfer_function.into()),
+                transfer_function.integer_code_point(),
+                "{:?}",
+                transfer_function
+            );
+        }
+        for code_point in 0..=255 {
+            let expected = core_video_value(CVTransferFunctionGetStringForIntegerCodePoint, code_point, &TRANSFER_FUNCTIONS);
+            assert_eq!(CVImageBufferTransferFunction::from_integer_code_point(code_point), expected, "{}", code_point);
+        }
+    }
+}
@@ -0,0 +1,509 @@
+use std::convert::TryFrom;
+
+use core_foundation::{
+    array::CFArray,
+    base::{CFType, TCFType},
+    dictionary::{CFDictionary, CFDictionaryRef},
+    number::CFNumber,
+    string::CFString,
+};
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+use core_graphics::geometry::CGRect;
+
+use crate::{
+    buffer::kCVAttachmentMode_ShouldPropagate,
+    image_buffer::{CVImageBuffer, CVImageBufferCleanAperture, CVImageBufferKeys, CVImageBufferPixelAspectRatio},
+    r#return::CVError,
+};
+
+const FLOAT_DENOMINATOR: i64 = 65536; /* fractional numbers are read in 1/65536 steps, exact for halves and quarters */
+
+#[inline]
+fn gcd(mut a: i64, mut b: i64) -> i64 {
+    while b != 0 {
+        let remainder = a % b;
+        a = b;
+        b = remainder;
+    }
+    a.abs()
+}
+
+// Kept reduced with a positive denominator, so that equal values compare equal
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Rational {
+    pub numerator: i32,
+    pub denominator: i32,
+}
+
+impl Rational {
+    // None when the reduced terms do not fit in an i32, as with i32::MIN / -1; a zero denominator is kept as is
+    pub fn new(numerator: i32, denominator: i32) -> Option<Self> {
+        let divisor = gcd(numerator as i64, denominator as i64);
+        if divisor == 0 {
+            return Some(Rational { numerator, denominator });
+        }
+        let sign = if denominator < 0 { -1 } else { 1 };
+        Some(Rational {
+            numerator: i32::try_from(sign * numerator as i64 / divisor).ok()?,
+            denominator: i32::try_from(sign * denominator as i64 / divisor).ok()?,
+        })
+    }
+
+    #[inline]
+    pub fn is_valid(&self) -> bool {
+        self.denominator != 0
+    }
+
+    #[inline]
+    pub fn to_f64(&self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    // A plain number, or a [numerator, denominator] array as written by QuickTime and AVFoundation
+    fn from_value(value: &CFType) -> Option<Self> {
+        if let Some(number) = value.downcast::<CFNumber>() {
+            let value = number.to_f64()?;
+            if value.fract() == 0.0 {
+                return i32::try_from(value as i64).ok().map(Rational::from);
+            }
+            let numerator = (value * FLOAT_DENOMINATOR as f64).round() as i64;
+            return Rational::new(i32::try_from(numerator).ok()?, FLOAT_DENOMINATOR as i32);
+        }
+        let array = value.downcast::<CFArray>()?;
+        let array: CFArray<CFType> = unsafe { CFArray::wrap_under_get_rule(array.as_concrete_TypeRef()) };
+        if array.len() != 2 {
+            return None;
+        }
+        let term = |index: isize| array.get(index)?.downcast::<CFNumber>()?.to_i32();
+        let rational = Rational::new(term(0)?, term(1)?)?;
+        if rational.is_valid() {
+            Some(rational)
+        } else {
+            None
+        }
+    }
+
+    fn to_value(self) -> CFType {
+        if self.denominator == 1 {
+            CFNumber::from(self.numerator).as_CFType()
+        } else {
+            CFArray::from_CFTypes(&[CFNumber::from(self.numerator), CFNumber::from(self.denominator)]).as_CFType()
+        }
+    }
+}
+
+impl From<i32> for Rational {
+    #[inline]
+    fn from(value: i32) -> Self {
+        Rational { numerator: value, denominator: 1 }
+    }
+}
+
+fn find_value<K: Into<CFString>>(dictionary: &CFDictionary<CFString, CFType>, key: K) -> Option<CFType> {
+    dictionary.find(key.into()).map(|value| value.clone())
+}
+
+fn get_dictionary_attachment(image_buffer: &CVImageBuffer, key: CVImageBufferKeys) -> Option<CFDictionary<CFString, CFType>> {
+    let mut attachment_mode = kCVAttachmentMode_ShouldPropagate;
+    let value = image_buffer.as_buffer().get_attachment(&CFString::from(key), &mut attachment_mode)?;
+    if !value.instance_of::<CFDictionary>() {
+        return None;
+    }
+    Some(unsafe { CFDictionary::wrap_under_get_rule(value.as_CFTypeRef() as CFDictionaryRef) })
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CleanRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+impl From<CleanRect> for CGRect {
+    fn from(clean_rect: CleanRect) -> Self {
+        CGRect::new(clean_rect.x, clean_rect.y, clean_rect.width, clean_rect.height)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct CropRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+// The picture area of the encoded image, as in kCVImageBufferCleanApertureKey; the offsets move the center of the clean
+// aperture away from the center of the encoded image, to the right and downwards when positive
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct CleanAperture {
+    pub width: Rational,
+    pub height: Rational,
+    pub horizontal_offset: Rational,
+    pub vertical_offset: Rational,
+}
+
+impl CleanAperture {
+    #[inline]
+    pub fn new(width: Rational, height: Rational, horizontal_offset: Rational, vertical_offset: Rational) -> Self {
+        CleanAperture { width, height, horizontal_offset, vertical_offset }
+    }
+
+    // Clean aperture of `width` by `height` pixels centered in the encoded image
+    #[inline]
+    pub fn centered(width: u32, height: u32) -> Self {
+        CleanAperture::new(Rational::from(width as i32), Rational::from(height as i32), Rational::from(0), Rational::from(0))
+    }
+
+    pub fn from_dictionary(dictionary: &CFDictionary<CFString, CFType>) -> Result<Self, CVError> {
+        let find = |key: CVImageBufferCleanAperture| find_value(dictionary, key).and_then(|value| Rational::from_value(&value));
+        let clean_aperture = CleanAperture {
+            width: find(CVImageBufferCleanAperture::Width).ok_or(CVError::InvalidArgument)?,
+            height: find(CVImageBufferCleanAperture::Height).ok_or(CVError::InvalidArgument)?,
+            horizontal_offset: find(CVImageBufferCleanAperture::HorizontalOffset).ok_or(CVError::InvalidArgument)?,
+            vertical_offset: find(CVImageBufferCleanAperture::VerticalOffset).ok_or(CVError::InvalidArgument)?,
+        };
+        clean_aperture.validate()?;
+        Ok(clean_aperture)
+    }
+
+    pub fn to_dictionary(&self) -> Result<CFDictionary<CFString, CFType>, CVError> {
+        self.validate()?;
+        Ok(CFDictionary::from_CFType_pairs(&[
+            (CFString::from(CVImageBufferCleanAperture::Width), self.width.to_value()),
+            (CFString::from(CVImageBufferCleanAperture::Height), self.height.to_value()),
+            (CFString::from(CVImageBufferCleanAperture::HorizontalOffset), self.horizontal_offset.to_value()),
+            (CFString::from(CVImageBufferCleanAperture::VerticalOffset), self.vertical_offset.to_value()),
+        ]))
+    }
+
+    // Every denominator must be non-zero and the size must be positive
+    pub fn validate(&self) -> Result<(), CVError> {
+        let terms = [self.width, self.height, self.horizontal_offset, self.vertical_offset];
+        if !terms.iter().all(Rational::is_valid) || self.width.numerator <= 0 || self.height.numerator <= 0 {
+            return Err(CVError::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    // The clean aperture in encoded pixels from the top left corner, fractional and unclamped like
+    // CVImageBufferGetCleanRect
+    pub fn clean_rect(&self, encoded_width: usize, encoded_height: usize) -> CleanRect {
+        let (width, height) = (self.width.to_f64(), self.height.to_f64());
+        CleanRect {
+            x: (encoded_width as f64 - width) / 2.0 + self.horizontal_offset.to_f64(),
+            y: (encoded_height as f64 - height) / 2.0 + self.vertical_offset.to_f64(),
+            width,
+            height,
+        }
+    }
+
+    // The whole pixels covered by the clean rect, clipped to the encoded image; None when nothing is left
+    pub fn crop(&self, encoded_width: usize, encoded_height: usize) -> Option<CropRect> {
+        let clean_rect = self.clean_rect(encoded_width, encoded_height);
+        let left = clean_rect.x.floor().clamp(0.0, encoded_width as f64) as usize;
+        let top = clean_rect.y.floor().clamp(0.0, encoded_height as f64) as usize;
+        let right = (clean_rect.x + clean_rect.width).ceil().clamp(0.0, encoded_width as f64) as usize;
+        let bottom = (clean_rect.y + clean_rect.height).ceil().clamp(0.0, encoded_height as f64) as usize;
+        if right <= left || bottom <= top {
+            return None;
+        }
+        Some(CropRect { x: left, y: top, width: right - left, height: bottom - top })
+    }
+}
+
+// Relative width and height of a pixel, as in kCVImageBufferPixelAspectRatioKey
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct PixelAspectRatio {
+    pub horizontal_spacing: u32,
+    pub vertical_spacing: u32,
+}
+
+impl Default for PixelAspectRatio {
+    fn default() -> Self {
+        PixelAspectRatio { horizontal_spacing: 1, vertical_spacing: 1 }
+    }
+}
+
+impl PixelAspectRatio {
+    #[inline]
+    pub fn new(horizontal_spacing: u32, vertical_spacing: u32) -> Self {
+        PixelAspectRatio { horizontal_spacing, vertical_spacing }
+    }
+
+    #[inline]
+    pub fn is_square(&self) -> bool {
+        self.horizontal_spacing == self.vertical_spacing
+    }
+
+    pub fn from_dictionary(dictionary: &CFDictionary<CFString, CFType>) -> Result<Self, CVError> {
+        let find = |key: CVImageBufferPixelAspectRatio| {
+            find_value(dictionary, key)
+                .and_then(|value| value.downcast::<CFNumber>())
+                .and_then(|number| number.to_i64())
+                .and_then(|spacing| u32::try_from(spacing).ok())
+        };
+        let pixel_aspect_ratio = PixelAspectRatio {
+            horizontal_spacing: find(CVImageBufferPixelAspectRatio::HorizontalSpacing).ok_or(CVError::InvalidArgument)?,
+            vertical_spacing: find(CVImageBufferPixelAspectRatio::VerticalSpacing).ok_or(CVError::InvalidArgument)?,
+        };
+        pixel_aspect_ratio.validate()?;
+        Ok(pixel_aspect_ratio)
+    }
+
+    pub fn to_dictionary(&self) -> Result<CFDictionary<CFString, CFType>, CVError> {
+        self.validate()?;
+        Ok(CFDictionary::from_CFType_pairs(&[
+            (CFString::from(CVImageBufferPixelAspectRatio::HorizontalSpacing), CFNumber::from(self.horizontal_spacing as i64).as_CFType()),
+            (CFString::from(CVImageBufferPixelAspectRatio::VerticalSpacing), CFNumber::from(self.vertical_spacing as i64).as_CFType()),
+        ]))
+    }
+
+    pub fn validate(&self) -> Result<(), CVError> {
+        if self.horizontal_spacing == 0 || self.vertical_spacing == 0 {
+            return Err(CVError::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    // Size in square pixels of a `width` by `height` picture; only the width is scaled
+    #[inline]
+    pub fn display_size(&self, width: f64, height: f64) -> (f64, f64) {
+        (width * self.horizontal_spacing as f64 / self.vertical_spacing as f64, height)
+    }
+}
+
+// Nominal display size in square pixels of an encoded image, like CVImageBufferGetDisplaySize: the clean aperture, or
+// the whole image without one, stretched horizontally by the pixel aspect ratio
+pub fn display_size(
+    encoded_width: usize,
+    encoded_height: usize,
+    clean_aperture: Option<&CleanAperture>,
+    pixel_aspect_ratio: Option<&PixelAspectRatio>,
+) -> (f64, f64) {
+    let (width, height) = match clean_aperture {
+        Some(clean_aperture) => (clean_aperture.width.to_f64(), clean_aperture.height.to_f64()),
+        None => (encoded_width as f64, encoded_height as f64),
+    };
+    pixel_aspect_ratio.copied().unwrap_or_default().display_size(width, height)
+}
+
+// Display size rounded to whole pixels, as for kCVImageBufferDisplayDimensionsKey
+pub fn display_dimensions(
+    encoded_width: usize,
+    encoded_height: usize,
+    clean_aperture: Option<&CleanAperture>,
+    pixel_aspect_ratio: Option<&PixelAspectRatio>,
+) -> (usize, usize) {
+    let (width, height) = display_size(encoded_width, encoded_height, clean_aperture, pixel_aspect_ratio);
+    (width.round() as usize, height.round() as usize)
+}
+
+impl CVImageBuffer {
+    // None when the attachment is missing or malformed
+    pub fn get_clean_aperture(&self) -> Option<CleanAperture> {
+        CleanAperture::from_dictionary(&get_dictionary_attachment(self, CVImageBufferKeys::CleanAperture)?).ok()
+    }
+
+    pub fn set_clean_aperture(&self, clean_aperture: &CleanAperture) -> Result<(), CVError> {
+        self.as_buffer().set_attachment(
+            &CFString::from(CVImageBufferKeys::CleanAperture),
+            &clean_aperture.to_dictionary()?.as_CFType(),
+            kCVAttachmentMode_ShouldPropagate,
+        );
+        Ok(())
+    }
+
+    // None when the attachment is missing or malformed
+    pub fn get_pixel_aspect_ratio(&self) -> Option<PixelAspectRatio> {
+        PixelAspectRatio::from_dictionary(&get_dictionary_attachment(self, CVImageBufferKeys::PixelAspectRatio)?).ok()
+    }
+
+    pub fn set_pixel_aspect_ratio(&self, pixel_aspect_ratio: &PixelAspectRatio) -> Result<(), CVError> {
+        self.as_buffer().set_attachment(
+            &CFString::from(CVImageBufferKeys::PixelAspectRatio),
+            &pixel_aspect_ratio.to_dictionary()?.as_CFType(),
+            kCVAttachmentMode_ShouldPropagate,
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rational() {
+        assert_eq!(Rational::new(6, -4), Some(Rational { numerator: -3, denominator: 2 }));
+        assert_eq!(Rational::new(i32::MIN, 2), Some(Rational { numerator: i32::MIN / 2, denominator: 1 }));
+        assert_eq!(Rational::new(i32::MIN, -1), None);
+        assert_eq!(Rational::new(1, i32::MIN), None);
+        assert!(!Rational::new(3, 0).unwrap().is_valid());
+    }
+
+    // A 720×486 NTSC frame with its usual 704×480 picture area, and apertures that are off center, fractional or
+    // larger than the frame
+    const ENCODED_SIZE: (usize, usize) = (720, 486);
+
+    fn off_center() -> CleanAperture {
+        CleanAperture::new(Rational::new(1405, 2).unwrap(), Rational::from(480), Rational::new(-3, 4).unwrap(), Rational::new(5, 2).unwrap())
+    }
+
+    #[test]
+    fn clean_rect() {
+        let (width, height) = ENCODED_SIZE;
+        let clean_rect = |clean_aperture: CleanAperture| clean_aperture.clean_rect(width, height);
+        assert_eq!(clean_rect(CleanAperture::centered(704, 480)), CleanRect { x: 8.0, y: 3.0, width: 704.0, height: 480.0 });
+        assert_eq!(clean_rect(CleanAperture::centered(703, 479)), CleanRect { x: 8.5, y: 3.5, width: 703.0, height: 479.0 });
+        assert_eq!(clean_rect(off_center()), CleanRect { x: 8.0, y: 5.5, width: 702.5, height: 480.0 });
+        let oversized = CleanAperture::new(Rational::from(800), Rational::from(500), Rational::from(12), Rational::from(-7));
+        assert_eq!(clean_rect(oversized), CleanRect { x: -28.0, y: -14.0, width: 800.0, height: 500.0 });
+    }
+
+    #[test]
+    fn crop() {
+        let (width, height) = ENCODED_SIZE;
+        let crop = |clean_aperture: CleanAperture| clean_aperture.crop(width, height);
+        assert_eq!(crop(CleanAperture::centered(704, 480)), Some(CropRect { x: 8, y: 3, width: 704, height: 480 }));
+        assert_eq!(crop(CleanAperture::centered(720, 486)), Some(CropRect { x: 0, y: 0, width: 720, height: 486 }));
+
+        // Partly covered pixels are kept, and whatever lies outside the frame is clipped
+        assert_eq!(crop(CleanAperture::centered(703, 479)), Some(CropRect { x: 8, y: 3, width: 704, height: 480 }));
+        assert_eq!(crop(off_center()), Some(CropRect { x: 8, y: 5, width: 703, height: 481 }));
+        let oversized = CleanAperture::new(Rational::from(800), Rational::from(500), Rational::from(12), Rational::from(-7));
+        assert_eq!(crop(oversized), Some(CropRect { x: 0, y: 0, width: 720, height: 486 }));
+        let outside = CleanAperture::new(Rational::from(16), Rational::from(16), Rational::from(400), Rational::from(0));
+        assert_eq!(crop(outside), None);
+    }
+
+    #[test]
+    fn validate() {
+        assert_eq!(CleanAperture::centered(704, 480).validate(), Ok(()));
+        assert_eq!(CleanAperture::centered(0, 480).validate(), Err(CVError::InvalidArgument));
+        let negative = CleanAperture::new(Rational::from(-704), Rational::from(480), Rational::from(0), Rational::from(0));
+        assert_eq!(negative.validate(), Err(CVError::InvalidArgument));
+        let zero_denominator = CleanAperture::new(Rational::from(704), Rational::from(480), Rational::new(1, 0).unwrap(), Rational::from(0));
+        assert_eq!(zero_denominator.validate(), Err(CVError::InvalidArgument));
+        assert_eq!(PixelAspectRatio::new(0, 1).validate(), Err(CVError::InvalidArgument));
+        assert!(PixelAspectRatio::default().is_square());
+    }
+
+    #[test]
+    fn display_dimensions() {
+        let (width, height) = ENCODED_SIZE;
+        let display_dimensions = |clean_aperture: Option<CleanAperture>, pixel_aspect_ratio: Option<PixelAspectRatio>| {
+            super::display_dimensions(width, height, clean_aperture.as_ref(), pixel_aspect_ratio.as_ref())
+        };
+        let clean_aperture = Some(CleanAperture::centered(704, 480));
+        assert_eq!(display_dimensions(None, None), (720, 486));
+        assert_eq!(display_dimensions(clean_aperture, None), (704, 480));
+        assert_eq!(display_dimensions(clean_aperture, Some(PixelAspectRatio::new(10, 11))), (640, 480));
+        assert_eq!(display_dimensions(clean_aperture, Some(PixelAspectRatio::new(40, 33))), (853, 480));
+        assert_eq!(display_dimensions(None, Some(PixelAspectRatio::new(10, 11))), (655, 486));
+        assert_eq!(display_dimensions(Some(off_center()), Some(PixelAspectRatio::new(3, 2))), (1054, 480));
+        assert_eq!(super::display_size(width, height, Some(&off_center()), None), (702.5, 480.0));
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    #[test]
+    fn dictionary_round_trip() {
+        let clean_aperture = off_center();
+        let dictionary = clean_aperture.to_dictionary().unwrap();
+        let width = find_value(&dictionary, CVImageBufferCleanAperture::Width).unwrap();
+        let width: CFArray<CFNumber> = unsafe { CFArray::wrap_under_get_rule(width.downcast::<CFArray>().unwrap().as_concrete_TypeRef()) };
+        assert_eq!(width.iter().map(|term| term.to_i32().unwrap()).collect::<Vec<_>>(), [1405, 2]);
+        let height = find_value(&dictionary, CVImageBufferCleanAperture::Height).unwrap();
+        assert_eq!(height.downcast::<CFNumber>().unwrap().to_i32(), Some(480));
+        assert_eq!(CleanAperture::from_dictionary(&dictionary), Ok(clean_aperture));
+        assert_eq!(CleanAperture::centered(0, 480).to_dictionary().err(), Some(CVError::InvalidArgument));
+
+        let pixel_aspect_ratio = PixelAspectRatio::new(40, 33);
+        assert_eq!(PixelAspectRatio::from_dictionary(&pixel_aspect_ratio.to_dictionary().unwrap()), Ok(pixel_aspect_ratio));
+        assert_eq!(PixelAspectRatio::new(40, 0).to_dictionary().err(), Some(CVError::InvalidArgument));
+
+        // Floats and [numerator, denominator] arrays, as other writers store them
+        let number = |value: f64| CFNumber::from(value).as_CFType();
+        let array = |terms: &[i32]| CFArray::from_CFTypes(&terms.iter().map(|&term| CFNumber::from(term)).collect::<Vec<_>>()).as_CFType();
+        assert_eq!(Rational::from_value(&number(480.0)), Some(Rational::from(480)));
+        assert_eq!(Rational::from_value(&number(702.5)), Rational::new(1405, 2));
+        assert_eq!(Rational::from_value(&number(-0.75)), Rational::new(-3, 4));
+        assert_eq!(Rational::from_value(&number(0.1)), Rational::new(6554, 65536));
+        assert_eq!(Rational::from_value(&number(1e10)), None);
+        assert_eq!(Rational::from_value(&array(&[1405, 2])), Rational::new(1405, 2));
+        assert_eq!(Rational::from_value(&array(&[10, -4])), Rational::new(-5, 2));
+        assert_eq!(Rational::from_value(&array(&[3, 0])), None);
+        assert_eq!(Rational::from_value(&array(&[1, 2, 3])), None);
+        assert_eq!(Rational::from_value(&CFString::from_static_string("704").as_CFType()), None);
+
+        let dictionary = CFDictionary::from_CFType_pairs(&[
+            (CFString::from(CVImageBufferCleanAperture::Width), number(702.5)),
+            (CFString::from(CVImageBufferCleanAperture::Height), array(&[960, 2])),
+            (CFString::from(CVImageBufferCleanAperture::HorizontalOffset), number(-0.75)),
+            (CFString::from(CVImageBufferCleanAperture::VerticalOffset), array(&[5, 2])),
+        ]);
+        assert_eq!(CleanAperture::from_dictionary(&dictionary), Ok(clean_aperture));
+        let dictionary = CFDictionary::from_CFType_pairs(&[
+            (CFString::from(CVImageBufferCleanAperture::Width), number(702.5)),
+            (CFString::from(CVImageBufferCleanAperture::Height), array(&[480, 0])),
+            (CFString::from(CVImageBufferCleanAperture::HorizontalOffset), number(0.0)),
+            (CFString::from(CVImageBufferCleanAperture::VerticalOffset), number(0.0)),
+        ]);
+        assert_eq!(CleanAperture::from_dictionary(&dictionary), Err(CVError::InvalidArgument));
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    #[test]
+    fn matches_core_video() {
+        use crate::{
+            image_buffer::TCVImageBuffer,
+            pixel_buffer::{kCVPixelFormatType_32BGRA, CVPixelBuffer},
+        };
+
+        let (encoded_width, encoded_height) = (720, 486);
+        let pixel_buffer = CVPixelBuffer::new(kCVPixelFormatType_32BGRA, encoded_width, encoded_height, None).unwrap();
+        let image_buffer = pixel_buffer.as_image_buffer();
+        let clean_apertures = [
+            None,
+            Some(CleanAperture::centered(704, 480)),
+            Some(CleanAperture::new(Rational::from(703), Rational::from(479), Rational::from(0), Rational::from(0))),
+            Some(CleanAperture::new(
+                Rational::new(1405, 2).unwrap(),
+                Rational::from(480),
+                Rational::new(-3, 4).unwrap(),
+                Rational::new(5, 2).unwrap(),
+            )),
+            Some(CleanAperture::new(Rational::from(800), Rational::from(500), Rational::from(12), Rational::from(-7))),
+        ];
+        let pixel_aspect_ratios = [None, Some(PixelAspectRatio::new(10, 11)), Some(PixelAspectRatio::new(40, 33)), Some(PixelAspectRatio::new(3, 2))];
+        for clean_aperture in &clean_apertures {
+            for pixel_aspect_ratio in &pixel_aspect_ratios {
+                let buffer = image_buffer.as_buffer();
+                buffer.remove_all_attachments();
+                if let Some(clean_aperture) = clean_aperture {
+                    image_buffer.set_clean_aperture(clean_aperture).unwrap();
+                }
+                if let Some(pixel_aspect_ratio) = pixel_aspect_ratio {
+                    image_buffer.set_pixel_aspect_ratio(pixel_aspect_ratio).unwrap();
+                }
+                let clean_rect = image_buffer.get_clean_rect();
+                let expected = clean_aperture
+                    .map(|clean_aperture| clean_aperture.clean_rect(encoded_width, encoded_height))
+                    .unwrap_or(CleanRect { x: 0.0, y: 0.0, width: encoded_width as f64, height: encoded_height as f64 });
+                let actual =
+                    CleanRect { x: clean_rect.origin.x, y: clean_rect.origin.y, width: clean_rect.size.width, height: clean_rect.size.height };
+                assert_eq!(actual, expected, "{:?} {:?}", clean_aperture, pixel_aspect_ratio);
+                let display_size = image_buffer.get_display_size();
+                assert_eq!(
+                    (display_size.width, display_size.height),
+                    super::display_size(encoded_width, encoded_height, clean_aperture.as_ref(), pixel_aspect_ratio.as_ref()),
+                    "{:?} {:?}",
+                    clean_aperture,
+                    pixel_aspect_ratio
+                );
+            }
+        }
+    }
+}
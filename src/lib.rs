@@ -1,9 +1,16 @@
 #![allow(non_snake_case, non_camel_case_types, non_upper_case_globals, improper_ctypes)]
 
+#[macro_use]
+extern crate bitflags;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
 extern crate block;
 #[macro_use]
 extern crate core_foundation;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
 extern crate core_graphics2 as core_graphics;
+#[cfg(feature = "half")]
+extern crate half;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
 extern crate io_surface;
 extern crate libc;
 #[cfg(feature = "metal")]
@@ -23,12 +30,18 @@ pub type GLint = libc::c_int;
 pub type GLuint = libc::c_uint;
 pub type OSType = u32;
 
+pub mod ambient_viewing_environment;
 pub mod base;
 pub mod buffer;
+pub mod color_conversion;
+pub mod color_description;
+pub mod color_primaries;
 #[cfg(all(target_os = "macos", feature = "display-link"))]
 pub mod display_link;
+pub mod hdr_metadata;
 pub mod host_time;
 pub mod image_buffer;
+pub mod image_geometry;
 #[cfg(feature = "metal")]
 pub mod metal_texture;
 #[cfg(feature = "metal")]
@@ -46,7 +59,24 @@ pub mod opengl_texture;
 #[cfg(target_os = "macos")]
 pub mod opengl_texture_cache;
 pub mod pixel_buffer;
+pub mod pixel_buffer_attributes;
+pub mod pixel_buffer_backend;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
 pub mod pixel_buffer_io_surface;
+pub mod pixel_buffer_lock;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
 pub mod pixel_buffer_pool;
+#[cfg(all(feature = "portable", not(any(target_os = "macos", target_os = "ios"))))]
+mod pixel_buffer_portable;
+pub mod pixel_format;
 pub mod pixel_format_description;
+pub mod plane_layout;
 pub mod r#return;
+pub mod region_of_interest;
+pub mod smpte_time;
+pub mod time;
+pub mod time_stamp;
+pub mod transfer_function;
+pub mod v210;
+pub mod virtual_display_link;
+pub mod ycbcr;
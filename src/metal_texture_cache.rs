@@ -11,7 +11,7 @@ use crate::{
     base::CVOptionFlags,
     image_buffer::CVImageBufferRef,
     metal_texture::{CVMetalTexture, CVMetalTextureRef},
-    r#return::{kCVReturnSuccess, CVReturn},
+    r#return::{CVError, CVReturn},
 };
 
 #[repr(C)]
@@ -76,7 +76,7 @@ impl CVMetalTextureCache {
         cache_attributes: Option<&CFDictionary<CFString, CFType>>,
         metal_device: metal::Device,
         texture_attributes: Option<&CFDictionary<CFString, CFType>>,
-    ) -> Result<CVMetalTextureCache, CVReturn> {
+    ) -> Result<CVMetalTextureCache, CVError> {
         let mut cache: CVMetalTextureCacheRef = null_mut();
         let status = unsafe {
             CVMetalTextureCacheCreate(
@@ -87,11 +87,7 @@ impl CVMetalTextureCache {
                 &mut cache,
             )
         };
-        if status == kCVReturnSuccess {
-            Ok(unsafe { TCFType::wrap_under_create_rule(cache) })
-        } else {
-            Err(status)
-        }
+        CVError::result(status).map(|_| unsafe { TCFType::wrap_under_create_rule(cache) })
     }
 
     #[inline]
@@ -103,7 +99,7 @@ impl CVMetalTextureCache {
         width: size_t,
         height: size_t,
         plane_index: size_t,
-    ) -> Result<CVMetalTexture, CVReturn> {
+    ) -> Result<CVMetalTexture, CVError> {
         let mut texture: CVMetalTextureRef = null_mut();
         let status = unsafe {
             CVMetalTextureCacheCreateTextureFromImage(
@@ -118,11 +114,7 @@ impl CVMetalTextureCache {
                 &mut texture,
             )
         };
-        if status == kCVReturnSuccess {
-            Ok(unsafe { TCFType::wrap_under_create_rule(texture) })
-        } else {
-            Err(status)
-        }
+        CVError::result(status).map(|_| unsafe { TCFType::wrap_under_create_rule(texture) })
     }
 
     #[inline]
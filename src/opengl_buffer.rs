@@ -10,7 +10,7 @@ use libc::size_t;
 use crate::{
     buffer::TCVBuffer,
     image_buffer::{CVImageBufferRef, TCVImageBuffer},
-    r#return::{kCVReturnSuccess, CVReturn},
+    r#return::{CVError, CVReturn},
     CGLContextObj, GLenum, GLint,
 };
 
@@ -80,16 +80,12 @@ impl_CFTypeDescription!(CVOpenGLBuffer);
 
 impl CVOpenGLBuffer {
     #[inline]
-    pub fn new(width: size_t, height: size_t, attributes: Option<&CFDictionary<CFString, CFType>>) -> Result<CVOpenGLBuffer, CVReturn> {
+    pub fn new(width: size_t, height: size_t, attributes: Option<&CFDictionary<CFString, CFType>>) -> Result<CVOpenGLBuffer, CVError> {
         let mut buffer: CVOpenGLBufferRef = null_mut();
         let status = unsafe {
             CVOpenGLBufferCreate(kCFAllocatorDefault, width, height, attributes.map_or(null(), |attrs| attrs.as_concrete_TypeRef()), &mut buffer)
         };
-        if status == kCVReturnSuccess {
-            Ok(unsafe { TCFType::wrap_under_create_rule(buffer) })
-        } else {
-            Err(status)
-        }
+        CVError::result(status).map(|_| unsafe { TCFType::wrap_under_create_rule(buffer) })
     }
 
     #[inline]
@@ -105,12 +101,8 @@ impl CVOpenGLBuffer {
     }
 
     #[inline]
-    pub unsafe fn attach(&self, cgl_context: CGLContextObj, face: GLenum, level: GLint, screen: GLint) -> Result<(), CVReturn> {
+    pub unsafe fn attach(&self, cgl_context: CGLContextObj, face: GLenum, level: GLint, screen: GLint) -> Result<(), CVError> {
         let status = unsafe { CVOpenGLBufferAttach(self.as_concrete_TypeRef(), cgl_context, face, level, screen) };
-        if status == kCVReturnSuccess {
-            Ok(())
-        } else {
-            Err(status)
-        }
+        CVError::result(status)
     }
 }
@@ -9,7 +9,7 @@ use libc::c_void;
 
 use crate::{
     opengl_buffer::{CVOpenGLBuffer, CVOpenGLBufferRef},
-    r#return::{kCVReturnSuccess, CVReturn},
+    r#return::{CVError, CVReturn},
 };
 
 #[repr(C)]
@@ -77,7 +77,7 @@ impl CVOpenGLBufferPool {
     pub fn new(
         pool_attributes: Option<&CFDictionary<CFString, CFType>>,
         opengl_buffer_attributes: Option<&CFDictionary<CFString, CFType>>,
-    ) -> Result<CVOpenGLBufferPool, CVReturn> {
+    ) -> Result<CVOpenGLBufferPool, CVError> {
         let mut pool: CVOpenGLBufferPoolRef = null_mut();
         let status = unsafe {
             CVOpenGLBufferPoolCreate(
@@ -87,11 +87,7 @@ impl CVOpenGLBufferPool {
                 &mut pool,
             )
         };
-        if status == kCVReturnSuccess {
-            Ok(unsafe { TCFType::wrap_under_create_rule(pool) })
-        } else {
-            Err(status)
-        }
+        CVError::result(status).map(|_| unsafe { TCFType::wrap_under_create_rule(pool) })
     }
 
     #[inline]
@@ -119,13 +115,9 @@ impl CVOpenGLBufferPool {
     }
 
     #[inline]
-    pub fn create_open_gl_buffer(&self) -> Result<CVOpenGLBuffer, CVReturn> {
+    pub fn create_open_gl_buffer(&self) -> Result<CVOpenGLBuffer, CVError> {
         let mut buffer: CVOpenGLBufferRef = null_mut();
         let status = unsafe { CVOpenGLBufferPoolCreateOpenGLBuffer(kCFAllocatorDefault, self.as_concrete_TypeRef(), &mut buffer) };
-        if status == kCVReturnSuccess {
-            Ok(unsafe { TCFType::wrap_under_create_rule(buffer) })
-        } else {
-            Err(status)
-        }
+        CVError::result(status).map(|_| unsafe { TCFType::wrap_under_create_rule(buffer) })
     }
 }
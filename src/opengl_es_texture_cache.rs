@@ -13,7 +13,7 @@ use crate::{
     base::CVOptionFlags,
     image_buffer::{CVImageBuffer, CVImageBufferRef},
     opengl_es_texture::{CVOpenGLESTexture, CVOpenGLESTextureRef},
-    r#return::{kCVReturnSuccess, CVReturn},
+    r#return::{CVError, CVReturn},
     GLenum, GLint, GLsizei,
 };
 
@@ -65,7 +65,7 @@ impl CVOpenGLESTextureCache {
         cache_attributes: Option<&CFDictionary<CFString, CFType>>,
         eagl_context: CVEAGLContext,
         texture_attributes: Option<&CFDictionary<CFString, CFType>>,
-    ) -> Result<CVOpenGLESTextureCache, CVReturn> {
+    ) -> Result<CVOpenGLESTextureCache, CVError> {
         let mut cache: CVOpenGLESTextureCacheRef = null_mut();
         let status = unsafe {
             CVOpenGLESTextureCacheCreate(
@@ -76,11 +76,7 @@ impl CVOpenGLESTextureCache {
                 &mut cache,
             )
         };
-        if status == kCVReturnSuccess {
-            Ok(unsafe { TCFType::wrap_under_create_rule(cache) })
-        } else {
-            Err(status)
-        }
+        CVError::result(status).map(|_| unsafe { TCFType::wrap_under_create_rule(cache) })
     }
 
     #[inline]
@@ -95,7 +91,7 @@ impl CVOpenGLESTextureCache {
         format: GLenum,
         type_: GLenum,
         plane_index: size_t,
-    ) -> Result<CVOpenGLESTexture, CVReturn> {
+    ) -> Result<CVOpenGLESTexture, CVError> {
         let mut texture: CVOpenGLESTextureRef = null_mut();
         let status = unsafe {
             CVOpenGLESTextureCacheCreateTextureFromImage(
@@ -113,11 +109,7 @@ impl CVOpenGLESTextureCache {
                 &mut texture,
             )
         };
-        if status == kCVReturnSuccess {
-            Ok(unsafe { TCFType::wrap_under_create_rule(texture) })
-        } else {
-            Err(status)
-        }
+        CVError::result(status).map(|_| unsafe { TCFType::wrap_under_create_rule(texture) })
     }
 
     #[inline]
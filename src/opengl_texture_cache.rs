@@ -11,7 +11,7 @@ use crate::{
     base::CVOptionFlags,
     image_buffer::{CVImageBuffer, CVImageBufferRef},
     opengl_texture::{CVOpenGLTexture, CVOpenGLTextureRef},
-    r#return::{kCVReturnSuccess, CVReturn},
+    r#return::{CVError, CVReturn},
     CGLContextObj, CGLPixelFormatObj,
 };
 
@@ -109,7 +109,7 @@ impl CVOpenGLTextureCache {
         cgl_context: CGLContextObj,
         cgl_pixel_format: CGLPixelFormatObj,
         texture_attributes: Option<&CFDictionary<CFString, CFType>>,
-    ) -> Result<CVOpenGLTextureCache, CVReturn> {
+    ) -> Result<CVOpenGLTextureCache, CVError> {
         let mut cache: CVOpenGLTextureCacheRef = null_mut();
         let status = unsafe {
             CVOpenGLTextureCacheCreate(
@@ -121,11 +121,7 @@ impl CVOpenGLTextureCache {
                 &mut cache,
             )
         };
-        if status == kCVReturnSuccess {
-            Ok(unsafe { TCFType::wrap_under_create_rule(cache) })
-        } else {
-            Err(status)
-        }
+        CVError::result(status).map(|_| unsafe { TCFType::wrap_under_create_rule(cache) })
     }
 
     #[inline]
@@ -133,7 +129,7 @@ impl CVOpenGLTextureCache {
         &self,
         source_image: &CVImageBuffer,
         attributes: Option<&CFDictionary<CFString, CFType>>,
-    ) -> Result<CVOpenGLTexture, CVReturn> {
+    ) -> Result<CVOpenGLTexture, CVError> {
         let mut texture: CVOpenGLTextureRef = null_mut();
         let status = unsafe {
             CVOpenGLTextureCacheCreateTextureFromImage(
@@ -144,11 +140,7 @@ impl CVOpenGLTextureCache {
                 &mut texture,
             )
         };
-        if status == kCVReturnSuccess {
-            Ok(unsafe { TCFType::wrap_under_create_rule(texture) })
-        } else {
-            Err(status)
-        }
+        CVError::result(status).map(|_| unsafe { TCFType::wrap_under_create_rule(texture) })
     }
 
     #[inline]
@@ -1,20 +1,30 @@
-use std::ptr::{null, null_mut};
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+use std::{
+    collections::BTreeMap,
+    ptr::{null, null_mut},
+    sync::{Mutex, PoisonError},
+};
 
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+use core_foundation::{
+    base::{kCFAllocatorDefault, CFType},
+    dictionary::CFDictionary,
+};
 use core_foundation::{
     array::CFArrayRef,
-    base::{kCFAllocatorDefault, Boolean, CFAllocatorRef, CFType, CFTypeID, TCFType},
-    dictionary::{CFDictionary, CFDictionaryRef},
+    base::{Boolean, CFAllocatorRef, CFTypeID, TCFType},
+    dictionary::CFDictionaryRef,
     string::{CFString, CFStringRef},
 };
 use libc::{c_void, size_t};
 
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
 use crate::{
-    base::CVOptionFlags,
     buffer::TCVBuffer,
-    image_buffer::{CVImageBufferRef, TCVImageBuffer},
-    r#return::{kCVReturnInvalidArgument, kCVReturnSuccess, CVReturn},
-    OSType,
+    image_buffer::TCVImageBuffer,
+    r#return::CVError,
 };
+use crate::{base::CVOptionFlags, image_buffer::CVImageBufferRef, r#return::CVReturn, OSType};
 
 pub type CVPixelBufferRef = CVImageBufferRef;
 
@@ -124,17 +134,20 @@ pub const kCVPixelFormatType_Lossy_422YpCbCr10PackedBiPlanarVideoRange: OSType =
 pub const kCVPixelBufferLock_ReadOnly: CVPixelBufferLockFlags = 0x00000001;
 
 #[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
 pub struct CVPlanarComponentInfo {
     pub offset: i32,
     pub rowBytes: u32,
 }
 
 #[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
 pub struct CVPlanarPixelBufferInfo {
     pub componentInfo: [CVPlanarComponentInfo; 1],
 }
 
 #[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
 pub struct CVPlanarPixelBufferInfo_YCbCrPlanar {
     pub componentInfoY: CVPlanarComponentInfo,
     pub componentInfoCb: CVPlanarComponentInfo,
@@ -142,6 +155,7 @@ pub struct CVPlanarPixelBufferInfo_YCbCrPlanar {
 }
 
 #[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
 pub struct CVPlanarPixelBufferInfo_YCbCrBiPlanar {
     pub componentInfoY: CVPlanarComponentInfo,
     pub componentInfoCbCr: CVPlanarComponentInfo,
@@ -333,20 +347,42 @@ impl From<CVPixelBufferKeys> for CFString {
     }
 }
 
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
 impl TCVBuffer for CVPixelBuffer {}
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
 impl TCVImageBuffer for CVPixelBuffer {}
 
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
 pub struct CVPixelBuffer(CVPixelBufferRef);
 
+#[cfg(all(feature = "portable", not(any(target_os = "macos", target_os = "ios"))))]
+pub use crate::pixel_buffer_portable::CVPixelBuffer;
+
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
 impl Drop for CVPixelBuffer {
     fn drop(&mut self) {
         unsafe { CVPixelBufferRelease(self.0) }
     }
 }
 
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
 impl_TCFType!(CVPixelBuffer, CVPixelBufferRef, CVPixelBufferGetTypeID);
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
 impl_CFTypeDescription!(CVPixelBuffer);
 
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+#[derive(Default)]
+struct LockState {
+    readers: usize,
+    writer: bool,
+}
+
+// Locks held on every buffer, keyed by the buffer rather than by the handle, since every handle to a buffer shares its
+// pixels
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+static LOCKS: Mutex<BTreeMap<usize, LockState>> = Mutex::new(BTreeMap::new());
+
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
 impl CVPixelBuffer {
     #[inline]
     pub fn new(
@@ -354,7 +390,7 @@ impl CVPixelBuffer {
         width: usize,
         height: usize,
         options: Option<&CFDictionary<CFString, CFType>>,
-    ) -> Result<CVPixelBuffer, CVReturn> {
+    ) -> Result<CVPixelBuffer, CVError> {
         let mut pixel_buffer: CVPixelBufferRef = null_mut();
         let status = unsafe {
             CVPixelBufferCreate(
@@ -366,11 +402,7 @@ impl CVPixelBuffer {
                 &mut pixel_buffer,
             )
         };
-        if status == kCVReturnSuccess {
-            Ok(unsafe { TCFType::wrap_under_create_rule(pixel_buffer) })
-        } else {
-            Err(status)
-        }
+        CVError::result(status).map(|_| unsafe { TCFType::wrap_under_create_rule(pixel_buffer) })
     }
 
     #[inline]
@@ -383,7 +415,7 @@ impl CVPixelBuffer {
         release_callback: CVPixelBufferReleaseBytesCallback,
         release_ref_con: *mut c_void,
         options: Option<&CFDictionary<CFString, CFType>>,
-    ) -> Result<CVPixelBuffer, CVReturn> {
+    ) -> Result<CVPixelBuffer, CVError> {
         let mut pixel_buffer: CVPixelBufferRef = null_mut();
         let status = unsafe {
             CVPixelBufferCreateWithBytes(
@@ -399,11 +431,7 @@ impl CVPixelBuffer {
                 &mut pixel_buffer,
             )
         };
-        if status == kCVReturnSuccess {
-            Ok(unsafe { TCFType::wrap_under_create_rule(pixel_buffer) })
-        } else {
-            Err(status)
-        }
+        CVError::result(status).map(|_| unsafe { TCFType::wrap_under_create_rule(pixel_buffer) })
     }
 
     #[inline]
@@ -421,13 +449,13 @@ impl CVPixelBuffer {
         release_callback: CVPixelBufferReleasePlanarBytesCallback,
         release_ref_con: *mut c_void,
         options: Option<&CFDictionary<CFString, CFType>>,
-    ) -> Result<CVPixelBuffer, CVReturn> {
+    ) -> Result<CVPixelBuffer, CVError> {
         if plane_base_address.len() != number_of_planes ||
             plane_width.len() != number_of_planes ||
             plane_height.len() != number_of_planes ||
             plane_bytes_per_row.len() != number_of_planes
         {
-            return Err(kCVReturnInvalidArgument);
+            return Err(CVError::InvalidArgument);
         }
         let mut pixel_buffer: CVPixelBufferRef = null_mut();
         let status = unsafe {
@@ -449,21 +477,52 @@ impl CVPixelBuffer {
                 &mut pixel_buffer,
             )
         };
-        if status == kCVReturnSuccess {
-            Ok(unsafe { TCFType::wrap_under_create_rule(pixel_buffer) })
-        } else {
-            Err(status)
+        CVError::result(status).map(|_| unsafe { TCFType::wrap_under_create_rule(pixel_buffer) })
+    }
+
+    // Locks are tracked per buffer together with the ones lock_read and lock_write take, so this fails while the buffer is
+    // locked for writing, or for reading when locking for writing
+    pub fn lock_base_address(&self, options: CVPixelBufferLockFlags) -> Result<(), CVError> {
+        let key = self.as_concrete_TypeRef() as usize;
+        let read_only = options & kCVPixelBufferLock_ReadOnly != 0;
+        {
+            let mut locks = LOCKS.lock().unwrap_or_else(PoisonError::into_inner);
+            let state = locks.entry(key).or_default();
+            if state.writer || (!read_only && state.readers > 0) {
+                return Err(CVError::InvalidArgument);
+            }
+            if read_only {
+                state.readers += 1;
+            } else {
+                state.writer = true;
+            }
         }
+        let status = unsafe { CVPixelBufferLockBaseAddress(self.as_concrete_TypeRef(), options) };
+        CVError::result(status).inspect_err(|_| self.release_lock(options))
     }
 
-    #[inline]
-    pub fn lock_base_address(&self, options: CVPixelBufferLockFlags) -> CVReturn {
-        unsafe { CVPixelBufferLockBaseAddress(self.as_concrete_TypeRef(), options) }
+    // Unsafe because releasing a lock that a guard still holds leaves the guard's slices dangling; the caller must only
+    // release locks it took itself with lock_base_address and the same options
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn unlock_base_address(&self, options: CVPixelBufferLockFlags) -> Result<(), CVError> {
+        let status = unsafe { CVPixelBufferUnlockBaseAddress(self.as_concrete_TypeRef(), options) };
+        self.release_lock(options);
+        CVError::result(status)
     }
 
-    #[inline]
-    pub fn unlock_base_address(&self, options: CVPixelBufferLockFlags) -> CVReturn {
-        unsafe { CVPixelBufferUnlockBaseAddress(self.as_concrete_TypeRef(), options) }
+    fn release_lock(&self, options: CVPixelBufferLockFlags) {
+        let mut locks = LOCKS.lock().unwrap_or_else(PoisonError::into_inner);
+        let key = self.as_concrete_TypeRef() as usize;
+        if let Some(state) = locks.get_mut(&key) {
+            if options & kCVPixelBufferLock_ReadOnly != 0 {
+                state.readers = state.readers.saturating_sub(1);
+            } else {
+                state.writer = false;
+            }
+            if state.readers == 0 && !state.writer {
+                locks.remove(&key);
+            }
+        }
     }
 
     #[inline]
@@ -534,8 +593,9 @@ impl CVPixelBuffer {
     }
 
     #[inline]
-    pub fn fill_extended_pixels(&self) -> CVReturn {
-        unsafe { CVPixelBufferFillExtendedPixels(self.as_concrete_TypeRef()) }
+    pub fn fill_extended_pixels(&self) -> Result<(), CVError> {
+        let status = unsafe { CVPixelBufferFillExtendedPixels(self.as_concrete_TypeRef()) };
+        CVError::result(status)
     }
 
     #[inline]
@@ -0,0 +1,642 @@
+use std::convert::TryFrom;
+
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+use core_foundation::{
+    array::CFArray,
+    base::{CFType, TCFType},
+    boolean::CFBoolean,
+    dictionary::CFDictionary,
+    number::CFNumber,
+    string::CFString,
+};
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+use core_foundation::{
+    base::{CFAllocator, CFAllocatorRef},
+    dictionary::CFDictionaryRef,
+};
+
+use crate::{
+    pixel_buffer::{
+        kCVVersatileBayer_BayerPattern_BGGR, kCVVersatileBayer_BayerPattern_GBRG, kCVVersatileBayer_BayerPattern_GRBG,
+        kCVVersatileBayer_BayerPattern_RGGB,
+    },
+    pixel_format::PixelFormat,
+    plane_layout::PlaneLayout,
+    r#return::CVError,
+};
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+use crate::{
+    pixel_buffer::{CVPixelBuffer, CVPixelBufferKeys},
+    OSType,
+};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CVVersatileBayerPattern {
+    RGGB,
+    GRBG,
+    GBRG,
+    BGGR,
+}
+
+impl From<CVVersatileBayerPattern> for u32 {
+    fn from(pattern: CVVersatileBayerPattern) -> u32 {
+        match pattern {
+            CVVersatileBayerPattern::RGGB => kCVVersatileBayer_BayerPattern_RGGB,
+            CVVersatileBayerPattern::GRBG => kCVVersatileBayer_BayerPattern_GRBG,
+            CVVersatileBayerPattern::GBRG => kCVVersatileBayer_BayerPattern_GBRG,
+            CVVersatileBayerPattern::BGGR => kCVVersatileBayer_BayerPattern_BGGR,
+        }
+    }
+}
+
+impl TryFrom<u32> for CVVersatileBayerPattern {
+    type Error = CVError;
+
+    fn try_from(pattern: u32) -> Result<Self, Self::Error> {
+        match pattern {
+            kCVVersatileBayer_BayerPattern_RGGB => Ok(CVVersatileBayerPattern::RGGB),
+            kCVVersatileBayer_BayerPattern_GRBG => Ok(CVVersatileBayerPattern::GRBG),
+            kCVVersatileBayer_BayerPattern_GBRG => Ok(CVVersatileBayerPattern::GBRG),
+            kCVVersatileBayer_BayerPattern_BGGR => Ok(CVVersatileBayerPattern::BGGR),
+            _ => Err(CVError::InvalidArgument),
+        }
+    }
+}
+
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+fn find_value(dictionary: &CFDictionary<CFString, CFType>, key: CVPixelBufferKeys) -> Option<CFType> {
+    dictionary.find(CFString::from(key)).map(|value| value.clone())
+}
+
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+fn find_number(dictionary: &CFDictionary<CFString, CFType>, key: CVPixelBufferKeys) -> Option<i64> {
+    find_value(dictionary, key)?.downcast::<CFNumber>()?.to_i64()
+}
+
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+fn find_size(dictionary: &CFDictionary<CFString, CFType>, key: CVPixelBufferKeys) -> Option<usize> {
+    find_number(dictionary, key).and_then(|number| usize::try_from(number).ok())
+}
+
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+fn find_bool(dictionary: &CFDictionary<CFString, CFType>, key: CVPixelBufferKeys) -> Option<bool> {
+    find_value(dictionary, key)?.downcast::<CFBoolean>().map(bool::from)
+}
+
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+fn push_number(pairs: &mut Vec<(CFString, CFType)>, key: CVPixelBufferKeys, value: Option<i64>) {
+    if let Some(value) = value {
+        pairs.push((CFString::from(key), CFNumber::from(value).as_CFType()));
+    }
+}
+
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+fn push_bool(pairs: &mut Vec<(CFString, CFType)>, key: CVPixelBufferKeys, value: Option<bool>) {
+    if let Some(value) = value {
+        pairs.push((CFString::from(key), CFBoolean::from(value).as_CFType()));
+    }
+}
+
+// Attributes for CVPixelBufferCreate and CVPixelBufferPoolCreate; keys left unset are omitted from the dictionary
+#[derive(Clone, Debug, Default)]
+pub struct PixelBufferAttributes {
+    pixel_formats: Vec<PixelFormat>,
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    memory_allocator: Option<CFType>,
+    width: Option<usize>,
+    height: Option<usize>,
+    extended_pixels: Option<(usize, usize, usize, usize)>, /* left, right, top, bottom */
+    bytes_per_row_alignment: Option<usize>,
+    plane_alignment: Option<usize>,
+    cg_bitmap_context_compatibility: Option<bool>,
+    cg_image_compatibility: Option<bool>,
+    opengl_compatibility: Option<bool>,
+    #[cfg(target_os = "ios")]
+    opengles_compatibility: Option<bool>,
+    metal_compatibility: Option<bool>,
+    #[cfg(target_os = "macos")]
+    opengl_texture_cache_compatibility: Option<bool>,
+    #[cfg(target_os = "ios")]
+    opengles_texture_cache_compatibility: Option<bool>,
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    io_surface_properties: Option<CFType>,
+    bayer_pattern: Option<CVVersatileBayerPattern>,
+}
+
+impl PixelBufferAttributes {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn pixel_format(mut self, pixel_format: PixelFormat) -> Self {
+        self.pixel_formats = vec![pixel_format];
+        self
+    }
+
+    // Pools accept a list of formats, the first one being the one buffers are created with
+    #[inline]
+    pub fn pixel_formats<I>(mut self, pixel_formats: I) -> Self
+    where
+        I: IntoIterator<Item = PixelFormat>,
+    {
+        self.pixel_formats = pixel_formats.into_iter().collect();
+        self
+    }
+
+    #[inline]
+    pub fn width(mut self, width: usize) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    #[inline]
+    pub fn height(mut self, height: usize) -> Self {
+        self.height = Some(height);
+        self
+    }
+
+    #[inline]
+    pub fn size(self, width: usize, height: usize) -> Self {
+        self.width(width).height(height)
+    }
+
+    #[inline]
+    pub fn extended_pixels(mut self, left: usize, right: usize, top: usize, bottom: usize) -> Self {
+        self.extended_pixels = Some((left, right, top, bottom));
+        self
+    }
+
+    #[inline]
+    pub fn bytes_per_row_alignment(mut self, alignment: usize) -> Self {
+        self.bytes_per_row_alignment = Some(alignment);
+        self
+    }
+
+    #[inline]
+    pub fn plane_alignment(mut self, alignment: usize) -> Self {
+        self.plane_alignment = Some(alignment);
+        self
+    }
+
+    #[inline]
+    pub fn cg_bitmap_context_compatibility(mut self, compatible: bool) -> Self {
+        self.cg_bitmap_context_compatibility = Some(compatible);
+        self
+    }
+
+    #[inline]
+    pub fn cg_image_compatibility(mut self, compatible: bool) -> Self {
+        self.cg_image_compatibility = Some(compatible);
+        self
+    }
+
+    #[inline]
+    pub fn opengl_compatibility(mut self, compatible: bool) -> Self {
+        self.opengl_compatibility = Some(compatible);
+        self
+    }
+
+    #[cfg(target_os = "ios")]
+    #[inline]
+    pub fn opengles_compatibility(mut self, compatible: bool) -> Self {
+        self.opengles_compatibility = Some(compatible);
+        self
+    }
+
+    #[inline]
+    pub fn metal_compatibility(mut self, compatible: bool) -> Self {
+        self.metal_compatibility = Some(compatible);
+        self
+    }
+
+    #[cfg(target_os = "macos")]
+    #[inline]
+    pub fn opengl_texture_cache_compatibility(mut self, compatible: bool) -> Self {
+        self.opengl_texture_cache_compatibility = Some(compatible);
+        self
+    }
+
+    #[cfg(target_os = "ios")]
+    #[inline]
+    pub fn opengles_texture_cache_compatibility(mut self, compatible: bool) -> Self {
+        self.opengles_texture_cache_compatibility = Some(compatible);
+        self
+    }
+
+    #[inline]
+    pub fn bayer_pattern(mut self, pattern: CVVersatileBayerPattern) -> Self {
+        self.bayer_pattern = Some(pattern);
+        self
+    }
+
+    #[inline]
+    pub fn get_pixel_formats(&self) -> &[PixelFormat] {
+        &self.pixel_formats
+    }
+
+    #[inline]
+    pub fn get_pixel_format(&self) -> Option<PixelFormat> {
+        self.pixel_formats.first().copied()
+    }
+
+    #[inline]
+    pub fn get_width(&self) -> Option<usize> {
+        self.width
+    }
+
+    #[inline]
+    pub fn get_height(&self) -> Option<usize> {
+        self.height
+    }
+
+    #[inline]
+    pub fn get_extended_pixels(&self) -> Option<(usize, usize, usize, usize)> {
+        self.extended_pixels
+    }
+
+    #[inline]
+    pub fn get_bytes_per_row_alignment(&self) -> Option<usize> {
+        self.bytes_per_row_alignment
+    }
+
+    #[inline]
+    pub fn get_plane_alignment(&self) -> Option<usize> {
+        self.plane_alignment
+    }
+
+    #[inline]
+    pub fn get_cg_bitmap_context_compatibility(&self) -> Option<bool> {
+        self.cg_bitmap_context_compatibility
+    }
+
+    #[inline]
+    pub fn get_cg_image_compatibility(&self) -> Option<bool> {
+        self.cg_image_compatibility
+    }
+
+    #[inline]
+    pub fn get_opengl_compatibility(&self) -> Option<bool> {
+        self.opengl_compatibility
+    }
+
+    #[cfg(target_os = "ios")]
+    #[inline]
+    pub fn get_opengles_compatibility(&self) -> Option<bool> {
+        self.opengles_compatibility
+    }
+
+    #[inline]
+    pub fn get_metal_compatibility(&self) -> Option<bool> {
+        self.metal_compatibility
+    }
+
+    #[cfg(target_os = "macos")]
+    #[inline]
+    pub fn get_opengl_texture_cache_compatibility(&self) -> Option<bool> {
+        self.opengl_texture_cache_compatibility
+    }
+
+    #[cfg(target_os = "ios")]
+    #[inline]
+    pub fn get_opengles_texture_cache_compatibility(&self) -> Option<bool> {
+        self.opengles_texture_cache_compatibility
+    }
+
+    #[inline]
+    pub fn get_bayer_pattern(&self) -> Option<CVVersatileBayerPattern> {
+        self.bayer_pattern
+    }
+
+    // The size and format CVPixelBufferCreate needs, if all of them are set; also fails for a Bayer pattern on a format
+    // other than versatile Bayer, and for sizes whose pixel data cannot be addressed
+    pub fn validate(&self) -> Result<(PixelFormat, usize, usize), CVError> {
+        let (pixel_format, width, height) = match (self.get_pixel_format(), self.width, self.height) {
+            (Some(pixel_format), Some(width), Some(height)) if width > 0 && height > 0 => (pixel_format, width, height),
+            (None, _, _) => return Err(CVError::InvalidPixelFormat),
+            (_, Some(_), Some(_)) => return Err(CVError::InvalidSize),
+            _ => return Err(CVError::InvalidPixelBufferAttributes),
+        };
+        if self.bayer_pattern.is_some() && pixel_format != PixelFormat::_16VersatileBayer {
+            return Err(CVError::InvalidPixelBufferAttributes);
+        }
+        if matches!(pixel_format.layout(), Some(layout) if !layout.compressed) {
+            let plane_layout = PlaneLayout::compute(
+                pixel_format,
+                width,
+                height,
+                self.bytes_per_row_alignment.unwrap_or(1),
+                self.plane_alignment.unwrap_or(1),
+                self.extended_pixels.unwrap_or_default(),
+            )?;
+            if plane_layout.data_size > isize::MAX as usize {
+                return Err(CVError::InvalidSize);
+            }
+        }
+        Ok((pixel_format, width, height))
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+impl PixelBufferAttributes {
+    #[inline]
+    pub fn memory_allocator(mut self, allocator: &CFAllocator) -> Self {
+        self.memory_allocator = Some(allocator.as_CFType());
+        self
+    }
+
+    #[inline]
+    pub fn io_surface_properties(mut self, properties: &CFDictionary<CFString, CFType>) -> Self {
+        self.io_surface_properties = Some(properties.as_CFType());
+        self
+    }
+
+    // An empty properties dictionary is enough to get a buffer backed by an IOSurface
+    #[inline]
+    pub fn io_surface_backed(self) -> Self {
+        self.io_surface_properties(&CFDictionary::from_CFType_pairs(&[]))
+    }
+
+    #[inline]
+    pub fn get_memory_allocator(&self) -> Option<CFAllocator> {
+        self.memory_allocator
+            .as_ref()
+            .filter(|allocator| allocator.instance_of::<CFAllocator>())
+            .map(|allocator| unsafe { CFAllocator::wrap_under_get_rule(allocator.as_CFTypeRef() as CFAllocatorRef) })
+    }
+
+    #[inline]
+    pub fn get_io_surface_properties(&self) -> Option<CFDictionary<CFString, CFType>> {
+        self.io_surface_properties
+            .as_ref()
+            .filter(|properties| properties.instance_of::<CFDictionary>())
+            .map(|properties| unsafe { CFDictionary::wrap_under_get_rule(properties.as_CFTypeRef() as CFDictionaryRef) })
+    }
+
+    #[inline]
+    pub fn is_io_surface_backed(&self) -> bool {
+        self.io_surface_properties.is_some()
+    }
+}
+
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+impl PixelBufferAttributes {
+    pub fn from_dictionary(dictionary: &CFDictionary<CFString, CFType>) -> Self {
+        let pixel_formats = match find_value(dictionary, CVPixelBufferKeys::PixelFormatType) {
+            Some(value) => {
+                if let Some(number) = value.downcast::<CFNumber>() {
+                    number.to_i64().map(|number| PixelFormat::from(number as OSType)).into_iter().collect()
+                } else if let Some(array) = value.downcast::<CFArray>() {
+                    let array: CFArray<CFType> = unsafe { CFArray::wrap_under_get_rule(array.as_concrete_TypeRef()) };
+                    array
+                        .iter()
+                        .filter_map(|value| value.downcast::<CFNumber>()?.to_i64())
+                        .map(|number| PixelFormat::from(number as OSType))
+                        .collect()
+                } else {
+                    Vec::new()
+                }
+            }
+            None => Vec::new(),
+        };
+        let extended_pixels = [
+            CVPixelBufferKeys::ExtendedPixelsLeft,
+            CVPixelBufferKeys::ExtendedPixelsRight,
+            CVPixelBufferKeys::ExtendedPixelsTop,
+            CVPixelBufferKeys::ExtendedPixelsBottom,
+        ]
+        .map(|key| find_size(dictionary, key));
+        let extended_pixels = if extended_pixels.iter().any(Option::is_some) {
+            let [left, right, top, bottom] = extended_pixels.map(Option::unwrap_or_default);
+            Some((left, right, top, bottom))
+        } else {
+            None
+        };
+
+        PixelBufferAttributes {
+            pixel_formats,
+            #[cfg(any(target_os = "macos", target_os = "ios"))]
+            memory_allocator: find_value(dictionary, CVPixelBufferKeys::MemoryAllocator),
+            width: find_size(dictionary, CVPixelBufferKeys::Width),
+            height: find_size(dictionary, CVPixelBufferKeys::Height),
+            extended_pixels,
+            bytes_per_row_alignment: find_size(dictionary, CVPixelBufferKeys::BytesPerRowAlignment),
+            plane_alignment: find_size(dictionary, CVPixelBufferKeys::PlaneAlignment),
+            cg_bitmap_context_compatibility: find_bool(dictionary, CVPixelBufferKeys::CGBitmapContextCompatibility),
+            cg_image_compatibility: find_bool(dictionary, CVPixelBufferKeys::CGImageCompatibility),
+            opengl_compatibility: find_bool(dictionary, CVPixelBufferKeys::OpenGLCompatibility),
+            #[cfg(target_os = "ios")]
+            opengles_compatibility: find_bool(dictionary, CVPixelBufferKeys::OpenGLESCompatibility),
+            metal_compatibility: find_bool(dictionary, CVPixelBufferKeys::MetalCompatibility),
+            #[cfg(target_os = "macos")]
+            opengl_texture_cache_compatibility: find_bool(dictionary, CVPixelBufferKeys::OpenGLTextureCacheCompatibility),
+            #[cfg(target_os = "ios")]
+            opengles_texture_cache_compatibility: find_bool(dictionary, CVPixelBufferKeys::OpenGLESTextureCacheCompatibility),
+            #[cfg(any(target_os = "macos", target_os = "ios"))]
+            io_surface_properties: find_value(dictionary, CVPixelBufferKeys::IOSurfaceProperties)
+                .filter(|properties| properties.instance_of::<CFDictionary>()),
+            bayer_pattern: find_number(dictionary, CVPixelBufferKeys::VersatileBayerKey_BayerPattern)
+                .and_then(|pattern| CVVersatileBayerPattern::try_from(pattern as u32).ok()),
+        }
+    }
+
+    pub fn to_dictionary(&self) -> CFDictionary<CFString, CFType> {
+        let mut pairs: Vec<(CFString, CFType)> = Vec::new();
+        match self.pixel_formats.as_slice() {
+            [] => {}
+            [pixel_format] => push_number(&mut pairs, CVPixelBufferKeys::PixelFormatType, Some(pixel_format.as_os_type() as i64)),
+            pixel_formats => {
+                let numbers: Vec<CFNumber> = pixel_formats.iter().map(|pixel_format| CFNumber::from(pixel_format.as_os_type() as i64)).collect();
+                pairs.push((CFString::from(CVPixelBufferKeys::PixelFormatType), CFArray::from_CFTypes(&numbers).as_CFType()));
+            }
+        }
+        #[cfg(any(target_os = "macos", target_os = "ios"))]
+        if let Some(allocator) = &self.memory_allocator {
+            pairs.push((CFString::from(CVPixelBufferKeys::MemoryAllocator), allocator.clone()));
+        }
+        push_number(&mut pairs, CVPixelBufferKeys::Width, self.width.map(|width| width as i64));
+        push_number(&mut pairs, CVPixelBufferKeys::Height, self.height.map(|height| height as i64));
+        if let Some((left, right, top, bottom)) = self.extended_pixels {
+            push_number(&mut pairs, CVPixelBufferKeys::ExtendedPixelsLeft, Some(left as i64));
+            push_number(&mut pairs, CVPixelBufferKeys::ExtendedPixelsTop, Some(top as i64));
+            push_number(&mut pairs, CVPixelBufferKeys::ExtendedPixelsRight, Some(right as i64));
+            push_number(&mut pairs, CVPixelBufferKeys::ExtendedPixelsBottom, Some(bottom as i64));
+        }
+        push_number(&mut pairs, CVPixelBufferKeys::BytesPerRowAlignment, self.bytes_per_row_alignment.map(|alignment| alignment as i64));
+        push_bool(&mut pairs, CVPixelBufferKeys::CGBitmapContextCompatibility, self.cg_bitmap_context_compatibility);
+        push_bool(&mut pairs, CVPixelBufferKeys::CGImageCompatibility, self.cg_image_compatibility);
+        push_bool(&mut pairs, CVPixelBufferKeys::OpenGLCompatibility, self.opengl_compatibility);
+        push_number(&mut pairs, CVPixelBufferKeys::PlaneAlignment, self.plane_alignment.map(|alignment| alignment as i64));
+        #[cfg(any(target_os = "macos", target_os = "ios"))]
+        if let Some(properties) = &self.io_surface_properties {
+            pairs.push((CFString::from(CVPixelBufferKeys::IOSurfaceProperties), properties.clone()));
+        }
+        #[cfg(target_os = "ios")]
+        push_bool(&mut pairs, CVPixelBufferKeys::OpenGLESCompatibility, self.opengles_compatibility);
+        push_bool(&mut pairs, CVPixelBufferKeys::MetalCompatibility, self.metal_compatibility);
+        #[cfg(target_os = "macos")]
+        push_bool(&mut pairs, CVPixelBufferKeys::OpenGLTextureCacheCompatibility, self.opengl_texture_cache_compatibility);
+        #[cfg(target_os = "ios")]
+        push_bool(&mut pairs, CVPixelBufferKeys::OpenGLESTextureCacheCompatibility, self.opengles_texture_cache_compatibility);
+        push_number(&mut pairs, CVPixelBufferKeys::VersatileBayerKey_BayerPattern, self.bayer_pattern.map(|pattern| u32::from(pattern) as i64));
+        CFDictionary::from_CFType_pairs(&pairs)
+    }
+}
+
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+impl CVPixelBuffer {
+    // Pixel format, width and height are taken from the attributes and must all be set
+    #[inline]
+    pub fn with_attributes(attributes: &PixelBufferAttributes) -> Result<CVPixelBuffer, CVError> {
+        let (pixel_format, width, height) = attributes.validate()?;
+        CVPixelBuffer::new(pixel_format.as_os_type(), width, height, Some(&attributes.to_dictionary()))
+    }
+
+    #[inline]
+    pub fn copy_attributes(&self) -> Option<PixelBufferAttributes> {
+        self.copy_creation_attributes()
+            .map(|attributes| PixelBufferAttributes::from_dictionary(&attributes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_defaults() {
+        let attributes = PixelBufferAttributes::new();
+        assert!(attributes.get_pixel_formats().is_empty());
+        assert_eq!(attributes.get_pixel_format(), None);
+        assert_eq!((attributes.get_width(), attributes.get_height()), (None, None));
+        assert_eq!(attributes.get_extended_pixels(), None);
+        assert_eq!(attributes.get_bytes_per_row_alignment(), None);
+        assert_eq!(attributes.get_plane_alignment(), None);
+        assert_eq!(attributes.get_cg_bitmap_context_compatibility(), None);
+        assert_eq!(attributes.get_cg_image_compatibility(), None);
+        assert_eq!(attributes.get_opengl_compatibility(), None);
+        assert_eq!(attributes.get_metal_compatibility(), None);
+        assert_eq!(attributes.get_bayer_pattern(), None);
+
+        let attributes = PixelBufferAttributes::new()
+            .pixel_formats([PixelFormat::_420YpCbCr8BiPlanarVideoRange, PixelFormat::_32BGRA])
+            .size(1920, 1080)
+            .extended_pixels(1, 2, 3, 4)
+            .bytes_per_row_alignment(64)
+            .plane_alignment(16)
+            .metal_compatibility(true);
+        assert_eq!(attributes.get_pixel_format(), Some(PixelFormat::_420YpCbCr8BiPlanarVideoRange));
+        assert_eq!(attributes.get_pixel_formats().len(), 2);
+        assert_eq!((attributes.get_width(), attributes.get_height()), (Some(1920), Some(1080)));
+        assert_eq!(attributes.get_extended_pixels(), Some((1, 2, 3, 4)));
+        assert_eq!(attributes.get_bytes_per_row_alignment(), Some(64));
+        assert_eq!(attributes.get_plane_alignment(), Some(16));
+        assert_eq!(attributes.get_metal_compatibility(), Some(true));
+        assert_eq!(attributes.clone().pixel_format(PixelFormat::_32BGRA).get_pixel_formats(), [PixelFormat::_32BGRA]);
+    }
+
+    #[test]
+    fn validate() {
+        let bgra = PixelBufferAttributes::new().pixel_format(PixelFormat::_32BGRA);
+        assert_eq!(bgra.clone().size(1920, 1080).validate(), Ok((PixelFormat::_32BGRA, 1920, 1080)));
+        assert_eq!(PixelBufferAttributes::new().size(16, 16).validate(), Err(CVError::InvalidPixelFormat));
+        assert_eq!(bgra.clone().width(16).validate(), Err(CVError::InvalidPixelBufferAttributes));
+        assert_eq!(bgra.clone().height(16).validate(), Err(CVError::InvalidPixelBufferAttributes));
+        assert_eq!(bgra.clone().size(0, 16).validate(), Err(CVError::InvalidSize));
+        assert_eq!(bgra.clone().size(16, 0).validate(), Err(CVError::InvalidSize));
+        assert_eq!(bgra.clone().size(0, 0).validate(), Err(CVError::InvalidSize));
+        assert_eq!(bgra.clone().size(16, 16).bytes_per_row_alignment(0).plane_alignment(0).validate(), Ok((PixelFormat::_32BGRA, 16, 16)));
+    }
+
+    #[test]
+    fn validate_oversized() {
+        let bgra = PixelBufferAttributes::new().pixel_format(PixelFormat::_32BGRA);
+        assert_eq!(bgra.clone().size(usize::MAX, 1).validate(), Err(CVError::InvalidSize));
+        assert_eq!(bgra.clone().size(1 << 31, 1 << 31).validate(), Err(CVError::InvalidSize));
+        assert_eq!(bgra.clone().size(16, 16).extended_pixels(usize::MAX, 0, 0, 0).validate(), Err(CVError::InvalidSize));
+        assert_eq!(bgra.clone().size(16, 16).bytes_per_row_alignment(usize::MAX).validate(), Err(CVError::InvalidSize));
+        let biplanar = PixelBufferAttributes::new().pixel_format(PixelFormat::_420YpCbCr8BiPlanarVideoRange);
+        assert_eq!(biplanar.size(1 << 20, 1 << 20).plane_alignment(usize::MAX).validate(), Err(CVError::InvalidSize));
+    }
+
+    #[test]
+    fn validate_conflicting() {
+        let attributes = PixelBufferAttributes::new().size(16, 16).bayer_pattern(CVVersatileBayerPattern::RGGB);
+        assert_eq!(attributes.clone().pixel_format(PixelFormat::_32BGRA).validate(), Err(CVError::InvalidPixelBufferAttributes));
+        assert_eq!(attributes.clone().pixel_format(PixelFormat::_14Bayer_RGGB).validate(), Err(CVError::InvalidPixelBufferAttributes));
+        assert_eq!(attributes.pixel_format(PixelFormat::_16VersatileBayer).validate(), Ok((PixelFormat::_16VersatileBayer, 16, 16)));
+    }
+
+    #[test]
+    fn bayer_pattern_codes() {
+        for pattern in [CVVersatileBayerPattern::RGGB, CVVersatileBayerPattern::GRBG, CVVersatileBayerPattern::GBRG, CVVersatileBayerPattern::BGGR] {
+            assert_eq!(CVVersatileBayerPattern::try_from(u32::from(pattern)), Ok(pattern));
+        }
+        assert_eq!(CVVersatileBayerPattern::try_from(4), Err(CVError::InvalidArgument));
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    #[test]
+    fn dictionary_round_trip() {
+        let attributes = PixelBufferAttributes::new()
+            .pixel_formats([PixelFormat::_420YpCbCr8BiPlanarVideoRange, PixelFormat::_32BGRA])
+            .size(1920, 1080)
+            .extended_pixels(1, 2, 3, 4)
+            .bytes_per_row_alignment(64)
+            .plane_alignment(16)
+            .cg_image_compatibility(false)
+            .metal_compatibility(true)
+            .bayer_pattern(CVVersatileBayerPattern::GBRG)
+            .io_surface_backed();
+        let dictionary = attributes.to_dictionary();
+        assert_eq!(find_size(&dictionary, CVPixelBufferKeys::ExtendedPixelsTop), Some(3));
+        assert_eq!(find_value(&dictionary, CVPixelBufferKeys::PixelFormatType).map(|value| value.instance_of::<CFArray>()), Some(true));
+        assert_eq!(find_value(&dictionary, CVPixelBufferKeys::CGBitmapContextCompatibility), None);
+
+        let parsed = PixelBufferAttributes::from_dictionary(&dictionary);
+        assert_eq!(parsed.get_pixel_formats(), attributes.get_pixel_formats());
+        assert_eq!((parsed.get_width(), parsed.get_height()), (Some(1920), Some(1080)));
+        assert_eq!(parsed.get_extended_pixels(), Some((1, 2, 3, 4)));
+        assert_eq!(parsed.get_bytes_per_row_alignment(), Some(64));
+        assert_eq!(parsed.get_plane_alignment(), Some(16));
+        assert_eq!(parsed.get_cg_bitmap_context_compatibility(), None);
+        assert_eq!(parsed.get_cg_image_compatibility(), Some(false));
+        assert_eq!(parsed.get_metal_compatibility(), Some(true));
+        assert_eq!(parsed.get_bayer_pattern(), Some(CVVersatileBayerPattern::GBRG));
+        assert!(parsed.is_io_surface_backed());
+        assert!(parsed.get_memory_allocator().is_none());
+        assert_eq!(PixelBufferAttributes::from_dictionary(&parsed.to_dictionary()).to_dictionary().len(), dictionary.len());
+
+        // A single pixel format is stored as a number, and one extended side implies zero for the others
+        let parsed = PixelBufferAttributes::from_dictionary(&CFDictionary::from_CFType_pairs(&[
+            (CFString::from(CVPixelBufferKeys::PixelFormatType), CFNumber::from(PixelFormat::_32BGRA.as_os_type() as i64).as_CFType()),
+            (CFString::from(CVPixelBufferKeys::ExtendedPixelsLeft), CFNumber::from(8).as_CFType()),
+            (CFString::from(CVPixelBufferKeys::Width), CFNumber::from(-1).as_CFType()),
+        ]));
+        assert_eq!(parsed.get_pixel_formats(), [PixelFormat::_32BGRA]);
+        assert_eq!(parsed.get_extended_pixels(), Some((8, 0, 0, 0)));
+        assert_eq!(parsed.get_width(), None);
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    #[test]
+    fn creation_attributes() {
+        let attributes = PixelBufferAttributes::new()
+            .pixel_format(PixelFormat::_420YpCbCr8BiPlanarVideoRange)
+            .size(640, 480)
+            .extended_pixels(16, 16, 8, 8)
+            .bytes_per_row_alignment(64)
+            .metal_compatibility(true)
+            .io_surface_backed();
+        let pixel_buffer = CVPixelBuffer::with_attributes(&attributes).unwrap();
+        assert_eq!(pixel_buffer.get_extended_pixels(), (16, 16, 8, 8));
+        let copied = pixel_buffer.copy_attributes().unwrap();
+        assert_eq!(copied.get_pixel_formats(), attributes.get_pixel_formats());
+        assert_eq!((copied.get_width(), copied.get_height()), (Some(640), Some(480)));
+        assert_eq!(copied.get_extended_pixels(), Some((16, 16, 8, 8)));
+        assert_eq!(copied.get_bytes_per_row_alignment(), Some(64));
+        assert_eq!(copied.get_metal_compatibility(), Some(true));
+        assert!(copied.is_io_surface_backed());
+    }
+}
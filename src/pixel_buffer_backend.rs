@@ -0,0 +1,175 @@
+// What the CoreVideo and the portable CVPixelBuffer have in common beyond their inherent methods, so that code written
+// against this trait builds with either backend: buffers are created from PixelBufferAttributes rather than a
+// dictionary, and image buffer attachments are typed values rather than CFTypes
+
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+use core_foundation::{
+    base::{CFType, TCFType},
+    number::CFNumber,
+    string::CFString,
+};
+
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+use crate::{
+    buffer::{kCVAttachmentMode_ShouldPropagate, TCVBuffer},
+    color_conversion::ALPHA_CHANNEL_MODES,
+    color_description::CHROMA_SUBSAMPLINGS,
+    color_primaries::COLOR_PRIMARIES,
+    pixel_buffer::CVPixelBuffer,
+    transfer_function::TRANSFER_FUNCTIONS,
+    ycbcr::{CHROMA_LOCATIONS, YCBCR_MATRICES},
+};
+use crate::{
+    image_buffer::{
+        CVImageBufferAlphaChannelMode, CVImageBufferChromaLocation, CVImageBufferChromaSubsampling, CVImageBufferColorPrimaries, CVImageBufferKeys,
+        CVImageBufferTransferFunction, CVImageBufferYCbCrMatrix,
+    },
+    pixel_buffer_attributes::PixelBufferAttributes,
+    r#return::CVError,
+};
+
+mod private {
+    pub trait Sealed {}
+}
+
+// Values that can be attached under a CVImageBufferKeys key: CoreVideo stores the enums as their CFString constants and
+// numbers as CFNumbers, the portable backend stores the values themselves
+pub trait AttachmentValue: private::Sealed + Copy + 'static {
+    #[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+    fn to_attachment(self) -> CFType;
+
+    #[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+    fn from_attachment(value: &CFType) -> Option<Self>;
+}
+
+macro_rules! impl_string_attachment_value {
+    ($($ty:ty => $candidates:expr),*) => {
+        $(
+            impl private::Sealed for $ty {}
+
+            impl AttachmentValue for $ty {
+                #[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+                fn to_attachment(self) -> CFType {
+                    CFString::from(self).as_CFType()
+                }
+
+                #[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+                fn from_attachment(value: &CFType) -> Option<Self> {
+                    let value = value.downcast::<CFString>()?;
+                    $candidates.iter().copied().find(|&candidate| CFString::from(candidate) == value)
+                }
+            }
+        )*
+    };
+}
+
+impl_string_attachment_value!(
+    CVImageBufferYCbCrMatrix => YCBCR_MATRICES,
+    CVImageBufferColorPrimaries => COLOR_PRIMARIES,
+    CVImageBufferTransferFunction => TRANSFER_FUNCTIONS,
+    CVImageBufferChromaLocation => CHROMA_LOCATIONS,
+    CVImageBufferChromaSubsampling => CHROMA_SUBSAMPLINGS,
+    CVImageBufferAlphaChannelMode => ALPHA_CHANNEL_MODES
+);
+
+impl private::Sealed for f64 {}
+
+impl AttachmentValue for f64 {
+    #[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+    fn to_attachment(self) -> CFType {
+        CFNumber::from(self).as_CFType()
+    }
+
+    #[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+    fn from_attachment(value: &CFType) -> Option<Self> {
+        value.downcast::<CFNumber>()?.to_f64()
+    }
+}
+
+pub trait PixelBufferBackend: Sized {
+    fn with_attributes(attributes: &PixelBufferAttributes) -> Result<Self, CVError>;
+
+    fn copy_attributes(&self) -> Option<PixelBufferAttributes>;
+
+    // None when nothing is attached under `key` or the attachment is not a `T`
+    fn get_image_attachment<T: AttachmentValue>(&self, key: CVImageBufferKeys) -> Option<T>;
+
+    // Attachments propagate, like the ones CoreVideo itself sets on image buffers
+    fn set_image_attachment<T: AttachmentValue>(&self, key: CVImageBufferKeys, value: T);
+
+    fn remove_image_attachment(&self, key: CVImageBufferKeys);
+}
+
+#[cfg(not(all(feature = "portable", not(any(target_os = "macos", target_os = "ios")))))]
+impl PixelBufferBackend for CVPixelBuffer {
+    #[inline]
+    fn with_attributes(attributes: &PixelBufferAttributes) -> Result<Self, CVError> {
+        CVPixelBuffer::with_attributes(attributes)
+    }
+
+    #[inline]
+    fn copy_attributes(&self) -> Option<PixelBufferAttributes> {
+        CVPixelBuffer::copy_attributes(self)
+    }
+
+    #[inline]
+    fn get_image_attachment<T: AttachmentValue>(&self, key: CVImageBufferKeys) -> Option<T> {
+        let mut attachment_mode = kCVAttachmentMode_ShouldPropagate;
+        T::from_attachment(&self.as_buffer().get_attachment(&CFString::from(key), &mut attachment_mode)?)
+    }
+
+    #[inline]
+    fn set_image_attachment<T: AttachmentValue>(&self, key: CVImageBufferKeys, value: T) {
+        self.as_buffer()
+            .set_attachment(&CFString::from(key), &value.to_attachment(), kCVAttachmentMode_ShouldPropagate);
+    }
+
+    #[inline]
+    fn remove_image_attachment(&self, key: CVImageBufferKeys) {
+        self.as_buffer().remove_attachment(&CFString::from(key));
+    }
+}
+
+#[cfg(all(test, any(feature = "portable", target_os = "macos", target_os = "ios")))]
+mod tests {
+    use super::*;
+    use crate::{pixel_buffer::CVPixelBuffer, pixel_format::PixelFormat, transfer_function::TransferFunction};
+
+    fn create<B: PixelBufferBackend>(pixel_format: PixelFormat) -> B {
+        B::with_attributes(&PixelBufferAttributes::new().pixel_format(pixel_format).size(16, 8).bytes_per_row_alignment(64)).unwrap()
+    }
+
+    #[test]
+    fn attributes() {
+        let pixel_buffer: CVPixelBuffer = create(PixelFormat::_420YpCbCr8BiPlanarVideoRange);
+        assert_eq!((pixel_buffer.get_width(), pixel_buffer.get_height()), (16, 8));
+        let attributes = PixelBufferBackend::copy_attributes(&pixel_buffer).unwrap();
+        assert_eq!(attributes.get_pixel_format(), Some(PixelFormat::_420YpCbCr8BiPlanarVideoRange));
+        assert_eq!(attributes.get_bytes_per_row_alignment(), Some(64));
+    }
+
+    #[test]
+    fn attachments() {
+        let pixel_buffer: CVPixelBuffer = create(PixelFormat::_32BGRA);
+        assert_eq!(pixel_buffer.get_image_attachment::<CVImageBufferColorPrimaries>(CVImageBufferKeys::ColorPrimaries), None);
+        pixel_buffer.set_image_attachment(CVImageBufferKeys::ColorPrimaries, CVImageBufferColorPrimaries::P3_D65);
+        pixel_buffer.set_image_attachment(CVImageBufferKeys::TransferFunction, CVImageBufferTransferFunction::UseGamma);
+        pixel_buffer.set_image_attachment(CVImageBufferKeys::GammaLevel, 2.2);
+
+        // Clones are handles to the same buffer and see the same attachments
+        let clone = pixel_buffer.clone();
+        assert_eq!(clone.get_image_attachment(CVImageBufferKeys::ColorPrimaries), Some(CVImageBufferColorPrimaries::P3_D65));
+        assert_eq!(clone.get_image_attachment(CVImageBufferKeys::GammaLevel), Some(2.2));
+        assert_eq!(TransferFunction::from_attachments(&clone), Some(TransferFunction::with_gamma(2.2)));
+        assert_eq!(clone.get_image_attachment::<CVImageBufferYCbCrMatrix>(CVImageBufferKeys::ColorPrimaries), None);
+        assert_eq!(clone.get_image_attachment::<f64>(CVImageBufferKeys::ColorPrimaries), None);
+
+        pixel_buffer.set_image_attachment(CVImageBufferKeys::ColorPrimaries, CVImageBufferColorPrimaries::ITU_R_2020);
+        assert_eq!(clone.get_image_attachment(CVImageBufferKeys::ColorPrimaries), Some(CVImageBufferColorPrimaries::ITU_R_2020));
+        clone.remove_image_attachment(CVImageBufferKeys::ColorPrimaries);
+        assert_eq!(pixel_buffer.get_image_attachment::<CVImageBufferColorPrimaries>(CVImageBufferKeys::ColorPrimaries), None);
+
+        let other: CVPixelBuffer = create(PixelFormat::_32BGRA);
+        assert_eq!(other.get_image_attachment::<f64>(CVImageBufferKeys::GammaLevel), None);
+    }
+}
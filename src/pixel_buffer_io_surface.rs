@@ -10,7 +10,7 @@ use io_surface::IOSurface;
 use crate::{
     io_surface::IOSurfaceRef,
     pixel_buffer::{CVPixelBuffer, CVPixelBufferRef},
-    r#return::{kCVReturnSuccess, CVReturn},
+    r#return::{CVError, CVReturn},
 };
 
 extern "C" {
@@ -59,7 +59,7 @@ impl From<CVPixelBufferIOSurfaceKeys> for CFString {
 
 impl CVPixelBuffer {
     #[inline]
-    pub fn from_io_surface(io_surface: &IOSurface, options: Option<&CFDictionary<CFString, CFType>>) -> Result<CVPixelBuffer, CVReturn> {
+    pub fn from_io_surface(io_surface: &IOSurface, options: Option<&CFDictionary<CFString, CFType>>) -> Result<CVPixelBuffer, CVError> {
         let mut pixel_buffer: CVPixelBufferRef = null_mut();
         let status = unsafe {
             CVPixelBufferCreateWithIOSurface(
@@ -69,11 +69,7 @@ impl CVPixelBuffer {
                 &mut pixel_buffer,
             )
         };
-        if status == kCVReturnSuccess {
-            Ok(unsafe { TCFType::wrap_under_create_rule(pixel_buffer) })
-        } else {
-            Err(status)
-        }
+        CVError::result(status).map(|_| unsafe { TCFType::wrap_under_create_rule(pixel_buffer) })
     }
 
     #[inline]
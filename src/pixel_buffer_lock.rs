@@ -0,0 +1,558 @@
+use std::{
+    mem::{align_of, size_of},
+    slice,
+};
+
+use crate::{
+    pixel_buffer::{kCVPixelBufferLock_ReadOnly, CVPixelBuffer, CVPixelBufferLockFlags},
+    pixel_format::PixelFormat,
+    r#return::CVError,
+    OSType,
+};
+
+mod private {
+    pub trait Sealed {}
+}
+
+// Sample types a plane row can be viewed as; all of them are valid for any bit pattern
+pub trait PlaneSample: private::Sealed + Copy + 'static {}
+
+macro_rules! impl_plane_sample {
+    ($($ty:ty),*) => {
+        $(
+            impl private::Sealed for $ty {}
+            impl PlaneSample for $ty {}
+        )*
+    };
+}
+
+impl_plane_sample!(u8, u16, u32, u64, f32, f64);
+#[cfg(feature = "half")]
+impl_plane_sample!(half::f16);
+
+fn cast_samples<T: PlaneSample>(bytes: &[u8]) -> Option<&[T]> {
+    if !(bytes.as_ptr() as usize).is_multiple_of(align_of::<T>()) || !bytes.len().is_multiple_of(size_of::<T>()) {
+        return None;
+    }
+    Some(unsafe { slice::from_raw_parts(bytes.as_ptr() as *const T, bytes.len() / size_of::<T>()) })
+}
+
+fn cast_samples_mut<T: PlaneSample>(bytes: &mut [u8]) -> Option<&mut [T]> {
+    if !(bytes.as_ptr() as usize).is_multiple_of(align_of::<T>()) || !bytes.len().is_multiple_of(size_of::<T>()) {
+        return None;
+    }
+    Some(unsafe { slice::from_raw_parts_mut(bytes.as_mut_ptr() as *mut T, bytes.len() / size_of::<T>()) })
+}
+
+#[derive(Clone, Copy, Debug)]
+struct PlaneGeometry {
+    base_address: *mut u8,
+    width: usize,
+    height: usize,
+    bytes_per_row: usize,
+    row_bytes: usize,
+}
+
+impl PlaneGeometry {
+    // Only the rows up to the last pixel of the last row are covered, so the padding after the last row is never touched
+    #[inline]
+    fn len(&self) -> usize {
+        if self.height == 0 {
+            0
+        } else {
+            self.bytes_per_row * (self.height - 1) + self.row_bytes
+        }
+    }
+}
+
+fn plane_geometry(pixel_buffer: &CVPixelBuffer, plane_index: usize) -> Option<PlaneGeometry> {
+    let (base_address, width, height, bytes_per_row) = if pixel_buffer.is_planar() {
+        if plane_index >= pixel_buffer.get_plane_count() {
+            return None;
+        }
+        (
+            unsafe { pixel_buffer.get_base_address_of_plane(plane_index) },
+            pixel_buffer.get_width_of_plane(plane_index),
+            pixel_buffer.get_height_of_plane(plane_index),
+            pixel_buffer.get_bytes_per_row_of_plane(plane_index),
+        )
+    } else {
+        if plane_index != 0 {
+            return None;
+        }
+        (unsafe { pixel_buffer.get_base_address() }, pixel_buffer.get_width(), pixel_buffer.get_height(), pixel_buffer.get_bytes_per_row())
+    };
+    if base_address.is_null() {
+        return None;
+    }
+    let row_bytes = PixelFormat::from(pixel_buffer.get_pixel_format())
+        .layout()
+        .and_then(|layout| layout.planes.get(plane_index))
+        .map_or(bytes_per_row, |plane| {
+            (width.div_ceil(plane.block_width as usize) * plane.bits_per_block as usize)
+                .div_ceil(8)
+                .min(bytes_per_row)
+        });
+    Some(PlaneGeometry { base_address: base_address as *mut u8, width, height, bytes_per_row, row_bytes })
+}
+
+fn plane_count(pixel_buffer: &CVPixelBuffer) -> usize {
+    if pixel_buffer.is_planar() {
+        pixel_buffer.get_plane_count()
+    } else {
+        1
+    }
+}
+
+pub struct PlaneRef<'a> {
+    data: &'a [u8],
+    width: usize,
+    height: usize,
+    bytes_per_row: usize,
+    row_bytes: usize,
+}
+
+impl<'a> PlaneRef<'a> {
+    fn new(geometry: PlaneGeometry) -> Self {
+        PlaneRef {
+            data: unsafe { slice::from_raw_parts(geometry.base_address, geometry.len()) },
+            width: geometry.width,
+            height: geometry.height,
+            bytes_per_row: geometry.bytes_per_row,
+            row_bytes: geometry.row_bytes,
+        }
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    #[inline]
+    pub fn bytes_per_row(&self) -> usize {
+        self.bytes_per_row
+    }
+
+    #[inline]
+    pub fn row_bytes(&self) -> usize {
+        self.row_bytes
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    #[inline]
+    pub fn row(&self, y: usize) -> Option<&'a [u8]> {
+        if y >= self.height {
+            return None;
+        }
+        let start = y * self.bytes_per_row;
+        Some(&self.data[start..start + self.row_bytes])
+    }
+
+    #[inline]
+    pub fn rows(&self) -> impl Iterator<Item = &'a [u8]> {
+        let row_bytes = self.row_bytes;
+        self.data.chunks(self.bytes_per_row.max(1)).map(move |row| &row[..row_bytes])
+    }
+
+    #[inline]
+    pub fn row_as<T: PlaneSample>(&self, y: usize) -> Option<&'a [T]> {
+        self.row(y).and_then(cast_samples)
+    }
+
+    // Yields `None` for rows that are not aligned to, or not a whole number of, `T`
+    #[inline]
+    pub fn rows_as<T: PlaneSample>(&self) -> impl Iterator<Item = Option<&'a [T]>> {
+        self.rows().map(cast_samples)
+    }
+}
+
+pub struct PlaneMut<'a> {
+    data: &'a mut [u8],
+    width: usize,
+    height: usize,
+    bytes_per_row: usize,
+    row_bytes: usize,
+}
+
+impl<'a> PlaneMut<'a> {
+    fn new(geometry: PlaneGeometry) -> Self {
+        PlaneMut {
+            data: unsafe { slice::from_raw_parts_mut(geometry.base_address, geometry.len()) },
+            width: geometry.width,
+            height: geometry.height,
+            bytes_per_row: geometry.bytes_per_row,
+            row_bytes: geometry.row_bytes,
+        }
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    #[inline]
+    pub fn bytes_per_row(&self) -> usize {
+        self.bytes_per_row
+    }
+
+    #[inline]
+    pub fn row_bytes(&self) -> usize {
+        self.row_bytes
+    }
+
+    #[inline]
+    pub fn as_plane_ref(&self) -> PlaneRef<'_> {
+        PlaneRef { data: self.data, width: self.width, height: self.height, bytes_per_row: self.bytes_per_row, row_bytes: self.row_bytes }
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        self.data
+    }
+
+    #[inline]
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        self.data
+    }
+
+    #[inline]
+    pub fn row(&self, y: usize) -> Option<&[u8]> {
+        if y >= self.height {
+            return None;
+        }
+        let start = y * self.bytes_per_row;
+        Some(&self.data[start..start + self.row_bytes])
+    }
+
+    #[inline]
+    pub fn row_mut(&mut self, y: usize) -> Option<&mut [u8]> {
+        if y >= self.height {
+            return None;
+        }
+        let start = y * self.bytes_per_row;
+        Some(&mut self.data[start..start + self.row_bytes])
+    }
+
+    #[inline]
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        let row_bytes = self.row_bytes;
+        self.data.chunks(self.bytes_per_row.max(1)).map(move |row| &row[..row_bytes])
+    }
+
+    #[inline]
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [u8]> {
+        let row_bytes = self.row_bytes;
+        self.data.chunks_mut(self.bytes_per_row.max(1)).map(move |row| &mut row[..row_bytes])
+    }
+
+    #[inline]
+    pub fn row_as<T: PlaneSample>(&self, y: usize) -> Option<&[T]> {
+        self.row(y).and_then(cast_samples)
+    }
+
+    #[inline]
+    pub fn row_as_mut<T: PlaneSample>(&mut self, y: usize) -> Option<&mut [T]> {
+        self.row_mut(y).and_then(cast_samples_mut)
+    }
+
+    #[inline]
+    pub fn rows_as<T: PlaneSample>(&self) -> impl Iterator<Item = Option<&[T]>> {
+        self.rows().map(cast_samples)
+    }
+
+    #[inline]
+    pub fn rows_as_mut<T: PlaneSample>(&mut self) -> impl Iterator<Item = Option<&mut [T]>> {
+        self.rows_mut().map(cast_samples_mut)
+    }
+}
+
+pub struct PixelBufferReadGuard<'a> {
+    pixel_buffer: &'a CVPixelBuffer,
+}
+
+pub struct PixelBufferWriteGuard<'a> {
+    pixel_buffer: &'a mut CVPixelBuffer,
+}
+
+impl CVPixelBuffer {
+    // Any number of read locks may be held at once on a buffer, through this or any other handle to it, but none while it
+    // is locked for writing
+    #[inline]
+    pub fn lock_read(&self) -> Result<PixelBufferReadGuard<'_>, CVError> {
+        self.lock_base_address(kCVPixelBufferLock_ReadOnly)?;
+        Ok(PixelBufferReadGuard { pixel_buffer: self })
+    }
+
+    // Fails while the buffer is locked for reading or writing through this or any other handle to it
+    #[inline]
+    pub fn lock_write(&mut self) -> Result<PixelBufferWriteGuard<'_>, CVError> {
+        self.lock_base_address(0)?;
+        Ok(PixelBufferWriteGuard { pixel_buffer: self })
+    }
+}
+
+macro_rules! impl_guard_getters {
+    ($guard:ident) => {
+        impl $guard<'_> {
+            #[inline]
+            pub fn width(&self) -> usize {
+                self.pixel_buffer.get_width()
+            }
+
+            #[inline]
+            pub fn height(&self) -> usize {
+                self.pixel_buffer.get_height()
+            }
+
+            #[inline]
+            pub fn pixel_format(&self) -> OSType {
+                self.pixel_buffer.get_pixel_format()
+            }
+
+            #[inline]
+            pub fn bytes_per_row(&self) -> usize {
+                self.pixel_buffer.get_bytes_per_row()
+            }
+
+            #[inline]
+            pub fn is_planar(&self) -> bool {
+                self.pixel_buffer.is_planar()
+            }
+
+            // Non-planar buffers are exposed as a single plane
+            #[inline]
+            pub fn plane_count(&self) -> usize {
+                plane_count(self.pixel_buffer)
+            }
+
+            #[inline]
+            pub fn base_address(&self) -> *const u8 {
+                unsafe { self.pixel_buffer.get_base_address() as *const u8 }
+            }
+
+            #[inline]
+            pub fn base_address_of_plane(&self, plane_index: usize) -> *const u8 {
+                unsafe { self.pixel_buffer.get_base_address_of_plane(plane_index) as *const u8 }
+            }
+
+            #[inline]
+            pub fn plane(&self, plane_index: usize) -> Option<PlaneRef<'_>> {
+                plane_geometry(self.pixel_buffer, plane_index).map(PlaneRef::new)
+            }
+
+            #[inline]
+            pub fn planes(&self) -> Vec<PlaneRef<'_>> {
+                (0..self.plane_count()).filter_map(|plane_index| self.plane(plane_index)).collect()
+            }
+        }
+    };
+}
+
+impl_guard_getters!(PixelBufferReadGuard);
+impl_guard_getters!(PixelBufferWriteGuard);
+
+impl PixelBufferReadGuard<'_> {
+    #[inline]
+    pub fn lock_flags(&self) -> CVPixelBufferLockFlags {
+        kCVPixelBufferLock_ReadOnly
+    }
+}
+
+impl Drop for PixelBufferReadGuard<'_> {
+    fn drop(&mut self) {
+        let _ = unsafe { self.pixel_buffer.unlock_base_address(kCVPixelBufferLock_ReadOnly) };
+    }
+}
+
+impl PixelBufferWriteGuard<'_> {
+    #[inline]
+    pub fn lock_flags(&self) -> CVPixelBufferLockFlags {
+        0
+    }
+
+    #[inline]
+    pub fn base_address_mut(&mut self) -> *mut u8 {
+        unsafe { self.pixel_buffer.get_base_address() as *mut u8 }
+    }
+
+    #[inline]
+    pub fn base_address_of_plane_mut(&mut self, plane_index: usize) -> *mut u8 {
+        unsafe { self.pixel_buffer.get_base_address_of_plane(plane_index) as *mut u8 }
+    }
+
+    #[inline]
+    pub fn plane_mut(&mut self, plane_index: usize) -> Option<PlaneMut<'_>> {
+        plane_geometry(self.pixel_buffer, plane_index).map(PlaneMut::new)
+    }
+
+    // Planes never overlap, so all of them can be borrowed mutably at once
+    #[inline]
+    pub fn planes_mut(&mut self) -> Vec<PlaneMut<'_>> {
+        (0..self.plane_count())
+            .filter_map(|plane_index| plane_geometry(self.pixel_buffer, plane_index))
+            .map(PlaneMut::new)
+            .collect()
+    }
+}
+
+impl Drop for PixelBufferWriteGuard<'_> {
+    fn drop(&mut self) {
+        let _ = unsafe { self.pixel_buffer.unlock_base_address(0) };
+    }
+}
+
+#[cfg(all(test, any(feature = "portable", target_os = "macos", target_os = "ios")))]
+mod tests {
+    use libc::c_void;
+
+    use super::*;
+    use crate::{
+        pixel_buffer::{kCVPixelFormatType_32BGRA, kCVPixelFormatType_420YpCbCr8BiPlanarVideoRange, kCVPixelFormatType_OneComponent8},
+        pixel_buffer_attributes::PixelBufferAttributes,
+    };
+
+    extern "C" fn release(_release_ref_con: *mut c_void, _base_address: *const *const c_void) {}
+
+    #[test]
+    fn locks_are_shared_by_clones() {
+        let mut pixel_buffer = CVPixelBuffer::new(kCVPixelFormatType_32BGRA, 16, 16, None).unwrap();
+        let mut clone = pixel_buffer.clone();
+        {
+            let mut guard = pixel_buffer.lock_write().unwrap();
+            assert_eq!(clone.lock_write().err(), Some(CVError::InvalidArgument));
+            assert_eq!(clone.lock_read().err(), Some(CVError::InvalidArgument));
+            guard.plane_mut(0).unwrap().as_bytes_mut().fill(0xFF);
+        }
+        {
+            let guard = pixel_buffer.lock_read().unwrap();
+            let clone_guard = clone.lock_read().unwrap();
+            assert!(guard.plane(0).unwrap().rows().all(|row| row.iter().all(|&byte| byte == 0xFF)));
+            assert_eq!(clone_guard.plane(0).unwrap().as_bytes(), guard.plane(0).unwrap().as_bytes());
+        }
+        {
+            let _guard = pixel_buffer.lock_read().unwrap();
+            assert_eq!(clone.lock_write().err(), Some(CVError::InvalidArgument));
+        }
+        let guard = clone.lock_write().unwrap();
+        assert_eq!((guard.width(), guard.height(), guard.pixel_format()), (16, 16, kCVPixelFormatType_32BGRA));
+        assert_eq!(guard.plane_count(), 1);
+    }
+
+    #[test]
+    fn buffers_lock_independently() {
+        let mut first = CVPixelBuffer::new(kCVPixelFormatType_32BGRA, 8, 8, None).unwrap();
+        let mut second = CVPixelBuffer::new(kCVPixelFormatType_32BGRA, 8, 8, None).unwrap();
+        let _first_guard = first.lock_write().unwrap();
+        let _second_guard = second.lock_write().unwrap();
+    }
+
+    #[test]
+    fn biplanar_planes() {
+        let mut pixel_buffer = CVPixelBuffer::new(kCVPixelFormatType_420YpCbCr8BiPlanarVideoRange, 16, 8, None).unwrap();
+        {
+            let mut guard = pixel_buffer.lock_write().unwrap();
+            let mut planes = guard.planes_mut();
+            assert_eq!(planes.len(), 2);
+            planes[0].rows_mut().for_each(|row| row.fill(0x10));
+            planes[1].rows_mut().for_each(|row| row.fill(0x80));
+        }
+        let guard = pixel_buffer.lock_read().unwrap();
+        let planes = guard.planes();
+        assert_eq!(planes.iter().map(|plane| (plane.width(), plane.height(), plane.row_bytes())).collect::<Vec<_>>(), [(16, 8, 16), (8, 4, 16)]);
+        assert_eq!(planes[0].rows().count(), 8);
+        assert_eq!(planes[1].rows().count(), 4);
+        assert!(planes[0].rows().all(|row| row == [0x10; 16]));
+        assert!(planes[1].rows().all(|row| row == [0x80; 16]));
+        assert!(guard.plane(2).is_none());
+    }
+
+    #[test]
+    fn row_trimming() {
+        // Five BGRA pixels take 20 of the 32 bytes of each row
+        let mut data = vec![0xEEu8; 32 * 3];
+        let mut pixel_buffer = unsafe {
+            CVPixelBuffer::new_with_bytes(kCVPixelFormatType_32BGRA, 5, 3, data.as_mut_ptr() as *mut c_void, 32, release, std::ptr::null_mut(), None)
+                .unwrap()
+        };
+        {
+            let mut guard = pixel_buffer.lock_write().unwrap();
+            let mut plane = guard.plane_mut(0).unwrap();
+            assert_eq!((plane.bytes_per_row(), plane.row_bytes(), plane.as_bytes().len()), (32, 20, 32 * 2 + 20));
+            assert_eq!(plane.row_mut(3), None);
+            for (y, row) in plane.rows_mut().enumerate() {
+                assert_eq!(row.len(), 20);
+                row.fill(y as u8);
+            }
+        }
+        drop(pixel_buffer);
+        for (y, row) in data.chunks(32).enumerate() {
+            assert_eq!(row[..20], [y as u8; 20]);
+            assert_eq!(row[20..], [0xEE; 12]);
+        }
+    }
+
+    #[test]
+    fn extended_row_trimming() {
+        let attributes = PixelBufferAttributes::new()
+            .pixel_format(kCVPixelFormatType_32BGRA.into())
+            .size(8, 4)
+            .extended_pixels(2, 2, 1, 1);
+        let mut pixel_buffer = CVPixelBuffer::with_attributes(&attributes).unwrap();
+        let mut guard = pixel_buffer.lock_write().unwrap();
+        let base_address = guard.base_address_mut();
+        // The right extension follows the last pixel of the first row
+        unsafe { base_address.add(32).write_bytes(0xEE, 8) };
+        let mut plane = guard.plane_mut(0).unwrap();
+        assert!(plane.bytes_per_row() >= 48);
+        assert_eq!((plane.width(), plane.height(), plane.row_bytes()), (8, 4, 32));
+        plane.rows_mut().for_each(|row| row.fill(0x11));
+        assert!(plane.rows().all(|row| row == [0x11; 32]));
+        assert_eq!(unsafe { std::slice::from_raw_parts(base_address.add(32), 8) }, [0xEE; 8]);
+    }
+
+    #[test]
+    fn rows_as_failures() {
+        // Rows of three one-byte pixels are not a whole number of u16
+        let pixel_buffer = CVPixelBuffer::new(kCVPixelFormatType_OneComponent8, 3, 2, None).unwrap();
+        let guard = pixel_buffer.lock_read().unwrap();
+        let plane = guard.plane(0).unwrap();
+        assert!(plane.rows_as::<u16>().all(|row| row.is_none()));
+        assert!(plane.rows_as::<u8>().all(|row| row.map(<[u8]>::len) == Some(3)));
+        drop(guard);
+
+        // Every other row of five bytes starts on an odd address
+        let mut data = vec![0u64; 2];
+        let pixel_buffer = unsafe {
+            CVPixelBuffer::new_with_bytes(
+                kCVPixelFormatType_OneComponent8,
+                4,
+                3,
+                data.as_mut_ptr() as *mut c_void,
+                5,
+                release,
+                std::ptr::null_mut(),
+                None,
+            )
+            .unwrap()
+        };
+        let guard = pixel_buffer.lock_read().unwrap();
+        let plane = guard.plane(0).unwrap();
+        assert_eq!(plane.rows_as::<u16>().map(|row| row.map(<[u16]>::len)).collect::<Vec<_>>(), [Some(2), None, Some(2)]);
+        assert_eq!(plane.row_as::<u16>(1), None);
+        assert_eq!(plane.row_as::<u32>(0).map(<[u32]>::len), Some(1));
+    }
+}
@@ -10,7 +10,7 @@ use libc::c_void;
 use crate::{
     base::CVOptionFlags,
     pixel_buffer::{CVPixelBuffer, CVPixelBufferRef},
-    r#return::{kCVReturnSuccess, CVReturn},
+    r#return::{CVError, CVReturn},
 };
 
 #[repr(C)]
@@ -94,7 +94,7 @@ impl CVPixelBufferPool {
     pub fn new(
         pool_attributes: Option<&CFDictionary<CFString, CFType>>,
         pixel_buffer_attributes: Option<&CFDictionary<CFString, CFType>>,
-    ) -> Result<CVPixelBufferPool, CVReturn> {
+    ) -> Result<CVPixelBufferPool, CVError> {
         let mut pool: CVPixelBufferPoolRef = null_mut();
         let status = unsafe {
             CVPixelBufferPoolCreate(
@@ -104,11 +104,7 @@ impl CVPixelBufferPool {
                 &mut pool,
             )
         };
-        if status == kCVReturnSuccess {
-            Ok(unsafe { TCFType::wrap_under_create_rule(pool) })
-        } else {
-            Err(status)
-        }
+        CVError::result(status).map(|_| unsafe { TCFType::wrap_under_create_rule(pool) })
     }
 
     #[inline]
@@ -136,18 +132,14 @@ impl CVPixelBufferPool {
     }
 
     #[inline]
-    pub fn create_pixel_buffer(&self) -> Result<CVPixelBuffer, CVReturn> {
+    pub fn create_pixel_buffer(&self) -> Result<CVPixelBuffer, CVError> {
         let mut pixel_buffer: CVPixelBufferRef = null_mut();
         let status = unsafe { CVPixelBufferPoolCreatePixelBuffer(kCFAllocatorDefault, self.as_concrete_TypeRef(), &mut pixel_buffer) };
-        if status == kCVReturnSuccess {
-            Ok(unsafe { TCFType::wrap_under_create_rule(pixel_buffer) })
-        } else {
-            Err(status)
-        }
+        CVError::result(status).map(|_| unsafe { TCFType::wrap_under_create_rule(pixel_buffer) })
     }
 
     #[inline]
-    pub fn create_pixel_buffer_with_aux_attributes(&self, auxAttributes: Option<&CFDictionary<CFString, CFType>>) -> Result<CVPixelBuffer, CVReturn> {
+    pub fn create_pixel_buffer_with_aux_attributes(&self, auxAttributes: Option<&CFDictionary<CFString, CFType>>) -> Result<CVPixelBuffer, CVError> {
         let mut pixel_buffer: CVPixelBufferRef = null_mut();
         let status = unsafe {
             CVPixelBufferPoolCreatePixelBufferWithAuxAttributes(
@@ -157,10 +149,6 @@ impl CVPixelBufferPool {
                 &mut pixel_buffer,
             )
         };
-        if status == kCVReturnSuccess {
-            Ok(unsafe { TCFType::wrap_under_create_rule(pixel_buffer) })
-        } else {
-            Err(status)
-        }
+        CVError::result(status).map(|_| unsafe { TCFType::wrap_under_create_rule(pixel_buffer) })
     }
 }
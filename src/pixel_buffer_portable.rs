@@ -0,0 +1,640 @@
+// Signatures mirror the CoreVideo-backed CVPixelBuffer, except that options are PixelBufferAttributes rather than a
+// dictionary, which would need CoreFoundation to read; code that has to build with either backend creates buffers and
+// reads and writes attachments through PixelBufferBackend, which both implement
+#![allow(clippy::missing_safety_doc, clippy::too_many_arguments)]
+
+use std::{
+    alloc::{self, Layout},
+    any::Any,
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt::{self, Debug, Formatter},
+    ptr::{null_mut, NonNull},
+    rc::Rc,
+    slice,
+};
+
+use libc::c_void;
+
+use crate::{
+    image_buffer::CVImageBufferKeys,
+    pixel_buffer::{kCVPixelBufferLock_ReadOnly, CVPixelBufferLockFlags, CVPixelBufferReleaseBytesCallback, CVPixelBufferReleasePlanarBytesCallback},
+    pixel_buffer_attributes::PixelBufferAttributes,
+    pixel_buffer_backend::{AttachmentValue, PixelBufferBackend},
+    pixel_format::{PixelFormat, PixelFormatLayout},
+    plane_layout::PlaneLayout,
+    r#return::CVError,
+    OSType,
+};
+
+// CoreVideo pads rows and planes to 64 bytes on current hardware
+const DEFAULT_BYTES_PER_ROW_ALIGNMENT: usize = 64;
+const DEFAULT_PLANE_ALIGNMENT: usize = 64;
+
+enum Storage {
+    Allocated {
+        data: NonNull<u8>,
+        layout: Layout,
+        plane_layout: PlaneLayout,
+    },
+    Bytes {
+        base_address: *mut c_void,
+        release_callback: CVPixelBufferReleaseBytesCallback,
+        release_ref_con: *mut c_void,
+    },
+    PlanarBytes {
+        data_ptr: *mut c_void,
+        data_size: usize,
+        plane_addresses: Vec<*mut c_void>,
+        release_callback: CVPixelBufferReleasePlanarBytesCallback,
+        release_ref_con: *mut c_void,
+    },
+}
+
+impl Drop for Storage {
+    fn drop(&mut self) {
+        match self {
+            Storage::Allocated { data, layout, .. } => unsafe { alloc::dealloc(data.as_ptr(), *layout) },
+            Storage::Bytes { base_address, release_callback, release_ref_con } => {
+                release_callback(*release_ref_con, *base_address as *const *const c_void)
+            }
+            Storage::PlanarBytes { data_ptr, data_size, plane_addresses, release_callback, release_ref_con } => release_callback(
+                *release_ref_con,
+                *data_ptr as *const *const c_void,
+                *data_size,
+                plane_addresses.len(),
+                plane_addresses.as_ptr() as *const *const c_void,
+            ),
+        }
+    }
+}
+
+struct Plane {
+    base_address: *mut c_void,
+    width: usize,
+    height: usize,
+    bytes_per_row: usize,
+}
+
+struct PixelBufferStorage {
+    pixel_format: OSType,
+    width: usize,
+    height: usize,
+    base_address: *mut c_void,
+    bytes_per_row: usize,
+    planar: bool,
+    planes: Vec<Plane>,
+    extended_pixels: (usize, usize, usize, usize),
+    attributes: PixelBufferAttributes,
+    readers: Cell<usize>,
+    writer: Cell<bool>,
+    attachments: RefCell<HashMap<CVImageBufferKeys, Box<dyn Any>>>,
+    storage: Storage,
+}
+
+// Takes the settings that shape the memory of a buffer from `options`: row and plane alignment and extended pixels; the
+// pixel format and size come from the arguments, and the compatibility flags do not apply to heap memory
+fn with_options(attributes: PixelBufferAttributes, options: Option<&PixelBufferAttributes>) -> PixelBufferAttributes {
+    let options = match options {
+        Some(options) => options,
+        None => return attributes,
+    };
+    let mut attributes = attributes;
+    if let Some(alignment) = options.get_bytes_per_row_alignment() {
+        attributes = attributes.bytes_per_row_alignment(alignment);
+    }
+    if let Some(alignment) = options.get_plane_alignment() {
+        attributes = attributes.plane_alignment(alignment);
+    }
+    if let Some((left, right, top, bottom)) = options.get_extended_pixels() {
+        attributes = attributes.extended_pixels(left, right, top, bottom);
+    }
+    attributes
+}
+
+// Memory passed in by the caller is taken as is: it must already satisfy the requested alignments and cannot grow
+// extended pixels
+fn check_bytes_options(attributes: &PixelBufferAttributes, bytes_per_row: &[usize], plane_addresses: &[*mut c_void]) -> Result<(), CVError> {
+    if matches!(attributes.get_extended_pixels(), Some(extended_pixels) if extended_pixels != (0, 0, 0, 0)) {
+        return Err(CVError::Unsupported);
+    }
+    let is_aligned = |value: usize, alignment: Option<usize>| alignment.and_then(|alignment| value.checked_rem(alignment)).unwrap_or(0) == 0;
+    if !bytes_per_row
+        .iter()
+        .all(|&bytes_per_row| is_aligned(bytes_per_row, attributes.get_bytes_per_row_alignment())) ||
+        !plane_addresses
+            .iter()
+            .all(|&address| is_aligned(address as usize, attributes.get_plane_alignment()))
+    {
+        return Err(CVError::InvalidPixelBufferAttributes);
+    }
+    Ok(())
+}
+
+fn format_layout(pixel_format: OSType) -> Result<&'static PixelFormatLayout, CVError> {
+    match PixelFormat::from(pixel_format).layout() {
+        Some(layout) if !layout.compressed => Ok(layout),
+        _ => Err(CVError::InvalidPixelFormat),
+    }
+}
+
+// Heap-backed stand-in for CVPixelBuffer on platforms without CoreVideo; clones share the same pixels, like retained CVPixelBufferRefs
+#[derive(Clone)]
+pub struct CVPixelBuffer(Rc<PixelBufferStorage>);
+
+impl CVPixelBuffer {
+    fn allocate(plane_layout: PlaneLayout, attributes: PixelBufferAttributes) -> Result<CVPixelBuffer, CVError> {
+        let alignment = attributes
+            .get_plane_alignment()
+            .unwrap_or(DEFAULT_PLANE_ALIGNMENT)
+            .max(attributes.get_bytes_per_row_alignment().unwrap_or(DEFAULT_BYTES_PER_ROW_ALIGNMENT))
+            .max(16)
+            .checked_next_power_of_two()
+            .ok_or(CVError::InvalidPixelBufferAttributes)?;
+        let layout = Layout::from_size_align(plane_layout.data_size, alignment).map_err(|_| CVError::InvalidSize)?;
+        let data = NonNull::new(unsafe { alloc::alloc_zeroed(layout) }).ok_or(CVError::AllocationFailed)?;
+        plane_layout.write_planar_info(unsafe { slice::from_raw_parts_mut(data.as_ptr(), plane_layout.header_size) })?;
+
+        let planes: Vec<Plane> = plane_layout
+            .plane_base_addresses(data.as_ptr() as *mut c_void)
+            .into_iter()
+            .zip(plane_layout.planes.iter())
+            .map(|(base_address, plane)| Plane { base_address, width: plane.width, height: plane.height, bytes_per_row: plane.bytes_per_row })
+            .collect();
+        let planar = plane_layout.is_planar();
+        // For planar buffers CoreVideo reports the header as the base address and a row size covering every plane
+        let (base_address, bytes_per_row) = if planar {
+            (data.as_ptr() as *mut c_void, plane_layout.data_size.div_ceil(plane_layout.height))
+        } else {
+            (planes[0].base_address, planes[0].bytes_per_row)
+        };
+        Ok(CVPixelBuffer(Rc::new(PixelBufferStorage {
+            pixel_format: plane_layout.pixel_format.as_os_type(),
+            width: plane_layout.width,
+            height: plane_layout.height,
+            base_address,
+            bytes_per_row,
+            planar,
+            planes,
+            extended_pixels: plane_layout.extended_pixels,
+            attributes,
+            readers: Cell::new(0),
+            writer: Cell::new(false),
+            attachments: RefCell::default(),
+            storage: Storage::Allocated { data, layout, plane_layout },
+        })))
+    }
+
+    // Of the options, only row and plane alignment and extended pixels are honoured
+    #[inline]
+    pub fn new(pixel_format: OSType, width: usize, height: usize, options: Option<&PixelBufferAttributes>) -> Result<CVPixelBuffer, CVError> {
+        let attributes = PixelBufferAttributes::new().pixel_format(PixelFormat::from(pixel_format)).size(width, height);
+        Self::with_attributes(&with_options(attributes, options))
+    }
+
+    #[inline]
+    pub fn with_attributes(attributes: &PixelBufferAttributes) -> Result<CVPixelBuffer, CVError> {
+        let (pixel_format, width, height) = attributes.validate()?;
+        let plane_layout = PlaneLayout::compute(
+            pixel_format,
+            width,
+            height,
+            attributes.get_bytes_per_row_alignment().unwrap_or(DEFAULT_BYTES_PER_ROW_ALIGNMENT),
+            attributes.get_plane_alignment().unwrap_or(DEFAULT_PLANE_ALIGNMENT),
+            attributes.get_extended_pixels().unwrap_or_default(),
+        )?;
+        Self::allocate(plane_layout, attributes.clone())
+    }
+
+    #[inline]
+    pub unsafe fn new_with_bytes(
+        pixel_format: OSType,
+        width: usize,
+        height: usize,
+        base_address: *mut c_void,
+        bytes_per_row: usize,
+        release_callback: CVPixelBufferReleaseBytesCallback,
+        release_ref_con: *mut c_void,
+        options: Option<&PixelBufferAttributes>,
+    ) -> Result<CVPixelBuffer, CVError> {
+        let attributes = PixelBufferAttributes::new().pixel_format(PixelFormat::from(pixel_format)).size(width, height);
+        let attributes = with_options(attributes, options);
+        let layout = format_layout(pixel_format)?;
+        if layout.is_planar() {
+            return Err(CVError::InvalidPixelFormat);
+        }
+        if width == 0 || height == 0 {
+            return Err(CVError::InvalidSize);
+        }
+        let plane = &layout.planes[0];
+        let row_bytes = (width.div_ceil(plane.block_width as usize) * plane.bits_per_block as usize).div_ceil(8);
+        if base_address.is_null() || bytes_per_row < row_bytes {
+            return Err(CVError::InvalidArgument);
+        }
+        check_bytes_options(&attributes, &[bytes_per_row], &[base_address])?;
+        Ok(CVPixelBuffer(Rc::new(PixelBufferStorage {
+            pixel_format,
+            width,
+            height,
+            base_address,
+            bytes_per_row,
+            planar: false,
+            planes: vec![Plane { base_address, width, height, bytes_per_row }],
+            extended_pixels: (0, 0, 0, 0),
+            attributes,
+            readers: Cell::new(0),
+            writer: Cell::new(false),
+            attachments: RefCell::default(),
+            storage: Storage::Bytes { base_address, release_callback, release_ref_con },
+        })))
+    }
+
+    #[inline]
+    pub unsafe fn new_with_planar_bytes(
+        pixel_format: OSType,
+        width: usize,
+        height: usize,
+        data_ptr: *mut c_void,
+        data_size: usize,
+        number_of_planes: usize,
+        plane_base_address: Vec<*mut c_void>,
+        plane_width: Vec<usize>,
+        plane_height: Vec<usize>,
+        plane_bytes_per_row: Vec<usize>,
+        release_callback: CVPixelBufferReleasePlanarBytesCallback,
+        release_ref_con: *mut c_void,
+        options: Option<&PixelBufferAttributes>,
+    ) -> Result<CVPixelBuffer, CVError> {
+        if plane_base_address.len() != number_of_planes ||
+            plane_width.len() != number_of_planes ||
+            plane_height.len() != number_of_planes ||
+            plane_bytes_per_row.len() != number_of_planes
+        {
+            return Err(CVError::InvalidArgument);
+        }
+        let attributes = PixelBufferAttributes::new().pixel_format(PixelFormat::from(pixel_format)).size(width, height);
+        let attributes = with_options(attributes, options);
+        let layout = format_layout(pixel_format)?;
+        if !layout.is_planar() || layout.plane_count() != number_of_planes {
+            return Err(CVError::InvalidPixelFormat);
+        }
+        if width == 0 || height == 0 {
+            return Err(CVError::InvalidSize);
+        }
+        if plane_base_address.iter().any(|base_address| base_address.is_null()) {
+            return Err(CVError::InvalidArgument);
+        }
+        check_bytes_options(&attributes, &plane_bytes_per_row, &plane_base_address)?;
+        let planes = (0..number_of_planes)
+            .map(|plane_index| Plane {
+                base_address: plane_base_address[plane_index],
+                width: plane_width[plane_index],
+                height: plane_height[plane_index],
+                bytes_per_row: plane_bytes_per_row[plane_index],
+            })
+            .collect();
+        Ok(CVPixelBuffer(Rc::new(PixelBufferStorage {
+            pixel_format,
+            width,
+            height,
+            base_address: data_ptr,
+            bytes_per_row: data_size / height,
+            planar: true,
+            planes,
+            extended_pixels: (0, 0, 0, 0),
+            attributes,
+            readers: Cell::new(0),
+            writer: Cell::new(false),
+            attachments: RefCell::default(),
+            storage: Storage::PlanarBytes { data_ptr, data_size, plane_addresses: plane_base_address, release_callback, release_ref_con },
+        })))
+    }
+
+    // Memory is always addressable, so locking only tracks readers and the writer, together with the ones lock_read and
+    // lock_write take, failing while the buffer is locked for writing, or for reading when locking for writing
+    pub fn lock_base_address(&self, options: CVPixelBufferLockFlags) -> Result<(), CVError> {
+        if self.0.writer.get() {
+            return Err(CVError::InvalidArgument);
+        }
+        if options & kCVPixelBufferLock_ReadOnly != 0 {
+            self.0.readers.set(self.0.readers.get() + 1);
+        } else if self.0.readers.get() > 0 {
+            return Err(CVError::InvalidArgument);
+        } else {
+            self.0.writer.set(true);
+        }
+        Ok(())
+    }
+
+    // Unsafe for the same reason as on Apple platforms: releasing a lock that a guard still holds leaves its slices
+    // dangling, so the caller must only release locks it took itself with lock_base_address and the same options
+    pub unsafe fn unlock_base_address(&self, options: CVPixelBufferLockFlags) -> Result<(), CVError> {
+        if options & kCVPixelBufferLock_ReadOnly != 0 {
+            let readers = self.0.readers.get().checked_sub(1).ok_or(CVError::InvalidArgument)?;
+            self.0.readers.set(readers);
+        } else if !self.0.writer.replace(false) {
+            return Err(CVError::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn get_width(&self) -> usize {
+        self.0.width
+    }
+
+    #[inline]
+    pub fn get_height(&self) -> usize {
+        self.0.height
+    }
+
+    #[inline]
+    pub fn get_pixel_format(&self) -> OSType {
+        self.0.pixel_format
+    }
+
+    #[inline]
+    pub unsafe fn get_base_address(&self) -> *mut c_void {
+        self.0.base_address
+    }
+
+    #[inline]
+    pub fn get_bytes_per_row(&self) -> usize {
+        self.0.bytes_per_row
+    }
+
+    #[inline]
+    pub fn is_planar(&self) -> bool {
+        self.0.planar
+    }
+
+    #[inline]
+    pub fn get_plane_count(&self) -> usize {
+        if self.0.planar {
+            self.0.planes.len()
+        } else {
+            0
+        }
+    }
+
+    #[inline]
+    fn plane(&self, plane_index: usize) -> Option<&Plane> {
+        if self.0.planar {
+            self.0.planes.get(plane_index)
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub fn get_width_of_plane(&self, plane_index: usize) -> usize {
+        self.plane(plane_index).map_or(0, |plane| plane.width)
+    }
+
+    #[inline]
+    pub fn get_height_of_plane(&self, plane_index: usize) -> usize {
+        self.plane(plane_index).map_or(0, |plane| plane.height)
+    }
+
+    #[inline]
+    pub unsafe fn get_base_address_of_plane(&self, plane_index: usize) -> *mut c_void {
+        self.plane(plane_index).map_or(null_mut(), |plane| plane.base_address)
+    }
+
+    #[inline]
+    pub fn get_bytes_per_row_of_plane(&self, plane_index: usize) -> usize {
+        self.plane(plane_index).map_or(0, |plane| plane.bytes_per_row)
+    }
+
+    #[inline]
+    pub fn get_extended_pixels(&self) -> (usize, usize, usize, usize) {
+        self.0.extended_pixels
+    }
+
+    // Replicates the edge pixels of every plane into its extended columns and rows, a whole block at a time
+    pub fn fill_extended_pixels(&self) -> Result<(), CVError> {
+        let (data, plane_layout) = match &self.0.storage {
+            Storage::Allocated { data, plane_layout, .. } => (data.as_ptr(), plane_layout),
+            _ => return Ok(()),
+        };
+        let (_, right, _, _) = plane_layout.extended_pixels;
+        if plane_layout.extended_pixels == (0, 0, 0, 0) {
+            return Ok(());
+        }
+        let layout = format_layout(self.0.pixel_format)?;
+        let data = unsafe { slice::from_raw_parts_mut(data, plane_layout.data_size) };
+
+        for (plane, geometry) in layout.planes.iter().zip(plane_layout.planes.iter()) {
+            if plane.bits_per_block % 8 != 0 {
+                return Err(CVError::Unsupported);
+            }
+            let block_width = plane.block_width as usize;
+            let block_height = plane.block_height as usize;
+            let bytes_per_block = plane.bits_per_block as usize / 8;
+            let bytes_per_row = geometry.bytes_per_row;
+
+            let top_rows = (geometry.offset - geometry.start) / bytes_per_row;
+            let left_bytes = (geometry.offset - geometry.start) % bytes_per_row;
+            let rows = geometry.height.div_ceil(block_height);
+            let row_bytes = geometry.width.div_ceil(block_width) * bytes_per_block;
+            let right_blocks = right.div_ceil(plane.horizontal_subsampling as usize).div_ceil(block_width);
+            let right_bytes = (right_blocks * bytes_per_block).min(bytes_per_row - left_bytes - row_bytes);
+            let bottom_rows = geometry.size / bytes_per_row - top_rows - rows;
+            let plane_data = &mut data[geometry.start..geometry.start + geometry.size];
+
+            for y in top_rows..top_rows + rows {
+                let row = &mut plane_data[y * bytes_per_row..(y + 1) * bytes_per_row];
+                let (first, last) = (left_bytes, left_bytes + row_bytes - bytes_per_block);
+                for x in (0..left_bytes).step_by(bytes_per_block) {
+                    row.copy_within(first..first + bytes_per_block, x);
+                }
+                for x in (last + bytes_per_block..last + bytes_per_block + right_bytes).step_by(bytes_per_block) {
+                    row.copy_within(last..last + bytes_per_block, x);
+                }
+            }
+            let span = left_bytes + row_bytes + right_bytes;
+            for y in 0..top_rows {
+                plane_data.copy_within(top_rows * bytes_per_row..top_rows * bytes_per_row + span, y * bytes_per_row);
+            }
+            let last_row = (top_rows + rows - 1) * bytes_per_row;
+            for y in top_rows + rows..top_rows + rows + bottom_rows {
+                plane_data.copy_within(last_row..last_row + span, y * bytes_per_row);
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn copy_attributes(&self) -> Option<PixelBufferAttributes> {
+        Some(self.0.attributes.clone())
+    }
+}
+
+impl PixelBufferBackend for CVPixelBuffer {
+    #[inline]
+    fn with_attributes(attributes: &PixelBufferAttributes) -> Result<Self, CVError> {
+        CVPixelBuffer::with_attributes(attributes)
+    }
+
+    #[inline]
+    fn copy_attributes(&self) -> Option<PixelBufferAttributes> {
+        CVPixelBuffer::copy_attributes(self)
+    }
+
+    #[inline]
+    fn get_image_attachment<T: AttachmentValue>(&self, key: CVImageBufferKeys) -> Option<T> {
+        self.0.attachments.borrow().get(&key)?.downcast_ref::<T>().copied()
+    }
+
+    #[inline]
+    fn set_image_attachment<T: AttachmentValue>(&self, key: CVImageBufferKeys, value: T) {
+        self.0.attachments.borrow_mut().insert(key, Box::new(value));
+    }
+
+    #[inline]
+    fn remove_image_attachment(&self, key: CVImageBufferKeys) {
+        self.0.attachments.borrow_mut().remove(&key);
+    }
+}
+
+impl PartialEq for CVPixelBuffer {
+    #[inline]
+    fn eq(&self, other: &CVPixelBuffer) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for CVPixelBuffer {}
+
+impl Debug for CVPixelBuffer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CVPixelBuffer")
+            .field("pixel_format", &PixelFormat::from(self.0.pixel_format))
+            .field("width", &self.0.width)
+            .field("height", &self.0.height)
+            .field("planar", &self.0.planar)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixel_buffer::{kCVPixelFormatType_32BGRA, kCVPixelFormatType_420YpCbCr8BiPlanarVideoRange};
+
+    #[test]
+    fn layout() {
+        let attributes = PixelBufferAttributes::new()
+            .pixel_format(PixelFormat::_420YpCbCr8BiPlanarVideoRange)
+            .size(64, 48)
+            .bytes_per_row_alignment(64)
+            .plane_alignment(64);
+        let pixel_buffer = CVPixelBuffer::with_attributes(&attributes).unwrap();
+        assert_eq!(pixel_buffer.get_pixel_format(), kCVPixelFormatType_420YpCbCr8BiPlanarVideoRange);
+        assert!(pixel_buffer.is_planar());
+        assert_eq!(pixel_buffer.get_plane_count(), 2);
+        assert_eq!((pixel_buffer.get_width_of_plane(0), pixel_buffer.get_height_of_plane(0)), (64, 48));
+        assert_eq!((pixel_buffer.get_width_of_plane(1), pixel_buffer.get_height_of_plane(1)), (32, 24));
+        assert_eq!((pixel_buffer.get_bytes_per_row_of_plane(0), pixel_buffer.get_bytes_per_row_of_plane(1)), (64, 64));
+        let (base_address, luma, chroma) = unsafe {
+            (
+                pixel_buffer.get_base_address() as usize,
+                pixel_buffer.get_base_address_of_plane(0) as usize,
+                pixel_buffer.get_base_address_of_plane(1) as usize,
+            )
+        };
+        assert_eq!((luma - base_address, chroma - luma), (64, 64 * 48));
+        assert_eq!(pixel_buffer.get_width_of_plane(2), 0);
+        assert!(unsafe { pixel_buffer.get_base_address_of_plane(2) }.is_null());
+
+        let attributes = PixelBufferAttributes::new()
+            .pixel_format(PixelFormat::_32BGRA)
+            .size(10, 3)
+            .bytes_per_row_alignment(16);
+        let pixel_buffer = CVPixelBuffer::with_attributes(&attributes).unwrap();
+        assert!(!pixel_buffer.is_planar());
+        assert_eq!(pixel_buffer.get_plane_count(), 0);
+        assert_eq!(pixel_buffer.get_bytes_per_row(), 48);
+        assert_eq!(pixel_buffer.copy_attributes().unwrap().get_bytes_per_row_alignment(), Some(16));
+        assert_eq!(CVPixelBuffer::new(kCVPixelFormatType_32BGRA, 0, 3, None).unwrap_err(), CVError::InvalidSize);
+        let options = PixelBufferAttributes::new()
+            .pixel_format(PixelFormat::_24RGB)
+            .bytes_per_row_alignment(64)
+            .extended_pixels(0, 2, 0, 0);
+        let pixel_buffer = CVPixelBuffer::new(kCVPixelFormatType_32BGRA, 10, 3, Some(&options)).unwrap();
+        assert_eq!(pixel_buffer.get_pixel_format(), kCVPixelFormatType_32BGRA);
+        assert_eq!((pixel_buffer.get_bytes_per_row(), pixel_buffer.get_extended_pixels()), (64, (0, 2, 0, 0)));
+    }
+
+    #[test]
+    fn fill_extended_pixels() {
+        let attributes = PixelBufferAttributes::new()
+            .pixel_format(PixelFormat::_32BGRA)
+            .size(2, 2)
+            .extended_pixels(1, 1, 1, 1)
+            .bytes_per_row_alignment(4);
+        let mut pixel_buffer = CVPixelBuffer::with_attributes(&attributes).unwrap();
+        assert_eq!(pixel_buffer.get_extended_pixels(), (1, 1, 1, 1));
+        assert_eq!(pixel_buffer.get_bytes_per_row(), 16);
+        {
+            let mut guard = pixel_buffer.lock_write().unwrap();
+            let mut plane = guard.plane_mut(0).unwrap();
+            plane.row_as_mut::<u32>(0).unwrap().copy_from_slice(&[1, 2]);
+            plane.row_as_mut::<u32>(1).unwrap().copy_from_slice(&[3, 4]);
+        }
+        pixel_buffer.fill_extended_pixels().unwrap();
+        let guard = pixel_buffer.lock_read().unwrap();
+        let pixels: Vec<u32> = (0..16).map(|index| unsafe { *(guard.base_address() as *const u32).offset(index - 5) }).collect();
+        assert_eq!(pixels, [1, 1, 2, 2, 1, 1, 2, 2, 3, 3, 4, 4, 3, 3, 4, 4]);
+    }
+
+    #[test]
+    fn locking() {
+        let pixel_buffer = CVPixelBuffer::new(kCVPixelFormatType_32BGRA, 4, 4, None).unwrap();
+        let clone = pixel_buffer.clone();
+        unsafe {
+            pixel_buffer.lock_base_address(kCVPixelBufferLock_ReadOnly).unwrap();
+            clone.lock_base_address(kCVPixelBufferLock_ReadOnly).unwrap();
+            assert_eq!(clone.lock_base_address(0), Err(CVError::InvalidArgument));
+            pixel_buffer.unlock_base_address(kCVPixelBufferLock_ReadOnly).unwrap();
+            assert_eq!(pixel_buffer.lock_base_address(0), Err(CVError::InvalidArgument));
+            clone.unlock_base_address(kCVPixelBufferLock_ReadOnly).unwrap();
+            assert_eq!(clone.unlock_base_address(kCVPixelBufferLock_ReadOnly), Err(CVError::InvalidArgument));
+
+            pixel_buffer.lock_base_address(0).unwrap();
+            assert_eq!(clone.lock_base_address(0), Err(CVError::InvalidArgument));
+            assert_eq!(clone.lock_base_address(kCVPixelBufferLock_ReadOnly), Err(CVError::InvalidArgument));
+            clone.unlock_base_address(0).unwrap();
+            assert_eq!(pixel_buffer.unlock_base_address(0), Err(CVError::InvalidArgument));
+        }
+
+        // Manual locks and guards share the same bookkeeping
+        let (mut first, mut second) = (pixel_buffer.clone(), clone);
+        pixel_buffer.lock_base_address(kCVPixelBufferLock_ReadOnly).unwrap();
+        assert!(first.lock_write().is_err());
+        drop(second.lock_read().unwrap());
+        unsafe { pixel_buffer.unlock_base_address(kCVPixelBufferLock_ReadOnly).unwrap() };
+        let guard = first.lock_write().unwrap();
+        assert_eq!(pixel_buffer.lock_base_address(kCVPixelBufferLock_ReadOnly), Err(CVError::InvalidArgument));
+        assert!(second.lock_write().is_err());
+        assert!(second.lock_read().is_err());
+        drop(guard);
+        let _guard = second.lock_write().unwrap();
+    }
+
+    #[test]
+    fn bytes_options() {
+        let mut data = vec![0u64; 64];
+        let address = data.as_mut_ptr() as *mut c_void;
+        let aligned = PixelBufferAttributes::new().bytes_per_row_alignment(16).plane_alignment(8);
+        assert_eq!(check_bytes_options(&aligned, &[64, 32], &[address]), Ok(()));
+        assert_eq!(check_bytes_options(&aligned, &[40], &[address]), Err(CVError::InvalidPixelBufferAttributes));
+        let misaligned = (data.as_mut_ptr() as *mut u8).wrapping_add(4) as *mut c_void;
+        assert_eq!(check_bytes_options(&aligned, &[64], &[misaligned]), Err(CVError::InvalidPixelBufferAttributes));
+        assert_eq!(check_bytes_options(&aligned.clone().bytes_per_row_alignment(0), &[40], &[address]), Ok(()));
+        let extended = PixelBufferAttributes::new().extended_pixels(0, 0, 0, 16);
+        assert_eq!(check_bytes_options(&extended, &[64], &[address]), Err(CVError::Unsupported));
+        assert_eq!(with_options(PixelBufferAttributes::new(), Some(&aligned)).get_plane_alignment(), Some(8));
+        assert_eq!(with_options(aligned.clone(), None).get_bytes_per_row_alignment(), Some(16));
+    }
+}
@@ -0,0 +1,673 @@
+use std::{
+    fmt::{self, Debug, Display, Formatter},
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+use crate::{pixel_buffer::*, pixel_format_description::CVPixelFormatComponentRange, r#return::CVError, OSType};
+
+macro_rules! pixel_formats {
+    ($($variant:ident => $constant:ident,)*) => {
+        #[derive(Clone, Copy)]
+        pub enum PixelFormat {
+            $($variant,)*
+            Unknown(OSType),
+        }
+
+        impl From<OSType> for PixelFormat {
+            fn from(pixel_format: OSType) -> Self {
+                match pixel_format {
+                    $($constant => PixelFormat::$variant,)*
+                    _ => PixelFormat::Unknown(pixel_format),
+                }
+            }
+        }
+
+        impl From<PixelFormat> for OSType {
+            fn from(pixel_format: PixelFormat) -> Self {
+                match pixel_format {
+                    $(PixelFormat::$variant => $constant,)*
+                    PixelFormat::Unknown(pixel_format) => pixel_format,
+                }
+            }
+        }
+
+        impl PixelFormat {
+            pub const ALL: &'static [PixelFormat] = &[$(PixelFormat::$variant,)*];
+
+            fn name(&self) -> &'static str {
+                match self {
+                    $(PixelFormat::$variant => stringify!($variant),)*
+                    PixelFormat::Unknown(_) => "Unknown",
+                }
+            }
+        }
+    };
+}
+
+pixel_formats! {
+    _1Monochrome => kCVPixelFormatType_1Monochrome,
+    _2Indexed => kCVPixelFormatType_2Indexed,
+    _4Indexed => kCVPixelFormatType_4Indexed,
+    _8Indexed => kCVPixelFormatType_8Indexed,
+    _1IndexedGray_WhiteIsZero => kCVPixelFormatType_1IndexedGray_WhiteIsZero,
+    _2IndexedGray_WhiteIsZero => kCVPixelFormatType_2IndexedGray_WhiteIsZero,
+    _4IndexedGray_WhiteIsZero => kCVPixelFormatType_4IndexedGray_WhiteIsZero,
+    _8IndexedGray_WhiteIsZero => kCVPixelFormatType_8IndexedGray_WhiteIsZero,
+    _16BE555 => kCVPixelFormatType_16BE555,
+    _16LE555 => kCVPixelFormatType_16LE555,
+    _16LE5551 => kCVPixelFormatType_16LE5551,
+    _16BE565 => kCVPixelFormatType_16BE565,
+    _16LE565 => kCVPixelFormatType_16LE565,
+    _24RGB => kCVPixelFormatType_24RGB,
+    _24BGR => kCVPixelFormatType_24BGR,
+    _32ARGB => kCVPixelFormatType_32ARGB,
+    _32BGRA => kCVPixelFormatType_32BGRA,
+    _32ABGR => kCVPixelFormatType_32ABGR,
+    _32RGBA => kCVPixelFormatType_32RGBA,
+    _64ARGB => kCVPixelFormatType_64ARGB,
+    _48RGB => kCVPixelFormatType_48RGB,
+    _32AlphaGray => kCVPixelFormatType_32AlphaGray,
+    _16Gray => kCVPixelFormatType_16Gray,
+    _30RGB => kCVPixelFormatType_30RGB,
+    _422YpCbCr8 => kCVPixelFormatType_422YpCbCr8,
+    _4444YpCbCrA8 => kCVPixelFormatType_4444YpCbCrA8,
+    _4444YpCbCrA8R => kCVPixelFormatType_4444YpCbCrA8R,
+    _4444AYpCbCr8 => kCVPixelFormatType_4444AYpCbCr8,
+    _4444AYpCbCr16 => kCVPixelFormatType_4444AYpCbCr16,
+    _4444AYpCbCrFloat => kCVPixelFormatType_4444AYpCbCrFloat,
+    _444YpCbCr8 => kCVPixelFormatType_444YpCbCr8,
+    _422YpCbCr16 => kCVPixelFormatType_422YpCbCr16,
+    _422YpCbCr10 => kCVPixelFormatType_422YpCbCr10,
+    _444YpCbCr10 => kCVPixelFormatType_444YpCbCr10,
+    _420YpCbCr8Planar => kCVPixelFormatType_420YpCbCr8Planar,
+    _420YpCbCr8PlanarFullRange => kCVPixelFormatType_420YpCbCr8PlanarFullRange,
+    _422YpCbCr_4A_8BiPlanar => kCVPixelFormatType_422YpCbCr_4A_8BiPlanar,
+    _420YpCbCr8BiPlanarVideoRange => kCVPixelFormatType_420YpCbCr8BiPlanarVideoRange,
+    _420YpCbCr8BiPlanarFullRange => kCVPixelFormatType_420YpCbCr8BiPlanarFullRange,
+    _422YpCbCr8BiPlanarVideoRange => kCVPixelFormatType_422YpCbCr8BiPlanarVideoRange,
+    _422YpCbCr8BiPlanarFullRange => kCVPixelFormatType_422YpCbCr8BiPlanarFullRange,
+    _444YpCbCr8BiPlanarVideoRange => kCVPixelFormatType_444YpCbCr8BiPlanarVideoRange,
+    _444YpCbCr8BiPlanarFullRange => kCVPixelFormatType_444YpCbCr8BiPlanarFullRange,
+    _422YpCbCr8_yuvs => kCVPixelFormatType_422YpCbCr8_yuvs,
+    _422YpCbCr8FullRange => kCVPixelFormatType_422YpCbCr8FullRange,
+    OneComponent8 => kCVPixelFormatType_OneComponent8,
+    TwoComponent8 => kCVPixelFormatType_TwoComponent8,
+    _30RGBLEPackedWideGamut => kCVPixelFormatType_30RGBLEPackedWideGamut,
+    ARGB2101010LEPacked => kCVPixelFormatType_ARGB2101010LEPacked,
+    _40ARGBLEWideGamut => kCVPixelFormatType_40ARGBLEWideGamut,
+    _40ARGBLEWideGamutPremultiplied => kCVPixelFormatType_40ARGBLEWideGamutPremultiplied,
+    OneComponent10 => kCVPixelFormatType_OneComponent10,
+    OneComponent12 => kCVPixelFormatType_OneComponent12,
+    OneComponent16 => kCVPixelFormatType_OneComponent16,
+    TwoComponent16 => kCVPixelFormatType_TwoComponent16,
+    OneComponent16Half => kCVPixelFormatType_OneComponent16Half,
+    OneComponent32Float => kCVPixelFormatType_OneComponent32Float,
+    TwoComponent16Half => kCVPixelFormatType_TwoComponent16Half,
+    TwoComponent32Float => kCVPixelFormatType_TwoComponent32Float,
+    _64RGBAHalf => kCVPixelFormatType_64RGBAHalf,
+    _128RGBAFloat => kCVPixelFormatType_128RGBAFloat,
+    _14Bayer_GRBG => kCVPixelFormatType_14Bayer_GRBG,
+    _14Bayer_RGGB => kCVPixelFormatType_14Bayer_RGGB,
+    _14Bayer_BGGR => kCVPixelFormatType_14Bayer_BGGR,
+    _14Bayer_GBRG => kCVPixelFormatType_14Bayer_GBRG,
+    DisparityFloat16 => kCVPixelFormatType_DisparityFloat16,
+    DisparityFloat32 => kCVPixelFormatType_DisparityFloat32,
+    DepthFloat16 => kCVPixelFormatType_DepthFloat16,
+    DepthFloat32 => kCVPixelFormatType_DepthFloat32,
+    _420YpCbCr10BiPlanarVideoRange => kCVPixelFormatType_420YpCbCr10BiPlanarVideoRange,
+    _422YpCbCr10BiPlanarVideoRange => kCVPixelFormatType_422YpCbCr10BiPlanarVideoRange,
+    _444YpCbCr10BiPlanarVideoRange => kCVPixelFormatType_444YpCbCr10BiPlanarVideoRange,
+    _420YpCbCr10BiPlanarFullRange => kCVPixelFormatType_420YpCbCr10BiPlanarFullRange,
+    _422YpCbCr10BiPlanarFullRange => kCVPixelFormatType_422YpCbCr10BiPlanarFullRange,
+    _444YpCbCr10BiPlanarFullRange => kCVPixelFormatType_444YpCbCr10BiPlanarFullRange,
+    _420YpCbCr8VideoRange_8A_TriPlanar => kCVPixelFormatType_420YpCbCr8VideoRange_8A_TriPlanar,
+    _16VersatileBayer => kCVPixelFormatType_16VersatileBayer,
+    _64RGBA_DownscaledProResRAW => kCVPixelFormatType_64RGBA_DownscaledProResRAW,
+    _422YpCbCr16BiPlanarVideoRange => kCVPixelFormatType_422YpCbCr16BiPlanarVideoRange,
+    _444YpCbCr16BiPlanarVideoRange => kCVPixelFormatType_444YpCbCr16BiPlanarVideoRange,
+    _444YpCbCr16VideoRange_16A_TriPlanar => kCVPixelFormatType_444YpCbCr16VideoRange_16A_TriPlanar,
+    Lossless_32BGRA => kCVPixelFormatType_Lossless_32BGRA,
+    Lossless_420YpCbCr8BiPlanarVideoRange => kCVPixelFormatType_Lossless_420YpCbCr8BiPlanarVideoRange,
+    Lossless_420YpCbCr8BiPlanarFullRange => kCVPixelFormatType_Lossless_420YpCbCr8BiPlanarFullRange,
+    Lossless_420YpCbCr10PackedBiPlanarVideoRange => kCVPixelFormatType_Lossless_420YpCbCr10PackedBiPlanarVideoRange,
+    Lossless_422YpCbCr10PackedBiPlanarVideoRange => kCVPixelFormatType_Lossless_422YpCbCr10PackedBiPlanarVideoRange,
+    Lossy_32BGRA => kCVPixelFormatType_Lossy_32BGRA,
+    Lossy_420YpCbCr8BiPlanarVideoRange => kCVPixelFormatType_Lossy_420YpCbCr8BiPlanarVideoRange,
+    Lossy_420YpCbCr8BiPlanarFullRange => kCVPixelFormatType_Lossy_420YpCbCr8BiPlanarFullRange,
+    Lossy_420YpCbCr10PackedBiPlanarVideoRange => kCVPixelFormatType_Lossy_420YpCbCr10PackedBiPlanarVideoRange,
+    Lossy_422YpCbCr10PackedBiPlanarVideoRange => kCVPixelFormatType_Lossy_422YpCbCr10PackedBiPlanarVideoRange,
+}
+
+impl PixelFormat {
+    #[inline]
+    pub fn as_os_type(&self) -> OSType {
+        OSType::from(*self)
+    }
+
+    #[inline]
+    pub fn is_known(&self) -> bool {
+        !matches!(PixelFormat::from(self.as_os_type()), PixelFormat::Unknown(_))
+    }
+}
+
+impl PartialEq for PixelFormat {
+    fn eq(&self, other: &PixelFormat) -> bool {
+        self.as_os_type() == other.as_os_type()
+    }
+}
+
+impl Eq for PixelFormat {}
+
+impl Hash for PixelFormat {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_os_type().hash(state)
+    }
+}
+
+// Prints the four character code, or the hexadecimal value if any of its bytes is not printable
+impl Display for PixelFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let code = self.as_os_type();
+        let bytes = code.to_be_bytes();
+        if bytes.iter().all(|byte| byte.is_ascii_graphic() || *byte == b' ') {
+            bytes.iter().try_for_each(|byte| write!(f, "{}", *byte as char))
+        } else {
+            write!(f, "0x{:08X}", code)
+        }
+    }
+}
+
+impl Debug for PixelFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}('{}')", self.name(), self)
+    }
+}
+
+// Accepts a four character code such as "420v", or a hexadecimal value such as "0x00000020"
+impl FromStr for PixelFormat {
+    type Err = CVError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() == 10 {
+            if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+                return OSType::from_str_radix(hex, 16).map(PixelFormat::from).map_err(|_| CVError::InvalidPixelFormat);
+            }
+        }
+        let bytes = s.as_bytes();
+        if bytes.len() != 4 || !bytes.is_ascii() {
+            return Err(CVError::InvalidPixelFormat);
+        }
+        Ok(PixelFormat::from(OSType::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PixelComponent {
+    Y,
+    Cb,
+    Cr,
+    R,
+    G,
+    B,
+    A,
+    Gray,
+    Index,
+    Depth,
+    Disparity,
+    Sensel,
+    Unspecified,
+    Unused,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PixelFormatPlaneLayout {
+    pub bits_per_block: u32,
+    pub block_width: u32,
+    pub block_height: u32,
+    pub horizontal_subsampling: u32,
+    pub vertical_subsampling: u32,
+    pub components: &'static [PixelComponent],
+}
+
+impl PixelFormatPlaneLayout {
+    const fn new(bits_per_block: u32, components: &'static [PixelComponent]) -> Self {
+        PixelFormatPlaneLayout { bits_per_block, block_width: 1, block_height: 1, horizontal_subsampling: 1, vertical_subsampling: 1, components }
+    }
+
+    const fn block(mut self, block_width: u32) -> Self {
+        self.block_width = block_width;
+        self
+    }
+
+    const fn subsampled(mut self, horizontal_subsampling: u32, vertical_subsampling: u32) -> Self {
+        self.horizontal_subsampling = horizontal_subsampling;
+        self.vertical_subsampling = vertical_subsampling;
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PixelFormatLayout {
+    pub planes: &'static [PixelFormatPlaneLayout],
+    pub contains_alpha: bool,
+    pub contains_ycbcr: bool,
+    pub contains_rgb: bool,
+    pub indexed: bool, /* components are indices into a color lookup table, so neither RGB nor YCbCr */
+    pub component_range: Option<CVPixelFormatComponentRange>,
+    pub horizontal_alignment: u32, /* width in pixels that every row is padded to a multiple of */
+    pub compressed: bool,
+}
+
+impl PixelFormatLayout {
+    const fn new(planes: &'static [PixelFormatPlaneLayout]) -> Self {
+        PixelFormatLayout {
+            planes,
+            contains_alpha: false,
+            contains_ycbcr: false,
+            contains_rgb: false,
+            indexed: false,
+            component_range: None,
+            horizontal_alignment: 1,
+            compressed: false,
+        }
+    }
+
+    const fn rgb(mut self) -> Self {
+        self.contains_rgb = true;
+        self
+    }
+
+    const fn ycbcr(mut self) -> Self {
+        self.contains_ycbcr = true;
+        self
+    }
+
+    const fn indexed(mut self) -> Self {
+        self.indexed = true;
+        self
+    }
+
+    const fn alpha(mut self) -> Self {
+        self.contains_alpha = true;
+        self
+    }
+
+    const fn range(mut self, component_range: CVPixelFormatComponentRange) -> Self {
+        self.component_range = Some(component_range);
+        self
+    }
+
+    const fn aligned(mut self, horizontal_alignment: u32) -> Self {
+        self.horizontal_alignment = horizontal_alignment;
+        self
+    }
+
+    const fn compressed(mut self) -> Self {
+        self.compressed = true;
+        self
+    }
+
+    #[inline]
+    pub fn is_planar(&self) -> bool {
+        self.planes.len() > 1
+    }
+
+    // Matches CVPixelBufferGetPlaneCount, which reports zero planes for non-planar formats
+    #[inline]
+    pub fn plane_count(&self) -> usize {
+        if self.is_planar() {
+            self.planes.len()
+        } else {
+            0
+        }
+    }
+}
+
+const fn plane(bits_per_block: u32, components: &'static [PixelComponent]) -> PixelFormatPlaneLayout {
+    PixelFormatPlaneLayout::new(bits_per_block, components)
+}
+
+macro_rules! layout {
+    ($($plane:expr),+) => {
+        PixelFormatLayout::new({
+            const PLANES: &[PixelFormatPlaneLayout] = &[$($plane),+];
+            PLANES
+        })
+    };
+}
+
+macro_rules! table_entry {
+    ($layout:expr) => {{
+        const LAYOUT: PixelFormatLayout = $layout;
+        Some(&LAYOUT)
+    }};
+}
+
+impl PixelFormat {
+    pub fn layout(&self) -> Option<&'static PixelFormatLayout> {
+        use self::{
+            CVPixelFormatComponentRange::{FullRange, VideoRange, WideRange},
+            PixelComponent::*,
+        };
+
+        match self {
+            PixelFormat::_1Monochrome => table_entry!(layout!(plane(1, &[Index])).indexed()),
+            PixelFormat::_2Indexed => table_entry!(layout!(plane(2, &[Index])).indexed()),
+            PixelFormat::_4Indexed => table_entry!(layout!(plane(4, &[Index])).indexed()),
+            PixelFormat::_8Indexed => table_entry!(layout!(plane(8, &[Index])).indexed()),
+            PixelFormat::_1IndexedGray_WhiteIsZero => table_entry!(layout!(plane(1, &[Index])).indexed()),
+            PixelFormat::_2IndexedGray_WhiteIsZero => table_entry!(layout!(plane(2, &[Index])).indexed()),
+            PixelFormat::_4IndexedGray_WhiteIsZero => table_entry!(layout!(plane(4, &[Index])).indexed()),
+            PixelFormat::_8IndexedGray_WhiteIsZero => table_entry!(layout!(plane(8, &[Index])).indexed()),
+            PixelFormat::_16BE555 => table_entry!(layout!(plane(16, &[Unused, R, G, B])).rgb().range(FullRange)),
+            PixelFormat::_16LE555 => table_entry!(layout!(plane(16, &[Unused, R, G, B])).rgb().range(FullRange)),
+            PixelFormat::_16LE5551 => table_entry!(layout!(plane(16, &[R, G, B, A])).rgb().alpha().range(FullRange)),
+            PixelFormat::_16BE565 => table_entry!(layout!(plane(16, &[R, G, B])).rgb().range(FullRange)),
+            PixelFormat::_16LE565 => table_entry!(layout!(plane(16, &[R, G, B])).rgb().range(FullRange)),
+            PixelFormat::_24RGB => table_entry!(layout!(plane(24, &[R, G, B])).rgb().range(FullRange)),
+            PixelFormat::_24BGR => table_entry!(layout!(plane(24, &[B, G, R])).rgb().range(FullRange)),
+            PixelFormat::_32ARGB => table_entry!(layout!(plane(32, &[A, R, G, B])).rgb().alpha().range(FullRange)),
+            PixelFormat::_32BGRA => table_entry!(layout!(plane(32, &[B, G, R, A])).rgb().alpha().range(FullRange)),
+            PixelFormat::_32ABGR => table_entry!(layout!(plane(32, &[A, B, G, R])).rgb().alpha().range(FullRange)),
+            PixelFormat::_32RGBA => table_entry!(layout!(plane(32, &[R, G, B, A])).rgb().alpha().range(FullRange)),
+            PixelFormat::_64ARGB => table_entry!(layout!(plane(64, &[A, R, G, B])).rgb().alpha().range(FullRange)),
+            PixelFormat::_48RGB => table_entry!(layout!(plane(48, &[R, G, B])).rgb().range(FullRange)),
+            PixelFormat::_32AlphaGray => table_entry!(layout!(plane(32, &[A, Gray])).alpha().range(FullRange)),
+            PixelFormat::_16Gray => table_entry!(layout!(plane(16, &[Gray])).range(FullRange)),
+            PixelFormat::_30RGB => table_entry!(layout!(plane(32, &[R, G, B, Unused])).rgb().range(FullRange)),
+            PixelFormat::_422YpCbCr8 => table_entry!(layout!(plane(32, &[Cb, Y, Cr, Y]).block(2)).ycbcr().range(VideoRange)),
+            PixelFormat::_4444YpCbCrA8 => table_entry!(layout!(plane(32, &[Cb, Y, Cr, A])).ycbcr().alpha().range(VideoRange)),
+            PixelFormat::_4444YpCbCrA8R => table_entry!(layout!(plane(32, &[A, Y, Cb, Cr])).ycbcr().alpha().range(FullRange)),
+            PixelFormat::_4444AYpCbCr8 => table_entry!(layout!(plane(32, &[A, Y, Cb, Cr])).ycbcr().alpha().range(VideoRange)),
+            PixelFormat::_4444AYpCbCr16 => table_entry!(layout!(plane(64, &[A, Y, Cb, Cr])).ycbcr().alpha().range(VideoRange)),
+            PixelFormat::_4444AYpCbCrFloat => table_entry!(layout!(plane(128, &[A, Y, Cb, Cr])).ycbcr().alpha()),
+            PixelFormat::_444YpCbCr8 => table_entry!(layout!(plane(24, &[Cr, Y, Cb])).ycbcr().range(VideoRange)),
+            PixelFormat::_422YpCbCr16 => table_entry!(layout!(plane(64, &[Cb, Y, Cr, Y]).block(2)).ycbcr().range(VideoRange)),
+            PixelFormat::_422YpCbCr10 => table_entry!(layout!(plane(128, &[Cb, Y, Cr, Y]).block(6)).ycbcr().range(VideoRange).aligned(48)),
+            PixelFormat::_444YpCbCr10 => table_entry!(layout!(plane(32, &[Cr, Y, Cb, Unused])).ycbcr().range(VideoRange)),
+            PixelFormat::_420YpCbCr8Planar => {
+                table_entry!(layout!(plane(8, &[Y]), plane(8, &[Cb]).subsampled(2, 2), plane(8, &[Cr]).subsampled(2, 2))
+                    .ycbcr()
+                    .range(VideoRange))
+            }
+            PixelFormat::_420YpCbCr8PlanarFullRange => {
+                table_entry!(layout!(plane(8, &[Y]), plane(8, &[Cb]).subsampled(2, 2), plane(8, &[Cr]).subsampled(2, 2))
+                    .ycbcr()
+                    .range(FullRange))
+            }
+            PixelFormat::_422YpCbCr_4A_8BiPlanar => {
+                table_entry!(layout!(plane(32, &[Cb, Y, Cr, Y]).block(2), plane(8, &[A])).ycbcr().alpha().range(VideoRange))
+            }
+            PixelFormat::_420YpCbCr8BiPlanarVideoRange => {
+                table_entry!(layout!(plane(8, &[Y]), plane(16, &[Cb, Cr]).subsampled(2, 2)).ycbcr().range(VideoRange))
+            }
+            PixelFormat::_420YpCbCr8BiPlanarFullRange => {
+                table_entry!(layout!(plane(8, &[Y]), plane(16, &[Cb, Cr]).subsampled(2, 2)).ycbcr().range(FullRange))
+            }
+            PixelFormat::_422YpCbCr8BiPlanarVideoRange => {
+                table_entry!(layout!(plane(8, &[Y]), plane(16, &[Cb, Cr]).subsampled(2, 1)).ycbcr().range(VideoRange))
+            }
+            PixelFormat::_422YpCbCr8BiPlanarFullRange => {
+                table_entry!(layout!(plane(8, &[Y]), plane(16, &[Cb, Cr]).subsampled(2, 1)).ycbcr().range(FullRange))
+            }
+            PixelFormat::_444YpCbCr8BiPlanarVideoRange => table_entry!(layout!(plane(8, &[Y]), plane(16, &[Cb, Cr])).ycbcr().range(VideoRange)),
+            PixelFormat::_444YpCbCr8BiPlanarFullRange => table_entry!(layout!(plane(8, &[Y]), plane(16, &[Cb, Cr])).ycbcr().range(FullRange)),
+            PixelFormat::_422YpCbCr8_yuvs => table_entry!(layout!(plane(32, &[Y, Cb, Y, Cr]).block(2)).ycbcr().range(VideoRange)),
+            PixelFormat::_422YpCbCr8FullRange => table_entry!(layout!(plane(32, &[Y, Cb, Y, Cr]).block(2)).ycbcr().range(FullRange)),
+            PixelFormat::OneComponent8 => table_entry!(layout!(plane(8, &[Gray])).range(FullRange)),
+            PixelFormat::TwoComponent8 => table_entry!(layout!(plane(16, &[Unspecified, Unspecified])).range(FullRange)),
+            PixelFormat::_30RGBLEPackedWideGamut => table_entry!(layout!(plane(32, &[Unused, R, G, B])).rgb().range(WideRange)),
+            PixelFormat::ARGB2101010LEPacked => table_entry!(layout!(plane(32, &[A, R, G, B])).rgb().alpha().range(FullRange)),
+            PixelFormat::_40ARGBLEWideGamut => table_entry!(layout!(plane(64, &[A, R, G, B])).rgb().alpha().range(WideRange)),
+            PixelFormat::_40ARGBLEWideGamutPremultiplied => table_entry!(layout!(plane(64, &[A, R, G, B])).rgb().alpha().range(WideRange)),
+            PixelFormat::OneComponent10 => table_entry!(layout!(plane(16, &[Gray])).range(FullRange)),
+            PixelFormat::OneComponent12 => table_entry!(layout!(plane(16, &[Gray])).range(FullRange)),
+            PixelFormat::OneComponent16 => table_entry!(layout!(plane(16, &[Gray])).range(FullRange)),
+            PixelFormat::TwoComponent16 => table_entry!(layout!(plane(32, &[Unspecified, Unspecified])).range(FullRange)),
+            PixelFormat::OneComponent16Half => table_entry!(layout!(plane(16, &[Gray]))),
+            PixelFormat::OneComponent32Float => table_entry!(layout!(plane(32, &[Gray]))),
+            PixelFormat::TwoComponent16Half => table_entry!(layout!(plane(32, &[Unspecified, Unspecified]))),
+            PixelFormat::TwoComponent32Float => table_entry!(layout!(plane(64, &[Unspecified, Unspecified]))),
+            PixelFormat::_64RGBAHalf => table_entry!(layout!(plane(64, &[R, G, B, A])).rgb().alpha()),
+            PixelFormat::_128RGBAFloat => table_entry!(layout!(plane(128, &[R, G, B, A])).rgb().alpha()),
+            PixelFormat::_14Bayer_GRBG => table_entry!(layout!(plane(16, &[Sensel]))),
+            PixelFormat::_14Bayer_RGGB => table_entry!(layout!(plane(16, &[Sensel]))),
+            PixelFormat::_14Bayer_BGGR => table_entry!(layout!(plane(16, &[Sensel]))),
+            PixelFormat::_14Bayer_GBRG => table_entry!(layout!(plane(16, &[Sensel]))),
+            PixelFormat::DisparityFloat16 => table_entry!(layout!(plane(16, &[Disparity]))),
+            PixelFormat::DisparityFloat32 => table_entry!(layout!(plane(32, &[Disparity]))),
+            PixelFormat::DepthFloat16 => table_entry!(layout!(plane(16, &[Depth]))),
+            PixelFormat::DepthFloat32 => table_entry!(layout!(plane(32, &[Depth]))),
+            PixelFormat::_420YpCbCr10BiPlanarVideoRange => {
+                table_entry!(layout!(plane(16, &[Y]), plane(32, &[Cb, Cr]).subsampled(2, 2)).ycbcr().range(VideoRange))
+            }
+            PixelFormat::_422YpCbCr10BiPlanarVideoRange => {
+                table_entry!(layout!(plane(16, &[Y]), plane(32, &[Cb, Cr]).subsampled(2, 1)).ycbcr().range(VideoRange))
+            }
+            PixelFormat::_444YpCbCr10BiPlanarVideoRange => table_entry!(layout!(plane(16, &[Y]), plane(32, &[Cb, Cr])).ycbcr().range(VideoRange)),
+            PixelFormat::_420YpCbCr10BiPlanarFullRange => {
+                table_entry!(layout!(plane(16, &[Y]), plane(32, &[Cb, Cr]).subsampled(2, 2)).ycbcr().range(FullRange))
+            }
+            PixelFormat::_422YpCbCr10BiPlanarFullRange => {
+                table_entry!(layout!(plane(16, &[Y]), plane(32, &[Cb, Cr]).subsampled(2, 1)).ycbcr().range(FullRange))
+            }
+            PixelFormat::_444YpCbCr10BiPlanarFullRange => table_entry!(layout!(plane(16, &[Y]), plane(32, &[Cb, Cr])).ycbcr().range(FullRange)),
+            PixelFormat::_420YpCbCr8VideoRange_8A_TriPlanar => {
+                table_entry!(layout!(plane(8, &[Y]), plane(16, &[Cb, Cr]).subsampled(2, 2), plane(8, &[A]))
+                    .ycbcr()
+                    .alpha()
+                    .range(VideoRange))
+            }
+            PixelFormat::_16VersatileBayer => table_entry!(layout!(plane(16, &[Sensel]))),
+            PixelFormat::_64RGBA_DownscaledProResRAW => table_entry!(layout!(plane(64, &[R, G, B, A])).rgb().alpha()),
+            PixelFormat::_422YpCbCr16BiPlanarVideoRange => {
+                table_entry!(layout!(plane(16, &[Y]), plane(32, &[Cb, Cr]).subsampled(2, 1)).ycbcr().range(VideoRange))
+            }
+            PixelFormat::_444YpCbCr16BiPlanarVideoRange => table_entry!(layout!(plane(16, &[Y]), plane(32, &[Cb, Cr])).ycbcr().range(VideoRange)),
+            PixelFormat::_444YpCbCr16VideoRange_16A_TriPlanar => {
+                table_entry!(layout!(plane(16, &[Y]), plane(32, &[Cb, Cr]), plane(16, &[A])).ycbcr().alpha().range(VideoRange))
+            }
+            PixelFormat::Lossless_32BGRA => table_entry!(layout!(plane(32, &[B, G, R, A])).rgb().alpha().range(FullRange).compressed()),
+            PixelFormat::Lossless_420YpCbCr8BiPlanarVideoRange => {
+                table_entry!(layout!(plane(8, &[Y]), plane(16, &[Cb, Cr]).subsampled(2, 2))
+                    .ycbcr()
+                    .range(VideoRange)
+                    .compressed())
+            }
+            PixelFormat::Lossless_420YpCbCr8BiPlanarFullRange => {
+                table_entry!(layout!(plane(8, &[Y]), plane(16, &[Cb, Cr]).subsampled(2, 2)).ycbcr().range(FullRange).compressed())
+            }
+            PixelFormat::Lossless_420YpCbCr10PackedBiPlanarVideoRange => {
+                table_entry!(layout!(plane(10, &[Y]), plane(20, &[Cb, Cr]).subsampled(2, 2))
+                    .ycbcr()
+                    .range(VideoRange)
+                    .compressed())
+            }
+            PixelFormat::Lossless_422YpCbCr10PackedBiPlanarVideoRange => {
+                table_entry!(layout!(plane(10, &[Y]), plane(20, &[Cb, Cr]).subsampled(2, 1))
+                    .ycbcr()
+                    .range(VideoRange)
+                    .compressed())
+            }
+            PixelFormat::Lossy_32BGRA => table_entry!(layout!(plane(32, &[B, G, R, A])).rgb().alpha().range(FullRange).compressed()),
+            PixelFormat::Lossy_420YpCbCr8BiPlanarVideoRange => {
+                table_entry!(layout!(plane(8, &[Y]), plane(16, &[Cb, Cr]).subsampled(2, 2))
+                    .ycbcr()
+                    .range(VideoRange)
+                    .compressed())
+            }
+            PixelFormat::Lossy_420YpCbCr8BiPlanarFullRange => {
+                table_entry!(layout!(plane(8, &[Y]), plane(16, &[Cb, Cr]).subsampled(2, 2)).ycbcr().range(FullRange).compressed())
+            }
+            PixelFormat::Lossy_420YpCbCr10PackedBiPlanarVideoRange => {
+                table_entry!(layout!(plane(10, &[Y]), plane(20, &[Cb, Cr]).subsampled(2, 2))
+                    .ycbcr()
+                    .range(VideoRange)
+                    .compressed())
+            }
+            PixelFormat::Lossy_422YpCbCr10PackedBiPlanarVideoRange => {
+                table_entry!(layout!(plane(10, &[Y]), plane(20, &[Cb, Cr]).subsampled(2, 1))
+                    .ycbcr()
+                    .range(VideoRange)
+                    .compressed())
+            }
+            PixelFormat::Unknown(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn known_formats_round_trip() {
+        assert_eq!(PixelFormat::ALL.len(), 91);
+        let codes: HashSet<OSType> = PixelFormat::ALL.iter().map(PixelFormat::as_os_type).collect();
+        assert_eq!(codes.len(), PixelFormat::ALL.len());
+        for &pixel_format in PixelFormat::ALL {
+            let code = OSType::from(pixel_format);
+            assert!(pixel_format.is_known());
+            assert_eq!(PixelFormat::from(code).name(), pixel_format.name());
+            assert_eq!(pixel_format.to_string().parse::<PixelFormat>(), Ok(pixel_format));
+        }
+    }
+
+    #[test]
+    fn unmapped_codes_are_unknown() {
+        for &code in &[0, 0x7A7A7A7A, 0xFFFFFFFF] {
+            let pixel_format = PixelFormat::from(code);
+            assert!(matches!(pixel_format, PixelFormat::Unknown(unknown) if unknown == code));
+            assert!(!pixel_format.is_known());
+            assert_eq!(OSType::from(pixel_format), code);
+            assert_eq!(pixel_format.to_string().parse::<PixelFormat>(), Ok(pixel_format));
+        }
+        assert_eq!(format!("{:?}", PixelFormat::from(0x7A7A7A7A)), "Unknown('zzzz')");
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(PixelFormat::_420YpCbCr8BiPlanarVideoRange.to_string(), "420v");
+        assert_eq!(PixelFormat::_32BGRA.to_string(), "BGRA");
+        assert_eq!(format!("{:?}", PixelFormat::_32BGRA), "_32BGRA('BGRA')");
+        assert_eq!(PixelFormat::_1Monochrome.to_string(), "0x00000001");
+        assert_eq!(PixelFormat::_32ARGB.to_string(), "0x00000020");
+        assert_eq!(PixelFormat::from(0x41420A43).to_string(), "0x41420A43");
+        assert_eq!(PixelFormat::from(0x41E94243).to_string(), "0x41E94243");
+    }
+
+    #[test]
+    fn from_str() {
+        assert_eq!("0x00000020".parse::<PixelFormat>(), Ok(PixelFormat::_32ARGB));
+        assert_eq!("0X00000018".parse::<PixelFormat>(), Ok(PixelFormat::_24RGB));
+        assert_eq!("v210".parse::<PixelFormat>(), Ok(PixelFormat::_422YpCbCr10));
+        assert_eq!("BGR".parse::<PixelFormat>(), Err(CVError::InvalidPixelFormat));
+        assert_eq!("BGRAA".parse::<PixelFormat>(), Err(CVError::InvalidPixelFormat));
+        assert_eq!("0x0000002G".parse::<PixelFormat>(), Err(CVError::InvalidPixelFormat));
+        assert_eq!("42é".parse::<PixelFormat>(), Err(CVError::InvalidPixelFormat));
+    }
+
+    // Bits per block, block width and subsampling of every plane
+    fn planes(pixel_format: PixelFormat) -> Vec<(u32, u32, u32, u32)> {
+        let layout = pixel_format.layout().unwrap();
+        layout
+            .planes
+            .iter()
+            .map(|plane| (plane.bits_per_block, plane.block_width, plane.horizontal_subsampling, plane.vertical_subsampling))
+            .collect()
+    }
+
+    #[test]
+    fn layouts() {
+        let layout = PixelFormat::_420YpCbCr8BiPlanarVideoRange.layout().unwrap();
+        assert_eq!(layout.plane_count(), 2);
+        assert_eq!(planes(PixelFormat::_420YpCbCr8BiPlanarVideoRange), [(8, 1, 1, 1), (16, 1, 2, 2)]);
+        assert_eq!(layout.planes[1].components, [PixelComponent::Cb, PixelComponent::Cr]);
+        assert!(layout.contains_ycbcr && !layout.contains_rgb && !layout.contains_alpha);
+        assert_eq!(layout.component_range, Some(CVPixelFormatComponentRange::VideoRange));
+
+        let layout = PixelFormat::from_str("x420").unwrap().layout().unwrap();
+        assert_eq!(layout.plane_count(), 2);
+        assert_eq!(planes(PixelFormat::_420YpCbCr10BiPlanarVideoRange), [(16, 1, 1, 1), (32, 1, 2, 2)]);
+
+        let layout = PixelFormat::from_str("v210").unwrap().layout().unwrap();
+        assert_eq!(layout.plane_count(), 0);
+        assert_eq!(planes(PixelFormat::_422YpCbCr10), [(128, 6, 1, 1)]);
+        assert_eq!(layout.horizontal_alignment, 48);
+
+        let layout = PixelFormat::_32BGRA.layout().unwrap();
+        assert_eq!((layout.plane_count(), planes(PixelFormat::_32BGRA)), (0, vec![(32, 1, 1, 1)]));
+        assert_eq!(layout.planes[0].components, [PixelComponent::B, PixelComponent::G, PixelComponent::R, PixelComponent::A]);
+        assert!(layout.contains_rgb && layout.contains_alpha && !layout.contains_ycbcr);
+
+        let layout = PixelFormat::_64RGBAHalf.layout().unwrap();
+        assert_eq!((layout.plane_count(), planes(PixelFormat::_64RGBAHalf)), (0, vec![(64, 1, 1, 1)]));
+        assert_eq!(layout.component_range, None);
+
+        let layout = PixelFormat::from_str("L565").unwrap().layout().unwrap();
+        assert_eq!((layout.plane_count(), planes(PixelFormat::_16LE565)), (0, vec![(16, 1, 1, 1)]));
+        assert!(layout.contains_rgb && !layout.contains_alpha);
+
+        for &pixel_format in &[PixelFormat::_1Monochrome, PixelFormat::_8Indexed, PixelFormat::_8IndexedGray_WhiteIsZero] {
+            let layout = pixel_format.layout().unwrap();
+            assert!(layout.indexed && !layout.contains_rgb && !layout.contains_ycbcr, "{:?}", pixel_format);
+        }
+        assert_eq!(PixelFormat::from(0x7A7A7A7A).layout(), None);
+    }
+
+    #[test]
+    fn table_consistency() {
+        for &pixel_format in PixelFormat::ALL {
+            let layout = pixel_format.layout().unwrap();
+            assert!(!layout.planes.is_empty(), "{:?}", pixel_format);
+            assert!(layout.planes.len() <= 3, "{:?}", pixel_format);
+            let classes = [layout.contains_rgb, layout.contains_ycbcr, layout.indexed].iter().filter(|&&class| class).count();
+            assert!(classes <= 1, "{:?}", pixel_format);
+            let has_alpha = layout.planes.iter().any(|plane| plane.components.contains(&PixelComponent::A));
+            assert_eq!(layout.contains_alpha, has_alpha, "{:?}", pixel_format);
+            for plane in layout.planes {
+                assert!(plane.bits_per_block > 0 && !plane.components.is_empty(), "{:?}", pixel_format);
+            }
+        }
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    #[test]
+    fn matches_core_video() {
+        use crate::pixel_format_description::{PixelFormatDescription, PixelFormatPlaneDescription};
+
+        // Compressed formats have no plane layout CoreVideo describes
+        for &pixel_format in PixelFormat::ALL {
+            let layout = pixel_format.layout().unwrap();
+            let description = match PixelFormatDescription::with_pixel_format_type(pixel_format.as_os_type()) {
+                Ok(description) if !layout.compressed => description,
+                _ => continue,
+            };
+            let planes: Vec<&PixelFormatPlaneDescription> =
+                if description.planes.is_empty() { vec![&description.plane] } else { description.planes.iter().collect() };
+            assert_eq!(planes.len(), layout.planes.len(), "{:?}", pixel_format);
+            for (plane, expected) in layout.planes.iter().zip(planes) {
+                assert_eq!(Some(plane.bits_per_block), expected.bits_per_block, "{:?}", pixel_format);
+                assert_eq!(plane.block_width, expected.block_width.unwrap_or(1), "{:?}", pixel_format);
+                assert_eq!(plane.block_height, expected.block_height.unwrap_or(1), "{:?}", pixel_format);
+                assert_eq!(plane.horizontal_subsampling, expected.horizontal_subsampling.unwrap_or(1), "{:?}", pixel_format);
+                assert_eq!(plane.vertical_subsampling, expected.vertical_subsampling.unwrap_or(1), "{:?}", pixel_format);
+                if let Some(alignment) = expected.block_horizontal_alignment {
+                    assert_eq!(layout.horizontal_alignment, (alignment * plane.block_width).max(1), "{:?}", pixel_format);
+                }
+            }
+            assert_eq!(layout.contains_alpha, description.contains_alpha.unwrap_or(false), "{:?}", pixel_format);
+            assert_eq!(layout.contains_ycbcr, description.contains_ycbcr.unwrap_or(false), "{:?}", pixel_format);
+            // CoreVideo does not tell indexed formats apart, so only the others are checked for RGB
+            if !layout.indexed {
+                assert_eq!(layout.contains_rgb, description.contains_rgb.unwrap_or(false), "{:?}", pixel_format);
+            }
+            if let Some(component_range) = description.component_range {
+                assert_eq!(layout.component_range, Some(component_range), "{:?}", pixel_format);
+            }
+        }
+    }
+}
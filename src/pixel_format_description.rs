@@ -1,23 +1,39 @@
+use std::{convert::TryFrom, mem, ptr};
+
 use core_foundation::{
     array::{CFArray, CFArrayRef},
     base::{kCFAllocatorDefault, Boolean, CFAllocatorRef, CFIndex, CFType, TCFType},
+    boolean::CFBoolean,
+    data::CFData,
     dictionary::{CFDictionary, CFDictionaryRef},
     number::CFNumber,
     string::{CFString, CFStringRef},
+    ConcreteCFType,
 };
 use libc::c_void;
 
-use crate::{pixel_buffer::CVPixelBufferRef, OSType};
+use crate::{pixel_buffer::CVPixelBufferRef, r#return::CVError, OSType};
 
 pub type CVFillExtendedPixelsCallBack = extern "C" fn(pixelBuffer: CVPixelBufferRef, refCon: *mut c_void) -> Boolean;
 
+// The callback is optional so that data read from a description dictionary, which may hold anything, is never an
+// invalid function pointer
 #[repr(C)]
+#[derive(Clone, Copy, Debug)]
 pub struct CVFillExtendedPixelsCallBackData {
     pub version: CFIndex,
-    pub fillCallBack: CVFillExtendedPixelsCallBack,
+    pub fillCallBack: Option<CVFillExtendedPixelsCallBack>,
     pub refCon: *mut c_void,
 }
 
+// Callbacks are compared by address
+impl PartialEq for CVFillExtendedPixelsCallBackData {
+    fn eq(&self, other: &Self) -> bool {
+        let address = |callback: Option<CVFillExtendedPixelsCallBack>| callback.map(|callback| callback as usize);
+        self.version == other.version && address(self.fillCallBack) == address(other.fillCallBack) && self.refCon == other.refCon
+    }
+}
+
 extern "C" {
     pub static kCVPixelFormatName: CFStringRef;
     pub static kCVPixelFormatConstant: CFStringRef;
@@ -137,28 +153,494 @@ impl From<CVPixelFormatDescriptionKeys> for CFString {
     }
 }
 
-pub fn pixel_format_description_create_with_pixel_format_type(pixel_format: OSType) -> Result<CFDictionary<CFString, CFType>, ()> {
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CVPixelFormatComponentRange {
+    VideoRange,
+    FullRange,
+    WideRange,
+}
+
+impl From<CVPixelFormatComponentRange> for CFStringRef {
+    fn from(component_range: CVPixelFormatComponentRange) -> Self {
+        unsafe {
+            match component_range {
+                CVPixelFormatComponentRange::VideoRange => kCVPixelFormatComponentRange_VideoRange,
+                CVPixelFormatComponentRange::FullRange => kCVPixelFormatComponentRange_FullRange,
+                CVPixelFormatComponentRange::WideRange => kCVPixelFormatComponentRange_WideRange,
+            }
+        }
+    }
+}
+
+impl From<CVPixelFormatComponentRange> for CFString {
+    fn from(component_range: CVPixelFormatComponentRange) -> Self {
+        unsafe { CFString::wrap_under_get_rule(CFStringRef::from(component_range)) }
+    }
+}
+
+fn find_value(dictionary: &CFDictionary<CFString, CFType>, key: CVPixelFormatDescriptionKeys) -> Option<CFType> {
+    dictionary.find(CFString::from(key)).map(|value| value.clone())
+}
+
+// Each find function gives None when the key is missing, and fails when the value has the wrong type or is out of range
+fn find_typed<T: ConcreteCFType>(dictionary: &CFDictionary<CFString, CFType>, key: CVPixelFormatDescriptionKeys) -> Result<Option<T>, CVError> {
+    find_value(dictionary, key)
+        .map(|value| value.downcast::<T>().ok_or(CVError::InvalidArgument))
+        .transpose()
+}
+
+fn find_number(dictionary: &CFDictionary<CFString, CFType>, key: CVPixelFormatDescriptionKeys) -> Result<Option<u32>, CVError> {
+    find_typed::<CFNumber>(dictionary, key)?
+        .map(|number| number.to_i64().and_then(|number| u32::try_from(number).ok()).ok_or(CVError::InvalidArgument))
+        .transpose()
+}
+
+fn find_bool(dictionary: &CFDictionary<CFString, CFType>, key: CVPixelFormatDescriptionKeys) -> Result<Option<bool>, CVError> {
+    Ok(find_typed::<CFBoolean>(dictionary, key)?.map(bool::from))
+}
+
+fn find_data(dictionary: &CFDictionary<CFString, CFType>, key: CVPixelFormatDescriptionKeys) -> Result<Option<Vec<u8>>, CVError> {
+    Ok(find_typed::<CFData>(dictionary, key)?.map(|data| data.bytes().to_vec()))
+}
+
+// Four character codes are stored either as a number or as a four character string
+fn find_os_type(dictionary: &CFDictionary<CFString, CFType>, key: CVPixelFormatDescriptionKeys) -> Result<Option<OSType>, CVError> {
+    let value = match find_value(dictionary, key) {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    if let Some(number) = value.downcast::<CFNumber>() {
+        return number
+            .to_i64()
+            .and_then(|number| OSType::try_from(number).ok())
+            .map(Some)
+            .ok_or(CVError::InvalidArgument);
+    }
+    let string = value.downcast::<CFString>().ok_or(CVError::InvalidArgument)?.to_string();
+    let bytes = string.as_bytes();
+    if bytes.len() == 4 {
+        Ok(Some(OSType::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])))
+    } else {
+        Err(CVError::InvalidArgument)
+    }
+}
+
+fn push_number(pairs: &mut Vec<(CFString, CFType)>, key: CVPixelFormatDescriptionKeys, value: Option<u32>) {
+    if let Some(value) = value {
+        pairs.push((CFString::from(key), CFNumber::from(value as i64).as_CFType()));
+    }
+}
+
+fn push_bool(pairs: &mut Vec<(CFString, CFType)>, key: CVPixelFormatDescriptionKeys, value: Option<bool>) {
+    if let Some(value) = value {
+        pairs.push((CFString::from(key), CFBoolean::from(value).as_CFType()));
+    }
+}
+
+fn push_data(pairs: &mut Vec<(CFString, CFType)>, key: CVPixelFormatDescriptionKeys, value: Option<&[u8]>) {
+    if let Some(value) = value {
+        pairs.push((CFString::from(key), CFData::from_buffer(value).as_CFType()));
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PixelFormatPlaneDescription {
+    pub bits_per_block: Option<u32>,
+    pub block_width: Option<u32>,
+    pub block_height: Option<u32>,
+    pub block_horizontal_alignment: Option<u32>,
+    pub block_vertical_alignment: Option<u32>,
+    pub black_block: Option<Vec<u8>>,
+    pub horizontal_subsampling: Option<u32>,
+    pub vertical_subsampling: Option<u32>,
+}
+
+impl PixelFormatPlaneDescription {
+    pub fn from_dictionary(dictionary: &CFDictionary<CFString, CFType>) -> Result<Self, CVError> {
+        Ok(PixelFormatPlaneDescription {
+            bits_per_block: find_number(dictionary, CVPixelFormatDescriptionKeys::BitsPerBlock)?,
+            block_width: find_number(dictionary, CVPixelFormatDescriptionKeys::BlockWidth)?,
+            block_height: find_number(dictionary, CVPixelFormatDescriptionKeys::BlockHeight)?,
+            block_horizontal_alignment: find_number(dictionary, CVPixelFormatDescriptionKeys::BlockHorizontalAlignment)?,
+            block_vertical_alignment: find_number(dictionary, CVPixelFormatDescriptionKeys::BlockVerticalAlignment)?,
+            black_block: find_data(dictionary, CVPixelFormatDescriptionKeys::BlackBlock)?,
+            horizontal_subsampling: find_number(dictionary, CVPixelFormatDescriptionKeys::HorizontalSubsampling)?,
+            vertical_subsampling: find_number(dictionary, CVPixelFormatDescriptionKeys::VerticalSubsampling)?,
+        })
+    }
+
+    fn push_pairs(&self, pairs: &mut Vec<(CFString, CFType)>) {
+        push_number(pairs, CVPixelFormatDescriptionKeys::BitsPerBlock, self.bits_per_block);
+        push_number(pairs, CVPixelFormatDescriptionKeys::BlockWidth, self.block_width);
+        push_number(pairs, CVPixelFormatDescriptionKeys::BlockHeight, self.block_height);
+        push_number(pairs, CVPixelFormatDescriptionKeys::BlockHorizontalAlignment, self.block_horizontal_alignment);
+        push_number(pairs, CVPixelFormatDescriptionKeys::BlockVerticalAlignment, self.block_vertical_alignment);
+        push_data(pairs, CVPixelFormatDescriptionKeys::BlackBlock, self.black_block.as_deref());
+        push_number(pairs, CVPixelFormatDescriptionKeys::HorizontalSubsampling, self.horizontal_subsampling);
+        push_number(pairs, CVPixelFormatDescriptionKeys::VerticalSubsampling, self.vertical_subsampling);
+    }
+
+    pub fn to_dictionary(&self) -> CFDictionary<CFString, CFType> {
+        let mut pairs = Vec::new();
+        self.push_pairs(&mut pairs);
+        CFDictionary::from_CFType_pairs(&pairs)
+    }
+}
+
+// Single plane formats keep the plane keys at the top level, so `plane` is only used when `planes` is empty
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PixelFormatDescription {
+    pub name: Option<String>,
+    pub constant: Option<OSType>,
+    pub codec_type: Option<OSType>,
+    pub fourcc: Option<OSType>,
+    pub contains_alpha: Option<bool>,
+    pub contains_ycbcr: Option<bool>,
+    pub contains_rgb: Option<bool>,
+    pub component_range: Option<CVPixelFormatComponentRange>,
+    pub planes: Vec<PixelFormatPlaneDescription>,
+    pub plane: PixelFormatPlaneDescription,
+    pub opengl_format: Option<u32>,
+    pub opengl_type: Option<u32>,
+    pub opengl_internal_format: Option<u32>,
+    pub cg_bitmap_info: Option<u32>,
+    pub qd_compatibility: Option<bool>,
+    pub cg_bitmap_context_compatibility: Option<bool>,
+    pub cg_image_compatibility: Option<bool>,
+    pub opengl_compatibility: Option<bool>,
+    #[cfg(target_os = "ios")]
+    pub opengles_compatibility: Option<bool>,
+    pub fill_extended_pixels_callback: Option<CVFillExtendedPixelsCallBackData>,
+}
+
+impl PixelFormatDescription {
+    // Fails when a value has the wrong type or is out of range
+    pub fn from_dictionary(dictionary: &CFDictionary<CFString, CFType>) -> Result<Self, CVError> {
+        let component_range = find_typed::<CFString>(dictionary, CVPixelFormatDescriptionKeys::ComponentRange)?
+            .map(|component_range| {
+                [CVPixelFormatComponentRange::VideoRange, CVPixelFormatComponentRange::FullRange, CVPixelFormatComponentRange::WideRange]
+                    .iter()
+                    .copied()
+                    .find(|range| CFString::from(*range) == component_range)
+                    .ok_or(CVError::InvalidArgument)
+            })
+            .transpose()?;
+        let planes = match find_typed::<CFArray>(dictionary, CVPixelFormatDescriptionKeys::Planes)? {
+            Some(planes) => planes
+                .iter()
+                .map(|plane| {
+                    let plane = unsafe { CFType::wrap_under_get_rule(*plane) }
+                        .downcast::<CFDictionary>()
+                        .ok_or(CVError::InvalidArgument)?;
+                    let plane = unsafe { CFDictionary::wrap_under_get_rule(plane.as_concrete_TypeRef()) };
+                    PixelFormatPlaneDescription::from_dictionary(&plane)
+                })
+                .collect::<Result<_, _>>()?,
+            None => Vec::new(),
+        };
+        let fill_extended_pixels_callback = find_data(dictionary, CVPixelFormatDescriptionKeys::FillExtendedPixelsCallback)?
+            .map(|data| {
+                if data.len() == mem::size_of::<CVFillExtendedPixelsCallBackData>() {
+                    Ok(unsafe { ptr::read_unaligned(data.as_ptr() as *const CVFillExtendedPixelsCallBackData) })
+                } else {
+                    Err(CVError::InvalidArgument)
+                }
+            })
+            .transpose()?;
+
+        Ok(PixelFormatDescription {
+            name: find_typed::<CFString>(dictionary, CVPixelFormatDescriptionKeys::Name)?.map(|name| name.to_string()),
+            constant: find_os_type(dictionary, CVPixelFormatDescriptionKeys::Constant)?,
+            codec_type: find_os_type(dictionary, CVPixelFormatDescriptionKeys::CodecType)?,
+            fourcc: find_os_type(dictionary, CVPixelFormatDescriptionKeys::FourCC)?,
+            contains_alpha: find_bool(dictionary, CVPixelFormatDescriptionKeys::ContainsAlpha)?,
+            contains_ycbcr: find_bool(dictionary, CVPixelFormatDescriptionKeys::ContainsYCbCr)?,
+            contains_rgb: find_bool(dictionary, CVPixelFormatDescriptionKeys::ContainsRGB)?,
+            component_range,
+            planes,
+            plane: PixelFormatPlaneDescription::from_dictionary(dictionary)?,
+            opengl_format: find_number(dictionary, CVPixelFormatDescriptionKeys::OpenGLFormat)?,
+            opengl_type: find_number(dictionary, CVPixelFormatDescriptionKeys::OpenGLType)?,
+            opengl_internal_format: find_number(dictionary, CVPixelFormatDescriptionKeys::OpenGLInternalFormat)?,
+            cg_bitmap_info: find_number(dictionary, CVPixelFormatDescriptionKeys::CGBitmapInfo)?,
+            qd_compatibility: find_bool(dictionary, CVPixelFormatDescriptionKeys::QDCompatibility)?,
+            cg_bitmap_context_compatibility: find_bool(dictionary, CVPixelFormatDescriptionKeys::CGBitmapContextCompatibility)?,
+            cg_image_compatibility: find_bool(dictionary, CVPixelFormatDescriptionKeys::CGImageCompatibility)?,
+            opengl_compatibility: find_bool(dictionary, CVPixelFormatDescriptionKeys::OpenGLCompatibility)?,
+            #[cfg(target_os = "ios")]
+            opengles_compatibility: find_bool(dictionary, CVPixelFormatDescriptionKeys::OpenGLESCompatibility)?,
+            fill_extended_pixels_callback,
+        })
+    }
+
+    pub fn to_dictionary(&self) -> CFDictionary<CFString, CFType> {
+        let mut pairs = Vec::new();
+        if let Some(name) = &self.name {
+            pairs.push((CFString::from(CVPixelFormatDescriptionKeys::Name), CFString::new(name).as_CFType()));
+        }
+        push_number(&mut pairs, CVPixelFormatDescriptionKeys::Constant, self.constant);
+        push_number(&mut pairs, CVPixelFormatDescriptionKeys::CodecType, self.codec_type);
+        push_number(&mut pairs, CVPixelFormatDescriptionKeys::FourCC, self.fourcc);
+        push_bool(&mut pairs, CVPixelFormatDescriptionKeys::ContainsAlpha, self.contains_alpha);
+        push_bool(&mut pairs, CVPixelFormatDescriptionKeys::ContainsYCbCr, self.contains_ycbcr);
+        push_bool(&mut pairs, CVPixelFormatDescriptionKeys::ContainsRGB, self.contains_rgb);
+        if let Some(component_range) = self.component_range {
+            pairs.push((CFString::from(CVPixelFormatDescriptionKeys::ComponentRange), CFString::from(component_range).as_CFType()));
+        }
+        if self.planes.is_empty() {
+            self.plane.push_pairs(&mut pairs);
+        } else {
+            let planes: Vec<CFType> = self.planes.iter().map(|plane| plane.to_dictionary().as_CFType()).collect();
+            pairs.push((CFString::from(CVPixelFormatDescriptionKeys::Planes), CFArray::from_CFTypes(&planes).as_CFType()));
+        }
+        push_number(&mut pairs, CVPixelFormatDescriptionKeys::OpenGLFormat, self.opengl_format);
+        push_number(&mut pairs, CVPixelFormatDescriptionKeys::OpenGLType, self.opengl_type);
+        push_number(&mut pairs, CVPixelFormatDescriptionKeys::OpenGLInternalFormat, self.opengl_internal_format);
+        push_number(&mut pairs, CVPixelFormatDescriptionKeys::CGBitmapInfo, self.cg_bitmap_info);
+        push_bool(&mut pairs, CVPixelFormatDescriptionKeys::QDCompatibility, self.qd_compatibility);
+        push_bool(&mut pairs, CVPixelFormatDescriptionKeys::CGBitmapContextCompatibility, self.cg_bitmap_context_compatibility);
+        push_bool(&mut pairs, CVPixelFormatDescriptionKeys::CGImageCompatibility, self.cg_image_compatibility);
+        push_bool(&mut pairs, CVPixelFormatDescriptionKeys::OpenGLCompatibility, self.opengl_compatibility);
+        #[cfg(target_os = "ios")]
+        push_bool(&mut pairs, CVPixelFormatDescriptionKeys::OpenGLESCompatibility, self.opengles_compatibility);
+        if let Some(callback) = &self.fill_extended_pixels_callback {
+            let bytes = unsafe {
+                std::slice::from_raw_parts(
+                    callback as *const CVFillExtendedPixelsCallBackData as *const u8,
+                    mem::size_of::<CVFillExtendedPixelsCallBackData>(),
+                )
+            };
+            push_data(&mut pairs, CVPixelFormatDescriptionKeys::FillExtendedPixelsCallback, Some(bytes));
+        }
+        CFDictionary::from_CFType_pairs(&pairs)
+    }
+
+    #[inline]
+    pub fn with_pixel_format_type(pixel_format: OSType) -> Result<Self, CVError> {
+        PixelFormatDescription::from_dictionary(&pixel_format_description_create_with_pixel_format_type(pixel_format)?)
+    }
+
+    #[inline]
+    pub fn register(&self, pixel_format: OSType) {
+        pixel_format_description_register_description_with_pixel_format_type(&self.to_dictionary(), pixel_format)
+    }
+}
+
+pub fn pixel_format_description_create_with_pixel_format_type(pixel_format: OSType) -> Result<CFDictionary<CFString, CFType>, CVError> {
     unsafe {
         let description = CVPixelFormatDescriptionCreateWithPixelFormatType(kCFAllocatorDefault, pixel_format);
         if description.is_null() {
-            Err(())
+            Err(CVError::InvalidPixelFormat)
         } else {
             Ok(TCFType::wrap_under_create_rule(description))
         }
     }
 }
 
-pub fn pixel_format_description_array_create_with_all_pixel_format_types() -> Result<CFArray<CFNumber>, ()> {
+pub fn pixel_format_description_array_create_with_all_pixel_format_types() -> Result<CFArray<CFNumber>, CVError> {
     unsafe {
         let array = CVPixelFormatDescriptionArrayCreateWithAllPixelFormatTypes(kCFAllocatorDefault);
         if array.is_null() {
-            Err(())
+            Err(CVError::Error)
         } else {
             Ok(TCFType::wrap_under_create_rule(array))
         }
     }
 }
 
+pub fn pixel_format_description_register_description_with_pixel_format_type(description: &CFDictionary<CFString, CFType>, pixel_format: OSType) {
+    unsafe { CVPixelFormatDescriptionRegisterDescriptionWithPixelFormatType(description.as_concrete_TypeRef(), pixel_format) }
+}
+
 pub fn is_compressed_pixel_format_available(pixel_format: OSType) -> bool {
     unsafe { CVIsCompressedPixelFormatAvailable(pixel_format) != 0 }
 }
+
+#[cfg(all(test, any(target_os = "macos", target_os = "ios")))]
+mod tests {
+    use super::*;
+    use crate::pixel_buffer::{kCVPixelFormatType_32BGRA, kCVPixelFormatType_420YpCbCr8BiPlanarVideoRange, kCVPixelFormatType_422YpCbCr10};
+
+    fn number(value: i64) -> CFType {
+        CFNumber::from(value).as_CFType()
+    }
+
+    fn boolean(value: bool) -> CFType {
+        CFBoolean::from(value).as_CFType()
+    }
+
+    fn data(bytes: &[u8]) -> CFType {
+        CFData::from_buffer(bytes).as_CFType()
+    }
+
+    fn dictionary(pairs: &[(&str, CFType)]) -> CFDictionary<CFString, CFType> {
+        let pairs: Vec<(CFString, CFType)> = pairs.iter().map(|(key, value)| (CFString::new(key), value.clone())).collect();
+        CFDictionary::from_CFType_pairs(&pairs)
+    }
+
+    // Descriptions as CVPixelFormatDescriptionCreateWithPixelFormatType returns them, keys this crate does not read
+    // included; the fill callback, which points into CoreVideo, is zeroed
+    fn recorded_420v() -> CFDictionary<CFString, CFType> {
+        let planes = CFArray::from_CFTypes(&[
+            dictionary(&[("BitsPerBlock", number(8)), ("BlackBlock", data(&[0x10]))]).as_CFType(),
+            dictionary(&[
+                ("BitsPerBlock", number(16)),
+                ("BlackBlock", data(&[0x80, 0x80])),
+                ("HorizontalSubsampling", number(2)),
+                ("VerticalSubsampling", number(2)),
+            ])
+            .as_CFType(),
+        ]);
+        dictionary(&[
+            ("BitsPerComponent", number(8)),
+            ("ComponentRange", CFString::new("VideoRange").as_CFType()),
+            ("ContainsAlpha", boolean(false)),
+            ("ContainsGrayscale", boolean(false)),
+            ("ContainsRGB", boolean(false)),
+            ("ContainsYCbCr", boolean(true)),
+            ("FillExtendedPixelsCallback", data(&[0; mem::size_of::<CVFillExtendedPixelsCallBackData>()])),
+            ("IOSurfaceCoreAnimationCompatibility", boolean(true)),
+            ("IOSurfaceOpenGLESFBOCompatibility", boolean(true)),
+            ("IOSurfaceOpenGLESTextureCompatibility", boolean(true)),
+            ("OpenGLESCompatibility", boolean(true)),
+            ("PixelFormat", number(kCVPixelFormatType_420YpCbCr8BiPlanarVideoRange as i64)),
+            ("Planes", planes.as_CFType()),
+        ])
+    }
+
+    fn recorded_bgra() -> CFDictionary<CFString, CFType> {
+        dictionary(&[
+            ("BitsPerBlock", number(32)),
+            ("BitsPerComponent", number(8)),
+            ("BlackBlock", data(&[0, 0, 0, 0xFF])),
+            ("CGBitmapContextCompatibility", boolean(true)),
+            ("CGBitmapInfo", number(8196)),
+            ("CGImageCompatibility", boolean(true)),
+            ("ContainsAlpha", boolean(true)),
+            ("ContainsGrayscale", boolean(false)),
+            ("ContainsRGB", boolean(true)),
+            ("ContainsYCbCr", boolean(false)),
+            ("IOSurfaceCoreAnimationCompatibility", boolean(true)),
+            ("OpenGLCompatibility", boolean(true)),
+            ("OpenGLFormat", number(0x80E1)),
+            ("OpenGLInternalFormat", number(0x1908)),
+            ("OpenGLType", number(0x8367)),
+            ("PixelFormat", number(kCVPixelFormatType_32BGRA as i64)),
+        ])
+    }
+
+    fn recorded_v210() -> CFDictionary<CFString, CFType> {
+        dictionary(&[
+            ("BitsPerBlock", number(128)),
+            ("BlackBlock", data(&[0x00, 0x02, 0x01, 0x20, 0x40, 0x00, 0x08, 0x04, 0x00, 0x02, 0x01, 0x20, 0x40, 0x00, 0x08, 0x04])),
+            ("BlockHorizontalAlignment", number(8)),
+            ("BlockWidth", number(6)),
+            ("ComponentRange", CFString::new("VideoRange").as_CFType()),
+            ("ContainsAlpha", boolean(false)),
+            ("ContainsGrayscale", boolean(false)),
+            ("ContainsRGB", boolean(false)),
+            ("ContainsYCbCr", boolean(true)),
+            ("PixelFormat", number(kCVPixelFormatType_422YpCbCr10 as i64)),
+        ])
+    }
+
+    #[test]
+    fn recorded_descriptions() {
+        let description = PixelFormatDescription::from_dictionary(&recorded_420v()).unwrap();
+        assert_eq!(description.constant, Some(kCVPixelFormatType_420YpCbCr8BiPlanarVideoRange));
+        assert_eq!((description.contains_alpha, description.contains_ycbcr, description.contains_rgb), (Some(false), Some(true), Some(false)));
+        assert_eq!(description.component_range, Some(CVPixelFormatComponentRange::VideoRange));
+        assert_eq!(description.planes.len(), 2);
+        assert_eq!(description.planes[0].black_block.as_deref(), Some(&[0x10][..]));
+        assert_eq!((description.planes[1].horizontal_subsampling, description.planes[1].vertical_subsampling), (Some(2), Some(2)));
+        assert_eq!(description.plane, PixelFormatPlaneDescription::default());
+        // A zeroed callback is read as no callback rather than a null function pointer
+        let callback = description.fill_extended_pixels_callback.unwrap();
+        assert!(callback.version == 0 && callback.fillCallBack.is_none() && callback.refCon.is_null());
+
+        let description = PixelFormatDescription::from_dictionary(&recorded_bgra()).unwrap();
+        assert_eq!(description.constant, Some(kCVPixelFormatType_32BGRA));
+        assert_eq!(description.plane.bits_per_block, Some(32));
+        assert_eq!((description.cg_bitmap_info, description.opengl_format, description.opengl_type), (Some(8196), Some(0x80E1), Some(0x8367)));
+        assert_eq!(description.fill_extended_pixels_callback, None);
+        assert!(description.planes.is_empty());
+
+        let description = PixelFormatDescription::from_dictionary(&recorded_v210()).unwrap();
+        assert_eq!((description.plane.bits_per_block, description.plane.block_width), (Some(128), Some(6)));
+        assert_eq!(description.plane.block_horizontal_alignment, Some(8));
+        assert_eq!(description.plane.black_block.as_ref().map(Vec::len), Some(16));
+    }
+
+    #[test]
+    fn round_trips() {
+        for recorded in &[recorded_420v(), recorded_bgra(), recorded_v210()] {
+            let description = PixelFormatDescription::from_dictionary(recorded).unwrap();
+            assert_eq!(PixelFormatDescription::from_dictionary(&description.to_dictionary()).unwrap(), description);
+            for plane in &description.planes {
+                assert_eq!(&PixelFormatPlaneDescription::from_dictionary(&plane.to_dictionary()).unwrap(), plane);
+            }
+        }
+    }
+
+    // The layout parts of the recorded descriptions match what this system reports
+    #[test]
+    fn recorded_match_system() {
+        for recorded in &[recorded_420v(), recorded_bgra(), recorded_v210()] {
+            let recorded = PixelFormatDescription::from_dictionary(recorded).unwrap();
+            let system = PixelFormatDescription::with_pixel_format_type(recorded.constant.unwrap()).unwrap();
+            assert_eq!(system.constant, recorded.constant);
+            assert_eq!(
+                (system.contains_alpha, system.contains_ycbcr, system.contains_rgb),
+                (recorded.contains_alpha, recorded.contains_ycbcr, recorded.contains_rgb)
+            );
+            assert_eq!(system.component_range, recorded.component_range);
+            assert_eq!(system.planes, recorded.planes);
+            assert_eq!(system.plane, recorded.plane);
+        }
+    }
+
+    #[test]
+    fn system_descriptions() {
+        let description = PixelFormatDescription::with_pixel_format_type(kCVPixelFormatType_420YpCbCr8BiPlanarVideoRange).unwrap();
+        let bits_per_block: Vec<_> = description.planes.iter().map(|plane| plane.bits_per_block).collect();
+        assert_eq!(bits_per_block, [Some(8), Some(16)]);
+        assert_eq!(description.planes[1].horizontal_subsampling, Some(2));
+        assert_eq!(description.component_range, Some(CVPixelFormatComponentRange::VideoRange));
+        let description = PixelFormatDescription::with_pixel_format_type(kCVPixelFormatType_32BGRA).unwrap();
+        assert_eq!(description.plane.bits_per_block, Some(32));
+        assert_eq!(description.contains_rgb, Some(true));
+        let description = PixelFormatDescription::with_pixel_format_type(kCVPixelFormatType_422YpCbCr10).unwrap();
+        assert_eq!((description.plane.bits_per_block, description.plane.block_width), (Some(128), Some(6)));
+    }
+
+    fn with_value(key: CVPixelFormatDescriptionKeys, value: CFType) -> CFDictionary<CFString, CFType> {
+        CFDictionary::from_CFType_pairs(&[(CFString::from(key), value)])
+    }
+
+    #[test]
+    fn malformed_dictionaries() {
+        let malformed = [
+            with_value(CVPixelFormatDescriptionKeys::BitsPerBlock, CFNumber::from(1i64 << 32).as_CFType()),
+            with_value(CVPixelFormatDescriptionKeys::BitsPerBlock, CFNumber::from(-1i64).as_CFType()),
+            with_value(CVPixelFormatDescriptionKeys::BitsPerBlock, CFString::new("32").as_CFType()),
+            with_value(CVPixelFormatDescriptionKeys::Constant, CFNumber::from(-1i64).as_CFType()),
+            with_value(CVPixelFormatDescriptionKeys::Constant, CFString::new("BGRAA").as_CFType()),
+            with_value(CVPixelFormatDescriptionKeys::ContainsAlpha, CFNumber::from(1i64).as_CFType()),
+            with_value(CVPixelFormatDescriptionKeys::ComponentRange, CFString::new("NarrowRange").as_CFType()),
+            with_value(CVPixelFormatDescriptionKeys::Planes, CFArray::from_CFTypes(&[CFNumber::from(8i64)]).as_CFType()),
+            with_value(CVPixelFormatDescriptionKeys::FillExtendedPixelsCallback, CFData::from_buffer(&[0; 3]).as_CFType()),
+        ];
+        for dictionary in &malformed {
+            assert_eq!(PixelFormatDescription::from_dictionary(dictionary).unwrap_err(), CVError::InvalidArgument);
+        }
+        let description =
+            PixelFormatDescription::from_dictionary(&with_value(CVPixelFormatDescriptionKeys::Constant, CFString::new("BGRA").as_CFType())).unwrap();
+        assert_eq!(description.constant, Some(kCVPixelFormatType_32BGRA));
+        let description = PixelFormatDescription::from_dictionary(&with_value(
+            CVPixelFormatDescriptionKeys::BitsPerBlock,
+            CFNumber::from(u32::MAX as i64).as_CFType(),
+        ))
+        .unwrap();
+        assert_eq!(description.plane.bits_per_block, Some(u32::MAX));
+    }
+}
@@ -0,0 +1,313 @@
+use std::{convert::TryFrom, mem::size_of};
+
+use libc::c_void;
+
+use crate::{
+    pixel_buffer::{CVPlanarComponentInfo, CVPlanarPixelBufferInfo_YCbCrBiPlanar, CVPlanarPixelBufferInfo_YCbCrPlanar},
+    pixel_format::PixelFormat,
+    r#return::CVError,
+};
+
+#[inline]
+fn round_up(value: usize, alignment: usize) -> Option<usize> {
+    let alignment = alignment.max(1);
+    value.checked_add(alignment - 1).map(|value| value / alignment * alignment)
+}
+
+#[inline]
+fn checked_sum(values: &[usize]) -> Result<usize, CVError> {
+    values.iter().try_fold(0usize, |sum, value| sum.checked_add(*value)).ok_or(CVError::InvalidSize)
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum PlanarPixelBufferInfo {
+    YCbCrPlanar,
+    YCbCrBiPlanar,
+}
+
+fn planar_pixel_buffer_info(pixel_format: PixelFormat) -> Option<PlanarPixelBufferInfo> {
+    match pixel_format {
+        PixelFormat::_420YpCbCr8Planar | PixelFormat::_420YpCbCr8PlanarFullRange => Some(PlanarPixelBufferInfo::YCbCrPlanar),
+        PixelFormat::_420YpCbCr8BiPlanarVideoRange |
+        PixelFormat::_420YpCbCr8BiPlanarFullRange |
+        PixelFormat::_422YpCbCr8BiPlanarVideoRange |
+        PixelFormat::_422YpCbCr8BiPlanarFullRange |
+        PixelFormat::_444YpCbCr8BiPlanarVideoRange |
+        PixelFormat::_444YpCbCr8BiPlanarFullRange => Some(PlanarPixelBufferInfo::YCbCrBiPlanar),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PlaneGeometry {
+    pub width: usize,
+    pub height: usize,
+    pub bytes_per_row: usize,
+    pub offset: usize, /* offset of the first non-extended pixel from the start of the data */
+    pub start: usize,  /* offset of the first extended row from the start of the data */
+    pub size: usize,   /* bytes spanned by the plane, including extended rows and columns */
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlaneLayout {
+    pub pixel_format: PixelFormat,
+    pub width: usize,
+    pub height: usize,
+    pub extended_pixels: (usize, usize, usize, usize),
+    pub header_size: usize,
+    pub planes: Vec<PlaneGeometry>,
+    pub data_size: usize,
+}
+
+impl PlaneLayout {
+    // Lays out each plane the way CVPixelBufferCreate does: rows padded to `row_alignment` bytes, each plane starting on a
+    // `plane_alignment` byte boundary, `extended_pixels` as (left, right, top, bottom) around the image, and a big-endian
+    // CVPlanarPixelBufferInfo header in front of the planes for formats that carry one. Fails with InvalidSize when any
+    // size or offset overflows.
+    pub fn compute(
+        pixel_format: PixelFormat,
+        width: usize,
+        height: usize,
+        row_alignment: usize,
+        plane_alignment: usize,
+        extended_pixels: (usize, usize, usize, usize),
+    ) -> Result<PlaneLayout, CVError> {
+        let layout = match pixel_format.layout() {
+            Some(layout) if !layout.compressed => layout,
+            _ => return Err(CVError::InvalidPixelFormat),
+        };
+        if width == 0 || height == 0 {
+            return Err(CVError::InvalidSize);
+        }
+
+        let (left, right, top, bottom) = extended_pixels;
+        let aligned_width = round_up(width, layout.horizontal_alignment as usize).ok_or(CVError::InvalidSize)?;
+        let header_size = match planar_pixel_buffer_info(pixel_format) {
+            Some(PlanarPixelBufferInfo::YCbCrPlanar) => size_of::<CVPlanarPixelBufferInfo_YCbCrPlanar>(),
+            Some(PlanarPixelBufferInfo::YCbCrBiPlanar) => size_of::<CVPlanarPixelBufferInfo_YCbCrBiPlanar>(),
+            None => 0,
+        };
+
+        let mut planes = Vec::with_capacity(layout.planes.len());
+        let mut cursor = header_size;
+        for plane in layout.planes {
+            let horizontal_subsampling = plane.horizontal_subsampling as usize;
+            let vertical_subsampling = plane.vertical_subsampling as usize;
+            let block_width = plane.block_width as usize;
+            let block_height = plane.block_height as usize;
+            let bits_per_block = plane.bits_per_block as usize;
+
+            let plane_width = width.div_ceil(horizontal_subsampling);
+            let plane_height = height.div_ceil(vertical_subsampling);
+            let plane_left = round_up(left.div_ceil(horizontal_subsampling), block_width).ok_or(CVError::InvalidSize)?;
+            let plane_right = right.div_ceil(horizontal_subsampling);
+            let plane_top = round_up(top.div_ceil(vertical_subsampling), block_height).ok_or(CVError::InvalidSize)?;
+            let plane_bottom = bottom.div_ceil(vertical_subsampling);
+
+            let total_width = checked_sum(&[aligned_width.div_ceil(horizontal_subsampling), plane_left, plane_right])?;
+            let total_height = round_up(checked_sum(&[plane_height, plane_top, plane_bottom])?, block_height).ok_or(CVError::InvalidSize)?;
+            let row_bits = total_width.div_ceil(block_width).checked_mul(bits_per_block).ok_or(CVError::InvalidSize)?;
+            let bytes_per_row = round_up(row_bits.div_ceil(8), row_alignment).ok_or(CVError::InvalidSize)?;
+            let size = bytes_per_row.checked_mul(total_height / block_height).ok_or(CVError::InvalidSize)?;
+
+            let start = round_up(cursor, plane_alignment).ok_or(CVError::InvalidSize)?;
+            let top_size = (plane_top / block_height).checked_mul(bytes_per_row).ok_or(CVError::InvalidSize)?;
+            let left_size = (plane_left / block_width).checked_mul(bits_per_block).ok_or(CVError::InvalidSize)? / 8;
+            let offset = checked_sum(&[start, top_size, left_size])?;
+            cursor = start.checked_add(size).ok_or(CVError::InvalidSize)?;
+
+            let geometry = PlaneGeometry { width: plane_width, height: plane_height, bytes_per_row, offset, start, size };
+            // The header stores offsets as i32 and row bytes as u32, so reject layouts it cannot describe
+            if header_size > 0 {
+                Self::component_info(&geometry)?;
+            }
+            planes.push(geometry);
+        }
+
+        Ok(PlaneLayout { pixel_format, width, height, extended_pixels, header_size, planes, data_size: cursor })
+    }
+
+    #[inline]
+    pub fn is_planar(&self) -> bool {
+        self.planes.len() > 1
+    }
+
+    #[inline]
+    pub fn plane_count(&self) -> usize {
+        if self.is_planar() {
+            self.planes.len()
+        } else {
+            0
+        }
+    }
+
+    #[inline]
+    pub fn plane_widths(&self) -> Vec<usize> {
+        self.planes.iter().map(|plane| plane.width).collect()
+    }
+
+    #[inline]
+    pub fn plane_heights(&self) -> Vec<usize> {
+        self.planes.iter().map(|plane| plane.height).collect()
+    }
+
+    #[inline]
+    pub fn plane_bytes_per_row(&self) -> Vec<usize> {
+        self.planes.iter().map(|plane| plane.bytes_per_row).collect()
+    }
+
+    // The address of the first non-extended pixel of every plane, for a buffer of `data_size` bytes starting at `data`
+    #[inline]
+    pub fn plane_base_addresses(&self, data: *mut c_void) -> Vec<*mut c_void> {
+        self.planes
+            .iter()
+            .map(|plane| (data as *mut u8).wrapping_add(plane.offset) as *mut c_void)
+            .collect()
+    }
+
+    #[inline]
+    fn component_info(plane: &PlaneGeometry) -> Result<CVPlanarComponentInfo, CVError> {
+        let offset = i32::try_from(plane.offset).map_err(|_| CVError::InvalidSize)?;
+        let row_bytes = u32::try_from(plane.bytes_per_row).map_err(|_| CVError::InvalidSize)?;
+        Ok(CVPlanarComponentInfo { offset: offset.to_be(), rowBytes: row_bytes.to_be() })
+    }
+
+    pub fn ycbcr_planar_info(&self) -> Option<CVPlanarPixelBufferInfo_YCbCrPlanar> {
+        if planar_pixel_buffer_info(self.pixel_format) != Some(PlanarPixelBufferInfo::YCbCrPlanar) {
+            return None;
+        }
+        Some(CVPlanarPixelBufferInfo_YCbCrPlanar {
+            componentInfoY: Self::component_info(&self.planes[0]).ok()?,
+            componentInfoCb: Self::component_info(&self.planes[1]).ok()?,
+            componentInfoCr: Self::component_info(&self.planes[2]).ok()?,
+        })
+    }
+
+    pub fn ycbcr_biplanar_info(&self) -> Option<CVPlanarPixelBufferInfo_YCbCrBiPlanar> {
+        if planar_pixel_buffer_info(self.pixel_format) != Some(PlanarPixelBufferInfo::YCbCrBiPlanar) {
+            return None;
+        }
+        Some(CVPlanarPixelBufferInfo_YCbCrBiPlanar {
+            componentInfoY: Self::component_info(&self.planes[0]).ok()?,
+            componentInfoCbCr: Self::component_info(&self.planes[1]).ok()?,
+        })
+    }
+
+    // Writes the big-endian CVPlanarPixelBufferInfo header, if the format has one, to the start of `data`
+    pub fn write_planar_info(&self, data: &mut [u8]) -> Result<(), CVError> {
+        if data.len() < self.header_size {
+            return Err(CVError::InvalidSize);
+        }
+        let info: Vec<CVPlanarComponentInfo> = match planar_pixel_buffer_info(self.pixel_format) {
+            Some(_) => self.planes.iter().map(Self::component_info).collect::<Result<_, _>>()?,
+            None => return Ok(()),
+        };
+        for (component, bytes) in info.iter().zip(data.chunks_exact_mut(size_of::<CVPlanarComponentInfo>())) {
+            bytes[..4].copy_from_slice(&i32::from_be(component.offset).to_be_bytes());
+            bytes[4..].copy_from_slice(&u32::from_be(component.rowBytes).to_be_bytes());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn biplanar_layout() {
+        let layout = PlaneLayout::compute(PixelFormat::_420YpCbCr8BiPlanarVideoRange, 1920, 1080, 64, 16, (0, 0, 0, 0)).unwrap();
+        assert_eq!(layout.header_size, 16);
+        assert_eq!(layout.plane_widths(), [1920, 960]);
+        assert_eq!(layout.plane_heights(), [1080, 540]);
+        assert_eq!(layout.plane_bytes_per_row(), [1920, 1920]);
+        assert_eq!(layout.planes[1].start, 16 + 1920 * 1080);
+        assert_eq!(layout.data_size, 16 + 1920 * 1080 + 1920 * 540);
+    }
+
+    #[test]
+    fn overflow() {
+        let no_extension = (0, 0, 0, 0);
+        for &(width, height, extended_pixels) in &[
+            (usize::MAX, 1, no_extension),
+            (usize::MAX / 4, 1, no_extension),
+            (1 << 20, usize::MAX / 4, no_extension),
+            (16, 16, (usize::MAX, 0, 0, 0)),
+            (16, 16, (0, usize::MAX - 8, 0, 0)),
+            (16, 16, (0, 0, usize::MAX / 2, usize::MAX / 2)),
+        ] {
+            let layout = PlaneLayout::compute(PixelFormat::_32BGRA, width, height, 16, 16, extended_pixels);
+            assert_eq!(layout, Err(CVError::InvalidSize));
+        }
+        let layout = PlaneLayout::compute(PixelFormat::_420YpCbCr8BiPlanarVideoRange, 1 << 16, 1 << 16, usize::MAX, 16, no_extension);
+        assert_eq!(layout, Err(CVError::InvalidSize));
+    }
+
+    #[test]
+    fn extended_pixels() {
+        let layout = PlaneLayout::compute(PixelFormat::_420YpCbCr8BiPlanarVideoRange, 64, 48, 16, 64, (8, 8, 16, 16)).unwrap();
+        assert_eq!(
+            layout.planes,
+            [
+                PlaneGeometry { width: 64, height: 48, bytes_per_row: 80, offset: 64 + 16 * 80 + 8, start: 64, size: 80 * 80 },
+                PlaneGeometry { width: 32, height: 24, bytes_per_row: 80, offset: 6464 + 8 * 80 + 8, start: 6464, size: 80 * 40 },
+            ]
+        );
+        assert_eq!(layout.data_size, 6464 + 80 * 40);
+    }
+
+    #[test]
+    fn alignment() {
+        let layout = PlaneLayout::compute(PixelFormat::_420YpCbCr8PlanarFullRange, 100, 50, 64, 4096, (0, 0, 0, 0)).unwrap();
+        assert_eq!(layout.header_size, 24);
+        assert_eq!(layout.plane_bytes_per_row(), [128, 64, 64]);
+        assert_eq!(layout.planes.iter().map(|plane| plane.start).collect::<Vec<_>>(), [4096, 12288, 16384]);
+        assert_eq!(layout.planes.iter().map(|plane| plane.offset).collect::<Vec<_>>(), [4096, 12288, 16384]);
+        assert_eq!(layout.data_size, 16384 + 64 * 25);
+
+        // v210 rows hold whole groups of 48 pixels, 6 pixels to every 16 bytes
+        let layout = PlaneLayout::compute(PixelFormat::_422YpCbCr10, 100, 2, 16, 16, (0, 0, 0, 0)).unwrap();
+        assert_eq!(layout.plane_widths(), [100]);
+        assert_eq!(layout.plane_bytes_per_row(), [384]);
+        let layout = PlaneLayout::compute(PixelFormat::_422YpCbCr10, 1280, 720, 16, 16, (0, 0, 0, 0)).unwrap();
+        assert_eq!(layout.plane_bytes_per_row(), [3456]);
+        assert_eq!(layout.data_size, 3456 * 720);
+    }
+
+    #[test]
+    fn planar_info() {
+        let layout = PlaneLayout::compute(PixelFormat::_420YpCbCr8PlanarFullRange, 100, 50, 64, 4096, (0, 0, 0, 0)).unwrap();
+        let mut data = [0xAAu8; 32];
+        layout.write_planar_info(&mut data).unwrap();
+        assert_eq!(
+            data[..24],
+            [
+                0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x80, /* Y */
+                0x00, 0x00, 0x30, 0x00, 0x00, 0x00, 0x00, 0x40, /* Cb */
+                0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00, 0x40, /* Cr */
+            ]
+        );
+        assert_eq!(data[24..], [0xAA; 8]);
+        let info = layout.ycbcr_planar_info().unwrap();
+        assert_eq!((i32::from_be(info.componentInfoCb.offset), u32::from_be(info.componentInfoCb.rowBytes)), (12288, 64));
+        assert!(layout.ycbcr_biplanar_info().is_none());
+        assert_eq!(layout.write_planar_info(&mut data[..23]), Err(CVError::InvalidSize));
+
+        let layout = PlaneLayout::compute(PixelFormat::_32BGRA, 16, 16, 16, 16, (0, 0, 0, 0)).unwrap();
+        let mut data = [0xAAu8; 16];
+        layout.write_planar_info(&mut data).unwrap();
+        assert_eq!(data, [0xAA; 16]);
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn planar_info_range() {
+        // Offsets past i32::MAX or row bytes past u32::MAX cannot be described by the header
+        let layout = PlaneLayout::compute(PixelFormat::_420YpCbCr8BiPlanarVideoRange, 1 << 16, 1 << 16, 16, 16, (0, 0, 0, 0));
+        assert_eq!(layout, Err(CVError::InvalidSize));
+        let layout = PlaneLayout::compute(PixelFormat::_420YpCbCr8BiPlanarVideoRange, 1 << 33, 2, 16, 16, (0, 0, 0, 0));
+        assert_eq!(layout, Err(CVError::InvalidSize));
+        let layout = PlaneLayout::compute(PixelFormat::_32BGRA, 1 << 31, 1, 16, 16, (0, 0, 0, 0)).unwrap();
+        assert_eq!(layout.plane_bytes_per_row(), [1 << 33]);
+    }
+}
@@ -0,0 +1,170 @@
+use core_foundation::{
+    base::{CFType, TCFType},
+    dictionary::{CFDictionary, CFDictionaryRef},
+    number::CFNumber,
+    string::CFString,
+};
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+use core_graphics::geometry::CGRect;
+
+use crate::{
+    buffer::kCVAttachmentMode_ShouldPropagate,
+    image_buffer::{CVImageBuffer, CVImageBufferKeys},
+    r#return::CVError,
+};
+
+// Keys of the dictionary representation of a CGRect, as made by CGRectCreateDictionaryRepresentation
+const X_KEY: &str = "X";
+const Y_KEY: &str = "Y";
+const WIDTH_KEY: &str = "Width";
+const HEIGHT_KEY: &str = "Height";
+
+// Rectangle in pixels of the image buffer, stored under kCVImageBufferRegionOfInterestKey in the dictionary
+// representation of a CGRect
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RegionOfInterest {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl RegionOfInterest {
+    #[inline]
+    pub fn new(x: f64, y: f64, width: f64, height: f64) -> Self {
+        RegionOfInterest { x, y, width, height }
+    }
+
+    pub fn from_dictionary(dictionary: &CFDictionary<CFString, CFType>) -> Result<Self, CVError> {
+        let find = |key: &'static str| {
+            dictionary
+                .find(CFString::from_static_string(key))
+                .and_then(|value| value.downcast::<CFNumber>())
+                .and_then(|number| number.to_f64())
+        };
+        let region_of_interest = RegionOfInterest {
+            x: find(X_KEY).ok_or(CVError::InvalidArgument)?,
+            y: find(Y_KEY).ok_or(CVError::InvalidArgument)?,
+            width: find(WIDTH_KEY).ok_or(CVError::InvalidArgument)?,
+            height: find(HEIGHT_KEY).ok_or(CVError::InvalidArgument)?,
+        };
+        region_of_interest.validate()?;
+        Ok(region_of_interest)
+    }
+
+    pub fn to_dictionary(&self) -> Result<CFDictionary<CFString, CFType>, CVError> {
+        self.validate()?;
+        let pairs: Vec<(CFString, CFType)> = [(X_KEY, self.x), (Y_KEY, self.y), (WIDTH_KEY, self.width), (HEIGHT_KEY, self.height)]
+            .iter()
+            .map(|&(key, value)| (CFString::from_static_string(key), CFNumber::from(value).as_CFType()))
+            .collect();
+        Ok(CFDictionary::from_CFType_pairs(&pairs))
+    }
+
+    // Every value must be finite and the size must not be negative
+    pub fn validate(&self) -> Result<(), CVError> {
+        let finite = [self.x, self.y, self.width, self.height].iter().all(|value| value.is_finite());
+        if !finite || self.width < 0.0 || self.height < 0.0 {
+            return Err(CVError::InvalidArgument);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+impl From<CGRect> for RegionOfInterest {
+    fn from(rect: CGRect) -> Self {
+        RegionOfInterest::new(rect.origin.x, rect.origin.y, rect.size.width, rect.size.height)
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+impl From<RegionOfInterest> for CGRect {
+    fn from(region_of_interest: RegionOfInterest) -> Self {
+        CGRect::new(region_of_interest.x, region_of_interest.y, region_of_interest.width, region_of_interest.height)
+    }
+}
+
+impl CVImageBuffer {
+    // None when the attachment is missing or malformed
+    pub fn get_region_of_interest(&self) -> Option<RegionOfInterest> {
+        let mut attachment_mode = kCVAttachmentMode_ShouldPropagate;
+        let value = self
+            .as_buffer()
+            .get_attachment(&CFString::from(CVImageBufferKeys::RegionOfInterest), &mut attachment_mode)?;
+        if !value.instance_of::<CFDictionary>() {
+            return None;
+        }
+        let dictionary: CFDictionary<CFString, CFType> = unsafe { CFDictionary::wrap_under_get_rule(value.as_CFTypeRef() as CFDictionaryRef) };
+        RegionOfInterest::from_dictionary(&dictionary).ok()
+    }
+
+    pub fn set_region_of_interest(&self, region_of_interest: &RegionOfInterest) -> Result<(), CVError> {
+        self.as_buffer().set_attachment(
+            &CFString::from(CVImageBufferKeys::RegionOfInterest),
+            &region_of_interest.to_dictionary()?.as_CFType(),
+            kCVAttachmentMode_ShouldPropagate,
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate() {
+        assert_eq!(RegionOfInterest::new(-8.0, 4.5, 0.0, 100.0).validate(), Ok(()));
+        assert_eq!(RegionOfInterest::new(0.0, 0.0, -1.0, 100.0).validate(), Err(CVError::InvalidArgument));
+        assert_eq!(RegionOfInterest::new(0.0, 0.0, 100.0, -1.0).validate(), Err(CVError::InvalidArgument));
+        assert_eq!(RegionOfInterest::new(f64::NAN, 0.0, 100.0, 100.0).validate(), Err(CVError::InvalidArgument));
+        assert_eq!(RegionOfInterest::new(0.0, 0.0, f64::INFINITY, 100.0).validate(), Err(CVError::InvalidArgument));
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    #[test]
+    fn dictionary_round_trip() {
+        let region_of_interest = RegionOfInterest::new(16.0, 8.5, 640.0, 360.25);
+        let dictionary = region_of_interest.to_dictionary().unwrap();
+        assert_eq!(dictionary.len(), 4);
+        let number = |key: &'static str| dictionary.find(CFString::from_static_string(key)).unwrap().downcast::<CFNumber>().unwrap().to_f64();
+        assert_eq!(number(HEIGHT_KEY), Some(360.25));
+        assert_eq!(RegionOfInterest::from_dictionary(&dictionary), Ok(region_of_interest));
+        assert_eq!(RegionOfInterest::new(0.0, 0.0, -1.0, 1.0).to_dictionary().err(), Some(CVError::InvalidArgument));
+
+        // CoreGraphics reads and writes the same representation
+        let rect = CGRect::from(region_of_interest);
+        assert_eq!(RegionOfInterest::from(rect), region_of_interest);
+        let dictionary: CFDictionary<CFString, CFType> =
+            unsafe { CFDictionary::wrap_under_create_rule(core_graphics::geometry::CGRectCreateDictionaryRepresentation(rect)) };
+        assert_eq!(RegionOfInterest::from_dictionary(&dictionary), Ok(region_of_interest));
+        let dictionary = region_of_interest.to_dictionary().unwrap();
+        assert_eq!(CGRect::from_dict_representation(&dictionary.to_untyped()), Some(rect));
+
+        // Every key is required and must hold a number
+        let pairs: Vec<(CFString, CFType)> = [(X_KEY, 0.0), (Y_KEY, 0.0), (WIDTH_KEY, 1.0)]
+            .iter()
+            .map(|&(key, value)| (CFString::from_static_string(key), CFNumber::from(value).as_CFType()))
+            .collect();
+        assert_eq!(RegionOfInterest::from_dictionary(&CFDictionary::from_CFType_pairs(&pairs)), Err(CVError::InvalidArgument));
+        let mut pairs = pairs;
+        pairs.push((CFString::from_static_string(HEIGHT_KEY), CFString::from_static_string("1").as_CFType()));
+        assert_eq!(RegionOfInterest::from_dictionary(&CFDictionary::from_CFType_pairs(&pairs)), Err(CVError::InvalidArgument));
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    #[test]
+    fn attachments() {
+        use crate::{
+            image_buffer::TCVImageBuffer,
+            pixel_buffer::{kCVPixelFormatType_32BGRA, CVPixelBuffer},
+        };
+
+        let pixel_buffer = CVPixelBuffer::new(kCVPixelFormatType_32BGRA, 720, 486, None).unwrap();
+        let image_buffer = pixel_buffer.as_image_buffer();
+        assert_eq!(image_buffer.get_region_of_interest(), None);
+        image_buffer.set_region_of_interest(&RegionOfInterest::new(8.0, 3.0, 704.0, 480.0)).unwrap();
+        assert_eq!(image_buffer.get_region_of_interest(), Some(RegionOfInterest::new(8.0, 3.0, 704.0, 480.0)));
+    }
+}
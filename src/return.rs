@@ -1,3 +1,8 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
 pub type CVReturn = i32;
 
 pub const kCVReturnSuccess: CVReturn = 0;
@@ -27,3 +32,174 @@ pub const kCVReturnInvalidPoolAttributes: CVReturn = -6691;
 pub const kCVReturnRetry: CVReturn = -6692;
 
 pub const kCVReturnLast: CVReturn = -6699;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CVError {
+    Error,
+    InvalidArgument,
+    AllocationFailed,
+    Unsupported,
+    InvalidDisplay,
+    DisplayLinkAlreadyRunning,
+    DisplayLinkNotRunning,
+    DisplayLinkCallbacksNotSet,
+    InvalidPixelFormat,
+    InvalidSize,
+    InvalidPixelBufferAttributes,
+    PixelBufferNotOpenGLCompatible,
+    PixelBufferNotMetalCompatible,
+    WouldExceedAllocationThreshold,
+    PoolAllocationFailed,
+    InvalidPoolAttributes,
+    Retry,
+    Unknown(CVReturn),
+}
+
+impl CVError {
+    // Maps kCVReturnSuccess to Ok and every other code to the matching error
+    #[inline]
+    pub fn result(code: CVReturn) -> Result<(), CVError> {
+        if code == kCVReturnSuccess {
+            Ok(())
+        } else {
+            Err(code.into())
+        }
+    }
+
+    #[inline]
+    pub fn code(&self) -> CVReturn {
+        match *self {
+            CVError::Error => kCVReturnError,
+            CVError::InvalidArgument => kCVReturnInvalidArgument,
+            CVError::AllocationFailed => kCVReturnAllocationFailed,
+            CVError::Unsupported => kCVReturnUnsupported,
+            CVError::InvalidDisplay => kCVReturnInvalidDisplay,
+            CVError::DisplayLinkAlreadyRunning => kCVReturnDisplayLinkAlreadyRunning,
+            CVError::DisplayLinkNotRunning => kCVReturnDisplayLinkNotRunning,
+            CVError::DisplayLinkCallbacksNotSet => kCVReturnDisplayLinkCallbacksNotSet,
+            CVError::InvalidPixelFormat => kCVReturnInvalidPixelFormat,
+            CVError::InvalidSize => kCVReturnInvalidSize,
+            CVError::InvalidPixelBufferAttributes => kCVReturnInvalidPixelBufferAttributes,
+            CVError::PixelBufferNotOpenGLCompatible => kCVReturnPixelBufferNotOpenGLCompatible,
+            CVError::PixelBufferNotMetalCompatible => kCVReturnPixelBufferNotMetalCompatible,
+            CVError::WouldExceedAllocationThreshold => kCVReturnWouldExceedAllocationThreshold,
+            CVError::PoolAllocationFailed => kCVReturnPoolAllocationFailed,
+            CVError::InvalidPoolAttributes => kCVReturnInvalidPoolAttributes,
+            CVError::Retry => kCVReturnRetry,
+            CVError::Unknown(code) => code,
+        }
+    }
+}
+
+// kCVReturnSuccess is not an error, so it converts to Unknown(0); use CVError::result to check a status code
+impl From<CVReturn> for CVError {
+    fn from(code: CVReturn) -> CVError {
+        match code {
+            kCVReturnError => CVError::Error,
+            kCVReturnInvalidArgument => CVError::InvalidArgument,
+            kCVReturnAllocationFailed => CVError::AllocationFailed,
+            kCVReturnUnsupported => CVError::Unsupported,
+            kCVReturnInvalidDisplay => CVError::InvalidDisplay,
+            kCVReturnDisplayLinkAlreadyRunning => CVError::DisplayLinkAlreadyRunning,
+            kCVReturnDisplayLinkNotRunning => CVError::DisplayLinkNotRunning,
+            kCVReturnDisplayLinkCallbacksNotSet => CVError::DisplayLinkCallbacksNotSet,
+            kCVReturnInvalidPixelFormat => CVError::InvalidPixelFormat,
+            kCVReturnInvalidSize => CVError::InvalidSize,
+            kCVReturnInvalidPixelBufferAttributes => CVError::InvalidPixelBufferAttributes,
+            kCVReturnPixelBufferNotOpenGLCompatible => CVError::PixelBufferNotOpenGLCompatible,
+            kCVReturnPixelBufferNotMetalCompatible => CVError::PixelBufferNotMetalCompatible,
+            kCVReturnWouldExceedAllocationThreshold => CVError::WouldExceedAllocationThreshold,
+            kCVReturnPoolAllocationFailed => CVError::PoolAllocationFailed,
+            kCVReturnInvalidPoolAttributes => CVError::InvalidPoolAttributes,
+            kCVReturnRetry => CVError::Retry,
+            _ => CVError::Unknown(code),
+        }
+    }
+}
+
+impl From<CVError> for CVReturn {
+    #[inline]
+    fn from(error: CVError) -> CVReturn {
+        error.code()
+    }
+}
+
+impl Display for CVError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let message = match *self {
+            CVError::Error => "an otherwise undefined error occurred",
+            CVError::InvalidArgument => "at least one of the arguments passed in is not valid",
+            CVError::AllocationFailed => "the allocation for a buffer or buffer pool failed",
+            CVError::Unsupported => "the operation or feature is not supported",
+            CVError::InvalidDisplay => "the display specified when creating a display link is invalid",
+            CVError::DisplayLinkAlreadyRunning => "the display link is already started and running",
+            CVError::DisplayLinkNotRunning => "the display link has not been started",
+            CVError::DisplayLinkCallbacksNotSet => "the output callback is not set",
+            CVError::InvalidPixelFormat => "the buffer does not support the specified pixel format",
+            CVError::InvalidSize => "the buffer cannot support the requested buffer size",
+            CVError::InvalidPixelBufferAttributes => "a buffer cannot be created with the specified attributes",
+            CVError::PixelBufferNotOpenGLCompatible => "the pixel buffer is not compatible with OpenGL",
+            CVError::PixelBufferNotMetalCompatible => "the pixel buffer is not compatible with Metal",
+            CVError::WouldExceedAllocationThreshold => "allocating the buffer would exceed the pool's allocation threshold",
+            CVError::PoolAllocationFailed => "the allocation for the buffer pool failed",
+            CVError::InvalidPoolAttributes => "a buffer pool cannot be created with the specified attributes",
+            CVError::Retry => "a scan hasn't completely traversed the buffer pool due to a concurrent operation",
+            CVError::Unknown(code) => return write!(f, "unknown CoreVideo error ({})", code),
+        };
+        write!(f, "{} ({})", message, self.code())
+    }
+}
+
+impl Error for CVError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODES: [CVReturn; 17] = [
+        kCVReturnError,
+        kCVReturnInvalidArgument,
+        kCVReturnAllocationFailed,
+        kCVReturnUnsupported,
+        kCVReturnInvalidDisplay,
+        kCVReturnDisplayLinkAlreadyRunning,
+        kCVReturnDisplayLinkNotRunning,
+        kCVReturnDisplayLinkCallbacksNotSet,
+        kCVReturnInvalidPixelFormat,
+        kCVReturnInvalidSize,
+        kCVReturnInvalidPixelBufferAttributes,
+        kCVReturnPixelBufferNotOpenGLCompatible,
+        kCVReturnPixelBufferNotMetalCompatible,
+        kCVReturnWouldExceedAllocationThreshold,
+        kCVReturnPoolAllocationFailed,
+        kCVReturnInvalidPoolAttributes,
+        kCVReturnRetry,
+    ];
+
+    #[test]
+    fn codes() {
+        for &code in CODES.iter() {
+            let error = CVError::from(code);
+            assert!(!matches!(error, CVError::Unknown(_)), "{}", code);
+            assert_eq!(error.code(), code);
+            assert_eq!(CVReturn::from(error), code);
+            assert_eq!(CVError::result(code), Err(error));
+        }
+        assert_eq!(CVError::from(kCVReturnFirst), CVError::Error);
+        for &code in &[kCVReturnSuccess, kCVReturnLast, -6674, 1] {
+            assert_eq!(CVError::from(code), CVError::Unknown(code));
+            assert_eq!(CVError::Unknown(code).code(), code);
+        }
+        assert_eq!(CVError::result(kCVReturnSuccess), Ok(()));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(CVError::InvalidSize.to_string(), "the buffer cannot support the requested buffer size (-6681)");
+        assert_eq!(CVError::Retry.to_string(), "a scan hasn't completely traversed the buffer pool due to a concurrent operation (-6692)");
+        assert_eq!(CVError::Unknown(-6674).to_string(), "unknown CoreVideo error (-6674)");
+        for &code in CODES.iter() {
+            assert!(CVError::from(code).to_string().ends_with(&format!("({})", code)));
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use std::{convert::TryFrom, fmt};
+
+use crate::{
+    base::{CVSMPTETime, CVSMPTETimeFlags, CVSMPTETimeType},
+    r#return::CVError,
+};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+const KNOWN_FLAGS: u32 = CVSMPTETimeFlags::kCVSMPTETimeValid as u32 | CVSMPTETimeFlags::kCVSMPTETimeRunning as u32;
+
+impl TryFrom<u32> for CVSMPTETimeType {
+    type Error = CVError;
+
+    fn try_from(time_type: u32) -> Result<Self, Self::Error> {
+        match time_type {
+            0 => Ok(CVSMPTETimeType::kCVSMPTETimeType24),
+            1 => Ok(CVSMPTETimeType::kCVSMPTETimeType25),
+            2 => Ok(CVSMPTETimeType::kCVSMPTETimeType30Drop),
+            3 => Ok(CVSMPTETimeType::kCVSMPTETimeType30),
+            4 => Ok(CVSMPTETimeType::kCVSMPTETimeType2997),
+            5 => Ok(CVSMPTETimeType::kCVSMPTETimeType2997Drop),
+            6 => Ok(CVSMPTETimeType::kCVSMPTETimeType60),
+            7 => Ok(CVSMPTETimeType::kCVSMPTETimeType5994),
+            _ => Err(CVError::InvalidArgument),
+        }
+    }
+}
+
+impl CVSMPTETimeType {
+    // Frames counted per timecode second, which is the rounded frame rate
+    pub fn frames_per_second(self) -> i64 {
+        match self {
+            CVSMPTETimeType::kCVSMPTETimeType24 => 24,
+            CVSMPTETimeType::kCVSMPTETimeType25 => 25,
+            CVSMPTETimeType::kCVSMPTETimeType30Drop |
+            CVSMPTETimeType::kCVSMPTETimeType30 |
+            CVSMPTETimeType::kCVSMPTETimeType2997 |
+            CVSMPTETimeType::kCVSMPTETimeType2997Drop => 30,
+            CVSMPTETimeType::kCVSMPTETimeType60 | CVSMPTETimeType::kCVSMPTETimeType5994 => 60,
+        }
+    }
+
+    // CoreVideo has no separate 59.94 drop type, so kCVSMPTETimeType5994 is always counted with drop frames, four per
+    // minute, and written with ';' like 29.97 drop; non-drop 59.94 timecode cannot be represented
+    #[inline]
+    pub fn is_drop_frame(self) -> bool {
+        matches!(self, CVSMPTETimeType::kCVSMPTETimeType30Drop | CVSMPTETimeType::kCVSMPTETimeType2997Drop | CVSMPTETimeType::kCVSMPTETimeType5994)
+    }
+
+    // Frame numbers skipped at the start of every minute not divisible by ten
+    #[inline]
+    fn dropped_frames(self) -> i64 {
+        if self.is_drop_frame() {
+            self.frames_per_second() / 15
+        } else {
+            0
+        }
+    }
+
+    // Actual frame rate as a fraction
+    pub fn frame_rate(self) -> (i64, i64) {
+        match self {
+            CVSMPTETimeType::kCVSMPTETimeType2997 | CVSMPTETimeType::kCVSMPTETimeType2997Drop => (30000, 1001),
+            CVSMPTETimeType::kCVSMPTETimeType5994 => (60000, 1001),
+            time_type => (time_type.frames_per_second(), 1),
+        }
+    }
+
+    #[inline]
+    pub fn frames_per_day(self) -> i64 {
+        self.frames_per_second() * SECONDS_PER_DAY - self.dropped_frames() * 24 * 54
+    }
+}
+
+impl CVSMPTETime {
+    // Timecode of the frame `frame_count` frames after midnight, wrapping around every 24 hours
+    pub fn from_frame_count(frame_count: i64, time_type: CVSMPTETimeType) -> Self {
+        let (frames_per_second, dropped) = (time_type.frames_per_second(), time_type.dropped_frames());
+        let mut frame_count = frame_count.rem_euclid(time_type.frames_per_day());
+        if dropped > 0 {
+            let frames_per_minute = frames_per_second * 60 - dropped;
+            let frames_per_ten_minutes = frames_per_minute * 10 + dropped;
+            let (tens, remainder) = (frame_count / frames_per_ten_minutes, frame_count % frames_per_ten_minutes);
+            frame_count += dropped * 9 * tens;
+            if remainder >= dropped {
+                frame_count += dropped * ((remainder - dropped) / frames_per_minute);
+            }
+        }
+        let seconds = frame_count / frames_per_second;
+        CVSMPTETime {
+            subframes: 0,
+            subframeDivisor: 0,
+            counter: 0,
+            type_: time_type as u32,
+            flags: CVSMPTETimeFlags::kCVSMPTETimeValid as u32,
+            hours: (seconds / 3600) as i16,
+            minutes: (seconds / 60 % 60) as i16,
+            seconds: (seconds % 60) as i16,
+            frames: (frame_count % frames_per_second) as i16,
+        }
+    }
+
+    // Same as from_frame_count, with `subframe_count` counted in 1/`subframe_divisor` frames
+    pub fn from_subframe_count(subframe_count: i64, subframe_divisor: i16, time_type: CVSMPTETimeType) -> Result<Self, CVError> {
+        if subframe_divisor <= 0 {
+            return Err(CVError::InvalidArgument);
+        }
+        let divisor = subframe_divisor as i64;
+        let mut smpte_time = CVSMPTETime::from_frame_count(subframe_count.div_euclid(divisor), time_type);
+        smpte_time.subframes = subframe_count.rem_euclid(divisor) as i16;
+        smpte_time.subframeDivisor = subframe_divisor;
+        Ok(smpte_time)
+    }
+
+    pub fn time_type(&self) -> Result<CVSMPTETimeType, CVError> {
+        CVSMPTETimeType::try_from(self.type_)
+    }
+
+    #[inline]
+    pub fn is_valid(&self) -> bool {
+        self.flags & CVSMPTETimeFlags::kCVSMPTETimeValid as u32 != 0
+    }
+
+    #[inline]
+    pub fn is_running(&self) -> bool {
+        self.flags & CVSMPTETimeFlags::kCVSMPTETimeRunning as u32 != 0
+    }
+
+    // The valid flag must be set and no unknown one, every field must be in range, and drop frame timecodes must not
+    // name a dropped frame
+    pub fn validate(&self) -> Result<(), CVError> {
+        let time_type = self.time_type()?;
+        if !self.is_valid() || self.flags & !KNOWN_FLAGS != 0 {
+            return Err(CVError::InvalidArgument);
+        }
+        let in_range = (0..24).contains(&self.hours) &&
+            (0..60).contains(&self.minutes) &&
+            (0..60).contains(&self.seconds) &&
+            (0..time_type.frames_per_second()).contains(&(self.frames as i64));
+        let subframes_in_range = if self.subframeDivisor > 0 {
+            (0..self.subframeDivisor).contains(&self.subframes)
+        } else {
+            self.subframeDivisor == 0 && self.subframes == 0
+        };
+        let dropped = self.seconds == 0 && self.minutes % 10 != 0 && (self.frames as i64) < time_type.dropped_frames();
+        if !in_range || !subframes_in_range || dropped {
+            return Err(CVError::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    // Frames since midnight
+    pub fn to_frame_count(&self) -> Result<i64, CVError> {
+        self.validate()?;
+        let time_type = self.time_type()?;
+        let minutes = self.hours as i64 * 60 + self.minutes as i64;
+        let seconds = minutes * 60 + self.seconds as i64;
+        Ok(seconds * time_type.frames_per_second() + self.frames as i64 - time_type.dropped_frames() * (minutes - minutes / 10))
+    }
+
+    // Subframes since midnight, in 1/`subframe_divisor` frames; subframes of another divisor are rounded down
+    pub fn to_subframe_count(&self, subframe_divisor: i16) -> Result<i64, CVError> {
+        if subframe_divisor <= 0 {
+            return Err(CVError::InvalidArgument);
+        }
+        let subframes = if self.subframeDivisor > 0 { self.subframes as i64 * subframe_divisor as i64 / self.subframeDivisor as i64 } else { 0 };
+        Ok(self.to_frame_count()? * subframe_divisor as i64 + subframes)
+    }
+
+    // `frame_count` frames later, or earlier when negative, wrapping around midnight; subframes are kept
+    pub fn add_frames(&self, frame_count: i64) -> Result<Self, CVError> {
+        let time_type = self.time_type()?;
+        let mut smpte_time = CVSMPTETime::from_frame_count(self.to_frame_count()? + frame_count, time_type);
+        smpte_time.subframes = self.subframes;
+        smpte_time.subframeDivisor = self.subframeDivisor;
+        smpte_time.counter = self.counter;
+        smpte_time.flags = self.flags;
+        Ok(smpte_time)
+    }
+
+    // Sum of two timecodes of the same type, wrapping around midnight, in the subframes of `self`
+    pub fn try_add(&self, other: &CVSMPTETime) -> Result<Self, CVError> {
+        self.combine(other, 1)
+    }
+
+    // Difference of two timecodes of the same type, wrapping around midnight, in the subframes of `self`
+    pub fn try_sub(&self, other: &CVSMPTETime) -> Result<Self, CVError> {
+        self.combine(other, -1)
+    }
+
+    fn combine(&self, other: &CVSMPTETime, sign: i64) -> Result<Self, CVError> {
+        let time_type = self.time_type()?;
+        if other.time_type()? != time_type {
+            return Err(CVError::InvalidArgument);
+        }
+        if self.subframeDivisor <= 0 {
+            return Ok(CVSMPTETime::from_frame_count(self.to_frame_count()? + sign * other.to_frame_count()?, time_type));
+        }
+        let subframe_count = self.to_subframe_count(self.subframeDivisor)? + sign * other.to_subframe_count(self.subframeDivisor)?;
+        let subframes_per_day = time_type.frames_per_day() * self.subframeDivisor as i64;
+        CVSMPTETime::from_subframe_count(subframe_count.rem_euclid(subframes_per_day), self.subframeDivisor, time_type)
+    }
+
+    // "HH:MM:SS:FF", or "HH:MM:SS;FF" for drop frame types, among them 59.94; "HH:MM:SS.FF" is also accepted
+    pub fn parse(text: &str, time_type: CVSMPTETimeType) -> Result<Self, CVError> {
+        let is_separator = |c: char| c == ':' || c == ';' || c == '.';
+        let text = text.trim();
+        let fields: Vec<&str> = text.split(is_separator).collect();
+        let drop_frame_separator = text.rfind(is_separator).and_then(|index| text[index..].chars().next()) == Some(';');
+        if fields.len() != 4 || (drop_frame_separator && !time_type.is_drop_frame()) {
+            return Err(CVError::InvalidArgument);
+        }
+        let mut values = [0i16; 4];
+        for (value, field) in values.iter_mut().zip(&fields) {
+            if field.is_empty() || !field.bytes().all(|byte| byte.is_ascii_digit()) {
+                return Err(CVError::InvalidArgument);
+            }
+            *value = field.parse().map_err(|_| CVError::InvalidArgument)?;
+        }
+        let mut smpte_time = CVSMPTETime::from_frame_count(0, time_type);
+        smpte_time.hours = values[0];
+        smpte_time.minutes = values[1];
+        smpte_time.seconds = values[2];
+        smpte_time.frames = values[3];
+        smpte_time.validate()?;
+        Ok(smpte_time)
+    }
+}
+
+impl fmt::Display for CVSMPTETime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let separator = if matches!(self.time_type(), Ok(time_type) if time_type.is_drop_frame()) { ';' } else { ':' };
+        write!(f, "{:02}:{:02}:{:02}{}{:02}", self.hours, self.minutes, self.seconds, separator, self.frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIME_TYPES: [CVSMPTETimeType; 8] = [
+        CVSMPTETimeType::kCVSMPTETimeType24,
+        CVSMPTETimeType::kCVSMPTETimeType25,
+        CVSMPTETimeType::kCVSMPTETimeType30Drop,
+        CVSMPTETimeType::kCVSMPTETimeType30,
+        CVSMPTETimeType::kCVSMPTETimeType2997,
+        CVSMPTETimeType::kCVSMPTETimeType2997Drop,
+        CVSMPTETimeType::kCVSMPTETimeType60,
+        CVSMPTETimeType::kCVSMPTETimeType5994,
+    ];
+
+    fn timecode(text: &str, time_type: CVSMPTETimeType) -> CVSMPTETime {
+        CVSMPTETime::parse(text, time_type).unwrap()
+    }
+
+    #[test]
+    fn frame_counts() {
+        for &time_type in &TIME_TYPES {
+            let frames_per_day = time_type.frames_per_day();
+            let mut previous = None;
+            for frame_count in 0..frames_per_day {
+                let smpte_time = CVSMPTETime::from_frame_count(frame_count, time_type);
+                assert_eq!(smpte_time.to_frame_count(), Ok(frame_count), "{} {:?}", smpte_time, time_type);
+                // Timecodes only ever increase within a day
+                let fields = (smpte_time.hours, smpte_time.minutes, smpte_time.seconds, smpte_time.frames);
+                assert!(previous < Some(fields));
+                previous = Some(fields);
+            }
+            assert_eq!(CVSMPTETime::from_frame_count(frames_per_day, time_type), CVSMPTETime::from_frame_count(0, time_type));
+            assert_eq!(CVSMPTETime::from_frame_count(-1, time_type), CVSMPTETime::from_frame_count(frames_per_day - 1, time_type));
+        }
+        assert_eq!(CVSMPTETimeType::kCVSMPTETimeType2997Drop.frames_per_day(), 2_589_408);
+        assert_eq!(CVSMPTETimeType::kCVSMPTETimeType5994.frames_per_day(), 5_178_816);
+    }
+
+    #[test]
+    fn text_round_trips() {
+        for &time_type in &TIME_TYPES {
+            for frame_count in (0..time_type.frames_per_day()).step_by(61) {
+                let smpte_time = CVSMPTETime::from_frame_count(frame_count, time_type);
+                assert_eq!(CVSMPTETime::parse(&smpte_time.to_string(), time_type), Ok(smpte_time));
+            }
+        }
+        let drop_frame = CVSMPTETimeType::kCVSMPTETimeType2997Drop;
+        assert_eq!(CVSMPTETime::from_frame_count(1800, drop_frame).to_string(), "00:01:00;02");
+        assert_eq!(CVSMPTETime::from_frame_count(1800, CVSMPTETimeType::kCVSMPTETimeType30).to_string(), "00:01:00:00");
+        assert_eq!(CVSMPTETime::parse(" 01:02:03.04 ", drop_frame), Ok(timecode("01:02:03;04", drop_frame)));
+        for text in ["00:00:00;00", "00:00:00", "00:00:00:00:00", "00:00:+0:00", "00::00:00", "24:00:00:00", "00:60:00:00", "00:00:00:30"] {
+            assert_eq!(CVSMPTETime::parse(text, CVSMPTETimeType::kCVSMPTETimeType30), Err(CVError::InvalidArgument), "{}", text);
+        }
+    }
+
+    #[test]
+    fn dropped_frames() {
+        for &time_type in &[CVSMPTETimeType::kCVSMPTETimeType30Drop, CVSMPTETimeType::kCVSMPTETimeType2997Drop] {
+            assert_eq!(CVSMPTETime::parse("00:01:00;00", time_type), Err(CVError::InvalidArgument));
+            assert_eq!(CVSMPTETime::parse("00:01:00;01", time_type), Err(CVError::InvalidArgument));
+            assert_eq!(timecode("00:01:00;02", time_type).to_frame_count(), Ok(1800));
+            assert_eq!(timecode("00:00:59;29", time_type).to_frame_count(), Ok(1799));
+            assert_eq!(timecode("00:10:00;00", time_type).to_frame_count(), Ok(17982));
+        }
+        let time_type = CVSMPTETimeType::kCVSMPTETimeType5994;
+        assert!(time_type.is_drop_frame());
+        assert_eq!(CVSMPTETime::parse("00:01:00;03", time_type), Err(CVError::InvalidArgument));
+        assert_eq!(timecode("00:01:00;04", time_type).to_frame_count(), Ok(3600));
+        assert!(!CVSMPTETimeType::kCVSMPTETimeType2997.is_drop_frame());
+        assert_eq!(timecode("00:01:00:00", CVSMPTETimeType::kCVSMPTETimeType2997).to_frame_count(), Ok(1800));
+
+        let mut smpte_time = timecode("00:02:00;02", CVSMPTETimeType::kCVSMPTETimeType2997Drop);
+        smpte_time.frames = 1;
+        assert_eq!(smpte_time.validate(), Err(CVError::InvalidArgument));
+    }
+
+    #[test]
+    fn arithmetic() {
+        let time_type = CVSMPTETimeType::kCVSMPTETimeType30;
+        let one_frame = timecode("00:00:00:01", time_type);
+        assert_eq!(timecode("23:59:59:29", time_type).try_add(&one_frame), Ok(timecode("00:00:00:00", time_type)));
+        assert_eq!(timecode("00:00:00:00", time_type).try_sub(&one_frame), Ok(timecode("23:59:59:29", time_type)));
+        assert_eq!(timecode("12:00:00:00", time_type).try_add(&timecode("13:00:00:05", time_type)), Ok(timecode("01:00:00:05", time_type)));
+
+        let drop_frame = CVSMPTETimeType::kCVSMPTETimeType2997Drop;
+        let one_frame = timecode("00:00:00;01", drop_frame);
+        assert_eq!(timecode("00:00:59;29", drop_frame).try_add(&one_frame), Ok(timecode("00:01:00;02", drop_frame)));
+        assert_eq!(timecode("00:00:00;00", drop_frame).try_sub(&one_frame), Ok(timecode("23:59:59;29", drop_frame)));
+        assert_eq!(timecode("00:01:00;02", drop_frame).add_frames(-1), Ok(timecode("00:00:59;29", drop_frame)));
+        assert_eq!(timecode("00:00:00;00", drop_frame).try_add(&timecode("00:00:00:00", time_type)), Err(CVError::InvalidArgument));
+
+        // Subframes are counted in the divisor of the left-hand side, 3/4 rounding down to one half
+        let half_frame = CVSMPTETime::from_subframe_count(1, 2, time_type).unwrap();
+        let quarter_frames = CVSMPTETime::from_subframe_count(3, 4, time_type).unwrap();
+        let sum = half_frame.try_add(&quarter_frames).unwrap();
+        assert_eq!((sum.frames, sum.subframes, sum.subframeDivisor), (1, 0, 2));
+        let difference = CVSMPTETime::from_subframe_count(0, 4, time_type).unwrap().try_sub(&quarter_frames).unwrap();
+        assert_eq!(difference.to_subframe_count(4), Ok(time_type.frames_per_day() * 4 - 3));
+    }
+}
@@ -0,0 +1,437 @@
+use std::{
+    cmp::Ordering,
+    convert::TryFrom,
+    hash::{Hash, Hasher},
+    ops::{Add, Sub},
+    time::Duration,
+};
+
+use crate::{
+    base::{CVTime, CVTimeFlags},
+    r#return::CVError,
+};
+
+const NANOSECONDS_PER_SECOND: i128 = 1_000_000_000;
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum CVTimeRoundingMethod {
+    #[default]
+    HalfAwayFromZero,
+    TowardZero,
+    AwayFromZero,
+    TowardPositiveInfinity,
+    TowardNegativeInfinity,
+}
+
+// Quotient of `numerator` by the positive `denominator`
+fn divide(numerator: i128, denominator: i128, rounding: CVTimeRoundingMethod) -> i128 {
+    let (quotient, remainder) = (numerator / denominator, numerator % denominator);
+    if remainder == 0 {
+        return quotient;
+    }
+    let away = if numerator < 0 { quotient - 1 } else { quotient + 1 };
+    match rounding {
+        CVTimeRoundingMethod::HalfAwayFromZero => {
+            if remainder.abs() * 2 >= denominator {
+                away
+            } else {
+                quotient
+            }
+        }
+        CVTimeRoundingMethod::TowardZero => quotient,
+        CVTimeRoundingMethod::AwayFromZero => away,
+        CVTimeRoundingMethod::TowardPositiveInfinity => quotient.max(away),
+        CVTimeRoundingMethod::TowardNegativeInfinity => quotient.min(away),
+    }
+}
+
+#[inline]
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    while b != 0 {
+        let remainder = a % b;
+        a = b;
+        b = remainder;
+    }
+    a.abs()
+}
+
+// Times without a positive timescale, such as kCVZeroTime, are read as zero
+impl CVTime {
+    pub const ZERO: CVTime = CVTime { timeValue: 0, timeScale: 0, flags: 0 };
+    pub const INDEFINITE: CVTime = CVTime { timeValue: 0, timeScale: 0, flags: CVTimeFlags::kCVTimeIsIndefinite as i32 };
+
+    #[inline]
+    pub const fn new(value: i64, scale: i32) -> Self {
+        CVTime { timeValue: value, timeScale: scale, flags: 0 }
+    }
+
+    #[inline]
+    pub fn is_indefinite(&self) -> bool {
+        self.flags & CVTimeFlags::kCVTimeIsIndefinite as i32 != 0
+    }
+
+    #[inline]
+    pub fn is_zero(&self) -> bool {
+        !self.is_indefinite() && (self.timeValue == 0 || self.timeScale <= 0)
+    }
+
+    // Value and timescale as a fraction with a positive denominator; None when indefinite
+    fn fraction(&self) -> Option<(i128, i128)> {
+        if self.is_indefinite() {
+            None
+        } else if self.timeScale <= 0 {
+            Some((0, 1))
+        } else {
+            Some((self.timeValue as i128, self.timeScale as i128))
+        }
+    }
+
+    fn from_fraction(numerator: i128, denominator: i128, scale: i32, rounding: CVTimeRoundingMethod) -> Option<CVTime> {
+        let value = divide(numerator.checked_mul(scale as i128)?, denominator, rounding);
+        Some(CVTime::new(i64::try_from(value).ok()?, scale))
+    }
+
+    // The same time in units of 1/`scale` seconds; None when `scale` is not positive or the value overflows
+    pub fn convert_scale(&self, scale: i32, rounding: CVTimeRoundingMethod) -> Option<CVTime> {
+        if scale <= 0 {
+            return None;
+        }
+        match self.fraction() {
+            Some((numerator, denominator)) => CVTime::from_fraction(numerator, denominator, scale, rounding),
+            None => Some(CVTime::INDEFINITE),
+        }
+    }
+
+    // Timescale both times can be expressed in: the least common multiple when it fits, otherwise the larger one, which
+    // may not hold both exactly
+    fn common_scale(&self, other: &CVTime) -> i32 {
+        match (self.timeScale.max(0) as i128, other.timeScale.max(0) as i128) {
+            (0, scale) | (scale, 0) => scale as i32,
+            (a, b) => i32::try_from(a / gcd(a, b) * b).unwrap_or_else(|_| a.max(b) as i32),
+        }
+    }
+
+    // Both times are converted to the common timescale, rounding half away from zero unless `exact`, in which case a
+    // time the timescale does not hold gives None
+    fn combine(&self, other: &CVTime, combine: fn(i64, i64) -> Option<i64>, exact: bool) -> Option<CVTime> {
+        if self.is_indefinite() || other.is_indefinite() {
+            return Some(CVTime::INDEFINITE);
+        }
+        let scale = self.common_scale(other);
+        if scale == 0 {
+            return Some(CVTime::ZERO);
+        }
+        let convert = |time: &CVTime| {
+            time.convert_scale(scale, CVTimeRoundingMethod::default())
+                .filter(|converted| !exact || converted == time)
+        };
+        Some(CVTime::new(combine(convert(self)?.timeValue, convert(other)?.timeValue)?, scale))
+    }
+
+    // Exact sum in the common timescale of both times, indefinite when either is; None on overflow, and when the
+    // timescales have no common multiple in an i32 that holds both times
+    #[inline]
+    pub fn checked_add(&self, other: &CVTime) -> Option<CVTime> {
+        self.combine(other, i64::checked_add, true)
+    }
+
+    // Exact difference in the common timescale of both times, indefinite when either is; None as for checked_add
+    #[inline]
+    pub fn checked_sub(&self, other: &CVTime) -> Option<CVTime> {
+        self.combine(other, i64::checked_sub, true)
+    }
+
+    // Infinity when indefinite
+    pub fn as_seconds_f64(&self) -> f64 {
+        match self.fraction() {
+            Some((numerator, denominator)) => numerator as f64 / denominator as f64,
+            None => f64::INFINITY,
+        }
+    }
+
+    // None when `seconds` is not finite or does not fit in `scale`
+    pub fn from_seconds_f64(seconds: f64, scale: i32) -> Option<CVTime> {
+        if !seconds.is_finite() || scale <= 0 {
+            return None;
+        }
+        let value = (seconds * scale as f64).round();
+        if value < i64::MIN as f64 || value >= i64::MAX as f64 {
+            return None;
+        }
+        Some(CVTime::new(value as i64, scale))
+    }
+
+    // None when the duration does not fit in `scale`
+    pub fn from_duration(duration: Duration, scale: i32, rounding: CVTimeRoundingMethod) -> Option<CVTime> {
+        if scale <= 0 {
+            return None;
+        }
+        CVTime::from_fraction(duration.as_nanos() as i128, NANOSECONDS_PER_SECOND, scale, rounding)
+    }
+}
+
+impl PartialEq for CVTime {
+    fn eq(&self, other: &CVTime) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for CVTime {}
+
+// Times are ordered by value, so 1/2 and 2/4 are equal; indefinite times are equal to each other and after every other
+impl Ord for CVTime {
+    fn cmp(&self, other: &CVTime) -> Ordering {
+        match (self.fraction(), other.fraction()) {
+            (Some((a, b)), Some((c, d))) => (a * d).cmp(&(c * b)),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+}
+
+impl PartialOrd for CVTime {
+    fn partial_cmp(&self, other: &CVTime) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Consistent with the ordering: equal fractions hash the same whatever their timescale
+impl Hash for CVTime {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.fraction()
+            .map(|(numerator, denominator)| {
+                let divisor = gcd(numerator, denominator);
+                (numerator / divisor, denominator / divisor)
+            })
+            .hash(state)
+    }
+}
+
+// Unlike checked_add and checked_sub, the operators round a time the common timescale does not hold half away from zero,
+// so they are exact whenever the timescales have a common multiple in an i32 and off by at most one unit of the larger
+// timescale otherwise; they only panic when the value overflows an i64
+impl Add for CVTime {
+    type Output = CVTime;
+
+    fn add(self, other: CVTime) -> CVTime {
+        self.combine(&other, i64::checked_add, false).expect("attempt to add with overflow")
+    }
+}
+
+impl Sub for CVTime {
+    type Output = CVTime;
+
+    fn sub(self, other: CVTime) -> CVTime {
+        self.combine(&other, i64::checked_sub, false).expect("attempt to subtract with overflow")
+    }
+}
+
+impl From<Duration> for CVTime {
+    // In nanoseconds when that fits, otherwise in microseconds, milliseconds and finally seconds
+    fn from(duration: Duration) -> Self {
+        [1_000_000_000, 1_000_000, 1_000, 1]
+            .iter()
+            .find_map(|&scale| CVTime::from_duration(duration, scale, CVTimeRoundingMethod::default()))
+            .unwrap_or(CVTime::INDEFINITE)
+    }
+}
+
+impl TryFrom<CVTime> for Duration {
+    type Error = CVError;
+
+    // Fails for indefinite and negative times; the nanoseconds are rounded half away from zero
+    fn try_from(time: CVTime) -> Result<Self, Self::Error> {
+        let (numerator, denominator) = time.fraction().ok_or(CVError::InvalidArgument)?;
+        if numerator < 0 {
+            return Err(CVError::InvalidArgument);
+        }
+        let nanoseconds = divide(numerator * NANOSECONDS_PER_SECOND, denominator, CVTimeRoundingMethod::default());
+        let seconds = u64::try_from(nanoseconds / NANOSECONDS_PER_SECOND).map_err(|_| CVError::InvalidArgument)?;
+        Ok(Duration::new(seconds, (nanoseconds % NANOSECONDS_PER_SECOND) as u32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+
+    use proptest::{prelude::*, sample::select};
+
+    use super::*;
+
+    const SCALES: [i32; 12] = [1, 2, 24, 25, 30, 600, 1000, 30000, 44100, 48000, 90000, 1_000_000_000];
+    const ROUNDING_METHODS: [CVTimeRoundingMethod; 5] = [
+        CVTimeRoundingMethod::HalfAwayFromZero,
+        CVTimeRoundingMethod::TowardZero,
+        CVTimeRoundingMethod::AwayFromZero,
+        CVTimeRoundingMethod::TowardPositiveInfinity,
+        CVTimeRoundingMethod::TowardNegativeInfinity,
+    ];
+
+    fn scale() -> impl Strategy<Value = i32> {
+        prop_oneof![select(&SCALES[..]), 1..=i32::MAX]
+    }
+
+    fn time() -> impl Strategy<Value = CVTime> {
+        (-1_000_000_000_000i64..1_000_000_000_000, scale()).prop_map(|(value, scale)| CVTime::new(value, scale))
+    }
+
+    fn hash(time: &CVTime) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        time.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn inexact_sums() {
+        let (a, b) = (CVTime::new(1, 1_000_000_007), CVTime::new(1, 999_999_937));
+        assert_eq!(a.checked_add(&b), None);
+        assert_eq!(a.checked_sub(&b), None);
+        assert_eq!(a.checked_add(&CVTime::new(0, 999_999_937)), Some(a));
+        assert_eq!(CVTime::new(1, 2) + CVTime::new(1, 3), CVTime::new(5, 6));
+        assert_eq!((CVTime::new(1, 2) + CVTime::new(1, 3)).timeScale, 6);
+
+        // The operators round into the larger timescale instead
+        let sum = CVTime::new(1, 90000) + CVTime::new(1, 1_000_000_000);
+        assert_eq!((sum.timeValue, sum.timeScale), (11112, 1_000_000_000));
+        let difference = CVTime::new(1, 90000) - CVTime::new(1, 1_000_000_000);
+        assert_eq!((difference.timeValue, difference.timeScale), (11110, 1_000_000_000));
+        assert_eq!(CVTime::new(1, 90000).checked_add(&CVTime::new(1, 1_000_000_000)), None);
+        let sum = a + b;
+        assert_eq!((sum.timeValue, sum.timeScale), (2, 1_000_000_007));
+        assert!((a - b).is_zero());
+    }
+
+    #[test]
+    #[should_panic(expected = "attempt to add with overflow")]
+    fn add_overflow() {
+        let _ = CVTime::new(i64::MAX, 1) + CVTime::new(1, 1);
+    }
+
+    proptest! {
+        #[test]
+        fn add_is_associative(a in time(), b in time(), c in time()) {
+            let lhs = a.checked_add(&b).and_then(|ab| ab.checked_add(&c));
+            let rhs = b.checked_add(&c).and_then(|bc| a.checked_add(&bc));
+            if let (Some(lhs), Some(rhs)) = (lhs, rhs) {
+                prop_assert_eq!(lhs, rhs);
+            }
+        }
+
+        #[test]
+        fn sub_inverts_add(a in time(), b in time()) {
+            if let Some(sum) = a.checked_add(&b) {
+                prop_assert_eq!(sum.checked_sub(&b), Some(a));
+                prop_assert_eq!(sum.checked_sub(&a), Some(b));
+            }
+            if let Some(difference) = a.checked_sub(&b) {
+                prop_assert_eq!(difference.checked_add(&b), Some(a));
+            }
+            prop_assert!(a.checked_sub(&a).unwrap().is_zero());
+        }
+
+        #[test]
+        fn common_scales_are_exact(value in any::<i32>(), a in select(&SCALES[..11]), b in select(&SCALES[..11])) {
+            let (a, b) = (CVTime::new(value as i64, a), CVTime::new(value as i64, b));
+            prop_assert_eq!(a + b - b, a);
+            prop_assert_eq!(a - b + b, a);
+            prop_assert_eq!(a.checked_add(&b), Some(a + b));
+            prop_assert_eq!(a.checked_sub(&b), Some(a - b));
+        }
+
+        #[test]
+        fn operators_round(a in any::<i32>(), a_scale in scale(), b in any::<i32>(), b_scale in scale()) {
+            let (a, b) = (CVTime::new(a as i64, a_scale), CVTime::new(b as i64, b_scale));
+            for (result, sign) in [(a + b, 1), (a - b, -1)] {
+                if let Some(exact) = if sign > 0 { a.checked_add(&b) } else { a.checked_sub(&b) } {
+                    prop_assert_eq!((result.timeValue, result.timeScale), (exact.timeValue, exact.timeScale));
+                }
+                // Each operand is off by at most half a unit of the result's timescale
+                let (a_scale, b_scale) = (a_scale as i128, b_scale as i128);
+                let exact = a.timeValue as i128 * b_scale + sign * b.timeValue as i128 * a_scale;
+                let error = result.timeValue as i128 * a_scale * b_scale - exact * result.timeScale as i128;
+                prop_assert!(error.abs() <= a_scale * b_scale);
+            }
+        }
+
+        #[test]
+        fn equal_fractions(time in time(), factor in 1i32..1000) {
+            prop_assume!(time.timeScale.checked_mul(factor).is_some());
+            let scaled = CVTime::new(time.timeValue * factor as i64, time.timeScale * factor);
+            prop_assert_eq!(scaled, time);
+            prop_assert_eq!(scaled.cmp(&time), Ordering::Equal);
+            prop_assert_eq!(hash(&scaled), hash(&time));
+        }
+
+        #[test]
+        fn ordering(a in time(), b in time()) {
+            let expected = (a.timeValue as i128 * b.timeScale as i128).cmp(&(b.timeValue as i128 * a.timeScale as i128));
+            prop_assert_eq!(a.cmp(&b), expected);
+            prop_assert_eq!(b.cmp(&a), expected.reverse());
+            prop_assert_eq!(a == b, expected == Ordering::Equal);
+            if a == b {
+                prop_assert_eq!(hash(&a), hash(&b));
+            }
+        }
+
+        #[test]
+        fn convert_scale(time in time(), scale in scale(), rounding in select(&ROUNDING_METHODS[..])) {
+            // The exact value is numerator / denominator in the new timescale
+            let (numerator, denominator) = (time.timeValue as i128 * scale as i128, time.timeScale as i128);
+            let converted = match time.convert_scale(scale, rounding) {
+                Some(converted) => converted,
+                None => {
+                    let quotient = numerator / denominator;
+                    prop_assert!(quotient >= i64::MAX as i128 || quotient <= i64::MIN as i128);
+                    return Ok(());
+                }
+            };
+            prop_assert_eq!(converted.timeScale, scale);
+            // How far the result is below the exact value
+            let remainder = numerator - converted.timeValue as i128 * denominator;
+            prop_assert!(remainder.abs() < denominator);
+            match rounding {
+                CVTimeRoundingMethod::HalfAwayFromZero => {
+                    prop_assert!(remainder.abs() * 2 <= denominator);
+                    if remainder.abs() * 2 == denominator {
+                        prop_assert!(remainder * numerator < 0);
+                    }
+                }
+                CVTimeRoundingMethod::TowardZero => prop_assert!(remainder * numerator >= 0),
+                CVTimeRoundingMethod::AwayFromZero => prop_assert!(remainder * numerator <= 0),
+                CVTimeRoundingMethod::TowardPositiveInfinity => prop_assert!(remainder <= 0),
+                CVTimeRoundingMethod::TowardNegativeInfinity => prop_assert!(remainder >= 0),
+            }
+        }
+
+        #[test]
+        fn duration_round_trips(seconds in 0u64..1_000_000_000, nanoseconds in 0u32..1_000_000_000) {
+            let duration = Duration::new(seconds, nanoseconds);
+            prop_assert_eq!(Duration::try_from(CVTime::from(duration)), Ok(duration));
+        }
+
+        #[test]
+        fn time_round_trips(value in 0i64..1_000_000_000_000, scale in 1i32..=1_000_000_000) {
+            let time = CVTime::new(value, scale);
+            let duration = Duration::try_from(time).unwrap();
+            prop_assert_eq!(CVTime::from_duration(duration, scale, CVTimeRoundingMethod::default()), Some(time));
+            prop_assert_eq!(Duration::try_from(CVTime::new(-value - 1, scale)), Err(CVError::InvalidArgument));
+        }
+
+        #[test]
+        fn indefinite_propagates(time in time(), scale in scale(), rounding in select(&ROUNDING_METHODS[..])) {
+            let indefinite = CVTime::INDEFINITE;
+            let results =
+                [time.checked_add(&indefinite), indefinite.checked_add(&time), time.checked_sub(&indefinite), indefinite.checked_sub(&time)];
+            for result in results {
+                prop_assert!(result.unwrap().is_indefinite());
+            }
+            prop_assert!(indefinite.convert_scale(scale, rounding).unwrap().is_indefinite());
+            prop_assert!(time < indefinite);
+            prop_assert_eq!(indefinite, CVTime { timeValue: time.timeValue, timeScale: scale, flags: indefinite.flags });
+            prop_assert_eq!(Duration::try_from(indefinite), Err(CVError::InvalidArgument));
+            prop_assert_eq!(indefinite.as_seconds_f64(), f64::INFINITY);
+        }
+    }
+}
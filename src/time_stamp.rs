@@ -0,0 +1,284 @@
+use crate::{
+    base::{CVSMPTETime, CVTime, CVTimeStamp, CVTimeStampFlags},
+    r#return::CVError,
+    time::CVTimeRoundingMethod,
+};
+
+const EMPTY_SMPTE_TIME: CVSMPTETime =
+    CVSMPTETime { subframes: 0, subframeDivisor: 0, counter: 0, type_: 0, flags: 0, hours: 0, minutes: 0, seconds: 0, frames: 0 };
+
+// Each getter is None unless the matching valid flag is set
+impl CVTimeStamp {
+    #[inline]
+    pub fn get_flags(&self) -> CVTimeStampFlags {
+        CVTimeStampFlags::from_bits_retain(self.flags)
+    }
+
+    #[inline]
+    fn has(&self, flags: CVTimeStampFlags) -> bool {
+        self.get_flags().contains(flags)
+    }
+
+    pub fn video_time(&self) -> Option<CVTime> {
+        if self.has(CVTimeStampFlags::kCVTimeStampVideoTimeValid) {
+            Some(CVTime::new(self.videoTime, self.videoTimeScale))
+        } else {
+            None
+        }
+    }
+
+    pub fn host_time(&self) -> Option<u64> {
+        if self.has(CVTimeStampFlags::kCVTimeStampHostTimeValid) {
+            Some(self.hostTime)
+        } else {
+            None
+        }
+    }
+
+    pub fn smpte_time(&self) -> Option<CVSMPTETime> {
+        if self.has(CVTimeStampFlags::kCVTimeStampSMPTETimeValid) {
+            Some(self.smpteTime)
+        } else {
+            None
+        }
+    }
+
+    pub fn rate_scalar(&self) -> Option<f64> {
+        if self.has(CVTimeStampFlags::kCVTimeStampRateScalarValid) {
+            Some(self.rateScalar)
+        } else {
+            None
+        }
+    }
+
+    // In the video timescale
+    pub fn refresh_period(&self) -> Option<CVTime> {
+        if self.has(CVTimeStampFlags::kCVTimeStampVideoRefreshPeriodValid) {
+            Some(CVTime::new(self.videoRefreshPeriod, self.videoTimeScale))
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub fn is_top_field(&self) -> bool {
+        self.has(CVTimeStampFlags::kCVTimeStampTopField)
+    }
+
+    #[inline]
+    pub fn is_bottom_field(&self) -> bool {
+        self.has(CVTimeStampFlags::kCVTimeStampBottomField)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CVTimeStampBuilder {
+    video_time: Option<CVTime>,
+    host_time: Option<u64>,
+    smpte_time: Option<CVSMPTETime>,
+    rate_scalar: Option<f64>,
+    refresh_period: Option<CVTime>,
+    field: CVTimeStampFlags,
+}
+
+impl CVTimeStampBuilder {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn video_time(mut self, video_time: CVTime) -> Self {
+        self.video_time = Some(video_time);
+        self
+    }
+
+    #[inline]
+    pub fn host_time(mut self, host_time: u64) -> Self {
+        self.host_time = Some(host_time);
+        self
+    }
+
+    #[inline]
+    pub fn smpte_time(mut self, smpte_time: CVSMPTETime) -> Self {
+        self.smpte_time = Some(smpte_time);
+        self
+    }
+
+    #[inline]
+    pub fn rate_scalar(mut self, rate_scalar: f64) -> Self {
+        self.rate_scalar = Some(rate_scalar);
+        self
+    }
+
+    #[inline]
+    pub fn refresh_period(mut self, refresh_period: CVTime) -> Self {
+        self.refresh_period = Some(refresh_period);
+        self
+    }
+
+    #[inline]
+    pub fn top_field(mut self) -> Self {
+        self.field = CVTimeStampFlags::kCVTimeStampTopField;
+        self
+    }
+
+    #[inline]
+    pub fn bottom_field(mut self) -> Self {
+        self.field = CVTimeStampFlags::kCVTimeStampBottomField;
+        self
+    }
+
+    // The refresh period is converted to the timescale of the video time, or gives the timescale when there is no
+    // video time; times without a positive timescale, such as CVTime::ZERO, are zero in any timescale. Fails for
+    // indefinite times or when the conversion overflows
+    pub fn build(&self) -> Result<CVTimeStamp, CVError> {
+        let mut flags = self.field;
+        let video_time_scale = [self.video_time, self.refresh_period]
+            .iter()
+            .flatten()
+            .map(|time| time.timeScale)
+            .find(|&scale| scale > 0)
+            .unwrap_or(0);
+        let in_video_time_scale = |time: CVTime| {
+            if time.is_indefinite() {
+                return Err(CVError::InvalidArgument);
+            }
+            if time.is_zero() {
+                return Ok(0);
+            }
+            time.convert_scale(video_time_scale, CVTimeRoundingMethod::default())
+                .map(|time| time.timeValue)
+                .ok_or(CVError::InvalidArgument)
+        };
+        let video_time = match self.video_time {
+            Some(video_time) => {
+                flags |= CVTimeStampFlags::kCVTimeStampVideoTimeValid;
+                in_video_time_scale(video_time)?
+            }
+            None => 0,
+        };
+        let video_refresh_period = match self.refresh_period {
+            Some(refresh_period) => {
+                flags |= CVTimeStampFlags::kCVTimeStampVideoRefreshPeriodValid;
+                in_video_time_scale(refresh_period)?
+            }
+            None => 0,
+        };
+        flags.set(CVTimeStampFlags::kCVTimeStampHostTimeValid, self.host_time.is_some());
+        flags.set(CVTimeStampFlags::kCVTimeStampSMPTETimeValid, self.smpte_time.is_some());
+        flags.set(CVTimeStampFlags::kCVTimeStampRateScalarValid, self.rate_scalar.is_some());
+        Ok(CVTimeStamp {
+            version: 0,
+            videoTimeScale: video_time_scale,
+            videoTime: video_time,
+            hostTime: self.host_time.unwrap_or(0),
+            rateScalar: self.rate_scalar.unwrap_or(0.0),
+            videoRefreshPeriod: video_refresh_period,
+            smpteTime: self.smpte_time.unwrap_or(EMPTY_SMPTE_TIME),
+            flags: flags.bits(),
+            reserved: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::CVSMPTETimeType;
+
+    #[test]
+    fn getters() {
+        let smpte_time = CVSMPTETime::from_frame_count(90, CVSMPTETimeType::kCVSMPTETimeType30);
+        let mut time_stamp = CVTimeStamp {
+            version: 0,
+            videoTimeScale: 600,
+            videoTime: 1200,
+            hostTime: 42,
+            rateScalar: 1.001,
+            videoRefreshPeriod: 20,
+            smpteTime: smpte_time,
+            flags: 0,
+            reserved: 0,
+        };
+        assert_eq!(time_stamp.get_flags(), CVTimeStampFlags::empty());
+        assert_eq!(time_stamp.video_time(), None);
+        assert_eq!(time_stamp.host_time(), None);
+        assert_eq!(time_stamp.smpte_time(), None);
+        assert_eq!(time_stamp.rate_scalar(), None);
+        assert_eq!(time_stamp.refresh_period(), None);
+        assert!(!time_stamp.is_top_field() && !time_stamp.is_bottom_field());
+
+        time_stamp.flags = (CVTimeStampFlags::kCVTimeStampVideoHostTimeValid |
+            CVTimeStampFlags::kCVTimeStampSMPTETimeValid |
+            CVTimeStampFlags::kCVTimeStampVideoRefreshPeriodValid |
+            CVTimeStampFlags::kCVTimeStampRateScalarValid |
+            CVTimeStampFlags::kCVTimeStampTopField)
+            .bits() |
+            1 << 32;
+        assert!(time_stamp
+            .get_flags()
+            .contains(CVTimeStampFlags::kCVTimeStampVideoTimeValid | CVTimeStampFlags::kCVTimeStampHostTimeValid));
+        assert_eq!(time_stamp.get_flags().bits(), time_stamp.flags);
+        assert_eq!(time_stamp.video_time().map(|time| (time.timeValue, time.timeScale)), Some((1200, 600)));
+        assert_eq!(time_stamp.host_time(), Some(42));
+        assert_eq!(time_stamp.smpte_time(), Some(smpte_time));
+        assert_eq!(time_stamp.rate_scalar(), Some(1.001));
+        assert_eq!(time_stamp.refresh_period().map(|time| (time.timeValue, time.timeScale)), Some((20, 600)));
+        assert!(time_stamp.is_top_field() && !time_stamp.is_bottom_field());
+    }
+
+    #[test]
+    fn builder() {
+        let time_stamp = CVTimeStampBuilder::new().build().unwrap();
+        assert_eq!(time_stamp.get_flags(), CVTimeStampFlags::empty());
+        assert_eq!((time_stamp.videoTimeScale, time_stamp.videoTime, time_stamp.hostTime), (0, 0, 0));
+
+        let smpte_time = CVSMPTETime::from_frame_count(1800, CVSMPTETimeType::kCVSMPTETimeType2997Drop);
+        let time_stamp = CVTimeStampBuilder::new()
+            .video_time(CVTime::new(3003, 30000))
+            .refresh_period(CVTime::new(1, 60))
+            .host_time(1234)
+            .smpte_time(smpte_time)
+            .rate_scalar(0.5)
+            .top_field()
+            .bottom_field()
+            .build()
+            .unwrap();
+        assert_eq!(
+            time_stamp.get_flags(),
+            CVTimeStampFlags::kCVTimeStampVideoHostTimeValid |
+                CVTimeStampFlags::kCVTimeStampVideoRefreshPeriodValid |
+                CVTimeStampFlags::kCVTimeStampSMPTETimeValid |
+                CVTimeStampFlags::kCVTimeStampRateScalarValid |
+                CVTimeStampFlags::kCVTimeStampBottomField
+        );
+        assert_eq!((time_stamp.videoTimeScale, time_stamp.videoTime, time_stamp.videoRefreshPeriod), (30000, 3003, 500));
+        assert_eq!(time_stamp.host_time(), Some(1234));
+        assert_eq!(time_stamp.smpte_time(), Some(smpte_time));
+        assert_eq!(time_stamp.rate_scalar(), Some(0.5));
+
+        // Without a video time the refresh period gives the timescale
+        let time_stamp = CVTimeStampBuilder::new().refresh_period(CVTime::new(1001, 60000)).build().unwrap();
+        assert_eq!((time_stamp.videoTimeScale, time_stamp.videoRefreshPeriod, time_stamp.video_time()), (60000, 1001, None));
+
+        // A zero time without a timescale is zero in the timescale of the refresh period
+        let time_stamp = CVTimeStampBuilder::new()
+            .video_time(CVTime::ZERO)
+            .refresh_period(CVTime::new(1, 24))
+            .build()
+            .unwrap();
+        assert_eq!((time_stamp.videoTimeScale, time_stamp.videoTime, time_stamp.videoRefreshPeriod), (24, 0, 1));
+        assert!(time_stamp.video_time().unwrap().is_zero());
+        let time_stamp = CVTimeStampBuilder::new().video_time(CVTime::ZERO).build().unwrap();
+        assert_eq!((time_stamp.videoTimeScale, time_stamp.videoTime), (0, 0));
+        assert!(time_stamp.get_flags().contains(CVTimeStampFlags::kCVTimeStampVideoTimeValid));
+
+        assert_eq!(CVTimeStampBuilder::new().video_time(CVTime::INDEFINITE).build().unwrap_err(), CVError::InvalidArgument);
+        let overflow = CVTimeStampBuilder::new()
+            .video_time(CVTime::new(1, i32::MAX))
+            .refresh_period(CVTime::new(i64::MAX, 1))
+            .build();
+        assert_eq!(overflow.unwrap_err(), CVError::InvalidArgument);
+    }
+}
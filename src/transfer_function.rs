@@ -0,0 +1,442 @@
+use crate::{
+    image_buffer::{CVImageBufferKeys, CVImageBufferTransferFunction},
+    pixel_buffer::CVPixelBuffer,
+    pixel_buffer_backend::PixelBufferBackend,
+    r#return::CVError,
+};
+
+pub(crate) const TRANSFER_FUNCTIONS: [CVImageBufferTransferFunction; 9] = [
+    CVImageBufferTransferFunction::ITU_R_709_2,
+    CVImageBufferTransferFunction::SMPTE_240M_1995,
+    CVImageBufferTransferFunction::UseGamma,
+    CVImageBufferTransferFunction::sRGB,
+    CVImageBufferTransferFunction::ITU_R_2020,
+    CVImageBufferTransferFunction::SMPTE_ST_428_1,
+    CVImageBufferTransferFunction::SMPTE_ST_2084_PQ,
+    CVImageBufferTransferFunction::ITU_R_2100_HLG,
+    CVImageBufferTransferFunction::Linear,
+];
+
+// ITU-R BT.709 rounds the constants of the curve BT.2020 gives at full precision
+const BT709_ALPHA: f64 = 1.099;
+const BT709_BETA: f64 = 0.018;
+const BT2020_ALPHA: f64 = 1.099_296_826_809_44;
+const BT2020_BETA: f64 = 0.018_053_968_510_807;
+const SMPTE_240M_ALPHA: f64 = 1.1115;
+const SMPTE_240M_BETA: f64 = 0.0228;
+
+const PQ_M1: f64 = 2610.0 / 16384.0;
+const PQ_M2: f64 = 2523.0 / 4096.0 * 128.0;
+const PQ_C1: f64 = 3424.0 / 4096.0;
+const PQ_C2: f64 = 2413.0 / 4096.0 * 32.0;
+const PQ_C3: f64 = 2392.0 / 4096.0 * 32.0;
+
+const HLG_A: f64 = 0.178_832_77;
+const HLG_B: f64 = 0.284_668_92;
+const HLG_C: f64 = 0.559_910_73;
+
+#[inline]
+fn encode_linear_segment(linear: f64, alpha: f64, beta: f64, slope: f64) -> f64 {
+    if linear < beta {
+        slope * linear
+    } else {
+        alpha * linear.powf(0.45) - (alpha - 1.0)
+    }
+}
+
+// The rounded ITU-R BT.709 constants leave a small gap between the two segments at beta, so the power segment is only
+// inverted from where it starts and every encoded value round trips
+#[inline]
+fn decode_linear_segment(encoded: f64, alpha: f64, beta: f64, slope: f64) -> f64 {
+    if encoded < alpha * beta.powf(0.45) - (alpha - 1.0) {
+        encoded / slope
+    } else {
+        ((encoded + (alpha - 1.0)) / alpha).powf(1.0 / 0.45)
+    }
+}
+
+// Curves that are power laws at heart are mirrored around zero so extended range values survive a round trip
+#[inline]
+fn mirrored(value: f64, curve: impl Fn(f64) -> f64) -> f64 {
+    if value < 0.0 {
+        -curve(-value)
+    } else {
+        curve(value)
+    }
+}
+
+// A curve of one transfer function, taking the value and the UseGamma exponent
+type Curve = fn(f64, f64) -> f64;
+
+fn encoding_curve(function: CVImageBufferTransferFunction) -> Curve {
+    match function {
+        CVImageBufferTransferFunction::ITU_R_709_2 => {
+            |linear, _| mirrored(linear, |linear| encode_linear_segment(linear, BT709_ALPHA, BT709_BETA, 4.5))
+        }
+        CVImageBufferTransferFunction::ITU_R_2020 => {
+            |linear, _| mirrored(linear, |linear| encode_linear_segment(linear, BT2020_ALPHA, BT2020_BETA, 4.5))
+        }
+        CVImageBufferTransferFunction::SMPTE_240M_1995 => {
+            |linear, _| mirrored(linear, |linear| encode_linear_segment(linear, SMPTE_240M_ALPHA, SMPTE_240M_BETA, 4.0))
+        }
+        CVImageBufferTransferFunction::sRGB => {
+            |linear, _| mirrored(linear, |linear| if linear <= 0.003_130_8 { 12.92 * linear } else { 1.055 * linear.powf(1.0 / 2.4) - 0.055 })
+        }
+        CVImageBufferTransferFunction::SMPTE_ST_428_1 => |linear, _| mirrored(linear, |linear| (48.0 * linear / 52.37).powf(1.0 / 2.6)),
+        CVImageBufferTransferFunction::SMPTE_ST_2084_PQ => |linear, _| {
+            let power = linear.max(0.0).powf(PQ_M1);
+            ((PQ_C1 + PQ_C2 * power) / (1.0 + PQ_C3 * power)).powf(PQ_M2)
+        },
+        CVImageBufferTransferFunction::ITU_R_2100_HLG => |linear, _| {
+            let linear = linear.max(0.0);
+            if linear <= 1.0 / 12.0 {
+                (3.0 * linear).sqrt()
+            } else {
+                HLG_A * (12.0 * linear - HLG_B).ln() + HLG_C
+            }
+        },
+        CVImageBufferTransferFunction::UseGamma => |linear, gamma| mirrored(linear, |linear| linear.powf(1.0 / gamma)),
+        CVImageBufferTransferFunction::Linear => |linear, _| linear,
+    }
+}
+
+fn decoding_curve(function: CVImageBufferTransferFunction) -> Curve {
+    match function {
+        CVImageBufferTransferFunction::ITU_R_709_2 => {
+            |encoded, _| mirrored(encoded, |encoded| decode_linear_segment(encoded, BT709_ALPHA, BT709_BETA, 4.5))
+        }
+        CVImageBufferTransferFunction::ITU_R_2020 => {
+            |encoded, _| mirrored(encoded, |encoded| decode_linear_segment(encoded, BT2020_ALPHA, BT2020_BETA, 4.5))
+        }
+        CVImageBufferTransferFunction::SMPTE_240M_1995 => {
+            |encoded, _| mirrored(encoded, |encoded| decode_linear_segment(encoded, SMPTE_240M_ALPHA, SMPTE_240M_BETA, 4.0))
+        }
+        CVImageBufferTransferFunction::sRGB => {
+            |encoded, _| mirrored(encoded, |encoded| if encoded <= 0.040_45 { encoded / 12.92 } else { ((encoded + 0.055) / 1.055).powf(2.4) })
+        }
+        CVImageBufferTransferFunction::SMPTE_ST_428_1 => |encoded, _| mirrored(encoded, |encoded| 52.37 / 48.0 * encoded.powf(2.6)),
+        // Past 1.0 the denominator turns negative, the signal is clamped to the range the curve is defined over
+        CVImageBufferTransferFunction::SMPTE_ST_2084_PQ => |encoded, _| {
+            let power = encoded.clamp(0.0, 1.0).powf(1.0 / PQ_M2);
+            ((power - PQ_C1).max(0.0) / (PQ_C2 - PQ_C3 * power)).powf(1.0 / PQ_M1)
+        },
+        CVImageBufferTransferFunction::ITU_R_2100_HLG => |encoded, _| {
+            let encoded = encoded.max(0.0);
+            if encoded <= 0.5 {
+                encoded * encoded / 3.0
+            } else {
+                (((encoded - HLG_C) / HLG_A).exp() + HLG_B) / 12.0
+            }
+        },
+        CVImageBufferTransferFunction::UseGamma => |encoded, gamma| mirrored(encoded, |encoded| encoded.powf(gamma)),
+        CVImageBufferTransferFunction::Linear => |encoded, _| encoded,
+    }
+}
+
+// `encode` maps linear light in [0, 1] to the nonlinear signal (the OETF, or the inverse EOTF for display referred
+// curves) and `decode` maps it back. Linear light is relative to 10000 cd/m² for PQ, to the 48 cd/m² reference white
+// for ST 428-1 (so its signal reaches 1.0 at 52.37 / 48), and is scene light for HLG
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TransferFunction {
+    pub function: CVImageBufferTransferFunction,
+    pub gamma: f32, /* exponent used by UseGamma, as found under kCVImageBufferGammaLevelKey */
+}
+
+impl From<CVImageBufferTransferFunction> for TransferFunction {
+    fn from(function: CVImageBufferTransferFunction) -> Self {
+        TransferFunction { function, gamma: 1.0 }
+    }
+}
+
+impl TransferFunction {
+    #[inline]
+    pub fn new(function: CVImageBufferTransferFunction) -> Self {
+        Self::from(function)
+    }
+
+    #[inline]
+    pub fn with_gamma(gamma: f32) -> Self {
+        TransferFunction { function: CVImageBufferTransferFunction::UseGamma, gamma }
+    }
+
+    // Reads kCVImageBufferTransferFunctionKey, and kCVImageBufferGammaLevelKey for UseGamma or when no transfer function
+    // is attached
+    pub fn from_attachments(pixel_buffer: &CVPixelBuffer) -> Option<Self> {
+        match pixel_buffer.get_image_attachment(CVImageBufferKeys::TransferFunction) {
+            Some(CVImageBufferTransferFunction::UseGamma) | None => get_gamma_level(pixel_buffer).map(Self::with_gamma),
+            Some(function) => Some(Self::from(function)),
+        }
+    }
+
+    // Evaluated in double precision, the PQ curve loses too much in single precision
+    #[inline]
+    pub fn encode(&self, linear: f32) -> f32 {
+        encoding_curve(self.function)(linear as f64, self.gamma as f64) as f32
+    }
+
+    #[inline]
+    pub fn decode(&self, encoded: f32) -> f32 {
+        decoding_curve(self.function)(encoded as f64, self.gamma as f64) as f32
+    }
+
+    // Slices long enough to pay for building a TransferCurve go through one, shorter ones through the curve itself
+    pub fn encode_slice(&self, values: &mut [f32]) {
+        apply_curve(values, encoding_curve(self.function), self.gamma as f64);
+    }
+
+    pub fn decode_slice(&self, values: &mut [f32]) {
+        apply_curve(values, decoding_curve(self.function), self.gamma as f64);
+    }
+
+    #[inline]
+    pub fn encoder(&self) -> TransferCurve {
+        TransferCurve::new(encoding_curve(self.function), self.gamma as f64)
+    }
+
+    #[inline]
+    pub fn decoder(&self) -> TransferCurve {
+        TransferCurve::new(decoding_curve(self.function), self.gamma as f64)
+    }
+
+    // Table of `encode` over every full range code of `bit_depth` bits, for quantized linear input
+    pub fn encoding_lut(&self, bit_depth: u32) -> Result<TransferFunctionLut, CVError> {
+        TransferFunctionLut::new(bit_depth, |value| self.encode(value))
+    }
+
+    // Table of `decode` over every full range code of `bit_depth` bits, for quantized signal input
+    pub fn decoding_lut(&self, bit_depth: u32) -> Result<TransferFunctionLut, CVError> {
+        TransferFunctionLut::new(bit_depth, |value| self.decode(value))
+    }
+}
+
+fn get_gamma_level(pixel_buffer: &CVPixelBuffer) -> Option<f32> {
+    pixel_buffer.get_image_attachment::<f64>(CVImageBufferKeys::GammaLevel).map(|gamma| gamma as f32)
+}
+
+pub(crate) fn set_gamma_level(pixel_buffer: &CVPixelBuffer, gamma: f32) {
+    pixel_buffer.set_image_attachment(CVImageBufferKeys::GammaLevel, gamma as f64);
+}
+
+// Code `n` of a `bit_depth` bit input stands for n / (2^bit_depth - 1)
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransferFunctionLut {
+    bit_depth: u32,
+    table: Vec<f32>,
+}
+
+impl TransferFunctionLut {
+    fn new(bit_depth: u32, function: impl Fn(f32) -> f32) -> Result<Self, CVError> {
+        if bit_depth == 0 || bit_depth > 16 {
+            return Err(CVError::InvalidArgument);
+        }
+        let max = ((1u32 << bit_depth) - 1) as f32;
+        let table = (0..1u32 << bit_depth).map(|code| function(code as f32 / max)).collect();
+        Ok(TransferFunctionLut { bit_depth, table })
+    }
+
+    #[inline]
+    pub fn bit_depth(&self) -> u32 {
+        self.bit_depth
+    }
+
+    // Codes past the top of the range saturate
+    #[inline]
+    pub fn get(&self, code: u16) -> f32 {
+        self.table[(code as usize).min(self.table.len() - 1)]
+    }
+
+    pub fn apply_slice(&self, codes: &[u16], output: &mut [f32]) -> Result<(), CVError> {
+        if output.len() < codes.len() {
+            return Err(CVError::InvalidSize);
+        }
+        codes.iter().zip(output).for_each(|(&code, output)| *output = self.get(code));
+        Ok(())
+    }
+}
+
+const CURVE_TABLE_BIT_DEPTH: u32 = 12;
+// Below this many table steps the power curves bend too sharply to interpolate between entries
+const CURVE_EXACT_STEPS: f32 = 64.0;
+
+// `encode` or `decode` resolved once for bulk use: a 12-bit table interpolated between entries, within a 12-bit code of
+// the curve. Values near zero and outside [0, 1] are evaluated exactly
+#[derive(Clone, Debug)]
+pub struct TransferCurve {
+    curve: Curve,
+    gamma: f64,
+    table: Vec<f32>,
+}
+
+impl TransferCurve {
+    fn new(curve: Curve, gamma: f64) -> Self {
+        let max = ((1u32 << CURVE_TABLE_BIT_DEPTH) - 1) as f32;
+        let table = (0..1u32 << CURVE_TABLE_BIT_DEPTH)
+            .map(|code| curve((code as f32 / max) as f64, gamma) as f32)
+            .collect();
+        TransferCurve { curve, gamma, table }
+    }
+
+    #[inline]
+    pub fn apply(&self, value: f32) -> f32 {
+        let steps = (self.table.len() - 1) as f32;
+        let position = value * steps;
+        if (CURVE_EXACT_STEPS..=steps).contains(&position) {
+            let index = (position as usize).min(self.table.len() - 2);
+            let (low, high) = (self.table[index], self.table[index + 1]);
+            low + (high - low) * (position - index as f32)
+        } else {
+            (self.curve)(value as f64, self.gamma) as f32
+        }
+    }
+
+    pub fn apply_slice(&self, values: &mut [f32]) {
+        values.iter_mut().for_each(|value| *value = self.apply(*value));
+    }
+}
+
+fn apply_curve(values: &mut [f32], curve: Curve, gamma: f64) {
+    if values.len() < 1 << CURVE_TABLE_BIT_DEPTH {
+        values.iter_mut().for_each(|value| *value = curve(*value as f64, gamma) as f32);
+    } else {
+        TransferCurve::new(curve, gamma).apply_slice(values);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f64, tolerance: f64, context: &str) {
+        assert!((actual as f64 - expected).abs() <= tolerance, "{}: {} != {}", context, actual, expected);
+    }
+
+    // Reference points of the ITU-R BT.709, BT.2020 and BT.2100, IEC 61966-2-1, SMPTE ST 2084 and ST 428-1 curves
+    #[test]
+    fn reference_points() {
+        use CVImageBufferTransferFunction::*;
+
+        let points = [
+            (ITU_R_709_2, 0.0, 0.0),
+            (ITU_R_709_2, 0.01, 0.045),
+            (ITU_R_709_2, 0.18, 0.409_008),
+            (ITU_R_709_2, 1.0, 1.0),
+            (ITU_R_2020, 0.018_053_968_510_807, 0.081_242_858),
+            (ITU_R_2020, 0.18, 0.408_848),
+            (ITU_R_2020, 1.0, 1.0),
+            (sRGB, 0.003_130_8, 0.040_45),
+            (sRGB, 0.18, 0.461_356),
+            (sRGB, 0.214_041, 0.5),
+            (sRGB, 1.0, 1.0),
+            // 0, 0.1, 1, 100, 1000 and 10000 cd/m²
+            (SMPTE_ST_2084_PQ, 0.0, 0.000_000_731),
+            (SMPTE_ST_2084_PQ, 0.000_01, 0.062_337),
+            (SMPTE_ST_2084_PQ, 0.000_1, 0.149_946),
+            (SMPTE_ST_2084_PQ, 0.01, 0.508_078),
+            (SMPTE_ST_2084_PQ, 0.1, 0.751_827),
+            (SMPTE_ST_2084_PQ, 1.0, 1.0),
+            (ITU_R_2100_HLG, 0.0, 0.0),
+            (ITU_R_2100_HLG, 1.0 / 12.0, 0.5),
+            (ITU_R_2100_HLG, 0.264_963, 0.75),
+            (ITU_R_2100_HLG, 1.0, 1.0),
+            // 48 cd/m² reference white, and the 52.37 cd/m² peak
+            (SMPTE_ST_428_1, 1.0, 0.967_043),
+            (SMPTE_ST_428_1, 52.37 / 48.0, 1.0),
+            (Linear, 0.5, 0.5),
+        ];
+        for &(function, linear, encoded) in &points {
+            let transfer_function = TransferFunction::new(function);
+            let context = format!("{:?} {}", function, linear);
+            assert_close(transfer_function.encode(linear as f32), encoded, 1e-5, &context);
+            assert_close(transfer_function.decode(encoded as f32), linear, 1e-5 + linear * 1e-4, &context);
+        }
+
+        let gamma = TransferFunction::with_gamma(2.2);
+        assert_close(gamma.encode(0.5), 0.5f64.powf(1.0 / 2.2), 1e-6, "gamma 2.2");
+        assert_close(gamma.decode(0.5), 0.5f64.powf(2.2), 1e-6, "gamma 2.2");
+    }
+
+    // Outside the range the curves are defined over, PQ and HLG clamp, the others mirror around zero
+    #[test]
+    fn out_of_range() {
+        let pq = TransferFunction::new(CVImageBufferTransferFunction::SMPTE_ST_2084_PQ);
+        for encoded in [1.0, 1.0087, 1.01, 1.5, 100.0] {
+            assert_eq!(pq.decode(encoded), 1.0);
+        }
+        assert_eq!(pq.decode(-0.5), 0.0);
+        assert_eq!(pq.encode(-0.5), pq.encode(0.0));
+        assert!(pq.encode(2.0).is_finite());
+
+        let hlg = TransferFunction::new(CVImageBufferTransferFunction::ITU_R_2100_HLG);
+        assert_eq!((hlg.encode(-1.0), hlg.decode(-1.0)), (0.0, 0.0));
+        assert!(hlg.decode(1.5).is_finite());
+
+        for function in
+            [CVImageBufferTransferFunction::ITU_R_709_2, CVImageBufferTransferFunction::sRGB, CVImageBufferTransferFunction::SMPTE_ST_428_1]
+        {
+            let transfer_function = TransferFunction::new(function);
+            assert_eq!(transfer_function.encode(-0.25), -transfer_function.encode(0.25));
+            assert_eq!(transfer_function.decode(-0.25), -transfer_function.decode(0.25));
+            assert!(transfer_function.encode(1.5) > 1.0);
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut transfer_functions: Vec<TransferFunction> = TRANSFER_FUNCTIONS.iter().map(|&function| TransferFunction::new(function)).collect();
+        transfer_functions.push(TransferFunction::with_gamma(2.4));
+        for transfer_function in transfer_functions {
+            for step in 0..=1000 {
+                let linear = step as f32 / 1000.0;
+                let encoded = transfer_function.encode(linear);
+                assert_close(transfer_function.decode(encoded), linear as f64, 1e-5 + linear as f64 * 1e-4, &format!("{:?}", transfer_function));
+                let encoded = step as f32 / 1000.0;
+                let linear = transfer_function.decode(encoded);
+                assert_close(transfer_function.encode(linear), encoded as f64, 1e-4, &format!("{:?}", transfer_function));
+            }
+        }
+    }
+
+    #[test]
+    fn slices() {
+        let values: Vec<f32> = (-100..=10100).map(|step| step as f32 / 10000.0).collect();
+        for &function in &TRANSFER_FUNCTIONS {
+            let transfer_function = TransferFunction::new(function);
+            for length in [16, values.len()] {
+                let mut encoded = values[..length].to_vec();
+                transfer_function.encode_slice(&mut encoded);
+                let mut decoded = values[..length].to_vec();
+                transfer_function.decode_slice(&mut decoded);
+                for (index, &value) in values[..length].iter().enumerate() {
+                    let context = format!("{:?} {}", function, value);
+                    assert_close(encoded[index], transfer_function.encode(value) as f64, 2.5e-4, &context);
+                    assert_close(decoded[index], transfer_function.decode(value) as f64, 2.5e-4, &context);
+                }
+            }
+
+            // Exact at the table entries and wherever the curve is evaluated directly
+            let encoder = transfer_function.encoder();
+            for value in [-0.5, 0.0, 0.001, 4095.0 / 4095.0, 2048.0 / 4095.0, 1.5] {
+                assert_close(encoder.apply(value), transfer_function.encode(value) as f64, 1e-6, &format!("{:?} {}", function, value));
+            }
+        }
+    }
+
+    #[test]
+    fn luts() {
+        let transfer_function = TransferFunction::new(CVImageBufferTransferFunction::sRGB);
+        let lut = transfer_function.decoding_lut(8).unwrap();
+        assert_eq!(lut.bit_depth(), 8);
+        assert_eq!((lut.get(0), lut.get(255), lut.get(1000)), (0.0, 1.0, 1.0));
+        assert_eq!(lut.get(128), transfer_function.decode(128.0 / 255.0));
+
+        let lut = transfer_function.encoding_lut(10).unwrap();
+        let mut output = [0.0; 4];
+        lut.apply_slice(&[0, 512, 1023], &mut output).unwrap();
+        assert_eq!(output, [0.0, transfer_function.encode(512.0 / 1023.0), 1.0, 0.0]);
+        assert_eq!(lut.apply_slice(&[0, 1], &mut output[..1]), Err(CVError::InvalidSize));
+
+        assert_eq!(transfer_function.encoding_lut(0), Err(CVError::InvalidArgument));
+        assert_eq!(transfer_function.decoding_lut(17), Err(CVError::InvalidArgument));
+        assert_eq!(transfer_function.decoding_lut(16).unwrap().get(u16::MAX), 1.0);
+    }
+}
@@ -0,0 +1,420 @@
+use crate::{pixel_buffer::CVPixelBuffer, pixel_format::PixelFormat, r#return::CVError, ycbcr::check_size};
+
+pub const V210_PIXELS_PER_GROUP: usize = 6;
+pub const V210_BYTES_PER_GROUP: usize = 16;
+pub const V210_ROW_ALIGNMENT: usize = 128;
+
+const COMPONENT_MASK: u32 = 0x3ff;
+
+// Bytes actually covered by the groups of a `width` pixel row
+#[inline]
+fn v210_row_bytes(width: usize) -> usize {
+    width.div_ceil(V210_PIXELS_PER_GROUP) * V210_BYTES_PER_GROUP
+}
+
+// Bytes per row of a v210 image, padded to 48 pixel (128 byte) boundaries as every v210 reader expects
+#[inline]
+pub fn v210_bytes_per_row(width: usize) -> usize {
+    v210_row_bytes(width).div_ceil(V210_ROW_ALIGNMENT) * V210_ROW_ALIGNMENT
+}
+
+fn check_row_lengths(width: usize, row: usize, y: usize, cb: usize, cr: usize) -> Result<(), CVError> {
+    let chroma_width = width.div_ceil(2);
+    if row < v210_row_bytes(width) || y < width || cb < chroma_width || cr < chroma_width {
+        return Err(CVError::InvalidSize);
+    }
+    Ok(())
+}
+
+// Every group is four little-endian words of three 10-bit components each, in the order Cb0 Y0 Cr0 Y1 Cb1 Y2 Cr1 Y3
+// Cb2 Y4 Cr2 Y5. Samples are right-aligned 10-bit values; components past the end of the row are skipped
+pub fn unpack_v210_row(row: &[u8], width: usize, y: &mut [u16], cb: &mut [u16], cr: &mut [u16]) -> Result<(), CVError> {
+    check_row_lengths(width, row.len(), y.len(), cb.len(), cr.len())?;
+    for (group, bytes) in row.chunks_exact(V210_BYTES_PER_GROUP).take(width.div_ceil(V210_PIXELS_PER_GROUP)).enumerate() {
+        let mut components = [0u16; 12];
+        for (word, bytes) in bytes.chunks_exact(4).enumerate() {
+            let word_value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            for component in 0..3 {
+                components[word * 3 + component] = ((word_value >> (component * 10)) & COMPONENT_MASK) as u16;
+            }
+        }
+        for pair in 0..3 {
+            let x = group * V210_PIXELS_PER_GROUP + pair * 2;
+            if x >= width {
+                break;
+            }
+            let chroma_x = x / 2;
+            cb[chroma_x] = components[pair * 4];
+            y[x] = components[pair * 4 + 1];
+            cr[chroma_x] = components[pair * 4 + 2];
+            if x + 1 < width {
+                y[x + 1] = components[pair * 4 + 3];
+            }
+        }
+    }
+    Ok(())
+}
+
+// The inverse of unpack_v210_row; only the low 10 bits of every sample are kept, and components past the end of the row
+// as well as any bytes of `row` after the last group are written as zero
+pub fn pack_v210_row(y: &[u16], cb: &[u16], cr: &[u16], width: usize, row: &mut [u8]) -> Result<(), CVError> {
+    check_row_lengths(width, row.len(), y.len(), cb.len(), cr.len())?;
+    for (group, bytes) in row.chunks_exact_mut(V210_BYTES_PER_GROUP).take(width.div_ceil(V210_PIXELS_PER_GROUP)).enumerate() {
+        let mut components = [0u16; 12];
+        for pair in 0..3 {
+            let x = group * V210_PIXELS_PER_GROUP + pair * 2;
+            if x >= width {
+                break;
+            }
+            let chroma_x = x / 2;
+            components[pair * 4] = cb[chroma_x];
+            components[pair * 4 + 1] = y[x];
+            components[pair * 4 + 2] = cr[chroma_x];
+            if x + 1 < width {
+                components[pair * 4 + 3] = y[x + 1];
+            }
+        }
+        for (word, bytes) in bytes.chunks_exact_mut(4).enumerate() {
+            let word_value =
+                (0..3).fold(0u32, |value, component| value | (components[word * 3 + component] as u32 & COMPONENT_MASK) << (component * 10));
+            bytes.copy_from_slice(&word_value.to_le_bytes());
+        }
+    }
+    row[v210_row_bytes(width)..].fill(0);
+    Ok(())
+}
+
+// 4:2:2 planes of right-aligned 10-bit samples, rows tightly packed, chroma half as wide as luma rounded up
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct YCbCr422Planes {
+    pub width: usize,
+    pub height: usize,
+    pub y: Vec<u16>,
+    pub cb: Vec<u16>,
+    pub cr: Vec<u16>,
+}
+
+impl YCbCr422Planes {
+    pub fn new(width: usize, height: usize) -> Self {
+        let chroma_size = width.div_ceil(2) * height;
+        YCbCr422Planes { width, height, y: vec![0; width * height], cb: vec![0; chroma_size], cr: vec![0; chroma_size] }
+    }
+
+    #[inline]
+    pub fn chroma_width(&self) -> usize {
+        self.width.div_ceil(2)
+    }
+
+    fn check(&self) -> Result<(), CVError> {
+        let chroma_size = self.chroma_width() * self.height;
+        if self.y.len() != self.width * self.height || self.cb.len() != chroma_size || self.cr.len() != chroma_size {
+            return Err(CVError::InvalidSize);
+        }
+        Ok(())
+    }
+}
+
+#[inline]
+fn is_v210(pixel_buffer: &CVPixelBuffer) -> bool {
+    PixelFormat::from(pixel_buffer.get_pixel_format()) == PixelFormat::_422YpCbCr10
+}
+
+#[inline]
+fn is_x422(pixel_buffer: &CVPixelBuffer) -> bool {
+    matches!(
+        PixelFormat::from(pixel_buffer.get_pixel_format()),
+        PixelFormat::_422YpCbCr10BiPlanarVideoRange | PixelFormat::_422YpCbCr10BiPlanarFullRange
+    )
+}
+
+pub fn unpack_v210(source: &CVPixelBuffer) -> Result<YCbCr422Planes, CVError> {
+    if !is_v210(source) {
+        return Err(CVError::InvalidPixelFormat);
+    }
+    let mut planes = YCbCr422Planes::new(source.get_width(), source.get_height());
+    let (width, chroma_width) = (planes.width, planes.chroma_width());
+    if width == 0 {
+        return Ok(planes);
+    }
+
+    let guard = source.lock_read()?;
+    let plane = guard.plane(0).ok_or(CVError::InvalidArgument)?;
+    let rows = planes
+        .y
+        .chunks_exact_mut(width)
+        .zip(planes.cb.chunks_exact_mut(chroma_width))
+        .zip(planes.cr.chunks_exact_mut(chroma_width));
+    for (row, ((y, cb), cr)) in plane.rows().zip(rows) {
+        unpack_v210_row(row, width, y, cb, cr)?;
+    }
+    Ok(planes)
+}
+
+pub fn pack_v210(planes: &YCbCr422Planes, destination: &mut CVPixelBuffer) -> Result<(), CVError> {
+    if !is_v210(destination) {
+        return Err(CVError::InvalidPixelFormat);
+    }
+    planes.check()?;
+    if planes.width != destination.get_width() || planes.height != destination.get_height() {
+        return Err(CVError::InvalidSize);
+    }
+    let (width, chroma_width) = (planes.width, planes.chroma_width());
+    if width == 0 {
+        return Ok(());
+    }
+
+    let mut guard = destination.lock_write()?;
+    let mut plane = guard.plane_mut(0).ok_or(CVError::InvalidArgument)?;
+    // Rows are padded out to the 128 byte boundary as far as the buffer's own rows reach
+    let padded_row_bytes = v210_bytes_per_row(width).min(plane.bytes_per_row());
+    let bytes_per_row = plane.bytes_per_row();
+    let rows = planes
+        .y
+        .chunks_exact(width)
+        .zip(planes.cb.chunks_exact(chroma_width))
+        .zip(planes.cr.chunks_exact(chroma_width));
+    for (row, ((y, cb), cr)) in plane.as_bytes_mut().chunks_mut(bytes_per_row).zip(rows) {
+        let end = row.len().min(padded_row_bytes);
+        pack_v210_row(y, cb, cr, width, &mut row[..end])?;
+    }
+    Ok(())
+}
+
+// x422 and xf22 keep the 10 bits in the MSBs of little-endian 16-bit samples, Cb before Cr in the chroma plane
+pub fn unpack_v210_to_x422(source: &CVPixelBuffer, destination: &mut CVPixelBuffer) -> Result<(), CVError> {
+    if !is_x422(destination) {
+        return Err(CVError::InvalidPixelFormat);
+    }
+    check_size(source, destination)?;
+    let planes = unpack_v210(source)?;
+    let (width, chroma_width) = (planes.width, planes.chroma_width());
+    if width == 0 {
+        return Ok(());
+    }
+
+    let mut guard = destination.lock_write()?;
+    let mut destination_planes = guard.planes_mut();
+    if destination_planes.len() != 2 {
+        return Err(CVError::InvalidArgument);
+    }
+    let mut chroma = destination_planes.pop().unwrap();
+    let mut luma = destination_planes.pop().unwrap();
+    for (row, y) in luma.rows_mut().zip(planes.y.chunks_exact(width)) {
+        for (bytes, &y) in row.chunks_exact_mut(2).zip(y) {
+            bytes.copy_from_slice(&(y << 6).to_le_bytes());
+        }
+    }
+    for (row, (cb, cr)) in chroma
+        .rows_mut()
+        .zip(planes.cb.chunks_exact(chroma_width).zip(planes.cr.chunks_exact(chroma_width)))
+    {
+        for (bytes, (&cb, &cr)) in row.chunks_exact_mut(4).zip(cb.iter().zip(cr)) {
+            bytes[..2].copy_from_slice(&(cb << 6).to_le_bytes());
+            bytes[2..].copy_from_slice(&(cr << 6).to_le_bytes());
+        }
+    }
+    Ok(())
+}
+
+// The 6 low bits of every x422 sample are dropped
+pub fn pack_x422_to_v210(source: &CVPixelBuffer, destination: &mut CVPixelBuffer) -> Result<(), CVError> {
+    if !is_x422(source) {
+        return Err(CVError::InvalidPixelFormat);
+    }
+    check_size(source, destination)?;
+    let mut planes = YCbCr422Planes::new(source.get_width(), source.get_height());
+    let (width, chroma_width) = (planes.width, planes.chroma_width());
+    if width == 0 {
+        return pack_v210(&planes, destination);
+    }
+    {
+        let guard = source.lock_read()?;
+        let luma = guard.plane(0).ok_or(CVError::InvalidArgument)?;
+        let chroma = guard.plane(1).ok_or(CVError::InvalidArgument)?;
+        for (row, y) in luma.rows().zip(planes.y.chunks_exact_mut(width)) {
+            for (bytes, y) in row.chunks_exact(2).zip(y) {
+                *y = u16::from_le_bytes([bytes[0], bytes[1]]) >> 6;
+            }
+        }
+        let chroma_rows = planes.cb.chunks_exact_mut(chroma_width).zip(planes.cr.chunks_exact_mut(chroma_width));
+        for (row, (cb, cr)) in chroma.rows().zip(chroma_rows) {
+            for (bytes, (cb, cr)) in row.chunks_exact(4).zip(cb.iter_mut().zip(cr)) {
+                *cb = u16::from_le_bytes([bytes[0], bytes[1]]) >> 6;
+                *cr = u16::from_le_bytes([bytes[2], bytes[3]]) >> 6;
+            }
+        }
+    }
+    pack_v210(&planes, destination)
+}
+
+#[cfg(all(test, any(feature = "portable", target_os = "macos", target_os = "ios")))]
+mod tests {
+    use super::*;
+
+    // Cb0 Y0 Cr0 Y1 Cb1 Y2 Cr1 Y3 Cb2 Y4 Cr2 Y5
+    const GROUP_COMPONENTS: [u16; 12] = [0x100, 0x200, 0x300, 0x040, 0x3ff, 0x001, 0x155, 0x2aa, 0x0f0, 0x30f, 0x123, 0x321];
+    const GROUP_BYTES: [u8; 16] = [0x00, 0x01, 0x08, 0x30, 0x40, 0xfc, 0x1f, 0x00, 0x55, 0xa9, 0x0a, 0x0f, 0x0f, 0x8f, 0x14, 0x32];
+
+    // Distinct 10-bit samples for every position of every plane
+    fn test_planes(width: usize, height: usize) -> YCbCr422Planes {
+        let mut planes = YCbCr422Planes::new(width, height);
+        let sample = |index: usize, seed: usize| ((index * 37 + seed) % 1024) as u16;
+        planes.y.iter_mut().enumerate().for_each(|(index, y)| *y = sample(index, 1));
+        planes.cb.iter_mut().enumerate().for_each(|(index, cb)| *cb = sample(index, 400));
+        planes.cr.iter_mut().enumerate().for_each(|(index, cr)| *cr = sample(index, 800));
+        planes
+    }
+
+    fn new_buffer(pixel_format: PixelFormat, width: usize, height: usize) -> CVPixelBuffer {
+        CVPixelBuffer::new(pixel_format.into(), width, height, None).unwrap()
+    }
+
+    // Rows up to the 128 byte boundary, or as far as the last one reaches
+    fn buffer_rows(pixel_buffer: &CVPixelBuffer) -> Vec<Vec<u8>> {
+        let guard = pixel_buffer.lock_read().unwrap();
+        let plane = guard.plane(0).unwrap();
+        plane
+            .as_bytes()
+            .chunks(plane.bytes_per_row())
+            .take(plane.height())
+            .map(|row| row[..row.len().min(v210_bytes_per_row(plane.width()))].to_vec())
+            .collect()
+    }
+
+    fn fill_buffer(pixel_buffer: &mut CVPixelBuffer, value: u8) {
+        pixel_buffer
+            .lock_write()
+            .unwrap()
+            .planes_mut()
+            .iter_mut()
+            .for_each(|plane| plane.as_bytes_mut().fill(value));
+    }
+
+    #[test]
+    fn group() {
+        let (mut y, mut cb, mut cr) = ([0u16; 6], [0u16; 3], [0u16; 3]);
+        unpack_v210_row(&GROUP_BYTES, 6, &mut y, &mut cb, &mut cr).unwrap();
+        assert_eq!(cb, [GROUP_COMPONENTS[0], GROUP_COMPONENTS[4], GROUP_COMPONENTS[8]]);
+        assert_eq!(
+            y,
+            [GROUP_COMPONENTS[1], GROUP_COMPONENTS[3], GROUP_COMPONENTS[5], GROUP_COMPONENTS[7], GROUP_COMPONENTS[9], GROUP_COMPONENTS[11]]
+        );
+        assert_eq!(cr, [GROUP_COMPONENTS[2], GROUP_COMPONENTS[6], GROUP_COMPONENTS[10]]);
+
+        let mut row = [0xffu8; 16];
+        pack_v210_row(&y, &cb, &cr, 6, &mut row).unwrap();
+        assert_eq!(row, GROUP_BYTES);
+
+        // Only the low 10 bits are packed
+        let y_high: Vec<u16> = y.iter().map(|&y| y | 0xfc00).collect();
+        pack_v210_row(&y_high, &cb, &cr, 6, &mut row).unwrap();
+        assert_eq!(row, GROUP_BYTES);
+
+        assert_eq!(unpack_v210_row(&GROUP_BYTES[..12], 6, &mut y, &mut cb, &mut cr), Err(CVError::InvalidSize));
+        assert_eq!(pack_v210_row(&y[..5], &cb, &cr, 6, &mut row), Err(CVError::InvalidSize));
+        assert_eq!(unpack_v210_row(&[], 0, &mut [], &mut [], &mut []), Ok(()));
+        assert_eq!(pack_v210_row(&[], &[], &[], 0, &mut []), Ok(()));
+    }
+
+    #[test]
+    fn row_lengths() {
+        assert_eq!([1, 6, 7, 12, 47, 48, 49, 96, 1920].map(v210_row_bytes), [16, 16, 32, 32, 128, 128, 144, 256, 5120]);
+        assert_eq!([1, 6, 47, 48, 49, 96, 1280, 1920].map(v210_bytes_per_row), [128, 128, 128, 128, 256, 256, 3456, 5120]);
+    }
+
+    // Widths that leave part of the last group or of the last pair unused
+    #[test]
+    fn partial_groups() {
+        for width in [1, 2, 3, 4, 5, 7, 11, 13, 47, 49] {
+            let planes = test_planes(width, 1);
+            let mut row = vec![0xffu8; v210_bytes_per_row(width)];
+            pack_v210_row(&planes.y, &planes.cb, &planes.cr, width, &mut row).unwrap();
+            assert!(row[v210_row_bytes(width)..].iter().all(|&byte| byte == 0), "{}", width);
+
+            let mut unpacked = YCbCr422Planes::new(width, 1);
+            unpack_v210_row(&row, width, &mut unpacked.y, &mut unpacked.cb, &mut unpacked.cr).unwrap();
+            assert_eq!(unpacked, planes, "{}", width);
+
+            // Components past the end of the row are zero
+            let group = &row[v210_row_bytes(width) - V210_BYTES_PER_GROUP..v210_row_bytes(width)];
+            let components: Vec<u16> = group
+                .chunks_exact(4)
+                .flat_map(|bytes| {
+                    let word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                    (0..3).map(move |component| ((word >> (component * 10)) & COMPONENT_MASK) as u16)
+                })
+                .collect();
+            let used = (width - 1) % V210_PIXELS_PER_GROUP + 1;
+            let used_components = used / 2 * 4 + used % 2 * 3;
+            assert!(components[used_components..].iter().all(|&component| component == 0), "{}", width);
+        }
+    }
+
+    #[test]
+    fn buffer_round_trip() {
+        for (width, height) in [(1, 1), (6, 2), (13, 3), (47, 2), (48, 2), (50, 4)] {
+            let planes = test_planes(width, height);
+            let mut source = new_buffer(PixelFormat::_422YpCbCr10, width, height);
+            fill_buffer(&mut source, 0xff);
+            pack_v210(&planes, &mut source).unwrap();
+            let rows = buffer_rows(&source);
+            assert!(rows.iter().all(|row| row[v210_row_bytes(width)..].iter().all(|&byte| byte == 0)));
+            assert!(rows[..height - 1].iter().all(|row| row.len() == v210_bytes_per_row(width)));
+
+            let unpacked = unpack_v210(&source).unwrap();
+            assert_eq!(unpacked, planes, "{}x{}", width, height);
+            let mut destination = new_buffer(PixelFormat::_422YpCbCr10, width, height);
+            fill_buffer(&mut destination, 0xff);
+            pack_v210(&unpacked, &mut destination).unwrap();
+            assert_eq!(buffer_rows(&destination), rows, "{}x{}", width, height);
+        }
+    }
+
+    #[test]
+    fn x422_round_trip() {
+        for (width, height) in [(2, 2), (7, 3), (48, 1), (62, 2)] {
+            for pixel_format in [PixelFormat::_422YpCbCr10BiPlanarVideoRange, PixelFormat::_422YpCbCr10BiPlanarFullRange] {
+                let planes = test_planes(width, height);
+                let mut source = new_buffer(PixelFormat::_422YpCbCr10, width, height);
+                pack_v210(&planes, &mut source).unwrap();
+                let mut x422 = new_buffer(pixel_format, width, height);
+                unpack_v210_to_x422(&source, &mut x422).unwrap();
+                {
+                    let guard = x422.lock_read().unwrap();
+                    let (luma, chroma) = (guard.plane(0).unwrap(), guard.plane(1).unwrap());
+                    let y: Vec<u16> = luma.rows_as::<u16>().flat_map(|row| row.unwrap()[..width].to_vec()).collect();
+                    let cbcr: Vec<u16> = chroma.rows_as::<u16>().flat_map(|row| row.unwrap()[..planes.chroma_width() * 2].to_vec()).collect();
+                    assert_eq!(y, planes.y.iter().map(|&y| y << 6).collect::<Vec<_>>());
+                    assert_eq!(cbcr.iter().step_by(2).copied().collect::<Vec<_>>(), planes.cb.iter().map(|&cb| cb << 6).collect::<Vec<_>>());
+                    assert_eq!(cbcr.iter().skip(1).step_by(2).copied().collect::<Vec<_>>(), planes.cr.iter().map(|&cr| cr << 6).collect::<Vec<_>>());
+                }
+
+                // The 6 low bits of x422 samples are dropped on the way back
+                x422.lock_write().unwrap().planes_mut().iter_mut().for_each(|plane| {
+                    plane
+                        .rows_as_mut::<u16>()
+                        .for_each(|row| row.unwrap().iter_mut().for_each(|sample| *sample |= 0x3f))
+                });
+                let mut destination = new_buffer(PixelFormat::_422YpCbCr10, width, height);
+                fill_buffer(&mut destination, 0xff);
+                pack_x422_to_v210(&x422, &mut destination).unwrap();
+                assert_eq!(buffer_rows(&destination), buffer_rows(&source), "{}x{} {:?}", width, height, pixel_format);
+            }
+        }
+    }
+
+    #[test]
+    fn mismatched_buffers() {
+        let source = new_buffer(PixelFormat::_422YpCbCr10, 12, 2);
+        let mut x422 = new_buffer(PixelFormat::_422YpCbCr10BiPlanarVideoRange, 14, 2);
+        assert_eq!(unpack_v210_to_x422(&source, &mut x422), Err(CVError::InvalidSize));
+        assert_eq!(pack_x422_to_v210(&x422, &mut new_buffer(PixelFormat::_422YpCbCr10, 12, 2)), Err(CVError::InvalidSize));
+        assert_eq!(unpack_v210(&x422), Err(CVError::InvalidPixelFormat));
+        let mut destination = new_buffer(PixelFormat::_422YpCbCr10, 12, 2);
+        assert_eq!(pack_v210(&test_planes(12, 3), &mut destination), Err(CVError::InvalidSize));
+        assert_eq!(pack_v210(&YCbCr422Planes::new(0, 0), &mut destination), Err(CVError::InvalidSize));
+        let mut planes = test_planes(12, 2);
+        planes.cr.pop();
+        assert_eq!(pack_v210(&planes, &mut destination), Err(CVError::InvalidSize));
+    }
+}
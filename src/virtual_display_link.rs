@@ -0,0 +1,371 @@
+use std::{
+    convert::TryFrom,
+    sync::{Arc, Mutex, MutexGuard, PoisonError, Weak},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    base::{CVOptionFlags, CVTime, CVTimeStamp},
+    host_time::HostTime,
+    r#return::{CVError, CVReturn},
+    time::CVTimeRoundingMethod,
+    time_stamp::CVTimeStampBuilder,
+};
+
+type OutputClosure = dyn Fn(&VirtualDisplayLink, &CVTimeStamp, &CVTimeStamp, CVOptionFlags, &mut CVOptionFlags) -> CVReturn + Send + Sync;
+
+struct State {
+    closure: Option<Arc<OutputClosure>>,
+    running: bool,
+    generation: u64, /* bumped by every start and stop, so that the thread of an earlier run exits */
+    frame: i64,
+    start_host_time: HostTime,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    refresh_period: CVTime,
+    manual: bool,
+    state: Mutex<State>,
+}
+
+// Wakes the display link thread so that it sees that every VirtualDisplayLink is gone and exits
+impl Drop for Shared {
+    fn drop(&mut self) {
+        if let Some(thread) = self.state.get_mut().unwrap_or_else(PoisonError::into_inner).thread.take() {
+            thread.thread().unpark();
+        }
+    }
+}
+
+impl Shared {
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn video_time(&self, frame: i64) -> Option<CVTime> {
+        Some(CVTime::new(frame.checked_mul(self.refresh_period.timeValue)?, self.refresh_period.timeScale))
+    }
+
+    fn host_time(&self, start_host_time: HostTime, frame: i64) -> Option<HostTime> {
+        start_host_time.checked_add(Duration::try_from(self.video_time(frame)?).ok()?)
+    }
+
+    // Frames started by `host_time`
+    fn frames_at(&self, start_host_time: HostTime, host_time: HostTime) -> Option<i64> {
+        let elapsed = host_time.saturating_duration_since(start_host_time);
+        let video_time = CVTime::from_duration(elapsed, self.refresh_period.timeScale, CVTimeRoundingMethod::TowardNegativeInfinity)?;
+        Some(video_time.timeValue / self.refresh_period.timeValue)
+    }
+
+    fn time_stamp(&self, start_host_time: HostTime, frame: i64) -> Option<CVTimeStamp> {
+        CVTimeStampBuilder::new()
+            .video_time(self.video_time(frame)?)
+            .host_time(self.host_time(start_host_time, frame)?.ticks())
+            .refresh_period(self.refresh_period)
+            .rate_scalar(1.0)
+            .build()
+            .ok()
+    }
+}
+
+// Software stand-in for CVDisplayLink with the same surface, calling the output closure once per refresh period from
+// its own thread, or on every tick in manual mode; output times are one refresh period after the current time
+#[derive(Clone)]
+pub struct VirtualDisplayLink(Arc<Shared>);
+
+impl VirtualDisplayLink {
+    fn with_mode(refresh_period: CVTime, manual: bool) -> Result<VirtualDisplayLink, CVError> {
+        if refresh_period.is_indefinite() || refresh_period.timeValue <= 0 || refresh_period.timeScale <= 0 {
+            return Err(CVError::InvalidArgument);
+        }
+        let state = State { closure: None, running: false, generation: 0, frame: 0, start_host_time: HostTime::default(), thread: None };
+        Ok(VirtualDisplayLink(Arc::new(Shared { refresh_period, manual, state: Mutex::new(state) })))
+    }
+
+    #[inline]
+    pub fn new(refresh_period: CVTime) -> Result<VirtualDisplayLink, CVError> {
+        VirtualDisplayLink::with_mode(refresh_period, false)
+    }
+
+    // `frames` refreshes every `seconds` seconds, such as 60000 every 1001 for 59.94 Hz
+    #[inline]
+    pub fn from_refresh_rate(frames: i32, seconds: i64) -> Result<VirtualDisplayLink, CVError> {
+        VirtualDisplayLink::new(CVTime::new(seconds, frames))
+    }
+
+    // Runs no thread: every call to tick outputs the next frame, and host times count from zero, so that runs are
+    // reproducible
+    #[inline]
+    pub fn manual(refresh_period: CVTime) -> Result<VirtualDisplayLink, CVError> {
+        VirtualDisplayLink::with_mode(refresh_period, true)
+    }
+
+    #[inline]
+    pub fn is_manual(&self) -> bool {
+        self.0.manual
+    }
+
+    pub fn set_output_closure<F>(&self, closure: Option<F>) -> Result<(), CVError>
+    where
+        F: Fn(&VirtualDisplayLink, &CVTimeStamp, &CVTimeStamp, CVOptionFlags, &mut CVOptionFlags) -> CVReturn + Send + Sync + 'static,
+    {
+        self.0.lock().closure = closure.map(|closure| Arc::new(closure) as Arc<OutputClosure>);
+        Ok(())
+    }
+
+    pub fn start(&self) -> Result<(), CVError> {
+        let mut state = self.0.lock();
+        if state.running {
+            return Err(CVError::DisplayLinkAlreadyRunning);
+        }
+        if state.closure.is_none() {
+            return Err(CVError::DisplayLinkCallbacksNotSet);
+        }
+        state.running = true;
+        state.generation += 1;
+        state.frame = 0;
+        state.start_host_time = if self.0.manual { HostTime::default() } else { HostTime::now() };
+        if !self.0.manual {
+            let (shared, generation) = (Arc::downgrade(&self.0), state.generation);
+            state.thread = Some(thread::spawn(move || run(shared, generation)));
+        }
+        Ok(())
+    }
+
+    // Waits for the output closure to return, unless called from it
+    pub fn stop(&self) -> Result<(), CVError> {
+        let thread = {
+            let mut state = self.0.lock();
+            state.running = false;
+            state.generation += 1;
+            state.thread.take()
+        };
+        if let Some(thread) = thread {
+            thread.thread().unpark();
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn is_running(&self) -> bool {
+        self.0.lock().running
+    }
+
+    #[inline]
+    pub fn get_nominal_output_video_refresh_period(&self) -> CVTime {
+        self.0.refresh_period
+    }
+
+    #[inline]
+    pub fn get_actual_output_video_refresh_period(&self) -> CVTime {
+        self.0.refresh_period
+    }
+
+    // Video time since start; in manual mode, the start of the frame the next tick outputs
+    pub fn get_current_time(&self) -> Result<CVTime, CVError> {
+        let state = self.0.lock();
+        if !state.running {
+            return Err(CVError::DisplayLinkNotRunning);
+        }
+        if self.0.manual {
+            return self.0.video_time(state.frame).ok_or(CVError::InvalidArgument);
+        }
+        let elapsed = HostTime::now().saturating_duration_since(state.start_host_time);
+        CVTime::from_duration(elapsed, self.0.refresh_period.timeScale, CVTimeRoundingMethod::TowardNegativeInfinity).ok_or(CVError::InvalidArgument)
+    }
+
+    // Outputs the next frame of a manual display link and returns what the output closure returned
+    pub fn tick(&self) -> Result<CVReturn, CVError> {
+        if !self.0.manual {
+            return Err(CVError::Unsupported);
+        }
+        let (closure, now, output_time) = {
+            let mut state = self.0.lock();
+            if !state.running {
+                return Err(CVError::DisplayLinkNotRunning);
+            }
+            let closure = state.closure.clone().ok_or(CVError::DisplayLinkCallbacksNotSet)?;
+            let now = self.0.time_stamp(state.start_host_time, state.frame).ok_or(CVError::InvalidArgument)?;
+            let output_time = self.0.time_stamp(state.start_host_time, state.frame + 1).ok_or(CVError::InvalidArgument)?;
+            state.frame += 1;
+            (closure, now, output_time)
+        };
+        let mut flags_out = 0;
+        Ok(closure(self, &now, &output_time, 0, &mut flags_out))
+    }
+}
+
+// Body of the display link thread; frames that are already over when the thread wakes up are skipped, and the thread
+// exits when the run it was started for ends or every VirtualDisplayLink is dropped. Only a weak reference is held while
+// waiting, so that the output closure is not called again once the last VirtualDisplayLink is gone
+fn run(link: Weak<Shared>, generation: u64) {
+    while let Some(shared) = link.upgrade() {
+        let (closure, now, output_time) = {
+            let mut state = shared.lock();
+            if state.generation != generation {
+                return;
+            }
+            let deadline = match shared.host_time(state.start_host_time, state.frame) {
+                Some(deadline) => deadline,
+                None => return,
+            };
+            let host_time = HostTime::now();
+            if host_time < deadline {
+                drop(state);
+                drop(shared);
+                thread::park_timeout(deadline - host_time);
+                continue;
+            }
+            let current = shared.frames_at(state.start_host_time, host_time).unwrap_or(state.frame);
+            state.frame = state.frame.max(current);
+            let closure = match state.closure.clone() {
+                Some(closure) => closure,
+                None => return,
+            };
+            let (now, output_time) =
+                match (shared.time_stamp(state.start_host_time, state.frame), shared.time_stamp(state.start_host_time, state.frame + 1)) {
+                    (Some(now), Some(output_time)) => (now, output_time),
+                    _ => return,
+                };
+            state.frame += 1;
+            (closure, now, output_time)
+        };
+        let mut flags_out = 0;
+        closure(&VirtualDisplayLink(shared), &now, &output_time, 0, &mut flags_out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Instant};
+
+    use super::*;
+    use crate::{base::CVTimeStampFlags, r#return::kCVReturnSuccess};
+
+    const FLAGS: CVTimeStampFlags = CVTimeStampFlags::kCVTimeStampVideoHostTimeValid
+        .union(CVTimeStampFlags::kCVTimeStampVideoRefreshPeriodValid)
+        .union(CVTimeStampFlags::kCVTimeStampRateScalarValid);
+
+    type Outputs = Arc<Mutex<Vec<(CVTimeStamp, CVTimeStamp)>>>;
+
+    fn recording_link(refresh_period: CVTime) -> (VirtualDisplayLink, Outputs) {
+        let link = VirtualDisplayLink::manual(refresh_period).unwrap();
+        let outputs = Outputs::default();
+        let recorded = outputs.clone();
+        link.set_output_closure(Some(move |_: &VirtualDisplayLink, now: &CVTimeStamp, output_time: &CVTimeStamp, _, _: &mut CVOptionFlags| {
+            recorded.lock().unwrap().push((*now, *output_time));
+            now.videoTime as CVReturn
+        }))
+        .unwrap();
+        (link, outputs)
+    }
+
+    // Host time of `video_time` in 1/`scale` seconds after a start at zero, rounded to the nearest nanosecond
+    fn host_time(video_time: i64, scale: i32) -> u64 {
+        let nanoseconds = (video_time as u128 * 2_000_000_000 + scale as u128) / (2 * scale as u128);
+        HostTime::from_nanos(nanoseconds).unwrap().ticks()
+    }
+
+    #[test]
+    fn manual_time_stamps() {
+        let (link, outputs) = recording_link(CVTime::new(1001, 60000));
+        link.start().unwrap();
+        for frame in 0..600 {
+            assert_eq!(link.get_current_time().unwrap(), CVTime::new(frame * 1001, 60000));
+            assert_eq!(link.tick(), Ok((frame * 1001) as CVReturn));
+        }
+        let outputs = outputs.lock().unwrap();
+        assert_eq!(outputs.len(), 600);
+        for (frame, (now, output_time)) in outputs.iter().enumerate() {
+            for (time_stamp, frame) in [(now, frame as i64), (output_time, frame as i64 + 1)] {
+                assert_eq!(time_stamp.videoTimeScale, 60000);
+                assert_eq!(time_stamp.videoTime, frame * 1001);
+                assert_eq!(time_stamp.videoRefreshPeriod, 1001);
+                assert_eq!(time_stamp.hostTime, host_time(frame * 1001, 60000));
+                assert_eq!(time_stamp.rateScalar, 1.0);
+                assert_eq!(time_stamp.get_flags(), FLAGS);
+            }
+        }
+    }
+
+    #[test]
+    fn manual_restart() {
+        let (link, outputs) = recording_link(CVTime::new(1, 24));
+        link.start().unwrap();
+        link.tick().unwrap();
+        link.tick().unwrap();
+        link.stop().unwrap();
+        link.start().unwrap();
+        link.tick().unwrap();
+        let video_times: Vec<i64> = outputs.lock().unwrap().iter().map(|(now, _)| now.videoTime).collect();
+        assert_eq!(video_times, [0, 1, 0]);
+        assert_eq!(outputs.lock().unwrap()[2].0.hostTime, 0);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(VirtualDisplayLink::new(CVTime::new(0, 60)).err(), Some(CVError::InvalidArgument));
+        assert_eq!(VirtualDisplayLink::new(CVTime::new(1, 0)).err(), Some(CVError::InvalidArgument));
+        assert_eq!(VirtualDisplayLink::manual(CVTime::INDEFINITE).err(), Some(CVError::InvalidArgument));
+        assert_eq!(
+            VirtualDisplayLink::from_refresh_rate(60000, 1001)
+                .unwrap()
+                .get_nominal_output_video_refresh_period(),
+            CVTime::new(1001, 60000)
+        );
+
+        let link = VirtualDisplayLink::manual(CVTime::new(1, 30)).unwrap();
+        assert!(link.is_manual());
+        assert_eq!(link.start(), Err(CVError::DisplayLinkCallbacksNotSet));
+        assert!(!link.is_running());
+        assert_eq!(link.tick(), Err(CVError::DisplayLinkNotRunning));
+        assert_eq!(link.get_current_time(), Err(CVError::DisplayLinkNotRunning));
+        link.set_output_closure(Some(|_: &VirtualDisplayLink, _: &CVTimeStamp, _: &CVTimeStamp, _, _: &mut CVOptionFlags| kCVReturnSuccess))
+            .unwrap();
+        assert_eq!(link.start(), Ok(()));
+        assert!(link.is_running());
+        assert_eq!(link.start(), Err(CVError::DisplayLinkAlreadyRunning));
+        assert_eq!(link.tick(), Ok(kCVReturnSuccess));
+        assert_eq!(link.stop(), Ok(()));
+        assert!(!link.is_running());
+        assert_eq!(link.stop(), Ok(()));
+        assert_eq!(link.tick(), Err(CVError::DisplayLinkNotRunning));
+
+        let link = VirtualDisplayLink::new(CVTime::new(1, 30)).unwrap();
+        assert!(!link.is_manual());
+        assert_eq!(link.tick(), Err(CVError::Unsupported));
+    }
+
+    #[test]
+    fn drop_ends_thread() {
+        let link = VirtualDisplayLink::new(CVTime::new(1, 1000)).unwrap();
+        let calls = Arc::new(Mutex::new(0));
+        let counter = calls.clone();
+        link.set_output_closure(Some(move |_: &VirtualDisplayLink, _: &CVTimeStamp, _: &CVTimeStamp, _, _: &mut CVOptionFlags| {
+            *counter.lock().unwrap() += 1;
+            kCVReturnSuccess
+        }))
+        .unwrap();
+        link.start().unwrap();
+        let start = Instant::now();
+        while *calls.lock().unwrap() < 3 {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(1));
+        }
+        drop(link);
+        // The closure, and with it the counter it holds, goes once the output in progress, if any, returns
+        while Arc::strong_count(&calls) > 1 {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(1));
+        }
+        let count = *calls.lock().unwrap();
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(*calls.lock().unwrap(), count);
+    }
+}
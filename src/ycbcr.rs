@@ -0,0 +1,862 @@
+use crate::{
+    image_buffer::{CVImageBufferChromaLocation, CVImageBufferKeys, CVImageBufferYCbCrMatrix},
+    pixel_buffer::CVPixelBuffer,
+    pixel_buffer_backend::PixelBufferBackend,
+    pixel_format::PixelFormat,
+    pixel_format_description::CVPixelFormatComponentRange,
+    r#return::CVError,
+};
+
+pub(crate) const YCBCR_MATRICES: [CVImageBufferYCbCrMatrix; 6] = [
+    CVImageBufferYCbCrMatrix::ITU_R_709_2,
+    CVImageBufferYCbCrMatrix::ITU_R_601_4,
+    CVImageBufferYCbCrMatrix::SMPTE_240M_1995,
+    CVImageBufferYCbCrMatrix::DCI_P3,
+    CVImageBufferYCbCrMatrix::P3_D65,
+    CVImageBufferYCbCrMatrix::ITU_R_2020,
+];
+
+pub(crate) const CHROMA_LOCATIONS: [CVImageBufferChromaLocation; 7] = [
+    CVImageBufferChromaLocation::Left,
+    CVImageBufferChromaLocation::Center,
+    CVImageBufferChromaLocation::TopLeft,
+    CVImageBufferChromaLocation::Top,
+    CVImageBufferChromaLocation::BottomLeft,
+    CVImageBufferChromaLocation::Bottom,
+    CVImageBufferChromaLocation::DV420,
+];
+
+// Luma weights of red and blue; green is whatever is left
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct YCbCrCoefficients {
+    pub kr: f32,
+    pub kb: f32,
+}
+
+impl YCbCrCoefficients {
+    #[inline]
+    pub fn kg(&self) -> f32 {
+        1.0 - self.kr - self.kb
+    }
+
+    // Y' in [0, 1] and Pb, Pr in [-0.5, 0.5] to nonlinear R'G'B'
+    #[inline]
+    pub fn to_rgb(&self, y: f32, pb: f32, pr: f32) -> [f32; 3] {
+        let r = y + 2.0 * (1.0 - self.kr) * pr;
+        let b = y + 2.0 * (1.0 - self.kb) * pb;
+        [r, (y - self.kr * r - self.kb * b) / self.kg(), b]
+    }
+
+    #[inline]
+    pub fn to_ycbcr(&self, rgb: [f32; 3]) -> (f32, f32, f32) {
+        let [r, g, b] = rgb;
+        let y = self.kr * r + self.kg() * g + self.kb * b;
+        (y, (b - y) / (2.0 * (1.0 - self.kb)), (r - y) / (2.0 * (1.0 - self.kr)))
+    }
+}
+
+impl From<CVImageBufferYCbCrMatrix> for YCbCrCoefficients {
+    fn from(matrix: CVImageBufferYCbCrMatrix) -> Self {
+        // The P3 matrices have no published constants, these are the luminance row of their RGB to XYZ matrices
+        let (kr, kb) = match matrix {
+            CVImageBufferYCbCrMatrix::ITU_R_709_2 => (0.2126, 0.0722),
+            CVImageBufferYCbCrMatrix::ITU_R_601_4 => (0.299, 0.114),
+            CVImageBufferYCbCrMatrix::SMPTE_240M_1995 => (0.212, 0.087),
+            CVImageBufferYCbCrMatrix::DCI_P3 => (0.209492, 0.068913),
+            CVImageBufferYCbCrMatrix::P3_D65 => (0.228975, 0.079287),
+            CVImageBufferYCbCrMatrix::ITU_R_2020 => (0.2627, 0.0593),
+        };
+        YCbCrCoefficients { kr, kb }
+    }
+}
+
+// Code values of black and of the nominal luma and chroma excursions for a component range and bit depth
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct YCbCrQuantization {
+    pub y_offset: f32,
+    pub y_scale: f32,
+    pub c_offset: f32,
+    pub c_scale: f32,
+    pub max: f32,
+}
+
+impl YCbCrQuantization {
+    pub fn new(component_range: CVPixelFormatComponentRange, bit_depth: u32) -> Self {
+        let shift = bit_depth.saturating_sub(8);
+        let max = ((1u64 << bit_depth) - 1) as f32;
+        let c_offset = (1u64 << (bit_depth - 1)) as f32;
+        match component_range {
+            CVPixelFormatComponentRange::VideoRange => YCbCrQuantization {
+                y_offset: (16u64 << shift) as f32,
+                y_scale: (219u64 << shift) as f32,
+                c_offset,
+                c_scale: (224u64 << shift) as f32,
+                max,
+            },
+            // Wide range only describes RGB formats, YCbCr data tagged with it is treated as full range
+            CVPixelFormatComponentRange::FullRange | CVPixelFormatComponentRange::WideRange => {
+                YCbCrQuantization { y_offset: 0.0, y_scale: max, c_offset, c_scale: max, max }
+            }
+        }
+    }
+}
+
+// Unset fields are taken from the YCbCr buffer's attachments, then from the defaults: ITU-R BT.601 up to 576 lines and
+// ITU-R BT.709 above, with chroma sited left. `dither` applies an ordered dither whenever the destination has fewer bits
+// per component than the source
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct YCbCrConversionOptions {
+    pub matrix: Option<CVImageBufferYCbCrMatrix>,
+    pub chroma_location: Option<CVImageBufferChromaLocation>,
+    pub dither: bool,
+}
+
+// Samples wider than 8 bits sit in the MSBs of little-endian 16-bit words
+#[inline]
+fn read_sample_8(row: &[u8], index: usize) -> f32 {
+    row[index] as f32
+}
+
+#[inline]
+fn read_sample_16<const BITS: u32>(row: &[u8], index: usize) -> f32 {
+    (u16::from_le_bytes([row[index * 2], row[index * 2 + 1]]) >> (16 - BITS)) as f32
+}
+
+#[inline]
+fn write_sample_8(row: &mut [u8], index: usize, code: f32) {
+    row[index] = code as u8;
+}
+
+#[inline]
+fn write_sample_16<const BITS: u32>(row: &mut [u8], index: usize, code: f32) {
+    row[index * 2..index * 2 + 2].copy_from_slice(&((code as u16) << (16 - BITS)).to_le_bytes());
+}
+
+type ReadSample = fn(&[u8], usize) -> f32;
+type WriteSample = fn(&mut [u8], usize, f32);
+
+// The sample accessors are picked for the bit depth once, when the format is looked up
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct YCbCrFormat {
+    pub(crate) subsampling: (usize, usize),
+    pub(crate) bit_depth: u32,
+    quantization: YCbCrQuantization,
+    read_sample: ReadSample,
+    write_sample: WriteSample,
+}
+
+impl YCbCrFormat {
+    #[inline]
+    fn read(&self, row: &[u8], index: usize) -> f32 {
+        (self.read_sample)(row, index)
+    }
+
+    #[inline]
+    fn write(&self, row: &mut [u8], index: usize, code: f32) {
+        (self.write_sample)(row, index, code)
+    }
+}
+
+pub(crate) fn ycbcr_format(pixel_format: PixelFormat) -> Option<YCbCrFormat> {
+    let bit_depth = match pixel_format {
+        PixelFormat::_420YpCbCr8BiPlanarVideoRange |
+        PixelFormat::_420YpCbCr8BiPlanarFullRange |
+        PixelFormat::_422YpCbCr8BiPlanarVideoRange |
+        PixelFormat::_422YpCbCr8BiPlanarFullRange |
+        PixelFormat::_444YpCbCr8BiPlanarVideoRange |
+        PixelFormat::_444YpCbCr8BiPlanarFullRange => 8,
+        PixelFormat::_420YpCbCr10BiPlanarVideoRange |
+        PixelFormat::_420YpCbCr10BiPlanarFullRange |
+        PixelFormat::_422YpCbCr10BiPlanarVideoRange |
+        PixelFormat::_422YpCbCr10BiPlanarFullRange |
+        PixelFormat::_444YpCbCr10BiPlanarVideoRange |
+        PixelFormat::_444YpCbCr10BiPlanarFullRange => 10,
+        PixelFormat::_422YpCbCr16BiPlanarVideoRange | PixelFormat::_444YpCbCr16BiPlanarVideoRange => 16,
+        _ => return None,
+    };
+    let layout = pixel_format.layout()?;
+    let chroma = layout.planes.get(1)?;
+    let (read_sample, write_sample): (ReadSample, WriteSample) = match bit_depth {
+        8 => (read_sample_8, write_sample_8),
+        10 => (read_sample_16::<10>, write_sample_16::<10>),
+        _ => (read_sample_16::<16>, write_sample_16::<16>),
+    };
+    Some(YCbCrFormat {
+        subsampling: (chroma.horizontal_subsampling as usize, chroma.vertical_subsampling as usize),
+        bit_depth,
+        quantization: YCbCrQuantization::new(layout.component_range?, bit_depth),
+        read_sample,
+        write_sample,
+    })
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum RGBFormat {
+    BGRA8,
+    #[cfg(feature = "half")]
+    RGBAHalf,
+    RGBAFloat,
+}
+
+impl RGBFormat {
+    #[inline]
+    pub(crate) fn bit_depth(self) -> u32 {
+        match self {
+            RGBFormat::BGRA8 => 8,
+            #[cfg(feature = "half")]
+            RGBFormat::RGBAHalf => 16,
+            RGBFormat::RGBAFloat => 32,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn pixel_format(self) -> PixelFormat {
+        match self {
+            RGBFormat::BGRA8 => PixelFormat::_32BGRA,
+            #[cfg(feature = "half")]
+            RGBFormat::RGBAHalf => PixelFormat::_64RGBAHalf,
+            RGBFormat::RGBAFloat => PixelFormat::_128RGBAFloat,
+        }
+    }
+
+    // Every component of these packed formats takes `bit_depth` bits
+    #[inline]
+    pub(crate) fn bytes_per_pixel(self) -> usize {
+        let components = self.pixel_format().layout().map_or(0, |layout| layout.planes[0].components.len());
+        components * self.bit_depth() as usize / 8
+    }
+
+    // Pixel accessors are resolved once per conversion rather than for every pixel
+    #[inline]
+    pub(crate) fn reader(self) -> ReadPixel {
+        match self {
+            RGBFormat::BGRA8 => read_bgra8,
+            #[cfg(feature = "half")]
+            RGBFormat::RGBAHalf => read_rgba_half,
+            RGBFormat::RGBAFloat => read_rgba_float,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn writer(self) -> WritePixel {
+        match self {
+            RGBFormat::BGRA8 => write_bgra8,
+            #[cfg(feature = "half")]
+            RGBFormat::RGBAHalf => write_rgba_half,
+            RGBFormat::RGBAFloat => write_rgba_float,
+        }
+    }
+}
+
+// RGBA of a pixel, whatever the order of its components
+pub(crate) type ReadPixel = fn(&[u8]) -> [f32; 4];
+// Writes RGBA to a pixel, dithering at (x, y) if asked to and if the format is quantized
+pub(crate) type WritePixel = fn(&mut [u8], [f32; 4], bool, usize, usize);
+
+fn read_bgra8(pixel: &[u8]) -> [f32; 4] {
+    [pixel[2] as f32 / 255.0, pixel[1] as f32 / 255.0, pixel[0] as f32 / 255.0, pixel[3] as f32 / 255.0]
+}
+
+#[cfg(feature = "half")]
+fn read_rgba_half(pixel: &[u8]) -> [f32; 4] {
+    let component = |index: usize| half::f16::from_le_bytes([pixel[index * 2], pixel[index * 2 + 1]]).to_f32();
+    [component(0), component(1), component(2), component(3)]
+}
+
+fn read_rgba_float(pixel: &[u8]) -> [f32; 4] {
+    let component = |index: usize| f32::from_le_bytes([pixel[index * 4], pixel[index * 4 + 1], pixel[index * 4 + 2], pixel[index * 4 + 3]]);
+    [component(0), component(1), component(2), component(3)]
+}
+
+// 8-bit components are clamped, floating point ones keep values outside [0, 1]; alpha is never dithered
+fn write_bgra8(pixel: &mut [u8], rgba: [f32; 4], dither: bool, x: usize, y: usize) {
+    let [r, g, b, alpha] = rgba;
+    let [r, g, b] = [r, g, b].map(|component| quantize(component * 255.0, 255.0, dither, x, y) as u8);
+    pixel.copy_from_slice(&[b, g, r, quantize(alpha * 255.0, 255.0, false, x, y) as u8]);
+}
+
+#[cfg(feature = "half")]
+fn write_rgba_half(pixel: &mut [u8], rgba: [f32; 4], _dither: bool, _x: usize, _y: usize) {
+    for (bytes, component) in pixel.chunks_exact_mut(2).zip(rgba) {
+        bytes.copy_from_slice(&half::f16::from_f32(component).to_le_bytes());
+    }
+}
+
+fn write_rgba_float(pixel: &mut [u8], rgba: [f32; 4], _dither: bool, _x: usize, _y: usize) {
+    for (bytes, component) in pixel.chunks_exact_mut(4).zip(rgba) {
+        bytes.copy_from_slice(&component.to_le_bytes());
+    }
+}
+
+pub(crate) fn rgb_format(pixel_format: PixelFormat) -> Option<RGBFormat> {
+    match pixel_format {
+        PixelFormat::_32BGRA => Some(RGBFormat::BGRA8),
+        #[cfg(feature = "half")]
+        PixelFormat::_64RGBAHalf => Some(RGBFormat::RGBAHalf),
+        PixelFormat::_128RGBAFloat => Some(RGBFormat::RGBAFloat),
+        _ => None,
+    }
+}
+
+// Frames are converted whole rather than field by field, so the top field's siting applies to both fields and the
+// bottom field's is only read when the top field's is missing; both are written, as CoreVideo does for progressive
+// content
+pub(crate) fn get_chroma_location(pixel_buffer: &CVPixelBuffer) -> Option<CVImageBufferChromaLocation> {
+    pixel_buffer
+        .get_image_attachment(CVImageBufferKeys::ChromaLocationTopField)
+        .or_else(|| pixel_buffer.get_image_attachment(CVImageBufferKeys::ChromaLocationBottomField))
+}
+
+pub(crate) fn set_attachments(pixel_buffer: &CVPixelBuffer, matrix: CVImageBufferYCbCrMatrix, chroma_location: CVImageBufferChromaLocation) {
+    pixel_buffer.set_image_attachment(CVImageBufferKeys::YCbCrMatrix, matrix);
+    pixel_buffer.set_image_attachment(CVImageBufferKeys::ChromaLocationTopField, chroma_location);
+    pixel_buffer.set_image_attachment(CVImageBufferKeys::ChromaLocationBottomField, chroma_location);
+}
+
+fn resolve_options(pixel_buffer: &CVPixelBuffer, options: &YCbCrConversionOptions) -> (CVImageBufferYCbCrMatrix, CVImageBufferChromaLocation) {
+    let matrix = options
+        .matrix
+        .or_else(|| pixel_buffer.get_image_attachment(CVImageBufferKeys::YCbCrMatrix))
+        .unwrap_or(if pixel_buffer.get_height() <= 576 { CVImageBufferYCbCrMatrix::ITU_R_601_4 } else { CVImageBufferYCbCrMatrix::ITU_R_709_2 });
+    let chroma_location = options
+        .chroma_location
+        .or_else(|| get_chroma_location(pixel_buffer))
+        .unwrap_or(CVImageBufferChromaLocation::Left);
+    (matrix, chroma_location)
+}
+
+// Position of the first Cb and Cr samples relative to the first luma sample, as fractions of the subsampling step
+fn chroma_siting(chroma_location: CVImageBufferChromaLocation) -> ((f32, f32), (f32, f32)) {
+    match chroma_location {
+        CVImageBufferChromaLocation::Left => ((0.0, 0.5), (0.0, 0.5)),
+        CVImageBufferChromaLocation::Center => ((0.5, 0.5), (0.5, 0.5)),
+        CVImageBufferChromaLocation::TopLeft => ((0.0, 0.0), (0.0, 0.0)),
+        CVImageBufferChromaLocation::Top => ((0.5, 0.0), (0.5, 0.0)),
+        CVImageBufferChromaLocation::BottomLeft => ((0.0, 1.0), (0.0, 1.0)),
+        CVImageBufferChromaLocation::Bottom => ((0.5, 1.0), (0.5, 1.0)),
+        CVImageBufferChromaLocation::DV420 => ((0.0, 1.0), (0.0, 0.0)), /* Cr on the top line, Cb on the bottom one */
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct UpsampleTap {
+    index0: usize,
+    index1: usize,
+    weight: f32, /* weight of index1 */
+}
+
+// Linear interpolation of the chroma samples at every luma position, repeating the edge samples outside the plane
+fn upsample_taps(luma_length: usize, chroma_length: usize, subsampling: usize, siting: f32) -> Vec<UpsampleTap> {
+    let offset = siting * (subsampling - 1) as f32;
+    (0..luma_length)
+        .map(|x| {
+            let position = ((x as f32 - offset) / subsampling as f32).max(0.0);
+            let index0 = (position as usize).min(chroma_length - 1);
+            let index1 = (index0 + 1).min(chroma_length - 1);
+            let weight = if index1 == index0 { 0.0 } else { position - index0 as f32 };
+            UpsampleTap { index0, index1, weight }
+        })
+        .collect()
+}
+
+// Triangle filter as wide as two subsampling steps centered on every chroma sample, so co-sited and interstitial
+// samples average the luma positions around their own siting
+fn downsample_taps(luma_length: usize, chroma_length: usize, subsampling: usize, siting: f32) -> Vec<Vec<(usize, f32)>> {
+    let radius = subsampling as f32;
+    let offset = siting * (subsampling - 1) as f32;
+    (0..chroma_length)
+        .map(|j| {
+            let center = (j * subsampling) as f32 + offset;
+            let first = (center - radius).floor() as isize + 1;
+            let last = (center + radius).ceil() as isize - 1;
+            let mut taps: Vec<(usize, f32)> = (first..=last)
+                .map(|i| (i.clamp(0, luma_length as isize - 1) as usize, radius - (i as f32 - center).abs()))
+                .filter(|&(_, weight)| weight > 0.0)
+                .collect();
+            let sum: f32 = taps.iter().map(|&(_, weight)| weight).sum();
+            taps.iter_mut().for_each(|(_, weight)| *weight /= sum);
+            taps
+        })
+        .collect()
+}
+
+// 4x4 ordered dither thresholds
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+#[inline]
+fn quantize(value: f32, max: f32, dither: bool, x: usize, y: usize) -> f32 {
+    let offset = if dither { (BAYER[y & 3][x & 3] as f32 + 0.5) / 16.0 - 0.5 } else { 0.0 };
+    (value + offset).round().clamp(0.0, max)
+}
+
+// Y' in [0, 1] and Pb, Pr in [-0.5, 0.5], the chroma planes `subsampling` times smaller than luma
+pub(crate) struct YCbCrImage {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) subsampling: (usize, usize),
+    pub(crate) y: Vec<f32>,
+    pub(crate) cb: Vec<f32>,
+    pub(crate) cr: Vec<f32>,
+}
+
+impl YCbCrImage {
+    #[inline]
+    fn chroma_size(&self) -> (usize, usize) {
+        (self.width.div_ceil(self.subsampling.0), self.height.div_ceil(self.subsampling.1))
+    }
+
+    fn upsample(&self, plane: &[f32], siting: (f32, f32)) -> Vec<f32> {
+        let (chroma_width, chroma_height) = self.chroma_size();
+        let columns = upsample_taps(self.width, chroma_width, self.subsampling.0, siting.0);
+        let rows = upsample_taps(self.height, chroma_height, self.subsampling.1, siting.1);
+        let mut upsampled = Vec::with_capacity(self.width * self.height);
+        for row in &rows {
+            let top = &plane[row.index0 * chroma_width..(row.index0 + 1) * chroma_width];
+            let bottom = &plane[row.index1 * chroma_width..(row.index1 + 1) * chroma_width];
+            upsampled.extend(columns.iter().map(|column| {
+                let sample = |line: &[f32]| line[column.index0] + (line[column.index1] - line[column.index0]) * column.weight;
+                let top = sample(top);
+                top + (sample(bottom) - top) * row.weight
+            }));
+        }
+        upsampled
+    }
+
+    fn downsample(&self, plane: &[f32], subsampling: (usize, usize), siting: (f32, f32)) -> Vec<f32> {
+        let (width, height) = (self.width, self.height);
+        let (chroma_width, chroma_height) = (width.div_ceil(subsampling.0), height.div_ceil(subsampling.1));
+        let columns = downsample_taps(width, chroma_width, subsampling.0, siting.0);
+        let rows = downsample_taps(height, chroma_height, subsampling.1, siting.1);
+        let horizontal: Vec<f32> = plane
+            .chunks_exact(width)
+            .flat_map(|line| columns.iter().map(move |taps| taps.iter().map(|&(x, weight)| line[x] * weight).sum::<f32>()))
+            .collect();
+        rows.iter()
+            .flat_map(|taps| {
+                let horizontal = &horizontal;
+                (0..chroma_width).map(move |x| taps.iter().map(|&(y, weight)| horizontal[y * chroma_width + x] * weight).sum::<f32>())
+            })
+            .collect()
+    }
+
+    // Brings the chroma planes to another subsampling, going through full resolution when neither side is 4:4:4
+    pub(crate) fn resampled(self, subsampling: (usize, usize), chroma_location: CVImageBufferChromaLocation) -> YCbCrImage {
+        if self.subsampling == subsampling {
+            return self;
+        }
+        let (cb_siting, cr_siting) = chroma_siting(chroma_location);
+        let full = if self.subsampling == (1, 1) {
+            self
+        } else {
+            let (cb, cr) = (self.upsample(&self.cb, cb_siting), self.upsample(&self.cr, cr_siting));
+            YCbCrImage { subsampling: (1, 1), cb, cr, ..self }
+        };
+        if subsampling == (1, 1) {
+            return full;
+        }
+        let (cb, cr) = (full.downsample(&full.cb, subsampling, cb_siting), full.downsample(&full.cr, subsampling, cr_siting));
+        YCbCrImage { subsampling, cb, cr, ..full }
+    }
+}
+
+pub(crate) fn read_ycbcr(pixel_buffer: &CVPixelBuffer, format: &YCbCrFormat) -> Result<YCbCrImage, CVError> {
+    let guard = pixel_buffer.lock_read()?;
+    let luma = guard.plane(0).ok_or(CVError::InvalidArgument)?;
+    let chroma = guard.plane(1).ok_or(CVError::InvalidArgument)?;
+    let quantization = format.quantization;
+    let (width, height, chroma_width) = (luma.width(), luma.height(), chroma.width());
+
+    let mut y = Vec::with_capacity(width * height);
+    for row in luma.rows() {
+        y.extend((0..width).map(|x| (format.read(row, x) - quantization.y_offset) / quantization.y_scale));
+    }
+    let mut cb = Vec::with_capacity(chroma_width * chroma.height());
+    let mut cr = Vec::with_capacity(chroma_width * chroma.height());
+    for row in chroma.rows() {
+        for x in 0..chroma_width {
+            cb.push((format.read(row, x * 2) - quantization.c_offset) / quantization.c_scale);
+            cr.push((format.read(row, x * 2 + 1) - quantization.c_offset) / quantization.c_scale);
+        }
+    }
+    Ok(YCbCrImage { width, height, subsampling: format.subsampling, y, cb, cr })
+}
+
+pub(crate) fn write_ycbcr(pixel_buffer: &mut CVPixelBuffer, format: &YCbCrFormat, image: &YCbCrImage, dither: bool) -> Result<(), CVError> {
+    let mut guard = pixel_buffer.lock_write()?;
+    let mut planes = guard.planes_mut();
+    if planes.len() != 2 {
+        return Err(CVError::InvalidArgument);
+    }
+    let mut chroma = planes.pop().unwrap();
+    let mut luma = planes.pop().unwrap();
+    let (chroma_width, chroma_height) = image.chroma_size();
+    if (luma.width(), luma.height(), chroma.width(), chroma.height()) != (image.width, image.height, chroma_width, chroma_height) {
+        return Err(CVError::InvalidSize);
+    }
+    let quantization = format.quantization;
+
+    for (y, (row, line)) in luma.rows_mut().zip(image.y.chunks_exact(image.width)).enumerate() {
+        for (x, &value) in line.iter().enumerate() {
+            format.write(row, x, quantize(quantization.y_offset + quantization.y_scale * value, quantization.max, dither, x, y));
+        }
+    }
+    let lines = image.cb.chunks_exact(chroma_width).zip(image.cr.chunks_exact(chroma_width));
+    for (y, (row, (cb, cr))) in chroma.rows_mut().zip(lines).enumerate() {
+        for (x, (&cb, &cr)) in cb.iter().zip(cr).enumerate() {
+            format.write(row, x * 2, quantize(quantization.c_offset + quantization.c_scale * cb, quantization.max, dither, x, y));
+            format.write(row, x * 2 + 1, quantize(quantization.c_offset + quantization.c_scale * cr, quantization.max, dither, x, y));
+        }
+    }
+    Ok(())
+}
+
+// Shared by the YCbCr, RGB and v210 conversions, which all map pixels one to one
+pub(crate) fn check_size(source: &CVPixelBuffer, destination: &CVPixelBuffer) -> Result<(), CVError> {
+    if source.get_width() != destination.get_width() || source.get_height() != destination.get_height() {
+        return Err(CVError::InvalidSize);
+    }
+    Ok(())
+}
+
+// Converts between the 8, 10 and 16-bit bi-planar YCbCr formats, remapping the component range and resampling chroma
+// as needed; the YCbCr matrix is left as is and, on the CoreVideo backend, attached to the destination along with the
+// chroma location
+pub fn convert_ycbcr(source: &CVPixelBuffer, destination: &mut CVPixelBuffer, options: Option<&YCbCrConversionOptions>) -> Result<(), CVError> {
+    let source_format = ycbcr_format(PixelFormat::from(source.get_pixel_format())).ok_or(CVError::InvalidPixelFormat)?;
+    let destination_format = ycbcr_format(PixelFormat::from(destination.get_pixel_format())).ok_or(CVError::InvalidPixelFormat)?;
+    check_size(source, destination)?;
+
+    let options = options.copied().unwrap_or_default();
+    let (matrix, chroma_location) = resolve_options(source, &options);
+    let image = read_ycbcr(source, &source_format)?.resampled(destination_format.subsampling, chroma_location);
+    write_ycbcr(destination, &destination_format, &image, options.dither && destination_format.bit_depth < source_format.bit_depth)?;
+    set_attachments(destination, matrix, chroma_location);
+    Ok(())
+}
+
+// Converts a bi-planar YCbCr buffer to 32BGRA, 64RGBAHalf (with the `half` feature) or 128RGBAFloat with opaque alpha
+pub fn convert_ycbcr_to_rgb(
+    source: &CVPixelBuffer,
+    destination: &mut CVPixelBuffer,
+    options: Option<&YCbCrConversionOptions>,
+) -> Result<(), CVError> {
+    let source_format = ycbcr_format(PixelFormat::from(source.get_pixel_format())).ok_or(CVError::InvalidPixelFormat)?;
+    let destination_format = rgb_format(PixelFormat::from(destination.get_pixel_format())).ok_or(CVError::InvalidPixelFormat)?;
+    check_size(source, destination)?;
+
+    let options = options.copied().unwrap_or_default();
+    let (matrix, chroma_location) = resolve_options(source, &options);
+    let coefficients = YCbCrCoefficients::from(matrix);
+    let image = read_ycbcr(source, &source_format)?.resampled((1, 1), chroma_location);
+    let dither = options.dither && destination_format.bit_depth() < source_format.bit_depth;
+
+    let write = destination_format.writer();
+
+    let mut guard = destination.lock_write()?;
+    let mut plane = guard.plane_mut(0).ok_or(CVError::InvalidArgument)?;
+    for (y, row) in plane.rows_mut().enumerate() {
+        for (x, pixel) in row.chunks_exact_mut(destination_format.bytes_per_pixel()).enumerate() {
+            let index = y * image.width + x;
+            let [r, g, b] = coefficients.to_rgb(image.y[index], image.cb[index], image.cr[index]);
+            write(pixel, [r, g, b, 1.0], dither, x, y);
+        }
+    }
+    Ok(())
+}
+
+// The inverse of convert_ycbcr_to_rgb, ignoring alpha; on the CoreVideo backend the matrix and chroma location used are
+// attached to the destination
+pub fn convert_rgb_to_ycbcr(
+    source: &CVPixelBuffer,
+    destination: &mut CVPixelBuffer,
+    options: Option<&YCbCrConversionOptions>,
+) -> Result<(), CVError> {
+    let source_format = rgb_format(PixelFormat::from(source.get_pixel_format())).ok_or(CVError::InvalidPixelFormat)?;
+    let destination_format = ycbcr_format(PixelFormat::from(destination.get_pixel_format())).ok_or(CVError::InvalidPixelFormat)?;
+    check_size(source, destination)?;
+
+    let options = options.copied().unwrap_or_default();
+    let (matrix, chroma_location) = resolve_options(destination, &options);
+    let coefficients = YCbCrCoefficients::from(matrix);
+    let image = {
+        let guard = source.lock_read()?;
+        let plane = guard.plane(0).ok_or(CVError::InvalidArgument)?;
+        let (width, height) = (plane.width(), plane.height());
+        let mut image = YCbCrImage {
+            width,
+            height,
+            subsampling: (1, 1),
+            y: Vec::with_capacity(width * height),
+            cb: Vec::with_capacity(width * height),
+            cr: Vec::with_capacity(width * height),
+        };
+        let read = source_format.reader();
+        for row in plane.rows() {
+            for pixel in row.chunks_exact(source_format.bytes_per_pixel()) {
+                let [r, g, b, _] = read(pixel);
+                let (y, cb, cr) = coefficients.to_ycbcr([r, g, b]);
+                image.y.push(y);
+                image.cb.push(cb);
+                image.cr.push(cr);
+            }
+        }
+        image.resampled(destination_format.subsampling, chroma_location)
+    };
+    write_ycbcr(destination, &destination_format, &image, options.dither && destination_format.bit_depth < source_format.bit_depth())?;
+    set_attachments(destination, matrix, chroma_location);
+    Ok(())
+}
+
+pub fn convert_ycbcr_to_bgra(
+    source: &CVPixelBuffer,
+    destination: &mut CVPixelBuffer,
+    options: Option<&YCbCrConversionOptions>,
+) -> Result<(), CVError> {
+    if PixelFormat::from(destination.get_pixel_format()) != PixelFormat::_32BGRA {
+        return Err(CVError::InvalidPixelFormat);
+    }
+    convert_ycbcr_to_rgb(source, destination, options)
+}
+
+pub fn convert_bgra_to_ycbcr(
+    source: &CVPixelBuffer,
+    destination: &mut CVPixelBuffer,
+    options: Option<&YCbCrConversionOptions>,
+) -> Result<(), CVError> {
+    if PixelFormat::from(source.get_pixel_format()) != PixelFormat::_32BGRA {
+        return Err(CVError::InvalidPixelFormat);
+    }
+    convert_rgb_to_ycbcr(source, destination, options)
+}
+
+#[cfg(all(test, any(feature = "portable", target_os = "macos", target_os = "ios")))]
+mod tests {
+    use super::*;
+
+    fn new_buffer(pixel_format: PixelFormat, width: usize, height: usize) -> CVPixelBuffer {
+        CVPixelBuffer::new(pixel_format.into(), width, height, None).unwrap()
+    }
+
+    // Codes wider than 8 bits are taken from the MSBs of their little-endian 16-bit words
+    fn plane_codes(pixel_buffer: &CVPixelBuffer, plane_index: usize, bit_depth: u32) -> Vec<Vec<u16>> {
+        let guard = pixel_buffer.lock_read().unwrap();
+        let plane = guard.plane(plane_index).unwrap();
+        plane
+            .rows()
+            .map(|row| {
+                if bit_depth > 8 {
+                    row.chunks_exact(2)
+                        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) >> (16 - bit_depth))
+                        .collect()
+                } else {
+                    row.iter().map(|&code| code as u16).collect()
+                }
+            })
+            .collect()
+    }
+
+    fn set_plane_codes(pixel_buffer: &mut CVPixelBuffer, plane_index: usize, bit_depth: u32, codes: &[Vec<u16>]) {
+        let mut guard = pixel_buffer.lock_write().unwrap();
+        let mut plane = guard.plane_mut(plane_index).unwrap();
+        for (row, line) in plane.rows_mut().zip(codes) {
+            for (index, &code) in line.iter().enumerate() {
+                if bit_depth > 8 {
+                    row[index * 2..index * 2 + 2].copy_from_slice(&(code << (16 - bit_depth)).to_le_bytes());
+                } else {
+                    row[index] = code as u8;
+                }
+            }
+        }
+    }
+
+    fn rgba_pixels(pixel_buffer: &CVPixelBuffer) -> Vec<[f32; 4]> {
+        let guard = pixel_buffer.lock_read().unwrap();
+        let plane = guard.plane(0).unwrap();
+        plane
+            .rows_as::<f32>()
+            .flat_map(|row| {
+                row.unwrap()
+                    .chunks_exact(4)
+                    .map(|pixel| [pixel[0], pixel[1], pixel[2], pixel[3]])
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rgb_formats() {
+        let mut formats = vec![(PixelFormat::_32BGRA, 4), (PixelFormat::_128RGBAFloat, 16)];
+        if cfg!(feature = "half") {
+            formats.push((PixelFormat::_64RGBAHalf, 8));
+        }
+        for (pixel_format, bytes_per_pixel) in formats {
+            let format = rgb_format(pixel_format).unwrap();
+            assert_eq!(format.pixel_format(), pixel_format);
+            assert_eq!(format.bytes_per_pixel(), bytes_per_pixel);
+            assert_eq!(format.bytes_per_pixel() * 8, pixel_format.layout().unwrap().planes[0].bits_per_block as usize);
+        }
+        assert_eq!(rgb_format(PixelFormat::_24RGB), None);
+    }
+
+    #[test]
+    fn quantization() {
+        let video = |y_offset, y_scale, c_offset, c_scale, max| YCbCrQuantization { y_offset, y_scale, c_offset, c_scale, max };
+        assert_eq!(YCbCrQuantization::new(CVPixelFormatComponentRange::VideoRange, 8), video(16.0, 219.0, 128.0, 224.0, 255.0));
+        assert_eq!(YCbCrQuantization::new(CVPixelFormatComponentRange::VideoRange, 10), video(64.0, 876.0, 512.0, 896.0, 1023.0));
+        assert_eq!(YCbCrQuantization::new(CVPixelFormatComponentRange::VideoRange, 16), video(4096.0, 56064.0, 32768.0, 57344.0, 65535.0));
+        assert_eq!(YCbCrQuantization::new(CVPixelFormatComponentRange::FullRange, 8), video(0.0, 255.0, 128.0, 255.0, 255.0));
+        assert_eq!(YCbCrQuantization::new(CVPixelFormatComponentRange::FullRange, 10), video(0.0, 1023.0, 512.0, 1023.0, 1023.0));
+        assert_eq!(
+            YCbCrQuantization::new(CVPixelFormatComponentRange::WideRange, 10),
+            YCbCrQuantization::new(CVPixelFormatComponentRange::FullRange, 10)
+        );
+    }
+
+    // Y'CbCr codes of R'G'B' primaries and secondaries from the ITU-R BT.601, BT.709 and BT.2020 equations
+    #[test]
+    fn reference_vectors() {
+        use CVImageBufferYCbCrMatrix::{ITU_R_2020, ITU_R_601_4, ITU_R_709_2};
+        use PixelFormat::{
+            _444YpCbCr10BiPlanarFullRange, _444YpCbCr10BiPlanarVideoRange, _444YpCbCr16BiPlanarVideoRange, _444YpCbCr8BiPlanarFullRange,
+            _444YpCbCr8BiPlanarVideoRange,
+        };
+
+        let (black, white, red, green, blue) = ([0.0, 0.0, 0.0], [1.0, 1.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]);
+        let (yellow, cyan, magenta) = ([1.0, 1.0, 0.0], [0.0, 1.0, 1.0], [1.0, 0.0, 1.0]);
+        let vectors = [
+            (_444YpCbCr8BiPlanarVideoRange, ITU_R_601_4, black, [16, 128, 128]),
+            (_444YpCbCr8BiPlanarVideoRange, ITU_R_601_4, white, [235, 128, 128]),
+            (_444YpCbCr8BiPlanarVideoRange, ITU_R_601_4, red, [81, 90, 240]),
+            (_444YpCbCr8BiPlanarVideoRange, ITU_R_601_4, green, [145, 54, 34]),
+            (_444YpCbCr8BiPlanarVideoRange, ITU_R_601_4, blue, [41, 240, 110]),
+            (_444YpCbCr8BiPlanarVideoRange, ITU_R_709_2, red, [63, 102, 240]),
+            (_444YpCbCr8BiPlanarVideoRange, ITU_R_709_2, green, [173, 42, 26]),
+            (_444YpCbCr8BiPlanarVideoRange, ITU_R_709_2, blue, [32, 240, 118]),
+            (_444YpCbCr8BiPlanarVideoRange, ITU_R_709_2, yellow, [219, 16, 138]),
+            (_444YpCbCr8BiPlanarFullRange, ITU_R_709_2, black, [0, 128, 128]),
+            (_444YpCbCr8BiPlanarFullRange, ITU_R_709_2, white, [255, 128, 128]),
+            (_444YpCbCr8BiPlanarFullRange, ITU_R_709_2, red, [54, 99, 255]),
+            (_444YpCbCr10BiPlanarVideoRange, ITU_R_709_2, black, [64, 512, 512]),
+            (_444YpCbCr10BiPlanarVideoRange, ITU_R_709_2, white, [940, 512, 512]),
+            (_444YpCbCr10BiPlanarVideoRange, ITU_R_709_2, red, [250, 409, 960]),
+            (_444YpCbCr10BiPlanarVideoRange, ITU_R_709_2, cyan, [754, 615, 64]),
+            (_444YpCbCr10BiPlanarFullRange, ITU_R_709_2, black, [0, 512, 512]),
+            (_444YpCbCr10BiPlanarFullRange, ITU_R_709_2, white, [1023, 512, 512]),
+            (_444YpCbCr10BiPlanarFullRange, ITU_R_709_2, magenta, [291, 906, 977]),
+            (_444YpCbCr10BiPlanarVideoRange, ITU_R_2020, red, [294, 387, 960]),
+            (_444YpCbCr10BiPlanarVideoRange, ITU_R_2020, green, [658, 189, 100]),
+            (_444YpCbCr10BiPlanarVideoRange, ITU_R_2020, blue, [116, 960, 476]),
+            (_444YpCbCr16BiPlanarVideoRange, ITU_R_709_2, black, [4096, 32768, 32768]),
+            (_444YpCbCr16BiPlanarVideoRange, ITU_R_709_2, white, [60160, 32768, 32768]),
+            (_444YpCbCr16BiPlanarVideoRange, ITU_R_709_2, red, [16015, 26198, 61440]),
+        ];
+        for &(pixel_format, matrix, rgb, [y, cb, cr]) in &vectors {
+            let options = YCbCrConversionOptions { matrix: Some(matrix), ..Default::default() };
+            let bit_depth = ycbcr_format(pixel_format).unwrap().bit_depth;
+            let mut source = new_buffer(PixelFormat::_128RGBAFloat, 2, 2);
+            source.lock_write().unwrap().plane_mut(0).unwrap().rows_as_mut::<f32>().for_each(|row| {
+                row.unwrap()
+                    .chunks_exact_mut(4)
+                    .for_each(|pixel| pixel.copy_from_slice(&[rgb[0], rgb[1], rgb[2], 1.0]))
+            });
+            let mut ycbcr = new_buffer(pixel_format, 2, 2);
+            convert_rgb_to_ycbcr(&source, &mut ycbcr, Some(&options)).unwrap();
+            assert_eq!(plane_codes(&ycbcr, 0, bit_depth), vec![vec![y; 2]; 2], "{:?} {:?} {:?}", pixel_format, matrix, rgb);
+            assert_eq!(plane_codes(&ycbcr, 1, bit_depth), vec![vec![cb, cr, cb, cr]; 2], "{:?} {:?} {:?}", pixel_format, matrix, rgb);
+
+            // Back to R'G'B' within the rounding of the codes
+            let mut destination = new_buffer(PixelFormat::_128RGBAFloat, 2, 2);
+            convert_ycbcr_to_rgb(&ycbcr, &mut destination, Some(&options)).unwrap();
+            let tolerance = 4.0 / ((1u32 << bit_depth) - 1) as f32;
+            for pixel in rgba_pixels(&destination) {
+                assert!(pixel[..3].iter().zip(&rgb).all(|(a, b)| (a - b).abs() < tolerance), "{:?} {:?} {:?}", pixel_format, pixel, rgb);
+                assert_eq!(pixel[3], 1.0);
+            }
+        }
+    }
+
+    // Converting to another range or bit depth maps the nominal codes of black, white and the chroma extremes onto each other
+    #[test]
+    fn range_conversion() {
+        use PixelFormat::{
+            _444YpCbCr10BiPlanarFullRange, _444YpCbCr10BiPlanarVideoRange, _444YpCbCr16BiPlanarVideoRange, _444YpCbCr8BiPlanarFullRange,
+            _444YpCbCr8BiPlanarVideoRange,
+        };
+
+        let cases = [
+            (_444YpCbCr10BiPlanarVideoRange, [[64, 512, 512], [940, 512, 512]], _444YpCbCr8BiPlanarFullRange, [[0, 128, 128], [255, 128, 128]]),
+            (_444YpCbCr8BiPlanarVideoRange, [[16, 16, 16], [235, 240, 240]], _444YpCbCr10BiPlanarVideoRange, [[64, 64, 64], [940, 960, 960]]),
+            (
+                _444YpCbCr10BiPlanarFullRange,
+                [[0, 512, 512], [1023, 512, 512]],
+                _444YpCbCr16BiPlanarVideoRange,
+                [[4096, 32768, 32768], [60160, 32768, 32768]],
+            ),
+            (
+                _444YpCbCr16BiPlanarVideoRange,
+                [[4096, 4096, 61440], [60160, 61440, 4096]],
+                _444YpCbCr10BiPlanarVideoRange,
+                [[64, 64, 960], [940, 960, 64]],
+            ),
+        ];
+        for &(source_format, source_codes, destination_format, destination_codes) in &cases {
+            let (source_depth, destination_depth) =
+                (ycbcr_format(source_format).unwrap().bit_depth, ycbcr_format(destination_format).unwrap().bit_depth);
+            let mut source = new_buffer(source_format, 2, 1);
+            set_plane_codes(&mut source, 0, source_depth, &[vec![source_codes[0][0], source_codes[1][0]]]);
+            set_plane_codes(&mut source, 1, source_depth, &[vec![source_codes[0][1], source_codes[0][2], source_codes[1][1], source_codes[1][2]]]);
+            let mut destination = new_buffer(destination_format, 2, 1);
+            convert_ycbcr(&source, &mut destination, None).unwrap();
+            assert_eq!(
+                plane_codes(&destination, 0, destination_depth),
+                [vec![destination_codes[0][0], destination_codes[1][0]]],
+                "{:?}",
+                destination_format
+            );
+            assert_eq!(
+                plane_codes(&destination, 1, destination_depth),
+                [vec![destination_codes[0][1], destination_codes[0][2], destination_codes[1][1], destination_codes[1][2]]],
+                "{:?}",
+                destination_format
+            );
+        }
+    }
+
+    // Upsampling 4:2:0 to 4:4:4 interpolates between chroma samples according to where they sit relative to luma
+    #[test]
+    fn chroma_siting() {
+        use CVImageBufferChromaLocation::{Bottom, BottomLeft, Center, Left, Top, TopLeft, DV420};
+
+        // Luma positions 0 to 3 between chroma samples of 64 and 192 sited on the first, between the two, or on the second
+        // luma sample of their pair
+        let (first, between, second) = (vec![64, 128, 192, 192], vec![64, 96, 160, 192], vec![64, 64, 128, 192]);
+        let cases = [
+            (Left, [&first, &between], [&first, &between]),
+            (Center, [&between, &between], [&between, &between]),
+            (TopLeft, [&first, &first], [&first, &first]),
+            (Top, [&between, &first], [&between, &first]),
+            (BottomLeft, [&first, &second], [&first, &second]),
+            (Bottom, [&between, &second], [&between, &second]),
+            (DV420, [&first, &second], [&first, &first]),
+        ];
+        for &(chroma_location, [cb_horizontal, cb_vertical], [cr_horizontal, cr_vertical]) in &cases {
+            let options = YCbCrConversionOptions { chroma_location: Some(chroma_location), ..Default::default() };
+            let mut source = new_buffer(PixelFormat::_420YpCbCr8BiPlanarVideoRange, 4, 4);
+            let mut destination = new_buffer(PixelFormat::_444YpCbCr8BiPlanarVideoRange, 4, 4);
+            let cb = |chroma: &[Vec<u16>], x: usize, y: usize| chroma[y][x * 2];
+            let cr = |chroma: &[Vec<u16>], x: usize, y: usize| chroma[y][x * 2 + 1];
+
+            set_plane_codes(&mut source, 1, 8, &[vec![64, 64, 192, 192], vec![64, 64, 192, 192]]);
+            convert_ycbcr(&source, &mut destination, Some(&options)).unwrap();
+            let chroma = plane_codes(&destination, 1, 8);
+            assert_eq!((0..4).map(|x| cb(&chroma, x, 0)).collect::<Vec<_>>(), *cb_horizontal, "{:?}", chroma_location);
+            assert_eq!((0..4).map(|x| cr(&chroma, x, 3)).collect::<Vec<_>>(), *cr_horizontal, "{:?}", chroma_location);
+
+            set_plane_codes(&mut source, 1, 8, &[vec![64, 64, 64, 64], vec![192, 192, 192, 192]]);
+            convert_ycbcr(&source, &mut destination, Some(&options)).unwrap();
+            let chroma = plane_codes(&destination, 1, 8);
+            assert_eq!((0..4).map(|y| cb(&chroma, 1, y)).collect::<Vec<_>>(), *cb_vertical, "{:?}", chroma_location);
+            assert_eq!((0..4).map(|y| cr(&chroma, 2, y)).collect::<Vec<_>>(), *cr_vertical, "{:?}", chroma_location);
+        }
+    }
+}